@@ -20,7 +20,7 @@ use tempfile::TempDir;
 async fn main() -> Result<()> {
   let (ui_config, task_state) = default_tasks_and_tags();
 
-  let task_state = TaskState::with_serde(task_state)?;
+  let mut task_state = TaskState::with_serde(task_state)?;
   let tasks_dir = TempDir::new()?;
   let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf()).await?;
   let () = task_state.save(&mut tasks_root_cap).await?;
@@ -37,6 +37,8 @@ async fn main() -> Result<()> {
   let mut ui_config_file_cap = ui_config_dir_write_guard.file_cap(&ui_config_file_name);
   let () = ui_config.save(&mut ui_config_file_cap).await?;
 
+  let trash_dir = TempDir::new()?;
+  let archive_dir = TempDir::new()?;
   let ui_state_dir = TempDir::new()?;
   let ui_state_file_name = OsString::from("ui-state.json");
   let ui_state_file_path = (ui_state_dir.path().to_path_buf(), ui_state_file_name);
@@ -45,6 +47,8 @@ async fn main() -> Result<()> {
     stdin(),
     stdout().lock(),
     tasks_dir.path().to_path_buf(),
+    trash_dir.path().to_path_buf(),
+    archive_dir.path().to_path_buf(),
     ui_config_file_path,
     ui_state_file_path,
   )
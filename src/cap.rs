@@ -6,6 +6,7 @@
 //! for vetted access from within the program itself.
 
 use std::ffi::OsStr;
+use std::ffi::OsString;
 use std::fs::Permissions;
 use std::future::Future;
 use std::io::ErrorKind;
@@ -17,6 +18,7 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::thread;
 
+use anyhow::bail;
 use anyhow::Context as _;
 use anyhow::Result;
 
@@ -24,7 +26,12 @@ use libc::S_IWUSR;
 
 use tokio::fs::metadata;
 use tokio::fs::read_dir;
+use tokio::fs::remove_file;
+use tokio::fs::rename;
 use tokio::fs::set_permissions;
+use tokio::fs::File;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt as _;
 use tokio::runtime::Handle;
 
 
@@ -136,6 +143,78 @@ where
 }
 
 
+/// Remove a file, if it exists.
+async fn remove_file_if_exists(path: &Path) -> Result<()> {
+  match remove_file(path).await {
+    Ok(()) => Ok(()),
+    Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+    r @ Err(_) => r.with_context(|| format!("failed to remove {}", path.display())),
+  }
+}
+
+/// Write `data` into the file at `temp_path`, make sure it is on disk,
+/// and then rename it to `path`.
+///
+/// The final file is write-protected and otherwise inherits the
+/// permissions of the one it replaces, if any.
+async fn replace_file(temp_path: &Path, path: &Path, data: &[u8]) -> Result<()> {
+  let () = remove_file_if_exists(temp_path).await?;
+
+  let mut file = OpenOptions::new()
+    .create_new(true)
+    .write(true)
+    .open(temp_path)
+    .await
+    .with_context(|| format!("failed to create {}", temp_path.display()))?;
+  let () = file
+    .write_all(data)
+    .await
+    .with_context(|| format!("failed to write {}", temp_path.display()))?;
+  let () = file
+    .sync_all()
+    .await
+    .with_context(|| format!("failed to sync {}", temp_path.display()))?;
+
+  let permissions = match metadata(path).await {
+    Ok(meta_data) => meta_data.permissions(),
+    Err(error) if error.kind() == ErrorKind::NotFound => file
+      .metadata()
+      .await
+      .with_context(|| format!("failed to retrieve meta data for {}", temp_path.display()))?
+      .permissions(),
+    Err(error) => {
+      return Err(error)
+        .with_context(|| format!("failed to retrieve meta data for {}", path.display()))
+    },
+  };
+  let () = file
+    .set_permissions(read_only(permissions))
+    .await
+    .with_context(|| format!("failed to adjust permissions of {}", temp_path.display()))?;
+  let () = drop(file);
+
+  let () = rename(temp_path, path).await.with_context(|| {
+    format!(
+      "failed to rename {} to {}",
+      temp_path.display(),
+      path.display()
+    )
+  })?;
+
+  // The rename is only guaranteed to be persistent once the directory
+  // containing the file has been synced as well.
+  if let Some(directory) = path.parent() {
+    let () = File::open(directory)
+      .await
+      .with_context(|| format!("failed to open directory {}", directory.display()))?
+      .sync_all()
+      .await
+      .with_context(|| format!("failed to sync directory {}", directory.display()))?;
+  }
+  Ok(())
+}
+
+
 /// Run a future from a synchronous context, blocking until it is
 /// resolved.
 fn run_async<Fut>(future: Fut)
@@ -308,26 +387,60 @@ impl<'cap> FileCap<'cap> {
     }
   }
 
-  /// Do something with the path represented by this [`FileCap`].
-  pub async fn with_writeable_path<F, Fut>(&mut self, f: F) -> Result<()>
-  where
-    F: FnOnce(PathBuf) -> Fut,
-    Fut: Future<Output = Result<()>>,
-  {
-    let () = change_item_permissions(&self.path, writeable).await?;
+  /// Atomically replace the contents of the file represented by this
+  /// [`FileCap`] with `data`, creating it if it does not exist.
+  ///
+  /// The data are first written to a temporary file in the same
+  /// directory, which is then renamed over the target. That way, a
+  /// crash or an I/O error (e.g., because the disk ran full) in the
+  /// middle of the operation can never leave behind a partially
+  /// written file.
+  pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+    let (directory, file) = match (self.path.parent(), self.path.file_name()) {
+      (Some(directory), Some(file)) => (directory, file),
+      _ => bail!("{} does not refer to a file", self.path.display()),
+    };
+
+    // We use a hidden file, which is ignored when loading tasks, just
+    // in case it ends up being left behind.
+    let mut temp_file = OsString::from(".");
+    let () = temp_file.push(file);
+    let () = temp_file.push(".tmp");
+    let temp_path = directory.join(temp_file);
 
-    let result = f(self.path.to_path_buf()).await;
-    match (result, change_item_permissions(&self.path, read_only).await) {
-      (Ok(()), Ok(())) => Ok(()),
-      (Ok(()), r @ Err(_)) => {
-        r.with_context(|| format!("failed to revert permissions of {}", self.path.display()))
-      },
-      (r @ Err(_), Ok(())) => r,
-      (r @ Err(_), Err(_)) => {
-        eprintln!("failed to revert permissions of {}", self.path.display());
-        r
-      },
+    let result = replace_file(&temp_path, &self.path, data).await;
+    if result.is_err() {
+      let _result = remove_file_if_exists(&temp_path).await;
     }
+    result
+  }
+
+  /// Append `data` to the file represented by this [`FileCap`],
+  /// creating it if it does not exist.
+  ///
+  /// As opposed to [`write`][FileCap::write], appending is not atomic:
+  /// a crash in the middle of the operation may leave behind partially
+  /// written data at the end of the file.
+  pub async fn append(&mut self, data: &[u8]) -> Result<()> {
+    // The file is write-protected. Write access is revoked again once
+    // the `WriteGuard` we got created from goes out of scope.
+    let () = change_item_permissions(&self.path, writeable).await?;
+
+    let mut file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .await
+      .with_context(|| format!("failed to open {}", self.path.display()))?;
+    let () = file
+      .write_all(data)
+      .await
+      .with_context(|| format!("failed to write {}", self.path.display()))?;
+    let () = file
+      .sync_data()
+      .await
+      .with_context(|| format!("failed to sync {}", self.path.display()))?;
+    Ok(())
   }
 
   /// Retrieve the path to the file this capability refers to.
@@ -396,13 +509,8 @@ mod tests {
     let mut capability = DirCap::for_dir(path.clone()).await.unwrap();
     let write_guard = capability.write().await.unwrap();
     let mut file_cap = write_guard.file_cap(OsStr::new("non-existent-file-in-non-existent-dir"));
-    let () = file_cap
-      .with_writeable_path(|path| async move {
-        assert!(!path.exists());
-        Ok(())
-      })
-      .await
-      .unwrap();
+    // Writing requires the directory to exist.
+    let _error = file_cap.write(b"test data").await.unwrap_err();
 
     assert!(!path.exists());
   }
@@ -472,13 +580,7 @@ mod tests {
       let error = write(file.path(), "test data").await.unwrap_err();
       assert_eq!(error.kind(), ErrorKind::PermissionDenied);
 
-      let () = file_cap
-        .with_writeable_path(|path| async move {
-          let () = write(path, "success").await.unwrap();
-          Ok(())
-        })
-        .await
-        .unwrap();
+      let () = file_cap.write(b"success").await.unwrap();
 
       // Outside of the specific call we should not be able to write.
       let error = write(file.path(), "test data").await.unwrap_err();
@@ -488,4 +590,65 @@ mod tests {
     let content = read_to_string(file.path()).await.unwrap();
     assert_eq!(content, "success");
   }
+
+  /// Make sure that writing through a [`FileCap`] replaces the file
+  /// without leaving behind any temporary files.
+  #[test]
+  async fn file_cap_write_replaces_file() {
+    let root = TempDir::new().unwrap();
+    let path = root.path().join("file");
+    let () = write(&path, "original").await.unwrap();
+    let () = set_permissions(&path, Permissions::from_mode(0o640))
+      .await
+      .unwrap();
+    // Simulate a temporary file left behind by an earlier failed
+    // write.
+    let () = write(root.path().join(".file.tmp"), "stale").await.unwrap();
+
+    {
+      let mut capability = DirCap::for_dir(root.path().to_path_buf()).await.unwrap();
+      let guard = capability.write().await.unwrap();
+      let mut file_cap = guard.file_cap(OsStr::new("file"));
+      let () = file_cap.write(b"replaced").await.unwrap();
+
+      // The file is write-protected, but otherwise retains its
+      // permissions.
+      let mode = metadata(&path).await.unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o440);
+    }
+
+    let content = read_to_string(&path).await.unwrap();
+    assert_eq!(content, "replaced");
+
+    let mut dir = read_dir(root.path()).await.unwrap();
+    let mut files = Vec::new();
+    while let Some(entry) = dir.next_entry().await.unwrap() {
+      let () = files.push(entry.file_name());
+    }
+    assert_eq!(files, vec![OsString::from("file")]);
+  }
+
+  /// Check that we can append to a write-protected file via a
+  /// `FileCap`.
+  #[test]
+  async fn file_cap_append() {
+    let root = TempDir::new().unwrap();
+    let path = root.path().join("file");
+
+    {
+      let mut capability = DirCap::for_dir(root.path().to_path_buf()).await.unwrap();
+      {
+        let guard = capability.write().await.unwrap();
+        let mut file_cap = guard.file_cap(OsStr::new("file"));
+        let () = file_cap.append(b"first\n").await.unwrap();
+      }
+
+      let guard = capability.write().await.unwrap();
+      let mut file_cap = guard.file_cap(OsStr::new("file"));
+      let () = file_cap.append(b"second\n").await.unwrap();
+    }
+
+    let content = read_to_string(&path).await.unwrap();
+    assert_eq!(content, "first\nsecond\n");
+  }
 }
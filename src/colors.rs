@@ -95,6 +95,10 @@ impl Color {
     Color::Rgb(Rgb(0xff, 0x00, 0x00))
   }
 
+  pub fn color208() -> Self {
+    Color::Rgb(Rgb(0xff, 0x87, 0x00))
+  }
+
   pub fn color235() -> Self {
     Color::Rgb(Rgb(0x26, 0x26, 0x26))
   }
@@ -103,6 +107,10 @@ impl Color {
     Color::Rgb(Rgb(0x58, 0x58, 0x58))
   }
 
+  pub fn color244() -> Self {
+    Color::Rgb(Rgb(0x80, 0x80, 0x80))
+  }
+
   pub fn dark_white() -> Self {
     Color::Rgb(Rgb(0xda, 0xda, 0xda))
   }
@@ -171,6 +179,14 @@ pub struct Colors {
   pub task_done_fg: Color,
   #[serde(default = "Color::reset")]
   pub task_done_bg: Color,
+  #[serde(default = "Color::color208")]
+  pub task_priority_fg: Color,
+  #[serde(default = "Color::reset")]
+  pub task_priority_bg: Color,
+  #[serde(default = "Color::color244")]
+  pub task_blocked_fg: Color,
+  #[serde(default = "Color::reset")]
+  pub task_blocked_bg: Color,
   #[serde(default = "Color::dark_white")]
   pub dialog_bg: Color,
   #[serde(default = "Color::color0")]
@@ -222,6 +238,10 @@ impl Default for Colors {
       task_not_started_bg: Color::reset(),
       task_done_fg: Color::bright_green(),
       task_done_bg: Color::reset(),
+      task_priority_fg: Color::color208(),
+      task_priority_bg: Color::reset(),
+      task_blocked_fg: Color::color244(),
+      task_blocked_bg: Color::reset(),
       dialog_fg: Color::color0(),
       dialog_bg: Color::dark_white(),
       dialog_selected_tag_fg: Color::color15(),
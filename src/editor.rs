@@ -0,0 +1,62 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::env::temp_dir;
+use std::env::var_os;
+use std::ffi::OsString;
+use std::fs::read_to_string;
+use std::fs::remove_file;
+use std::fs::write;
+use std::process::Command;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use uuid::Uuid;
+
+
+/// The editor to use if the user did not configure one.
+const DEFAULT_EDITOR: &str = "vi";
+
+
+/// Retrieve the command of the editor to use.
+fn editor() -> OsString {
+  var_os("VISUAL")
+    .or_else(|| var_os("EDITOR"))
+    .filter(|editor| !editor.is_empty())
+    .unwrap_or_else(|| OsString::from(DEFAULT_EDITOR))
+}
+
+/// Edit the provided text in the user's editor of choice and return
+/// the result.
+///
+/// The editor is looked up via the `VISUAL` and `EDITOR` environment
+/// variables, in that order. It is run through the shell, meaning that
+/// it may contain arguments.
+pub fn edit_text(text: &str) -> Result<String> {
+  let path = temp_dir().join(format!("notnow-{}.txt", Uuid::new_v4()));
+  let () = write(&path, text)
+    .with_context(|| format!("failed to write temporary file {}", path.display()))?;
+
+  let mut script = editor();
+  script.push(r#" "$1""#);
+
+  let result = Command::new("sh")
+    .arg("-c")
+    .arg(&script)
+    .arg("sh")
+    .arg(&path)
+    .status()
+    .with_context(|| format!("failed to run editor `{}`", script.to_string_lossy()))
+    .and_then(|status| {
+      if !status.success() {
+        bail!("editor exited with {status}")
+      }
+      read_to_string(&path)
+        .with_context(|| format!("failed to read temporary file {}", path.display()))
+    });
+
+  let _result = remove_file(&path);
+  result
+}
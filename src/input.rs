@@ -0,0 +1,192 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Result;
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+
+use libc::poll;
+use libc::pollfd;
+use libc::POLLIN;
+
+
+/// The time in milliseconds we wait for input to become available
+/// before checking whether we got paused.
+const POLL_TIMEOUT_MS: i32 = 100;
+
+
+#[derive(Debug, Default)]
+struct State {
+  /// Whether reading is currently paused.
+  paused: bool,
+  /// Whether the reader is currently waiting for input to arrive.
+  polling: bool,
+}
+
+
+#[derive(Debug, Default)]
+struct Shared {
+  state: Mutex<State>,
+  condvar: Condvar,
+}
+
+
+/// A handle for pausing and resuming consumption of input by a
+/// [`PausableReader`].
+///
+/// Pausing is necessary when handing the terminal over to another
+/// program, such as a text editor, which would otherwise compete with
+/// us for user input.
+#[derive(Clone, Debug)]
+pub struct InputPause(Arc<Shared>);
+
+impl InputPause {
+  /// Pause reading of input.
+  ///
+  /// Once this method returns, the associated reader will not consume
+  /// any more input until [`InputPause::resume`] is called.
+  pub fn pause(&self) {
+    let Shared { state, condvar } = self.0.as_ref();
+    // SANITY: We never panic while holding the lock.
+    let mut state = state.lock().unwrap();
+    state.paused = true;
+
+    while state.polling {
+      state = condvar.wait(state).unwrap();
+    }
+  }
+
+  /// Resume reading of input.
+  pub fn resume(&self) {
+    let Shared { state, condvar } = self.0.as_ref();
+    // SANITY: We never panic while holding the lock.
+    let mut state = state.lock().unwrap();
+    state.paused = false;
+    let () = condvar.notify_all();
+  }
+}
+
+
+/// A reader that only consumes input while not being paused.
+#[derive(Debug)]
+pub struct PausableReader<R> {
+  /// The reader we wrap.
+  reader: R,
+  /// State shared with the `InputPause` handle.
+  shared: Arc<Shared>,
+}
+
+impl<R> PausableReader<R>
+where
+  R: AsRawFd,
+{
+  /// Create a new `PausableReader` wrapping the provided one, along
+  /// with the handle used for pausing it.
+  pub fn new(reader: R) -> (Self, InputPause) {
+    let shared = Arc::new(Shared::default());
+    let slf = Self {
+      reader,
+      shared: shared.clone(),
+    };
+    (slf, InputPause(shared))
+  }
+
+  /// Wait for input to become available while we are not paused.
+  fn wait_readable(&self) -> Result<()> {
+    let Shared { state, condvar } = self.shared.as_ref();
+
+    loop {
+      {
+        // SANITY: We never panic while holding the lock.
+        let mut state = state.lock().unwrap();
+        while state.paused {
+          state = condvar.wait(state).unwrap();
+        }
+        state.polling = true;
+      }
+
+      let mut fd = pollfd {
+        fd: self.reader.as_raw_fd(),
+        events: POLLIN,
+        revents: 0,
+      };
+      // SAFETY: `fd` is a valid `pollfd` object and we pass in the
+      //         correct count.
+      let rc = unsafe { poll(&mut fd, 1, POLL_TIMEOUT_MS) };
+      let result = if rc < 0 {
+        Some(Error::last_os_error())
+      } else {
+        None
+      };
+
+      // SANITY: We never panic while holding the lock.
+      let mut state = state.lock().unwrap();
+      state.polling = false;
+      let () = condvar.notify_all();
+
+      match result {
+        Some(err) if err.kind() == ErrorKind::Interrupted => continue,
+        Some(err) => return Err(err),
+        // If we got paused in the meantime we must not consume the
+        // input that may have arrived: it's meant for someone else.
+        None if rc > 0 && !state.paused => return Ok(()),
+        None => continue,
+      }
+    }
+  }
+}
+
+impl<R> Read for PausableReader<R>
+where
+  R: Read + AsRawFd,
+{
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    let () = self.wait_readable()?;
+    self.reader.read(buf)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Write as _;
+  use std::os::unix::net::UnixStream;
+  use std::thread;
+  use std::time::Duration;
+
+
+  /// Check that a paused reader does not consume any input until it is
+  /// resumed again.
+  #[test]
+  fn pause_resume() {
+    let (mut writer, reader) = UnixStream::pair().unwrap();
+    let (mut reader, pause) = PausableReader::new(reader);
+
+    let () = writer.write_all(b"a").unwrap();
+    let mut buf = [0; 1];
+    assert_eq!(reader.read(&mut buf).unwrap(), 1);
+    assert_eq!(&buf, b"a");
+
+    let () = pause.pause();
+    let () = writer.write_all(b"b").unwrap();
+
+    let handle = thread::spawn(move || {
+      let mut buf = [0; 1];
+      let _count = reader.read(&mut buf).unwrap();
+      buf
+    });
+
+    thread::sleep(Duration::from_millis(2 * POLL_TIMEOUT_MS as u64));
+    assert!(!handle.is_finished());
+
+    let () = pause.resume();
+    assert_eq!(&handle.join().unwrap(), b"b");
+  }
+}
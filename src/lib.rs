@@ -66,19 +66,26 @@ extern crate test as unstable_test;
 mod cap;
 mod colors;
 mod db;
+mod editor;
 mod id;
+mod input;
 mod line;
 mod ops;
 mod position;
+mod priority;
+mod recurrence;
+mod reminder;
 mod resize;
 mod ser;
 mod state;
+mod sync;
 mod tags;
 mod tasks;
 #[cfg(any(test, feature = "test"))]
 pub mod test;
 mod ui;
 mod view;
+mod watch;
 
 pub use crate::cap::DirCap;
 pub use crate::state::TaskState;
@@ -86,6 +93,7 @@ pub use crate::ui::Config as UiConfig;
 pub use crate::ui::State as UiState;
 
 use std::env::args_os;
+use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::fs::create_dir_all;
 use std::fs::remove_file;
@@ -96,10 +104,13 @@ use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Result as IoResult;
 use std::io::Write;
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::mpsc::channel;
 use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::Sender;
 use std::thread;
 
@@ -111,6 +122,10 @@ use anyhow::Result;
 #[cfg(feature = "coredump")]
 use cdump::register_panic_handler;
 
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
 use dirs::cache_dir;
 use dirs::config_dir;
 
@@ -118,19 +133,33 @@ use termion::event::Event as TermEvent;
 use termion::event::Key;
 use termion::input::TermReadEventsAndRaw;
 use termion::raw::IntoRawMode;
+use termion::raw::RawTerminal;
 use termion::screen::IntoAlternateScreen as _;
 
 use tokio::runtime::Builder;
 
-use gui::Renderer;
+use gui::Id;
+use gui::MutCap as _;
 use gui::Ui;
 
+use crate::editor::edit_text;
+use crate::input::InputPause;
+use crate::input::PausableReader;
 use crate::resize::receive_window_resizes;
+use crate::ser::backends::Json;
+use crate::ser::state::UiConfig as SerUiConfig;
+use crate::state::load_state_from_file;
+use crate::state::merge_tasks_meta_files;
+use crate::state::Store;
+use crate::sync::read_collections;
+use crate::sync::sync;
+use crate::tasks::Task;
 use crate::ui::Event as UiEvent;
 use crate::ui::Message;
 use crate::ui::Renderer as TermUiRenderer;
 use crate::ui::Ui as TermUi;
 use crate::ui::UiData as TermUiData;
+use crate::watch::receive_task_changes;
 
 
 /// A tuple of (directory path, file name) representing the path to a
@@ -145,6 +174,10 @@ pub enum Event {
   Key(Key, Vec<u8>),
   /// The window has been resized.
   Resize,
+  /// Task files got changed by another program.
+  TasksChanged,
+  /// The time at which the next reminder is due has been reached.
+  Timer,
 }
 
 
@@ -166,6 +199,27 @@ fn tasks_root() -> Result<PathBuf> {
   )
 }
 
+/// Retrieve the path to the program's trash directory, which holds the
+/// files of removed tasks.
+fn trash_root() -> Result<PathBuf> {
+  Ok(
+    config_dir()
+      .ok_or_else(|| anyhow!("unable to determine config directory"))?
+      .join("notnow")
+      .join("trash"),
+  )
+}
+
+/// Retrieve the path to the archive directory.
+fn archive_root() -> Result<PathBuf> {
+  Ok(
+    config_dir()
+      .ok_or_else(|| anyhow!("unable to determine config directory"))?
+      .join("notnow")
+      .join("archive"),
+  )
+}
+
 /// Retrieve the path to the UI's configuration file, in the form of a
 /// (directory path, file name) tuple.
 fn ui_config() -> Result<FilePath> {
@@ -211,17 +265,68 @@ where
   });
 }
 
+/// Edit the notes of a task in an external editor, handing over the
+/// terminal for the time being.
+async fn edit_notes<W>(
+  ui: &mut Ui<UiEvent, Message>,
+  renderer: &TermUiRenderer<RawTerminal<W>>,
+  input: &InputPause,
+  widget: Id,
+  task: Rc<Task>,
+) -> Result<()>
+where
+  W: Write,
+{
+  let () = input.pause();
+  let () = renderer
+    .suspend()
+    .context("failed to suspend terminal based renderer")?;
+
+  let notes = task.notes().unwrap_or_default();
+  let result = edit_text(&notes);
+
+  let () = renderer
+    .resume()
+    .context("failed to resume terminal based renderer")?;
+  let () = input.resume();
+
+  let _message = ui.send(widget, Message::EditedNotes(task, result)).await;
+  Ok(())
+}
+
+/// Check for reminders that fired, returning the time at which the
+/// next one is due, if any, and whether the UI got updated.
+async fn check_reminders(ui: &mut Ui<UiEvent, Message>, root: Id) -> (Option<DateTime<Utc>>, bool) {
+  match ui.send(root, Message::CheckReminders(Utc::now())).await {
+    Some(Message::CheckedReminders(next, updated)) => (next, updated),
+    // Without an answer there is no reminder for us to wait for.
+    _ => (None, false),
+  }
+}
+
 /// Handle events in a loop.
-async fn run_loop<R>(
+async fn run_loop<W>(
   mut ui: Ui<UiEvent, Message>,
-  renderer: &R,
+  root: Id,
+  renderer: &TermUiRenderer<RawTerminal<W>>,
   recv_event: &Receiver<IoResult<Event>>,
+  input: &InputPause,
 ) -> Result<()>
 where
-  R: Renderer,
+  W: Write,
 {
+  // We check for reminders whenever the timer for the next one expires
+  // and, because reminders may have been changed, after every UI
+  // update.
+  let (mut next, updated) = check_reminders(&mut ui, root).await;
+  if updated {
+    ui.render(renderer);
+  }
+
   'handler: loop {
     let mut render = false;
+    let mut reload = false;
+    let mut check = false;
     // We want to read keys in batches in order to avoid unnecessary
     // render invocations, for example when a user pastes text (where
     // each key would result in a UI update). To make that happen we
@@ -229,7 +334,17 @@ where
     // use a single recv call to block once for an event and then use an
     // iterator to read and handle every key event queued up to this
     // point.
-    let event = recv_event.recv().unwrap();
+    // If a reminder is scheduled we only block until it is due, at
+    // which point we wake up to fire it.
+    let event = if let Some(next) = next {
+      let timeout = (next - Utc::now()).to_std().unwrap_or_default();
+      match recv_event.recv_timeout(timeout) {
+        Err(RecvTimeoutError::Timeout) => Ok(Event::Timer),
+        result => result.unwrap(),
+      }
+    } else {
+      recv_event.recv().unwrap()
+    };
     for event in Some(event).into_iter().chain(recv_event.try_iter()) {
       match event? {
         Event::Key(key, _raw) => {
@@ -245,14 +360,38 @@ where
             match event {
               UiEvent::Quit => break 'handler,
               UiEvent::Updated => render = true,
+              UiEvent::EditNotes(widget, task) => {
+                let () = edit_notes(&mut ui, renderer, input, widget, task).await?;
+                render = true
+              },
               UiEvent::Key(..) => {},
             }
           }
         },
         Event::Resize => render = true,
+        // Changes typically come in bursts, e.g., when a synchronization
+        // program updates many files at once, so we only reload once
+        // for the entire batch.
+        Event::TasksChanged => reload = true,
+        Event::Timer => check = true,
       }
     }
 
+    if reload {
+      let updated = ui
+        .send(root, Message::ReloadTasks)
+        .await
+        .map(|message| message.is_updated())
+        .unwrap_or(false);
+      render |= updated;
+    }
+
+    if check || render {
+      let updated;
+      (next, updated) = check_reminders(&mut ui, root).await;
+      render |= updated;
+    }
+
     if render {
       ui.render(renderer);
     }
@@ -265,24 +404,59 @@ pub async fn run_prog<R, W>(
   in_: R,
   out: W,
   tasks_root: PathBuf,
+  trash_root: PathBuf,
+  archive_root: PathBuf,
   ui_config_path: FilePath,
   ui_state_path: FilePath,
 ) -> Result<()>
 where
-  R: Read + Send + 'static,
+  R: Read + AsRawFd + Send + 'static,
   W: Write,
 {
-  let task_state = TaskState::load(&tasks_root)
-    .await
-    .context("failed to load task state")?;
   let ui_config_file = ui_config_path.0.join(&ui_config_path.1);
   let ui_state_file = ui_state_path.0.join(&ui_state_path.1);
+  // Tasks get archived as part of loading them, but the full UI
+  // configuration can only be loaded once tasks are available. Hence,
+  // we retrieve the archiving setting separately.
+  let archive_after_days = load_state_from_file::<Json, SerUiConfig>(&ui_config_file)
+    .await
+    .with_context(|| {
+      format!(
+        "failed to load UI configuration from {}",
+        ui_config_file.display()
+      )
+    })?
+    .and_then(|config| config.archive_after_days);
+
+  // The tasks directory has to exist for us to be able to watch it.
+  let () = create_dir_all(&tasks_root)
+    .with_context(|| format!("failed to create directory {}", tasks_root.display()))?;
+  let mut tasks_root_cap = DirCap::for_dir(tasks_root.clone()).await?;
+  let () = create_dir_all(&archive_root)
+    .with_context(|| format!("failed to create directory {}", archive_root.display()))?;
+  let mut archive = Store::new(DirCap::for_dir(archive_root).await?);
+
+  let mut task_state = if let Some(days) = archive_after_days {
+    let cutoff = Utc::now() - Duration::days(days.into());
+    TaskState::load_and_archive(&mut tasks_root_cap, &mut archive, cutoff).await
+  } else {
+    TaskState::load(&tasks_root).await
+  }
+  .context("failed to load task state")?;
+  let () = create_dir_all(&trash_root)
+    .with_context(|| format!("failed to create directory {}", trash_root.display()))?;
+  let () = task_state.set_trash(Store::new(DirCap::for_dir(trash_root).await?));
+  let () = task_state.set_archive(archive);
   let ui_config = UiConfig::load(&ui_config_file, &task_state)
     .await
     .context("failed to load UI configuration")?;
   let UiConfig {
     colors,
     toggle_tag,
+    reminder_command,
+    sync,
+    archive_after_days,
+    max_undo_steps,
     views,
   } = ui_config;
 
@@ -302,9 +476,14 @@ where
   let ui_state_dir_cap = DirCap::for_dir(ui_state_path.0).await?;
   let ui_state_file = ui_state_path.1;
 
-  let tasks_root_cap = DirCap::for_dir(tasks_root).await?;
+  // Each collection lives in a directory of its own, all of which we
+  // watch along with the tasks root.
+  let collections = task_state
+    .collections()
+    .map(str::to_string)
+    .collect::<Vec<_>>();
 
-  let (ui, _) = Ui::new(
+  let (mut ui, root) = Ui::new(
     || {
       Box::new(TermUiData::new(
         tasks_root_cap,
@@ -313,6 +492,10 @@ where
         (ui_state_dir_cap, ui_state_file),
         colors,
         toggle_tag,
+        reminder_command,
+        sync,
+        archive_after_days,
+        max_undo_steps,
       ))
     },
     |id, cap| Box::new(TermUi::new(id, cap, views, ui_state)),
@@ -321,13 +504,27 @@ where
   let (send_event, recv_event) = channel();
   receive_window_resizes(send_event.clone())
     .context("failed to instantiate infrastructure for handling window resize events")?;
+  receive_task_changes(&tasks_root, collections, send_event.clone()).with_context(|| {
+    format!(
+      "failed to watch tasks directory {} for changes",
+      tasks_root.display()
+    )
+  })?;
+  // We may have to hand over the terminal to other programs
+  // temporarily, in which case we must not read any of their input.
+  let (in_, input) = PausableReader::new(in_);
   receive_keys(in_, send_event);
 
+  // Restore the undo history of the last session and offer to replay
+  // operations that got lost because we terminated without saving.
+  let _message = ui.send(root, Message::RestoreHistory).await;
+  let _message = ui.send(root, Message::CheckJournal).await;
+
   // Initially we need to trigger a render in order to have the most
   // recent data presented.
   ui.render(&renderer);
 
-  run_loop(ui, &renderer, &recv_event).await
+  run_loop(ui, root, &renderer, &recv_event, &input).await
 }
 
 /// Run a function after attempting to create a lock file and remove it
@@ -390,16 +587,92 @@ fn run_now() -> Result<()> {
   let ui_config = ui_config()?;
   let ui_state = ui_state()?;
   let tasks_root = tasks_root()?;
+  let trash_root = trash_root()?;
+  let archive_root = archive_root()?;
   let rt = Builder::new_current_thread()
     .build()
     .context("failed to instantiate async runtime")?;
 
   let stdin = stdin();
   let stdout = stdout();
-  let future = run_prog(stdin, stdout.lock(), tasks_root, ui_config, ui_state);
+  let future = run_prog(
+    stdin,
+    stdout.lock(),
+    tasks_root,
+    trash_root,
+    archive_root,
+    ui_config,
+    ui_state,
+  );
   rt.block_on(future)
 }
 
+/// Merge two diverged copies of the task meta data file.
+fn merge_meta(path1: &Path, path2: &Path) -> Result<()> {
+  let rt = Builder::new_current_thread()
+    .build()
+    .context("failed to instantiate async runtime")?;
+
+  rt.block_on(merge_tasks_meta_files(path1, path2))
+}
+
+/// Synchronize the task files with the configured server.
+async fn sync_tasks(tasks_root: PathBuf, archive_root: PathBuf, ui_config: FilePath) -> Result<()> {
+  let ui_config_file = ui_config.0.join(&ui_config.1);
+  let config = load_state_from_file::<Json, SerUiConfig>(&ui_config_file)
+    .await
+    .with_context(|| {
+      format!(
+        "failed to load UI configuration from {}",
+        ui_config_file.display()
+      )
+    })?
+    .and_then(|config| config.sync)
+    .ok_or_else(|| {
+      anyhow!(
+        "task synchronization is not configured in {}",
+        ui_config_file.display()
+      )
+    })?;
+
+  let () = create_dir_all(&tasks_root)
+    .with_context(|| format!("failed to create directory {}", tasks_root.display()))?;
+  let collections = read_collections(&tasks_root).await?;
+  let mut tasks_root_cap = DirCap::for_dir(tasks_root).await?;
+  let summary = sync(&config, &mut tasks_root_cap, &collections, Some(&archive_root))
+    .await
+    .context("failed to synchronize tasks")?;
+  println!("{summary}");
+  Ok(())
+}
+
+/// Synchronize the task files of the default configuration.
+fn sync_now() -> Result<()> {
+  let ui_config = ui_config()?;
+  let tasks_root = tasks_root()?;
+  let archive_root = archive_root()?;
+  let rt = Builder::new_current_thread()
+    .build()
+    .context("failed to instantiate async runtime")?;
+
+  rt.block_on(sync_tasks(tasks_root, archive_root, ui_config))
+}
+
+/// Remove all files from the trash.
+fn empty_trash() -> Result<()> {
+  let trash_root = trash_root()?;
+  let rt = Builder::new_current_thread()
+    .build()
+    .context("failed to instantiate async runtime")?;
+
+  rt.block_on(async {
+    let () = create_dir_all(&trash_root)
+      .with_context(|| format!("failed to create directory {}", trash_root.display()))?;
+    let mut trash = Store::new(DirCap::for_dir(trash_root).await?);
+    trash.empty().await.context("failed to empty trash")
+  })
+}
+
 /// Parse the arguments and run the program.
 fn run_with_args(lock_file: &Path) -> Result<()> {
   match args_os().len() {
@@ -410,12 +683,22 @@ fn run_with_args(lock_file: &Path) -> Result<()> {
 
 USAGE:
   {name} [OPTIONS]
+  {name} --merge-meta <FILE1> <FILE2>
+  {name} sync
+  {name} empty-trash
 
 OPTIONS:
   -f, --force      Force reclamation of stale lock files in case a previous program
                    instance terminated improperly
   -h, --help       Print help information
   -V, --version    Print version information
+  --merge-meta     Merge two diverged copies of the task meta data file, writing
+                   the result to both of them
+
+COMMANDS:
+  sync             Synchronize tasks with the CalDAV server configured in the
+                   UI configuration
+  empty-trash      Remove the files of all deleted tasks for good
 ",
         name = env!("CARGO_CRATE_NAME"),
         version = env!("NOTNOW_VERSION"),
@@ -425,10 +708,21 @@ OPTIONS:
     2 if args_os().any(|arg| &arg == "--force" || &arg == "-f") => {
       with_lockfile(lock_file, true, run_now)
     },
+    2 if args_os().nth(1).as_deref() == Some(OsStr::new("sync")) => sync_now(),
+    2 if args_os().nth(1).as_deref() == Some(OsStr::new("empty-trash")) => {
+      // The trash must not be emptied underneath a running instance.
+      with_lockfile(lock_file, false, empty_trash)
+    },
     2 if args_os().any(|arg| &arg == "--version" || &arg == "-V") => {
       println!("{} {}", env!("CARGO_CRATE_NAME"), env!("NOTNOW_VERSION"));
       Ok(())
     },
+    4 if args_os().nth(1).as_deref() == Some(OsStr::new("--merge-meta")) => {
+      // SANITY: We checked the number of arguments above.
+      let path1 = PathBuf::from(args_os().nth(2).unwrap());
+      let path2 = PathBuf::from(args_os().nth(3).unwrap());
+      merge_meta(&path1, &path2)
+    },
     _ => bail!("encountered unsupported number of program arguments"),
   }
 }
@@ -38,6 +38,98 @@ impl<O, D, T> Ops<O, D, T> {
       _phantom: PhantomData,
     }
   }
+
+  /// Create a new `Ops` object from a previously retrieved history.
+  ///
+  /// `done` contains the operations that can be undone, from the least
+  /// to the most recently executed one, and `undone` those that can be
+  /// redone, starting with the next one to redo. Operations not fitting
+  /// into `max_count` are dropped, starting with the oldest.
+  pub fn with_history(max_count: usize, done: Vec<O>, undone: Vec<O>) -> Self {
+    let mut slf = Self::new(max_count);
+    let skip = done.len().saturating_sub(max_count);
+    for op in done.into_iter().skip(skip) {
+      let () = slf.ops.push_front(Some(op));
+    }
+
+    // Operations to redo occupy the slots at the back, up to the
+    // sentinel value separating them from those executed.
+    let (done, _undone) = slf.counts();
+    let capacity = slf.ops.len() - done - 1;
+    for (idx, op) in undone.into_iter().take(capacity).enumerate() {
+      slf.ops[idx] = Some(op);
+    }
+    slf
+  }
+
+  /// Retrieve the maximum number of operations that we keep record of.
+  pub fn max_count(&self) -> usize {
+    self.ops.len() - 1
+  }
+
+  /// Change the maximum number of operations that we keep record of,
+  /// dropping the oldest ones if necessary.
+  pub fn set_max_count(&mut self, max_count: usize) {
+    let (done, undone) = self.take_history();
+    *self = Self::with_history(max_count, done, undone);
+  }
+
+  /// Forget about all operations for which `affected` returns `true`,
+  /// along with those that could only be undone or redone after them.
+  pub fn forget<F>(&mut self, mut affected: F)
+  where
+    F: FnMut(&O) -> bool,
+  {
+    let max_count = self.max_count();
+    let (mut done, mut undone) = self.take_history();
+    if let Some(idx) = done.iter().rposition(&mut affected) {
+      let _forgotten = done.drain(..=idx);
+    }
+    if let Some(idx) = undone.iter().position(&mut affected) {
+      let () = undone.truncate(idx);
+    }
+    *self = Self::with_history(max_count, done, undone);
+  }
+
+  /// Take the operations that can be undone and redone, in the order
+  /// [`Ops::history`] reports them in, leaving the object empty.
+  fn take_history(&mut self) -> (Vec<O>, Vec<O>) {
+    let len = self.ops.len();
+    let (done, undone) = self.counts();
+    let done = (len - done..len)
+      .filter_map(|idx| self.ops[idx].take())
+      .collect();
+    let undone = (0..undone).filter_map(|idx| self.ops[idx].take()).collect();
+    (done, undone)
+  }
+
+  /// Retrieve the operations that can be undone, from the least to the
+  /// most recently executed one, as well as those that can be redone,
+  /// starting with the next one to redo.
+  pub fn history(&self) -> (Vec<&O>, Vec<&O>) {
+    let len = self.ops.len();
+    let (done, undone) = self.counts();
+    let done = (len - done..len)
+      .filter_map(|idx| self.ops[idx].as_ref())
+      .collect();
+    let undone = (0..undone).filter_map(|idx| self.ops[idx].as_ref()).collect();
+    (done, undone)
+  }
+
+  /// Count the operations that can be undone and redone, respectively.
+  fn counts(&self) -> (usize, usize) {
+    let len = self.ops.len();
+    // Executed operations are located at the front, operations to redo
+    // at the back.
+    let done = (0..len)
+      .rev()
+      .take_while(|idx| self.ops[*idx].is_some())
+      .count();
+    let undone = (0..len - done)
+      .take_while(|idx| self.ops[*idx].is_some())
+      .count();
+    (done, undone)
+  }
 }
 
 impl<O, D, T> Ops<O, D, T>
@@ -235,4 +327,68 @@ pub mod tests {
       assert_eq!(data, 2);
     }
   }
+
+  /// Check that we can retrieve the history of operations and create an
+  /// `Ops` object from it again.
+  #[test]
+  fn history_round_trip() {
+    let mut data = 0;
+    let mut ops = Ops::<AddOp, usize, ()>::new(3);
+
+    for value in [1, 2, 4, 8] {
+      ops.exec(AddOp(value), &mut data);
+    }
+    assert!(ops.undo(&mut data).is_some());
+    assert_eq!(data, 7);
+
+    let values = |ops: &[&AddOp]| ops.iter().map(|op| op.0).collect::<Vec<_>>();
+    let (done, undone) = ops.history();
+    assert_eq!(values(&done), vec![2, 4]);
+    assert_eq!(values(&undone), vec![8]);
+
+    let done = values(&done).into_iter().map(AddOp).collect();
+    let undone = values(&undone).into_iter().map(AddOp).collect();
+    let mut ops = Ops::<AddOp, usize, ()>::with_history(3, done, undone);
+    assert!(ops.redo(&mut data).is_some());
+    assert_eq!(data, 15);
+    assert!(ops.redo(&mut data).is_none());
+
+    // Shrinking the history drops the oldest operations.
+    let () = ops.set_max_count(2);
+    assert_eq!(ops.max_count(), 2);
+    assert!(ops.undo(&mut data).is_some());
+    assert!(ops.undo(&mut data).is_some());
+    assert_eq!(data, 3);
+    assert!(ops.undo(&mut data).is_none());
+
+    let () = ops.set_max_count(4);
+    let (done, undone) = ops.history();
+    assert!(done.is_empty());
+    assert_eq!(values(&undone), vec![4, 8]);
+  }
+
+  /// Check that we can forget about operations affected by some
+  /// external change.
+  #[test]
+  fn forget_operations() {
+    let mut data = 0;
+    let mut ops = Ops::<AddOp, usize, ()>::new(8);
+
+    for value in [1, 2, 4, 8, 16, 32] {
+      ops.exec(AddOp(value), &mut data);
+    }
+    assert!(ops.undo(&mut data).is_some());
+    assert!(ops.undo(&mut data).is_some());
+    assert_eq!(data, 15);
+
+    let values = |ops: &[&AddOp]| ops.iter().map(|op| op.0).collect::<Vec<_>>();
+    let () = ops.forget(|op| op.0 == 2 || op.0 == 32);
+    let (done, undone) = ops.history();
+    assert_eq!(values(&done), vec![4, 8]);
+    assert_eq!(values(&undone), vec![16]);
+
+    assert!(ops.redo(&mut data).is_some());
+    assert!(ops.redo(&mut data).is_none());
+    assert_eq!(data, 31);
+  }
 }
@@ -0,0 +1,102 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::ser::tasks::Priority as SerPriority;
+use crate::ser::ToSerde;
+
+
+/// The priority of a task.
+///
+/// Priorities are ordered, with `Low` being the lowest and `High` the
+/// highest one.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Priority {
+  /// A low priority.
+  Low,
+  /// A medium priority.
+  Medium,
+  /// A high priority.
+  High,
+}
+
+impl Priority {
+  /// Raise the provided (optional) priority by one level.
+  ///
+  /// An unset priority is raised to `Low`. `High` stays unchanged.
+  pub fn raise(priority: Option<Self>) -> Option<Self> {
+    match priority {
+      None => Some(Priority::Low),
+      Some(Priority::Low) => Some(Priority::Medium),
+      Some(Priority::Medium) | Some(Priority::High) => Some(Priority::High),
+    }
+  }
+
+  /// Lower the provided (optional) priority by one level.
+  ///
+  /// Lowering `Low` clears the priority.
+  pub fn lower(priority: Option<Self>) -> Option<Self> {
+    match priority {
+      Some(Priority::High) => Some(Priority::Medium),
+      Some(Priority::Medium) => Some(Priority::Low),
+      Some(Priority::Low) | None => None,
+    }
+  }
+}
+
+impl From<SerPriority> for Priority {
+  fn from(other: SerPriority) -> Self {
+    match other {
+      SerPriority::Low => Priority::Low,
+      SerPriority::Medium => Priority::Medium,
+      SerPriority::High => Priority::High,
+    }
+  }
+}
+
+impl ToSerde for Priority {
+  type Output = SerPriority;
+
+  /// Convert the priority into a serializable one.
+  fn to_serde(&self) -> Self::Output {
+    match self {
+      Priority::Low => SerPriority::Low,
+      Priority::Medium => SerPriority::Medium,
+      Priority::High => SerPriority::High,
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we can raise and lower priorities.
+  #[test]
+  fn raise_lower() {
+    let mut priority = None;
+    let mut raised = Vec::new();
+    for _ in 0..4 {
+      priority = Priority::raise(priority);
+      raised.push(priority);
+    }
+
+    let expected = vec![
+      Some(Priority::Low),
+      Some(Priority::Medium),
+      Some(Priority::High),
+      Some(Priority::High),
+    ];
+    assert_eq!(raised, expected);
+
+    let mut lowered = Vec::new();
+    for _ in 0..4 {
+      priority = Priority::lower(priority);
+      lowered.push(priority);
+    }
+
+    let expected = vec![Some(Priority::Medium), Some(Priority::Low), None, None];
+    assert_eq!(lowered, expected);
+  }
+}
@@ -0,0 +1,277 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Error;
+use anyhow::Result;
+
+use chrono::Days;
+use chrono::Months;
+use chrono::NaiveDate;
+
+
+/// The format of dates as used in `UNTIL` rule parts.
+const UNTIL_DATE_FORMAT: &str = "%Y%m%d";
+
+
+/// The frequency with which a task recurs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Frequency {
+  Daily,
+  Weekly,
+  Monthly,
+  Yearly,
+}
+
+impl FromStr for Frequency {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s.to_ascii_uppercase().as_str() {
+      "DAILY" => Ok(Frequency::Daily),
+      "WEEKLY" => Ok(Frequency::Weekly),
+      "MONTHLY" => Ok(Frequency::Monthly),
+      "YEARLY" => Ok(Frequency::Yearly),
+      _ => bail!("unsupported recurrence frequency '{s}'"),
+    }
+  }
+}
+
+impl Display for Frequency {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    let s = match self {
+      Frequency::Daily => "DAILY",
+      Frequency::Weekly => "WEEKLY",
+      Frequency::Monthly => "MONTHLY",
+      Frequency::Yearly => "YEARLY",
+    };
+    f.write_str(s)
+  }
+}
+
+
+/// A rule describing how a task recurs, in the form of an RFC 5545
+/// `RRULE` value.
+///
+/// Only a subset of the rule parts defined by the RFC is supported:
+/// `FREQ` (with one of `DAILY`, `WEEKLY`, `MONTHLY`, or `YEARLY`),
+/// `INTERVAL`, `COUNT`, and `UNTIL`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recurrence {
+  /// The base frequency of the recurrence.
+  freq: Frequency,
+  /// The number of `freq` units between two occurrences.
+  interval: u32,
+  /// The number of occurrences left, including the current one.
+  count: Option<u32>,
+  /// The date after which the task no longer recurs.
+  until: Option<NaiveDate>,
+}
+
+impl Recurrence {
+  /// Move `date` forward by `n` intervals of the recurrence.
+  fn step(&self, date: NaiveDate, n: u32) -> Option<NaiveDate> {
+    let n = self.interval.checked_mul(n)?;
+    match self.freq {
+      Frequency::Daily => date.checked_add_days(Days::new(u64::from(n))),
+      Frequency::Weekly => date.checked_add_days(Days::new(7 * u64::from(n))),
+      Frequency::Monthly => date.checked_add_months(Months::new(n)),
+      Frequency::Yearly => date.checked_add_months(Months::new(n.checked_mul(12)?)),
+    }
+  }
+
+  /// Advance the recurrence past the occurrence due at `due`,
+  /// returning the due date of the next occurrence.
+  ///
+  /// `None` is returned if there is no further occurrence. Monthly and
+  /// yearly recurrences falling onto a day that does not exist in the
+  /// target month are clamped to the last day of said month.
+  ///
+  /// `COUNT` refers to the number of occurrences of the entire series
+  /// and other clients interpret it as such. Rather than counting down,
+  /// a rule using it is converted into the equivalent one ending with
+  /// the last occurrence (`UNTIL`), counted from `due`.
+  pub fn advance(&mut self, due: NaiveDate) -> Option<NaiveDate> {
+    if let Some(count) = self.count.take() {
+      self.until = self.step(due, count - 1);
+    }
+
+    let next = self.step(due, 1)?;
+    if let Some(until) = self.until {
+      if next > until {
+        return None
+      }
+    }
+    Some(next)
+  }
+}
+
+impl FromStr for Recurrence {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let mut freq = None;
+    let mut interval = 1;
+    let mut count = None;
+    let mut until = None;
+
+    for part in s.split(';').filter(|part| !part.is_empty()) {
+      let (name, value) = part
+        .split_once('=')
+        .ok_or_else(|| anyhow!("invalid recurrence rule part '{part}'"))?;
+
+      match name.to_ascii_uppercase().as_str() {
+        "FREQ" => freq = Some(Frequency::from_str(value)?),
+        "INTERVAL" => {
+          interval = u32::from_str(value)
+            .ok()
+            .filter(|interval| *interval > 0)
+            .ok_or_else(|| anyhow!("invalid recurrence interval '{value}'"))?
+        },
+        "COUNT" => {
+          count = u32::from_str(value)
+            .ok()
+            .filter(|count| *count > 0)
+            .map(Some)
+            .ok_or_else(|| anyhow!("invalid recurrence count '{value}'"))?
+        },
+        "UNTIL" => {
+          // We only track dates, so any time component is ignored.
+          let date = value.get(..8).unwrap_or(value);
+          let date = NaiveDate::parse_from_str(date, UNTIL_DATE_FORMAT)
+            .with_context(|| format!("invalid recurrence end date '{value}'"))?;
+          until = Some(date)
+        },
+        _ => bail!("unsupported recurrence rule part '{name}'"),
+      }
+    }
+
+    if count.is_some() && until.is_some() {
+      bail!("recurrence rule must not contain both COUNT and UNTIL")
+    }
+
+    let freq = freq.ok_or_else(|| anyhow!("recurrence rule lacks a frequency (FREQ)"))?;
+    Ok(Self {
+      freq,
+      interval,
+      count,
+      until,
+    })
+  }
+}
+
+impl Display for Recurrence {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(f, "FREQ={}", self.freq)?;
+    if self.interval != 1 {
+      write!(f, ";INTERVAL={}", self.interval)?;
+    }
+    if let Some(count) = self.count {
+      write!(f, ";COUNT={count}")?;
+    }
+    if let Some(until) = self.until {
+      write!(f, ";UNTIL={}", until.format(UNTIL_DATE_FORMAT))?;
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Create a `NaiveDate` from the given components.
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+  }
+
+
+  /// Check that we can parse and format recurrence rules.
+  #[test]
+  fn parse_format() {
+    let rules = [
+      "FREQ=DAILY",
+      "FREQ=WEEKLY;INTERVAL=2",
+      "FREQ=MONTHLY;COUNT=3",
+      "FREQ=YEARLY;UNTIL=20250101",
+    ];
+    for rule in rules {
+      let recurrence = Recurrence::from_str(rule).unwrap();
+      assert_eq!(recurrence.to_string(), rule);
+    }
+
+    let recurrence = Recurrence::from_str("freq=weekly;interval=1").unwrap();
+    assert_eq!(recurrence.to_string(), "FREQ=WEEKLY");
+
+    let recurrence = Recurrence::from_str("FREQ=DAILY;UNTIL=20250101T120000Z").unwrap();
+    assert_eq!(recurrence.until, Some(date(2025, 1, 1)));
+  }
+
+  /// Make sure that we reject invalid and unsupported rules.
+  #[test]
+  fn parse_invalid() {
+    let rules = [
+      "",
+      "INTERVAL=2",
+      "FREQ=HOURLY",
+      "FREQ=DAILY;INTERVAL=0",
+      "FREQ=DAILY;COUNT=x",
+      "FREQ=DAILY;UNTIL=2025",
+      "FREQ=WEEKLY;BYDAY=MO",
+      "FREQ=DAILY;COUNT=2;UNTIL=20250101",
+      "FREQ",
+    ];
+    for rule in rules {
+      assert!(Recurrence::from_str(rule).is_err(), "{rule}");
+    }
+  }
+
+  /// Check that we can advance recurrences to their next occurrence.
+  #[test]
+  fn advance() {
+    let mut recurrence = Recurrence::from_str("FREQ=DAILY").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 12, 31)), Some(date(2024, 1, 1)));
+
+    let mut recurrence = Recurrence::from_str("FREQ=WEEKLY;INTERVAL=2").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 4, 1)), Some(date(2023, 4, 15)));
+
+    let mut recurrence = Recurrence::from_str("FREQ=MONTHLY").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 1, 31)), Some(date(2023, 2, 28)));
+
+    let mut recurrence = Recurrence::from_str("FREQ=YEARLY").unwrap();
+    assert_eq!(recurrence.advance(date(2024, 2, 29)), Some(date(2025, 2, 28)));
+  }
+
+  /// Check that recurrences limited in their number of occurrences or
+  /// time end eventually.
+  #[test]
+  fn advance_limited() {
+    let mut recurrence = Recurrence::from_str("FREQ=DAILY;COUNT=2").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 4, 1)), Some(date(2023, 4, 2)));
+    assert_eq!(recurrence.to_string(), "FREQ=DAILY;UNTIL=20230402");
+    assert_eq!(recurrence.advance(date(2023, 4, 2)), None);
+
+    // The end of monthly series does not drift due to clamping.
+    let mut recurrence = Recurrence::from_str("FREQ=MONTHLY;COUNT=3").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 1, 31)), Some(date(2023, 2, 28)));
+    assert_eq!(recurrence.advance(date(2023, 2, 28)), Some(date(2023, 3, 28)));
+    assert_eq!(recurrence.to_string(), "FREQ=MONTHLY;UNTIL=20230331");
+    assert_eq!(recurrence.advance(date(2023, 3, 28)), None);
+
+    let mut recurrence = Recurrence::from_str("FREQ=DAILY;COUNT=1").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 4, 1)), None);
+
+    let mut recurrence = Recurrence::from_str("FREQ=WEEKLY;UNTIL=20230410").unwrap();
+    assert_eq!(recurrence.advance(date(2023, 4, 1)), Some(date(2023, 4, 8)));
+    assert_eq!(recurrence.advance(date(2023, 4, 8)), None);
+  }
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::rc::Rc;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::tasks::Task;
+
+
+/// Find the reminders of the given tasks that fire in the time range
+/// (`from`, `to`].
+///
+/// Returned are the tasks with at least one reminder firing in said
+/// range as well as the time of the earliest reminder after `to`, if
+/// any. Completed tasks are not considered.
+pub fn due_reminders<'t, I>(
+  tasks: I,
+  from: DateTime<Utc>,
+  to: DateTime<Utc>,
+) -> (Vec<Rc<Task>>, Option<DateTime<Utc>>)
+where
+  I: IntoIterator<Item = &'t Rc<Task>>,
+{
+  let mut due = Vec::new();
+  let mut next = None::<DateTime<Utc>>;
+
+  for task in tasks {
+    if task.completed().is_some() {
+      continue
+    }
+
+    let reminders = task.reminders();
+    if reminders.iter().any(|time| *time > from && *time <= to) {
+      due.push(task.clone())
+    }
+
+    if let Some(time) = reminders.into_iter().filter(|time| *time > to).min() {
+      next = Some(next.map_or(time, |next| next.min(time)));
+    }
+  }
+
+  (due, next)
+}
+
+/// Run the user provided reminder `command` with the given text.
+///
+/// The command is run through the shell, meaning that it may contain
+/// arguments. The text is provided to it as the final argument. The
+/// caller is responsible for waiting for the returned [`Child`].
+pub fn notify(command: &str, text: &str) -> Result<Child> {
+  let script = format!(r#"{command} "$1""#);
+
+  Command::new("sh")
+    .arg("-c")
+    .arg(&script)
+    .arg("sh")
+    .arg(text)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .with_context(|| format!("failed to run reminder command `{command}`"))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::read_to_string;
+
+  use chrono::Duration;
+
+  use tempfile::NamedTempFile;
+
+  use crate::ser::tasks::Alarm as SerAlarm;
+  use crate::ser::tasks::Task as SerTask;
+  use crate::ser::tasks::Trigger as SerTrigger;
+  use crate::tasks::Tasks;
+
+
+  /// Check that we correctly determine due reminders as well as the
+  /// next one to fire.
+  #[test]
+  fn find_due_reminders() {
+    let now = Utc::now();
+    let alarm = |offset| SerAlarm {
+      trigger: SerTrigger::Absolute(now + Duration::minutes(offset)),
+      properties: Vec::new(),
+    };
+
+    let mut task1 = SerTask::new("task1");
+    task1.alarms = vec![alarm(-5), alarm(10)];
+    let mut task2 = SerTask::new("task2");
+    task2.alarms = vec![alarm(-1)];
+    task2.completed = Some(now);
+    let mut task3 = SerTask::new("task3");
+    task3.alarms = vec![alarm(5)];
+    let task4 = SerTask::new("task4");
+
+    let tasks = Tasks::with_serde_tasks(vec![task1, task2, task3, task4]).unwrap();
+
+    let (due, next) = tasks.iter(|iter| due_reminders(iter, now - Duration::minutes(10), now));
+    let due = due.iter().map(|task| task.summary()).collect::<Vec<_>>();
+    assert_eq!(due, vec!["task1"]);
+    assert_eq!(next, Some(now + Duration::minutes(5)));
+
+    let (due, next) = tasks.iter(|iter| due_reminders(iter, now, now + Duration::hours(1)));
+    let due = due.iter().map(|task| task.summary()).collect::<Vec<_>>();
+    assert_eq!(due, vec!["task1", "task3"]);
+    assert_eq!(next, None);
+  }
+
+  /// Make sure that we can run a reminder command.
+  #[test]
+  fn run_reminder_command() {
+    let file = NamedTempFile::new().unwrap();
+    let command = format!("echo > {}", file.path().display());
+    let status = notify(&command, "reminder text").unwrap().wait().unwrap();
+    assert!(status.success());
+    assert_eq!(read_to_string(file.path()).unwrap(), "reminder text\n");
+  }
+}
@@ -2,40 +2,474 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::convert::TryFrom;
+use std::panic::catch_unwind;
 use std::str::FromStr as _;
 
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
 use anyhow::Error;
 use anyhow::Result;
 
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::SubsecRound as _;
+use chrono::Utc;
+
+use icalendar::parser::read_calendar;
+use icalendar::parser::unfold;
+use icalendar::parser::Component as ParsedComponent;
 use icalendar::Calendar;
+use icalendar::CalendarComponent;
+use icalendar::CalendarDateTime;
 use icalendar::Component as _;
+use icalendar::DatePerhapsTime;
+use icalendar::EventLike as _;
+use icalendar::Property as IcalProperty;
 use icalendar::Todo;
+use icalendar::TodoStatus;
 
+use crate::ser::id::uuid_from_name;
 use crate::ser::tags::Tag;
+use crate::ser::tasks::Alarm;
+use crate::ser::tasks::Component;
 use crate::ser::tasks::Id as TaskId;
+use crate::ser::tasks::Priority;
+use crate::ser::tasks::Property;
+use crate::ser::tasks::Related;
 use crate::ser::tasks::Task;
+use crate::ser::tasks::Tasks;
+use crate::ser::tasks::Trigger;
+use crate::ser::tasks::Unknown;
 
+use super::util::component_from_parsed;
+use super::util::emit_calendar;
 use super::util::emit_list;
+use super::util::emit_text_list;
+use super::util::escape_text;
+use super::util::format_duration;
+use super::util::format_utc_date_time;
+use super::util::insert_into_todo;
+use super::util::parse_duration;
 use super::util::parse_list;
-use super::util::try_from_calendar_with_single_todo;
+use super::util::parse_utc_date_time;
+use super::util::property_from_parsed;
+use super::util::text_list_from_parsed;
+use super::util::unescape_text;
+use super::util::unknown_from_parsed;
+use super::util::value_from_parsed;
 use super::SerICal;
 
 
+/// The name of the property storing a task's unique identifier.
+const UID_PROPERTY: &str = "UID";
 /// The name of the property used for storing a task's tags.
 const TAGS_PROPERTY: &str = "TAGS";
+/// The name of the property storing the names of a task's tags.
+const CATEGORIES_PROPERTY: &str = "CATEGORIES";
 /// The name of the property used for storing a task's "position".
 const POSITION_PROPERTY: &str = "POSITION";
+/// The name of the property storing a task's recurrence rule.
+const RRULE_PROPERTY: &str = "RRULE";
+/// The name of the property identifying an instance of a recurring
+/// task that is overridden.
+const RECURRENCE_ID_PROPERTY: &str = "RECURRENCE-ID";
+/// The name of the property relating a task to another one.
+const RELATED_TO_PROPERTY: &str = "RELATED-TO";
+/// The name of the parameter specifying the type of a relationship.
+const RELTYPE_PARAMETER: &str = "RELTYPE";
+/// The relationship type referring to a task's parent.
+const RELTYPE_PARENT: &str = "PARENT";
+/// The relationship type referring to a task another one depends on.
+const RELTYPE_DEPENDS_ON: &str = "DEPENDS-ON";
+/// The name of the component representing an alarm.
+const ALARM_COMPONENT: &str = "VALARM";
+/// The name of the property describing when an alarm fires.
+const TRIGGER_PROPERTY: &str = "TRIGGER";
+/// The name of the property describing what an alarm does.
+const ACTION_PROPERTY: &str = "ACTION";
+/// The name of the property containing the text displayed by an alarm.
+const DESCRIPTION_PROPERTY: &str = "DESCRIPTION";
+/// The name of the parameter specifying what a relative trigger is
+/// relative to.
+const RELATED_PARAMETER: &str = "RELATED";
+/// The name of the parameter specifying the type of a property value.
+const VALUE_PARAMETER: &str = "VALUE";
+/// The name of the property storing a task's creation time.
+const CREATED_PROPERTY: &str = "CREATED";
+/// The name of the property storing a task's last modification time.
+const LAST_MODIFIED_PROPERTY: &str = "LAST-MODIFIED";
+/// The name of the property storing a task's start date.
+const DTSTART_PROPERTY: &str = "DTSTART";
+/// The name of the property storing a task's due date.
+const DUE_PROPERTY: &str = "DUE";
+/// The name of the property storing a task's priority.
+const PRIORITY_PROPERTY: &str = "PRIORITY";
+/// The name of the property storing a task's completion status.
+const STATUS_PROPERTY: &str = "STATUS";
+/// The properties that we interpret. Everything else is preserved
+/// as-is.
+const KNOWN_PROPERTIES: [&str; 15] = [
+  UID_PROPERTY,
+  "DTSTAMP",
+  CREATED_PROPERTY,
+  LAST_MODIFIED_PROPERTY,
+  "SUMMARY",
+  "DESCRIPTION",
+  TAGS_PROPERTY,
+  CATEGORIES_PROPERTY,
+  DTSTART_PROPERTY,
+  DUE_PROPERTY,
+  PRIORITY_PROPERTY,
+  RRULE_PROPERTY,
+  STATUS_PROPERTY,
+  "COMPLETED",
+  POSITION_PROPERTY,
+];
+/// The properties that we interpret, but that other clients may use
+/// with values we cannot represent. Those are additionally preserved
+/// as-is for as long as the data they map to is not changed.
+const VERBATIM_PROPERTIES: [&str; 4] = [
+  DTSTART_PROPERTY,
+  DUE_PROPERTY,
+  PRIORITY_PROPERTY,
+  STATUS_PROPERTY,
+];
+
+
+/// Convert a [`DatePerhapsTime`] into the date it refers to.
+///
+/// We only track start and due dates at the granularity of days. A
+/// time component, as may have been set by other clients, is ignored.
+fn date_perhaps_time_to_date(date: DatePerhapsTime) -> NaiveDate {
+  match date {
+    DatePerhapsTime::Date(date) => date,
+    DatePerhapsTime::DateTime(date_time) => match date_time {
+      CalendarDateTime::Floating(date_time) => date_time.date(),
+      CalendarDateTime::Utc(date_time) => date_time.with_timezone(&Local).date_naive(),
+      CalendarDateTime::WithTimezone { date_time, .. } => date_time.date(),
+    },
+  }
+}
+
+
+/// Create a [`Todo`] containing only the provided property, for the
+/// purpose of interpreting it.
+fn todo_with_property(property: &Property) -> Todo {
+  let mut ical_property = IcalProperty::new(&property.name.to_ascii_uppercase(), &property.value);
+  for (key, value) in &property.params {
+    ical_property.add_parameter(key, value.as_deref().unwrap_or_default());
+  }
+
+  let mut todo = Todo::new();
+  todo.append_property(ical_property);
+  todo
+}
+
+
+/// Check whether a property we interpret has a value that we cannot
+/// represent, meaning that it has to be preserved as-is.
+fn is_lossy(property: &Property) -> bool {
+  let todo = todo_with_property(property);
+  if property.name.eq_ignore_ascii_case(DTSTART_PROPERTY) {
+    // We do not track times and time zones.
+    !matches!(todo.get_start(), Some(DatePerhapsTime::Date(..)))
+  } else if property.name.eq_ignore_ascii_case(DUE_PROPERTY) {
+    !matches!(todo.get_due(), Some(DatePerhapsTime::Date(..)))
+  } else if property.name.eq_ignore_ascii_case(PRIORITY_PROPERTY) {
+    // We only track three priority levels, each mapping to a single
+    // value.
+    let value = todo
+      .get_priority()
+      .and_then(priority_from_ical)
+      .map(priority_to_ical);
+    value.map(|value| value.to_string()).as_deref() != Some(property.value.as_str())
+  } else if property.name.eq_ignore_ascii_case(STATUS_PROPERTY) {
+    // Statuses such as `CANCELLED` or `IN-PROCESS` are not tracked.
+    !matches!(
+      todo.get_status(),
+      Some(TodoStatus::Completed | TodoStatus::NeedsAction)
+    )
+  } else {
+    false
+  }
+}
+
+
+/// Check whether a property preserved as-is still reflects the state
+/// of the provided task, i.e., whether the data it maps to remained
+/// unchanged. Properties we do not interpret always do.
+fn is_current(property: &Property, task: &Task) -> bool {
+  let todo = todo_with_property(property);
+  if property.name.eq_ignore_ascii_case(DTSTART_PROPERTY) {
+    todo.get_start().map(date_perhaps_time_to_date) == task.start
+  } else if property.name.eq_ignore_ascii_case(DUE_PROPERTY) {
+    todo.get_due().map(date_perhaps_time_to_date) == task.due
+  } else if property.name.eq_ignore_ascii_case(PRIORITY_PROPERTY) {
+    todo.get_priority().and_then(priority_from_ical) == task.priority
+  } else if property.name.eq_ignore_ascii_case(STATUS_PROPERTY) {
+    // Completing the task overrides any other status.
+    task.completed.is_none()
+  } else {
+    true
+  }
+}
+
+
+/// Convert a [`Priority`] into the RFC 5545 `PRIORITY` value
+/// representing it.
+fn priority_to_ical(priority: Priority) -> u32 {
+  match priority {
+    Priority::High => 1,
+    Priority::Medium => 5,
+    Priority::Low => 9,
+  }
+}
+
+
+/// Convert an RFC 5545 `PRIORITY` value into a [`Priority`].
+///
+/// Per the RFC, values 1 to 4 are considered high, 5 medium, and 6 to 9
+/// low priority. 0 marks the priority as undefined.
+fn priority_from_ical(priority: u32) -> Option<Priority> {
+  match priority {
+    1..=4 => Some(Priority::High),
+    5 => Some(Priority::Medium),
+    6..=9 => Some(Priority::Low),
+    _ => None,
+  }
+}
+
+
+/// Determine the ID of a task from its `UID`.
+///
+/// Other clients may use UIDs that are not UUIDs. For those we derive
+/// a stable ID from the UID text.
+fn id_from_uid(uid: &str) -> TaskId {
+  TaskId::from_str(uid).unwrap_or_else(|_| uuid_from_name(uid))
+}
+
+
+/// Create a `RELATED-TO` property referring to the task with the given
+/// ID, using the provided relationship type.
+fn related_to_property(reltype: &str, id: TaskId) -> Property {
+  Property {
+    name: RELATED_TO_PROPERTY.to_string(),
+    params: vec![(RELTYPE_PARAMETER.to_string(), Some(reltype.to_string()))],
+    value: id.as_hyphenated().to_string(),
+  }
+}
+
+
+/// Retrieve the ID of the task referred to by a property, if it is a
+/// `RELATED-TO` property of the given relationship type.
+///
+/// Per RFC 5545, a `RELATED-TO` property without `RELTYPE` parameter
+/// refers to the parent.
+fn related_from_property(property: &Property, reltype: &str) -> Option<TaskId> {
+  if !property.name.eq_ignore_ascii_case(RELATED_TO_PROPERTY) {
+    return None
+  }
+
+  let actual = property
+    .params
+    .iter()
+    .find(|(key, _value)| key.eq_ignore_ascii_case(RELTYPE_PARAMETER))
+    .and_then(|(_key, value)| value.as_deref())
+    .unwrap_or(RELTYPE_PARENT);
+  if !actual.eq_ignore_ascii_case(reltype) {
+    return None
+  }
+  // We can only refer to tasks having one of our IDs. Everything else
+  // is preserved as an unknown property.
+  TaskId::from_str(&property.value).ok()
+}
+
+
+/// Create a `VALARM` component representing an alarm of the task with
+/// the given summary.
+///
+/// Alarms lacking an `ACTION` (i.e., ones created by us) are emitted as
+/// alarms displaying the task's summary.
+fn alarm_to_component(alarm: &Alarm, summary: &str) -> Component {
+  let trigger = match alarm.trigger {
+    Trigger::Absolute(time) => Property {
+      name: TRIGGER_PROPERTY.to_string(),
+      params: vec![(VALUE_PARAMETER.to_string(), Some("DATE-TIME".to_string()))],
+      value: format_utc_date_time(time),
+    },
+    Trigger::Relative { offset, related } => {
+      let params = match related {
+        Related::Start => Vec::new(),
+        Related::End => vec![(RELATED_PARAMETER.to_string(), Some("END".to_string()))],
+      };
+      Property {
+        name: TRIGGER_PROPERTY.to_string(),
+        params,
+        value: format_duration(offset),
+      }
+    },
+  };
+
+  let mut properties = Vec::new();
+  let has_action = alarm
+    .properties
+    .iter()
+    .any(|property| property.name.eq_ignore_ascii_case(ACTION_PROPERTY));
+  if !has_action {
+    let action = Property {
+      name: ACTION_PROPERTY.to_string(),
+      params: Vec::new(),
+      value: "DISPLAY".to_string(),
+    };
+    let description = Property {
+      name: DESCRIPTION_PROPERTY.to_string(),
+      params: Vec::new(),
+      value: escape_text(summary),
+    };
+    let () = properties.extend([action, description]);
+  }
+  let () = properties.extend(alarm.properties.iter().cloned());
+
+  // We conventionally place the trigger right after the action, which
+  // is where most clients have it as well.
+  let idx = properties
+    .iter()
+    .position(|property| property.name.eq_ignore_ascii_case(ACTION_PROPERTY))
+    .map(|idx| idx + 1)
+    .unwrap_or(0);
+  let () = properties.insert(idx, trigger);
+
+  Component {
+    name: ALARM_COMPONENT.to_string(),
+    properties,
+    components: Vec::new(),
+  }
+}
+
+
+/// Retrieve an [`Alarm`] from a component, if it is a `VALARM`
+/// component with a trigger that we understand.
+///
+/// Alarms displaying the summary of the task they belong to are
+/// considered as created by us. Their action and description are
+/// dropped, so that they get regenerated from the current summary.
+fn alarm_from_component(component: &Component, summary: &str) -> Option<Alarm> {
+  if !component.name.eq_ignore_ascii_case(ALARM_COMPONENT) || !component.components.is_empty() {
+    return None
+  }
+
+  let idx = component
+    .properties
+    .iter()
+    .position(|property| property.name.eq_ignore_ascii_case(TRIGGER_PROPERTY))?;
+  let trigger = &component.properties[idx];
+  let param = |name: &str| {
+    trigger
+      .params
+      .iter()
+      .find(|(key, _value)| key.eq_ignore_ascii_case(name))
+      .and_then(|(_key, value)| value.as_deref())
+  };
+
+  let trigger = match param(VALUE_PARAMETER) {
+    Some(value) if value.eq_ignore_ascii_case("DATE-TIME") => {
+      Trigger::Absolute(parse_utc_date_time(&trigger.value)?)
+    },
+    Some(value) if !value.eq_ignore_ascii_case("DURATION") => return None,
+    Some(..) | None => {
+      let offset = parse_duration(&trigger.value)?;
+      let related = match param(RELATED_PARAMETER) {
+        Some(related) if related.eq_ignore_ascii_case("END") => Related::End,
+        Some(..) | None => Related::Start,
+      };
+      Trigger::Relative { offset, related }
+    },
+  };
+
+  let mut properties = component.properties.clone();
+  let _trigger = properties.remove(idx);
+
+  let summary = escape_text(summary);
+  let is_ours = properties.len() == 2
+    && properties.iter().all(|property| {
+      let value = &property.value;
+      (property.name.eq_ignore_ascii_case(ACTION_PROPERTY) && value == "DISPLAY")
+        || (property.name.eq_ignore_ascii_case(DESCRIPTION_PROPERTY) && *value == summary)
+    });
+  if is_ours {
+    let () = properties.clear();
+  }
+
+  Some(Alarm {
+    trigger,
+    properties,
+  })
+}
 
 
 impl From<&Task> for Todo {
   fn from(task: &Task) -> Self {
     let mut todo = Todo::new();
-    todo.uid(&task.id.as_hyphenated().to_string());
+    // A UID that is not a UUID is preserved verbatim with the unknown
+    // properties. It takes precedence over the ID derived from it.
+    let uid = task
+      .unknown
+      .properties
+      .iter()
+      .find(|property| property.name.eq_ignore_ascii_case(UID_PROPERTY))
+      .map(|property| property.value.clone())
+      .unwrap_or_else(|| task.id.as_hyphenated().to_string());
+    // Interpreted properties preserved as-is take precedence over the
+    // data they map to for as long as the latter is unchanged.
+    let verbatim = |name: &str| {
+      task.unknown.properties.iter().any(|property| {
+        property.name.eq_ignore_ascii_case(name) && is_current(property, task)
+      })
+    };
+    todo.uid(&uid);
     todo.summary(&task.summary);
 
+    if let Some(notes) = &task.notes {
+      todo.description(&escape_text(notes));
+    }
     if let Some(tags) = emit_list(&task.tags) {
       todo.add_property(TAGS_PROPERTY, &tags);
     }
+    if let Some(categories) = emit_text_list(&task.categories) {
+      todo.add_property(CATEGORIES_PROPERTY, &categories);
+    }
+    if let Some(start) = task.start {
+      if !verbatim(DTSTART_PROPERTY) {
+        todo.starts(start);
+      }
+    }
+    if let Some(due) = task.due {
+      if !verbatim(DUE_PROPERTY) {
+        todo.due(due);
+      }
+    }
+    if let Some(priority) = task.priority {
+      if !verbatim(PRIORITY_PROPERTY) {
+        todo.priority(priority_to_ical(priority));
+      }
+    }
+    if let Some(recurrence) = &task.recurrence {
+      todo.add_property(RRULE_PROPERTY, recurrence);
+    }
+    if let Some(completed) = task.completed {
+      todo.status(TodoStatus::Completed);
+      todo.completed(completed);
+    } else if task.completion_recorded && !verbatim(STATUS_PROPERTY) {
+      todo.status(TodoStatus::NeedsAction);
+    }
+    if let Some(created) = task.created {
+      todo.add_property(CREATED_PROPERTY, &format_utc_date_time(created));
+    }
+    if let Some(modified) = task.modified {
+      todo.add_property(LAST_MODIFIED_PROPERTY, &format_utc_date_time(modified));
+    }
+    todo.timestamp(task.stamp);
     if let Some(position) = &task.position {
       todo.add_property(POSITION_PROPERTY, &position.to_string());
     }
@@ -58,20 +492,46 @@ impl TryFrom<&Todo> for Task {
   type Error = Error;
 
   fn try_from(todo: &Todo) -> Result<Self, Self::Error> {
-    // TODO: Ideally we would hook up the TODO's completion state. The
-    //       problem is that currently doing so would require a lot of
-    //       outside knowledge as to which tag actually maps to that.
-
     let id = todo
       .get_uid()
-      .map(TaskId::from_str)
-      .transpose()?
+      .map(id_from_uid)
       .unwrap_or_else(TaskId::new_v4);
     let summary = todo.get_summary().unwrap_or("").to_string();
+    let notes = todo.get_description().map(unescape_text);
     let tags = todo
       .property_value(TAGS_PROPERTY)
       .map(parse_list::<Tag>)
       .unwrap_or_else(|| Ok(Vec::new()))?;
+    let start = todo.get_start().map(date_perhaps_time_to_date);
+    let due = todo.get_due().map(date_perhaps_time_to_date);
+    let priority = todo.get_priority().and_then(priority_from_ical);
+    let recurrence = todo.property_value(RRULE_PROPERTY).map(str::to_string);
+    let completed = todo.get_completed().or_else(|| {
+      // Not all clients may record the time of completion. If a task is
+      // marked as completed without it, fall back to the time stamp of
+      // the object and, as a last resort, to the current time.
+      if let Some(TodoStatus::Completed) = todo.get_status() {
+        let completed = todo
+          .get_timestamp()
+          .unwrap_or_else(|| Utc::now().trunc_subsecs(0));
+        Some(completed)
+      } else {
+        None
+      }
+    });
+    // Data written before completion got tracked lack a status.
+    let completion_recorded = todo.property_value(STATUS_PROPERTY).is_some() || completed.is_some();
+    let created = todo
+      .property_value(CREATED_PROPERTY)
+      .and_then(parse_utc_date_time);
+    let modified = todo
+      .property_value(LAST_MODIFIED_PROPERTY)
+      .and_then(parse_utc_date_time);
+    // The time stamp is mandatory, but if it is missing nonetheless we
+    // just use the current time.
+    let stamp = todo
+      .get_timestamp()
+      .unwrap_or_else(|| Utc::now().trunc_subsecs(0));
     let position = todo
       .property_value(POSITION_PROPERTY)
       .map(f64::from_str)
@@ -80,34 +540,257 @@ impl TryFrom<&Todo> for Task {
     Ok(Task {
       id,
       summary,
+      notes,
       tags,
+      // Categories may be spread over multiple properties, which
+      // `icalendar` would collapse. They are filled in by the caller.
+      categories: Vec::new(),
+      start,
+      due,
+      priority,
+      recurrence,
+      completed,
+      completion_recorded,
+      created,
+      modified,
+      stamp,
+      // Relations to other tasks as well as unknown data can only be
+      // retrieved from the parsed representation and are filled in by
+      // the caller.
+      parent: None,
+      depends_on: Vec::new(),
+      alarms: Vec::new(),
       position,
+      unknown: Unknown::default(),
+      // The file name is not part of the serialized representation.
+      file_name: None,
+      collection: None,
     })
   }
 }
 
 
-impl TryFrom<&Calendar> for Task {
-  type Error = Error;
+/// Emit the `VTODO` component representing the given task.
+fn todo_to_ical_string(task: &Task) -> String {
+  let calendar = Calendar::from(task);
+  // `RELATED-TO` properties may legitimately occur multiple times, but
+  // `icalendar` would collapse them. So we emit the ones referring to
+  // other tasks ourselves.
+  let related = task
+    .parent
+    .map(|parent| related_to_property(RELTYPE_PARENT, parent))
+    .into_iter()
+    .chain(
+      task
+        .depends_on
+        .iter()
+        .map(|id| related_to_property(RELTYPE_DEPENDS_ON, *id)),
+    )
+    .collect::<Vec<_>>();
+  // The same goes for alarms, which are components that `icalendar`
+  // cannot represent.
+  let components = task
+    .alarms
+    .iter()
+    .map(|alarm| alarm_to_component(alarm, &task.summary))
+    .chain(task.unknown.components.iter().cloned())
+    .collect::<Vec<_>>();
+  let unknown = task
+    .unknown
+    .properties
+    .iter()
+    .filter(|property| !property.name.eq_ignore_ascii_case(UID_PROPERTY))
+    .filter(|property| is_current(property, task));
+  let ical = insert_into_todo(calendar.to_string(), related.iter().chain(unknown), &components);
+
+  // SANITY: The calendar contains exactly one `VTODO` component.
+  let start = ical.find("BEGIN:VTODO\r\n").unwrap();
+  let end = ical.rfind("END:VTODO\r\n").unwrap() + "END:VTODO\r\n".len();
+  ical[start..end].to_string()
+}
+
+
+/// Create a [`Task`] from a parsed `VTODO` component.
+fn task_from_parsed(component: &ParsedComponent<'_>) -> Result<Task> {
+  let mut unknown = unknown_from_parsed(component, &KNOWN_PROPERTIES);
+  let verbatim = component
+    .properties
+    .iter()
+    .filter(|property| {
+      VERBATIM_PROPERTIES
+        .iter()
+        .any(|name| property.name.as_str().eq_ignore_ascii_case(name))
+    })
+    .map(property_from_parsed)
+    .filter(is_lossy);
+  let () = unknown.properties.extend(verbatim);
+  if let Some(uid) = value_from_parsed(component, UID_PROPERTY) {
+    if TaskId::from_str(uid).is_err() {
+      let property = Property {
+        name: UID_PROPERTY.to_string(),
+        params: Vec::new(),
+        value: uid.to_string(),
+      };
+      let () = unknown.properties.insert(0, property);
+    }
+  }
+  let categories = text_list_from_parsed(component, CATEGORIES_PROPERTY);
+
+  let todo = match CalendarComponent::from(component.clone()) {
+    CalendarComponent::Todo(todo) => todo,
+    // SANITY: The caller only provides `VTODO` components, which
+    //         `icalendar` always converts into a `Todo`.
+    _ => unreachable!(),
+  };
+  let mut task = Task::try_from(&todo).with_context(|| {
+    format!(
+      "failed to convert TODO {} into object",
+      todo.get_uid().unwrap_or("<undefined>")
+    )
+  })?;
+  task.categories = categories;
+  task.parent = unknown
+    .properties
+    .iter()
+    .enumerate()
+    .find_map(|(idx, property)| {
+      related_from_property(property, RELTYPE_PARENT).map(|parent| (idx, parent))
+    })
+    .map(|(idx, parent)| {
+      let _property = unknown.properties.remove(idx);
+      parent
+    });
+  let () = unknown.properties.retain(|property| {
+    if let Some(id) = related_from_property(property, RELTYPE_DEPENDS_ON) {
+      let () = task.depends_on.push(id);
+      false
+    } else {
+      true
+    }
+  });
+  let () = unknown.components.retain(|component| {
+    if let Some(alarm) = alarm_from_component(component, &task.summary) {
+      let () = task.alarms.push(alarm);
+      false
+    } else {
+      true
+    }
+  });
+  task.unknown = unknown;
+  Ok(task)
+}
+
+
+/// Create the tasks described by the `VTODO` components of an iCalendar
+/// string.
+///
+/// All other components found on the calendar level, such as
+/// `VTIMEZONE` definitions, are preserved with each of the tasks.
+/// `VTODO` components overriding individual instances of a recurring
+/// task (i.e., ones with a `RECURRENCE-ID` sharing the UID of another
+/// `VTODO`) are not interpreted, but preserved with the recurring task.
+/// Other `VTODO` components sharing a UID result in an error.
+fn tasks_from_ical_string(data: &str) -> Result<Vec<Task>> {
+  let unfolded = unfold(data);
+  // The parser may panic on malformed input, such as a truncated file.
+  // We want such input to be reported as a regular error instead.
+  let parsed = catch_unwind(|| read_calendar(&unfolded))
+    .map_err(|_| anyhow!("failed to parse malformed iCalendar data"))?
+    .map_err(Error::msg)?;
+  let (todos, others) = parsed
+    .components
+    .iter()
+    .partition::<Vec<_>, _>(|component| component.name.as_str().eq_ignore_ascii_case("VTODO"));
+  let calendar_components = others
+    .into_iter()
+    .map(component_from_parsed)
+    .collect::<Vec<_>>();
+
+  let uid = |todo: &ParsedComponent<'_>| value_from_parsed(todo, UID_PROPERTY).map(str::to_string);
+  let is_override = |todo: &ParsedComponent<'_>| {
+    value_from_parsed(todo, RECURRENCE_ID_PROPERTY).is_some()
+  };
+  let recurring = todos
+    .iter()
+    .filter(|todo| !is_override(todo))
+    .map(|todo| uid(todo))
+    .collect::<Vec<_>>();
+  let (overrides, todos) = todos
+    .into_iter()
+    .partition::<Vec<_>, _>(|todo| is_override(todo) && recurring.contains(&uid(todo)));
+
+  let mut uids = Vec::with_capacity(todos.len());
+  let mut tasks = Vec::with_capacity(todos.len());
+  for todo in todos {
+    let uid = uid(todo);
+    if uid.is_some() && uids.contains(&uid) {
+      bail!(
+        "calendar contains multiple TODO components with UID {}",
+        uid.as_deref().unwrap_or_default()
+      )
+    }
+
+    let mut task = task_from_parsed(todo)?;
+    task.unknown.calendar_components = calendar_components.clone();
+    let () = uids.push(uid);
+    let () = tasks.push(task);
+  }
 
-  fn try_from(calendar: &Calendar) -> Result<Self, Self::Error> {
-    try_from_calendar_with_single_todo::<Self>(calendar)
+  for todo in overrides {
+    // SANITY: We only consider overrides for which a recurring `VTODO`
+    //         with the same UID exists.
+    let idx = uids.iter().position(|uid_| *uid_ == uid(todo)).unwrap();
+    let () = tasks[idx]
+      .unknown
+      .calendar_components
+      .push(component_from_parsed(todo));
   }
+  Ok(tasks)
 }
 
 
 impl SerICal for Task {
   #[inline]
   fn to_ical_string(&self) -> String {
-    let calendar = Calendar::from(self);
-    calendar.to_string()
+    let components = self.unknown.calendar_components.iter().collect::<Vec<_>>();
+    emit_calendar(&components, [todo_to_ical_string(self).as_str()])
+  }
+
+  fn from_ical_string(data: &str) -> Result<Self, Error> {
+    let mut tasks = tasks_from_ical_string(data)?;
+    match tasks.len() {
+      1 => Ok(tasks.remove(0)),
+      0 => bail!("calendar contains no TODO components"),
+      _ => bail!("calendar contains multiple TODO components"),
+    }
+  }
+}
+
+
+/// A list of tasks is represented by a single calendar containing
+/// multiple `VTODO` components.
+impl SerICal for Tasks {
+  fn to_ical_string(&self) -> String {
+    // Calendar level components are preserved with each task, but we
+    // only want to emit them once.
+    let mut components = Vec::new();
+    for component in self
+      .0
+      .iter()
+      .flat_map(|task| task.unknown.calendar_components.iter())
+    {
+      if !components.contains(&component) {
+        let () = components.push(component);
+      }
+    }
+
+    let todos = self.0.iter().map(todo_to_ical_string).collect::<Vec<_>>();
+    emit_calendar(&components, todos.iter().map(String::as_str))
   }
 
   #[inline]
   fn from_ical_string(data: &str) -> Result<Self, Error> {
-    let calendar = Calendar::from_str(data).map_err(Error::msg)?;
-    let task = Task::try_from(&calendar)?;
-    Ok(task)
+    tasks_from_ical_string(data).map(Tasks)
   }
 }
 
@@ -116,6 +799,8 @@ impl SerICal for Task {
 mod tests {
   use super::*;
 
+  use chrono::TimeZone as _;
+
   use crate::ser::tags::Id as TagId;
 
   use super::super::iCal;
@@ -145,4 +830,658 @@ mod tests {
 
     assert_eq!(new_task, task);
   }
+
+  /// Make sure that we can serialize and deserialize a `Task` that has
+  /// multi-line notes.
+  #[test]
+  fn serialize_deserialize_task_with_notes() {
+    let mut task = Task::new("test task");
+    task.notes = Some("first line, with comma\nsecond; line\n\\o/\n".to_string());
+
+    let data = iCal::serialize(&task).unwrap();
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+
+    assert_eq!(new_task, task);
+  }
+
+  /// Make sure that we can serialize and deserialize a `Task` that has
+  /// a due date set.
+  #[test]
+  fn serialize_deserialize_task_with_due_date() {
+    let mut task = Task::new("test task");
+    task.due = NaiveDate::from_ymd_opt(2023, 4, 1);
+
+    let data = iCal::serialize(&task).unwrap();
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+
+    assert_eq!(new_task, task);
+  }
+
+  /// Make sure that we can serialize and deserialize a `Task` that has
+  /// a priority set.
+  #[test]
+  fn serialize_deserialize_task_with_priority() {
+    for priority in [Priority::Low, Priority::Medium, Priority::High] {
+      let mut task = Task::new("test task");
+      task.priority = Some(priority);
+
+      let data = iCal::serialize(&task).unwrap();
+      let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+
+      assert_eq!(new_task, task);
+    }
+  }
+
+  /// Make sure that we can serialize and deserialize a `Task` that
+  /// recurs.
+  #[test]
+  fn serialize_deserialize_task_with_recurrence() {
+    let mut task = Task::new("test task");
+    task.due = NaiveDate::from_ymd_opt(2023, 4, 1);
+    task.recurrence = Some("FREQ=WEEKLY;INTERVAL=2".to_string());
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert!(ical.contains("RRULE:FREQ=WEEKLY;INTERVAL=2\r\n"), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that a task's start date survives a serialization round
+  /// trip.
+  #[test]
+  fn serialize_deserialize_task_with_start_date() {
+    let mut task = Task::new("test task");
+    task.start = NaiveDate::from_ymd_opt(2023, 3, 1);
+    task.due = NaiveDate::from_ymd_opt(2023, 4, 1);
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert!(ical.contains("DTSTART;VALUE=DATE:20230301\r\n"), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that a task's parent survives a serialization round trip.
+  #[test]
+  fn serialize_deserialize_task_with_parent() {
+    let mut task = Task::new("test task");
+    task.parent = Some(TaskId::new_v4());
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    let expected = format!("RELATED-TO;RELTYPE=PARENT:{}\r\n", task.parent.unwrap());
+    assert!(ical.contains(&expected), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that `RELATED-TO` properties without relationship type
+  /// are interpreted as parent relations, while other relationship
+  /// types are preserved as-is.
+  #[test]
+  fn deserialize_task_relations() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+SUMMARY:test task\r
+RELATED-TO;RELTYPE=SIBLING:b5a0c8a4-3e8b-4c1f-9d0a-2f6e4c1d7b3e\r
+RELATED-TO:0b7e5c2a-6d4f-4a8e-b1c3-9e2d7f5a8c6b\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    let expected = TaskId::from_str("0b7e5c2a-6d4f-4a8e-b1c3-9e2d7f5a8c6b").unwrap();
+    assert_eq!(task.parent, Some(expected));
+    assert_eq!(task.unknown.properties.len(), 1);
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data).unwrap();
+    assert!(
+      ical.contains("RELATED-TO;RELTYPE=SIBLING:b5a0c8a4-3e8b-4c1f-9d0a-2f6e4c1d7b3e\r\n"),
+      "{ical}"
+    );
+  }
+
+  /// Check that a task's dependencies survive a serialization round
+  /// trip.
+  #[test]
+  fn serialize_deserialize_task_with_dependencies() {
+    let mut task = Task::new("test task");
+    task.parent = Some(TaskId::new_v4());
+    task.depends_on = vec![TaskId::new_v4(), TaskId::new_v4()];
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    for id in &task.depends_on {
+      let expected = format!("RELATED-TO;RELTYPE=DEPENDS-ON:{id}\r\n");
+      assert!(ical.contains(&expected), "{ical}");
+    }
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Make sure that creation and modification times survive a
+  /// serialization round trip.
+  #[test]
+  fn serialize_deserialize_task_with_timestamps() {
+    let mut task = Task::new("test task");
+    task.created = Some(Utc.with_ymd_and_hms(2023, 4, 1, 12, 30, 0).unwrap());
+    task.modified = Some(Utc.with_ymd_and_hms(2023, 4, 2, 8, 0, 15).unwrap());
+    task.stamp = Utc.with_ymd_and_hms(2023, 4, 2, 8, 0, 15).unwrap();
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert!(ical.contains("CREATED:20230401T123000Z\r\n"), "{ical}");
+    assert!(ical.contains("LAST-MODIFIED:20230402T080015Z\r\n"), "{ical}");
+    assert!(ical.contains("DTSTAMP:20230402T080015Z\r\n"), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that RFC 5545 `PRIORITY` values are mapped to the expected
+  /// priorities.
+  #[test]
+  fn deserialize_task_priorities() {
+    let priority = |value| {
+      let data = format!(
+        "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+SUMMARY:test task\r
+PRIORITY:{value}\r
+END:VTODO\r
+END:VCALENDAR\r
+"
+      );
+      let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+      task.priority
+    };
+
+    assert_eq!(priority(0), None);
+    assert_eq!(priority(1), Some(Priority::High));
+    assert_eq!(priority(4), Some(Priority::High));
+    assert_eq!(priority(5), Some(Priority::Medium));
+    assert_eq!(priority(6), Some(Priority::Low));
+    assert_eq!(priority(9), Some(Priority::Low));
+  }
+
+  /// Check that priority values we cannot represent are preserved when
+  /// reading and writing a task, unless the priority gets changed.
+  #[test]
+  fn serialize_deserialize_task_with_foreign_priority() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:test task\r
+PRIORITY:2\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let mut task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.priority, Some(Priority::High));
+
+    let serialized = iCal::serialize(&task).unwrap();
+    let string = String::from_utf8(serialized.clone()).unwrap();
+    assert!(string.contains("PRIORITY:2\r\n"), "{string}");
+    assert!(!string.contains("PRIORITY:1\r\n"), "{string}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&serialized).unwrap();
+    assert_eq!(new_task, task);
+
+    task.priority = Some(Priority::Low);
+    let serialized = iCal::serialize(&task).unwrap();
+    let string = String::from_utf8(serialized).unwrap();
+    assert!(string.contains("PRIORITY:9\r\n"), "{string}");
+    assert!(!string.contains("PRIORITY:2\r\n"), "{string}");
+  }
+
+  /// Make sure that we can serialize and deserialize a `Task` that is
+  /// completed.
+  #[test]
+  fn serialize_deserialize_completed_task() {
+    let mut task = Task::new("test task");
+    task.completed = Some(Utc::now().trunc_subsecs(0));
+
+    let data = iCal::serialize(&task).unwrap();
+    let string = String::from_utf8(data.clone()).unwrap();
+    assert!(string.contains("STATUS:COMPLETED"), "{string}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that a task marked as completed without a completion time
+  /// stamp is still read back as completed.
+  #[test]
+  fn deserialize_completed_task_without_time_stamp() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:test task\r
+STATUS:COMPLETED\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    let expected = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+    assert_eq!(task.completed, Some(expected));
+  }
+
+  /// Check that statuses we do not track are preserved when reading
+  /// and writing a task, unless it gets completed.
+  #[test]
+  fn serialize_deserialize_task_with_foreign_status() {
+    for status in ["CANCELLED", "IN-PROCESS"] {
+      let data = format!(
+        "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:test task\r
+STATUS:{status}\r
+END:VTODO\r
+END:VCALENDAR\r
+"
+      );
+      let mut task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+      assert_eq!(task.completed, None);
+      assert!(task.completion_recorded);
+
+      let serialized = iCal::serialize(&task).unwrap();
+      let string = String::from_utf8(serialized.clone()).unwrap();
+      assert!(string.contains(&format!("STATUS:{status}\r\n")), "{string}");
+      assert!(!string.contains("STATUS:NEEDS-ACTION"), "{string}");
+
+      let new_task = <iCal as Backend<Task>>::deserialize(&serialized).unwrap();
+      assert_eq!(new_task, task);
+
+      task.completed = Some(Utc.with_ymd_and_hms(2023, 4, 2, 12, 0, 0).unwrap());
+      let serialized = iCal::serialize(&task).unwrap();
+      let string = String::from_utf8(serialized).unwrap();
+      assert!(string.contains("STATUS:COMPLETED\r\n"), "{string}");
+      assert!(!string.contains(&format!("STATUS:{status}")), "{string}");
+    }
+  }
+
+  /// Check that a due date including a time, as may be set by other
+  /// clients, is read back as the corresponding date.
+  #[test]
+  fn deserialize_task_with_due_date_time() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+SUMMARY:test task\r
+DUE:20230401T120000\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.due, NaiveDate::from_ymd_opt(2023, 4, 1));
+  }
+
+  /// Check that properties and components we do not know about are
+  /// preserved when reading and writing a task.
+  #[test]
+  fn preserve_unknown_data() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:test task\r
+CATEGORIES:work\r
+CATEGORIES:home\r
+X-OTHER-CLIENT;X-PARAM=value:some\\, text\r
+BEGIN:X-OTHER-COMPONENT\r
+X-KIND:note\r
+X-POSITION;X-UNIT=px:12\r
+X-COLOR:red\r
+END:X-OTHER-COMPONENT\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.summary, "test task");
+
+    let names = task
+      .unknown
+      .properties
+      .iter()
+      .map(|property| property.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(names, vec!["X-OTHER-CLIENT"]);
+    assert_eq!(task.unknown.properties[0].value, "some\\, text");
+    assert_eq!(
+      task.unknown.properties[0].params,
+      vec![("X-PARAM".to_string(), Some("value".to_string()))]
+    );
+    assert_eq!(task.unknown.components.len(), 1);
+    assert_eq!(task.unknown.components[0].name, "X-OTHER-COMPONENT");
+    assert_eq!(task.unknown.components[0].properties.len(), 3);
+
+    let serialized = iCal::serialize(&task).unwrap();
+    let string = String::from_utf8(serialized.clone()).unwrap();
+    assert!(string.contains("CATEGORIES:work,home\r\n"), "{string}");
+    assert!(
+      string.contains("X-POSITION;X-UNIT=px:12\r\n"),
+      "{string}"
+    );
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&serialized).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that tag names are stored as categories and that we can
+  /// read back categories spread over multiple properties.
+  #[test]
+  fn serialize_deserialize_task_with_categories() {
+    let mut task = Task::new("test task");
+    task.categories = vec!["work".to_string(), "a, b; c".to_string()];
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert!(
+      ical.contains("CATEGORIES:work,a\\, b\\; c\r\n"),
+      "{ical}"
+    );
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//other client//EN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+SUMMARY:test task\r
+CATEGORIES:work,home\r
+categories:errands\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.categories, vec!["work", "home", "errands"]);
+    assert!(task.tags.is_empty());
+    assert!(task.unknown.is_empty());
+  }
+
+  /// Check that a task's alarms survive a serialization round trip.
+  #[test]
+  fn serialize_deserialize_task_with_alarms() {
+    let mut task = Task::new("test task");
+    task.alarms = vec![
+      Alarm {
+        trigger: Trigger::Absolute(Utc.with_ymd_and_hms(2023, 4, 1, 9, 30, 0).unwrap()),
+        properties: Vec::new(),
+      },
+      Alarm {
+        trigger: Trigger::Relative {
+          offset: -15 * 60,
+          related: Related::End,
+        },
+        properties: Vec::new(),
+      },
+    ];
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert!(
+      ical.contains("TRIGGER;VALUE=DATE-TIME:20230401T093000Z\r\n"),
+      "{ical}"
+    );
+    assert!(ical.contains("TRIGGER;RELATED=END:-PT15M\r\n"), "{ical}");
+    assert_eq!(ical.matches("ACTION:DISPLAY\r\n").count(), 2, "{ical}");
+
+    let mut new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+
+    // The description of our alarms follows the task's summary.
+    new_task.summary = "renamed task".to_string();
+    let data = iCal::serialize(&new_task).unwrap();
+    let ical = String::from_utf8(data).unwrap();
+    assert_eq!(ical.matches("DESCRIPTION:renamed task\r\n").count(), 2, "{ical}");
+    assert!(!ical.contains("DESCRIPTION:test task"), "{ical}");
+  }
+
+  /// Check that alarms created by other clients are interpreted
+  /// correctly and that ones we do not understand are preserved.
+  #[test]
+  fn deserialize_task_alarms() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:ICALENDAR-RS\r
+CALSCALE:GREGORIAN\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+SUMMARY:test task\r
+BEGIN:VALARM\r
+ACTION:AUDIO\r
+TRIGGER;RELATED=START:-P1DT2H\r
+END:VALARM\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:reminder\r
+END:VALARM\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.alarms.len(), 1);
+    assert_eq!(
+      task.alarms[0].trigger,
+      Trigger::Relative {
+        offset: -(26 * 60 * 60),
+        related: Related::Start,
+      }
+    );
+    assert_eq!(task.unknown.components.len(), 1);
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    // The foreign alarm's action is retained as-is.
+    assert!(ical.contains("ACTION:AUDIO\r\n"), "{ical}");
+    assert!(ical.contains("TRIGGER:-P1DT2H\r\n"), "{ical}");
+    assert!(ical.contains("DESCRIPTION:reminder\r\n"), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that calendar level components accompanying a task, such as
+  /// time zone definitions, are accepted and preserved.
+  #[test]
+  fn deserialize_task_with_timezone() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Nextcloud Tasks v0.15.0\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+DTSTART:19701025T030000\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+SUMMARY:test task\r
+DUE;TZID=Europe/Berlin:20230405T120000\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.summary, "test task");
+    assert_eq!(task.due, NaiveDate::from_ymd_opt(2023, 4, 5));
+    assert_eq!(task.unknown.calendar_components.len(), 1);
+    assert_eq!(task.unknown.calendar_components[0].name, "VTIMEZONE");
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert!(
+      ical.contains("END:VTIMEZONE\r\nBEGIN:VTODO\r\n"),
+      "{ical}"
+    );
+
+    assert!(
+      ical.contains("DUE;TZID=Europe/Berlin:20230405T120000\r\n"),
+      "{ical}"
+    );
+    assert!(!ical.contains("DUE;VALUE=DATE"), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+
+    // Once the due date is changed, the original property is dropped.
+    let mut task = new_task;
+    task.due = NaiveDate::from_ymd_opt(2023, 4, 6);
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data).unwrap();
+    assert!(ical.contains("DUE;VALUE=DATE:20230406\r\n"), "{ical}");
+    assert!(!ical.contains("TZID=Europe/Berlin:"), "{ical}");
+  }
+
+  /// Make sure that we can serialize and deserialize multiple tasks
+  /// stored in a single calendar.
+  #[test]
+  fn serialize_deserialize_multiple_tasks() {
+    let timezone = Component {
+      name: "VTIMEZONE".to_string(),
+      properties: vec![Property {
+        name: "TZID".to_string(),
+        params: Vec::new(),
+        value: "Europe/Berlin".to_string(),
+      }],
+      components: Vec::new(),
+    };
+
+    let mut task1 = Task::new("task 1");
+    task1.unknown.calendar_components = vec![timezone.clone()];
+    let mut task2 = Task::new("task 2");
+    task2.depends_on = vec![task1.id];
+    task2.unknown.calendar_components = vec![timezone];
+    let tasks = Tasks(vec![task1.clone(), task2]);
+
+    let data = iCal::serialize(&tasks).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    // The time zone definition is emitted only once.
+    assert_eq!(ical.matches("BEGIN:VTIMEZONE\r\n").count(), 1, "{ical}");
+    assert_eq!(ical.matches("BEGIN:VTODO\r\n").count(), 2, "{ical}");
+
+    let new_tasks = <iCal as Backend<Tasks>>::deserialize(&data).unwrap();
+    assert_eq!(new_tasks, tasks);
+
+    // A single task can only be deserialized from a calendar
+    // containing exactly one.
+    let err = <iCal as Backend<Task>>::deserialize(&data).unwrap_err();
+    assert_eq!(err.to_string(), "calendar contains multiple TODO components");
+
+    let data = iCal::serialize(&Tasks(vec![task1.clone()])).unwrap();
+    assert_eq!(data, iCal::serialize(&task1).unwrap());
+  }
+
+  /// Check that a UID that is not a UUID results in a stable ID and
+  /// is written back unchanged.
+  #[test]
+  fn deserialize_task_with_foreign_uid() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Other Client//EN\r
+BEGIN:VTODO\r
+UID:20231010T100000Z-1234@example.com\r
+DTSTAMP:20231010T100000Z\r
+SUMMARY:foreign task\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let task = <iCal as Backend<Task>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(task.id, uuid_from_name("20231010T100000Z-1234@example.com"));
+
+    let data = iCal::serialize(&task).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert_eq!(ical.matches("UID:").count(), 1, "{ical}");
+    assert!(ical.contains("UID:20231010T100000Z-1234@example.com\r\n"), "{ical}");
+
+    let new_task = <iCal as Backend<Task>>::deserialize(&data).unwrap();
+    assert_eq!(new_task, task);
+  }
+
+  /// Check that `VTODO` components overriding instances of a recurring
+  /// task are preserved with it, while other components sharing a UID
+  /// are rejected.
+  #[test]
+  fn deserialize_task_with_overrides() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Nextcloud Tasks v0.15.0\r
+BEGIN:VTODO\r
+UID:4f4b0c1e-3c2f-4f8a-9f44-0e3b7a3c2d11\r
+DTSTAMP:20231010T100000Z\r
+SUMMARY:weekly review\r
+RRULE:FREQ=WEEKLY\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:4f4b0c1e-3c2f-4f8a-9f44-0e3b7a3c2d11\r
+DTSTAMP:20231011T100000Z\r
+RECURRENCE-ID;VALUE=DATE:20231017\r
+SUMMARY:weekly review (moved)\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let tasks = <iCal as Backend<Tasks>>::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(tasks.0.len(), 1);
+    let task = &tasks.0[0];
+    assert_eq!(task.summary, "weekly review");
+    assert_eq!(task.unknown.calendar_components.len(), 1);
+
+    let data = iCal::serialize(&tasks).unwrap();
+    let ical = String::from_utf8(data.clone()).unwrap();
+    assert_eq!(ical.matches("BEGIN:VTODO\r\n").count(), 2, "{ical}");
+    assert!(ical.contains("RECURRENCE-ID;VALUE=DATE:20231017\r\n"), "{ical}");
+
+    let new_tasks = <iCal as Backend<Tasks>>::deserialize(&data).unwrap();
+    assert_eq!(new_tasks, tasks);
+
+    let data = ical.replace("RECURRENCE-ID;VALUE=DATE:20231017\r\n", "");
+    let err = <iCal as Backend<Tasks>>::deserialize(data.as_bytes()).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "calendar contains multiple TODO components with UID 4f4b0c1e-3c2f-4f8a-9f44-0e3b7a3c2d11"
+    );
+  }
+
+  /// Check that deserialization of truncated data fails gracefully.
+  #[test]
+  fn deserialize_truncated_task() {
+    let data = iCal::serialize(&Task::new("test task")).unwrap();
+    let err = <iCal as Backend<Task>>::deserialize(&data[..20]).unwrap_err();
+    assert_eq!(err.to_string(), "failed to parse malformed iCalendar data");
+  }
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std::fmt::Display;
+use std::fmt::Write as _;
 use std::str::FromStr;
 
 use anyhow::bail;
@@ -9,14 +10,28 @@ use anyhow::Context as _;
 use anyhow::Error;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::NaiveDateTime;
+use chrono::TimeZone as _;
+use chrono::Utc;
+
+use icalendar::parser::Component as ParsedComponent;
+use icalendar::parser::Parameter as ParsedParameter;
+use icalendar::parser::Property as ParsedProperty;
 use icalendar::Calendar;
 use icalendar::CalendarComponent;
 use icalendar::Component as _;
 use icalendar::Todo;
 
+use crate::ser::tasks::Component;
+use crate::ser::tasks::Property;
+use crate::ser::tasks::Unknown;
+
 
 /// The separator to use between list items.
 const LIST_ITEM_SEPARATOR: char = '|';
+/// The format of a UTC date-time value, as per RFC 5545 section 3.3.5.
+const UTC_DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
 
 
 /// Parse a list of items that can be built from strings.
@@ -41,13 +56,8 @@ where
 }
 
 
-/// Emit a list of items as a string.
-///
-/// # Notes
-/// This function does no escaping on the individual item's contents and
-/// there is risk of a clash when parsing again if they contain
-/// `LIST_ITEM_SEPARATOR` characters.
-pub(super) fn emit_list<I, T>(iter: I) -> Option<String>
+/// Join a list of items into a string, using the given separator.
+fn join<I, T>(iter: I, separator: char) -> Option<String>
 where
   I: IntoIterator<Item = T>,
   T: Display,
@@ -59,12 +69,359 @@ where
   let mut iter = iter.into_iter();
   iter.next().map(|first| {
     iter.fold(first.to_string(), |list, item| {
-      format!("{list}{LIST_ITEM_SEPARATOR}{item}")
+      format!("{list}{separator}{item}")
     })
   })
 }
 
 
+/// Emit a list of items as a string.
+///
+/// # Notes
+/// This function does no escaping on the individual item's contents and
+/// there is risk of a clash when parsing again if they contain
+/// `LIST_ITEM_SEPARATOR` characters.
+pub(super) fn emit_list<I, T>(iter: I) -> Option<String>
+where
+  I: IntoIterator<Item = T>,
+  T: Display,
+{
+  join(iter, LIST_ITEM_SEPARATOR)
+}
+
+
+/// Format a UTC time stamp as a `DATE-TIME` property value.
+pub(super) fn format_utc_date_time(time: DateTime<Utc>) -> String {
+  time.format(UTC_DATE_TIME_FORMAT).to_string()
+}
+
+
+/// Parse a UTC `DATE-TIME` property value.
+pub(super) fn parse_utc_date_time(string: &str) -> Option<DateTime<Utc>> {
+  NaiveDateTime::parse_from_str(string, UTC_DATE_TIME_FORMAT)
+    .ok()
+    .map(|time| Utc.from_utc_datetime(&time))
+}
+
+
+/// Format a number of seconds as a `DURATION` property value, as per
+/// RFC 5545 section 3.3.6.
+pub(super) fn format_duration(seconds: i64) -> String {
+  let sign = if seconds < 0 { "-" } else { "" };
+  let seconds = seconds.unsigned_abs();
+  let (weeks, days) = (seconds / (7 * 86400), seconds / 86400);
+  let (hours, minutes, seconds) = (seconds / 3600 % 24, seconds / 60 % 60, seconds % 60);
+
+  let mut string = format!("{sign}P");
+  if days != 0 && days % 7 == 0 && hours == 0 && minutes == 0 && seconds == 0 {
+    let () = write!(string, "{weeks}W").unwrap();
+    return string
+  }
+
+  if days != 0 {
+    let () = write!(string, "{days}D").unwrap();
+  }
+  if hours != 0 || minutes != 0 || seconds != 0 || days == 0 {
+    let () = string.push('T');
+    if hours != 0 {
+      let () = write!(string, "{hours}H").unwrap();
+    }
+    if minutes != 0 {
+      let () = write!(string, "{minutes}M").unwrap();
+    }
+    if seconds != 0 || (hours == 0 && minutes == 0) {
+      let () = write!(string, "{seconds}S").unwrap();
+    }
+  }
+  string
+}
+
+
+/// Parse a `DURATION` property value into the number of seconds it
+/// represents.
+pub(super) fn parse_duration(string: &str) -> Option<i64> {
+  /// Sum up the components of a duration, with each number being
+  /// followed by one of the designators in `units`, in order.
+  fn sum(mut string: &str, units: &[(char, i64)]) -> Option<i64> {
+    let mut total = 0i64;
+    let mut units = units.iter();
+    while !string.is_empty() {
+      let idx = string.find(|c: char| !c.is_ascii_digit())?;
+      let (number, rest) = string.split_at(idx);
+      let number = number.parse::<i64>().ok()?;
+      let designator = rest.chars().next()?;
+      let (_unit, factor) = units.find(|(unit, _factor)| *unit == designator)?;
+      total = total.checked_add(number.checked_mul(*factor)?)?;
+      string = &rest[1..];
+    }
+    Some(total)
+  }
+
+  let (sign, string) = match string.as_bytes().first()? {
+    b'-' => (-1, &string[1..]),
+    b'+' => (1, &string[1..]),
+    _ => (1, string),
+  };
+  let string = string.strip_prefix('P')?;
+  let (date, time) = match string.split_once('T') {
+    Some((date, time)) if !time.is_empty() => (date, Some(time)),
+    Some(..) => return None,
+    None => (string, None),
+  };
+
+  let date = sum(date, &[('W', 7 * 86400), ('D', 86400)])?;
+  let time = time
+    .map(|time| sum(time, &[('H', 3600), ('M', 60), ('S', 1)]))
+    .unwrap_or(Some(0))?;
+  if string.is_empty() {
+    return None
+  }
+  Some(sign * (date + time))
+}
+
+
+/// Escape a string for usage as a `TEXT` property value, as per RFC 5545
+/// section 3.3.11.
+pub(super) fn escape_text(string: &str) -> String {
+  let mut escaped = String::with_capacity(string.len());
+  for c in string.chars() {
+    match c {
+      '\\' => escaped.push_str("\\\\"),
+      ';' => escaped.push_str("\\;"),
+      ',' => escaped.push_str("\\,"),
+      '\n' => escaped.push_str("\\n"),
+      // Carriage returns are dropped; line breaks are represented by
+      // line feeds only.
+      '\r' => (),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+
+/// Unescape a `TEXT` property value, reverting the effect of
+/// [`escape_text`].
+pub(super) fn unescape_text(string: &str) -> String {
+  let mut unescaped = String::with_capacity(string.len());
+  let mut chars = string.chars();
+  while let Some(c) = chars.next() {
+    if c == '\\' {
+      match chars.next() {
+        Some('n') | Some('N') => unescaped.push('\n'),
+        Some(c) => unescaped.push(c),
+        None => unescaped.push('\\'),
+      }
+    } else {
+      unescaped.push(c)
+    }
+  }
+  unescaped
+}
+
+
+/// Emit a list of strings as a property value containing multiple
+/// comma separated `TEXT` values, such as `CATEGORIES`.
+pub(super) fn emit_text_list<I, S>(iter: I) -> Option<String>
+where
+  I: IntoIterator<Item = S>,
+  S: AsRef<str>,
+{
+  join(iter.into_iter().map(|s| escape_text(s.as_ref())), ',')
+}
+
+
+/// Retrieve the values of all properties with the given name from a
+/// parsed component, each of which contains a list of comma separated
+/// `TEXT` values, reverting the effect of [`emit_text_list`].
+pub(super) fn text_list_from_parsed(component: &ParsedComponent<'_>, name: &str) -> Vec<String> {
+  let mut items = Vec::new();
+  for property in component
+    .properties
+    .iter()
+    .filter(|property| property.name.as_str().eq_ignore_ascii_case(name))
+  {
+    let value = property.val.as_str();
+    let mut start = 0;
+    let mut escaped = false;
+    for (idx, c) in value.char_indices() {
+      match c {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        ',' => {
+          let () = items.push(unescape_text(&value[start..idx]));
+          start = idx + 1;
+        },
+        _ => (),
+      }
+    }
+    let () = items.push(unescape_text(&value[start..]));
+  }
+
+  let () = items.retain(|item| !item.is_empty());
+  items
+}
+
+
+/// Retrieve the value of the first property with the given name from
+/// a parsed component.
+pub(super) fn value_from_parsed<'comp>(
+  component: &'comp ParsedComponent<'_>,
+  name: &str,
+) -> Option<&'comp str> {
+  component
+    .properties
+    .iter()
+    .find(|property| property.name.as_str().eq_ignore_ascii_case(name))
+    .map(|property| property.val.as_str())
+}
+
+
+/// Convert a parsed property into a [`Property`].
+pub(super) fn property_from_parsed(property: &ParsedProperty<'_>) -> Property {
+  Property {
+    name: property.name.as_str().to_string(),
+    params: property
+      .params
+      .iter()
+      .map(|param| {
+        let value = param.val.as_ref().map(|value| value.as_str().to_string());
+        (param.key.as_str().to_string(), value)
+      })
+      .collect(),
+    value: property.val.as_str().to_string(),
+  }
+}
+
+
+/// Convert a parsed component into a [`Component`].
+pub(super) fn component_from_parsed(component: &ParsedComponent<'_>) -> Component {
+  Component {
+    name: component.name.as_str().to_string(),
+    properties: component
+      .properties
+      .iter()
+      .map(property_from_parsed)
+      .collect(),
+    components: component
+      .components
+      .iter()
+      .map(component_from_parsed)
+      .collect(),
+  }
+}
+
+
+/// Collect all properties not contained in `known` as well as all
+/// sub-components of the provided parsed component.
+pub(super) fn unknown_from_parsed(component: &ParsedComponent<'_>, known: &[&str]) -> Unknown {
+  Unknown {
+    properties: component
+      .properties
+      .iter()
+      .filter(|property| {
+        !known
+          .iter()
+          .any(|known| property.name.as_str().eq_ignore_ascii_case(known))
+      })
+      .map(property_from_parsed)
+      .collect(),
+    components: component
+      .components
+      .iter()
+      .map(component_from_parsed)
+      .collect(),
+    // Calendar level components are not part of the component itself
+    // and have to be filled in by the caller.
+    calendar_components: Vec::new(),
+  }
+}
+
+
+/// Emit a [`Property`] in iCalendar format.
+fn write_property(property: &Property, out: &mut String) {
+  let property = ParsedProperty {
+    name: property.name.as_str().into(),
+    val: property.value.as_str().into(),
+    params: property
+      .params
+      .iter()
+      .map(|(key, value)| ParsedParameter {
+        key: key.as_str().into(),
+        val: value.as_deref().map(Into::into),
+      })
+      .collect(),
+  };
+  // SANITY: Writing to a `String` is infallible.
+  let () = write!(out, "{property}").unwrap();
+}
+
+
+/// Emit a [`Component`] in iCalendar format.
+fn write_component(component: &Component, out: &mut String) {
+  // SANITY: Writing to a `String` is infallible.
+  let () = write!(out, "BEGIN:{}\r\n", component.name).unwrap();
+  let () = component
+    .properties
+    .iter()
+    .for_each(|property| write_property(property, out));
+  let () = component
+    .components
+    .iter()
+    .for_each(|component| write_component(component, out));
+  let () = write!(out, "END:{}\r\n", component.name).unwrap();
+}
+
+
+/// Insert properties and components into the (last) `VTODO` component
+/// of the provided iCalendar string, verbatim.
+///
+/// `icalendar` stores properties by name and would collapse duplicate
+/// ones, so we emit data it does not know about ourselves.
+pub(super) fn insert_into_todo<'prop, P>(
+  mut ical: String,
+  properties: P,
+  components: &[Component],
+) -> String
+where
+  P: IntoIterator<Item = &'prop Property>,
+{
+  let mut insert = String::new();
+  let () = properties
+    .into_iter()
+    .for_each(|property| write_property(property, &mut insert));
+  let () = components
+    .iter()
+    .for_each(|component| write_component(component, &mut insert));
+
+  if let Some(idx) = ical.rfind("END:VTODO\r\n") {
+    let () = ical.insert_str(idx, &insert);
+  }
+  ical
+}
+
+
+/// Create an iCalendar string of a calendar comprising the provided
+/// components followed by the given, already emitted, `VTODO`
+/// components.
+pub(super) fn emit_calendar<'todo, I>(components: &[&Component], todos: I) -> String
+where
+  I: IntoIterator<Item = &'todo str>,
+{
+  let mut insert = String::new();
+  let () = components
+    .iter()
+    .for_each(|component| write_component(component, &mut insert));
+  let () = todos.into_iter().for_each(|todo| insert.push_str(todo));
+
+  let mut ical = Calendar::new().to_string();
+  if let Some(idx) = ical.rfind("END:VCALENDAR\r\n") {
+    let () = ical.insert_str(idx, &insert);
+  }
+  ical
+}
+
+
 /// Attempt to extract a custom object from a [`Calendar`] with a single
 /// [`Todo`] component.
 pub(super) fn try_from_calendar_with_single_todo<T>(calendar: &Calendar) -> Result<T>
@@ -75,21 +432,26 @@ where
   where
     T: for<'todo> TryFrom<&'todo Todo, Error = Error>,
   {
-    match calendar.components.as_slice() {
-      [component] => {
-        if let CalendarComponent::Todo(todo) = component {
-          T::try_from(todo).with_context(|| {
-            format!(
-              "failed to convert TODO {} into object",
-              todo.get_uid().unwrap_or("<undefined>")
-            )
-          })
-        } else {
-          bail!("calendar contains unsupported component type")
-        }
-      },
-      [] => bail!("calendar contains no components"),
-      [..] => bail!("calendar contains multiple components"),
+    // Other components, such as `VTIMEZONE` definitions, may accompany
+    // the `VTODO` and are ignored.
+    let todos = calendar
+      .components
+      .iter()
+      .filter_map(|component| match component {
+        CalendarComponent::Todo(todo) => Some(todo),
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+
+    match todos.as_slice() {
+      [todo] => T::try_from(todo).with_context(|| {
+        format!(
+          "failed to convert TODO {} into object",
+          todo.get_uid().unwrap_or("<undefined>")
+        )
+      }),
+      [] => bail!("calendar contains no TODO components"),
+      [..] => bail!("calendar contains multiple TODO components"),
     }
   }
 
@@ -103,6 +465,8 @@ where
 mod tests {
   use super::*;
 
+  use icalendar::Event;
+
   use crate::ser::tags::Id as TagId;
   use crate::ser::tags::Tag;
   use crate::ser::tasks::Task;
@@ -124,6 +488,55 @@ mod tests {
     assert_eq!(parse_list::<Tag>(&emit_list(tags).unwrap()).unwrap(), tags);
   }
 
+  /// Check that we can format and parse `DURATION` values.
+  #[test]
+  fn format_parse_duration() {
+    let durations = [
+      (0, "PT0S"),
+      (-900, "-PT15M"),
+      (3600, "PT1H"),
+      (5405, "PT1H30M5S"),
+      (86400, "P1D"),
+      (-90000, "-P1DT1H"),
+      (1209600, "P2W"),
+    ];
+    for (seconds, string) in durations {
+      assert_eq!(format_duration(seconds), string);
+      assert_eq!(parse_duration(string), Some(seconds), "{string}");
+    }
+
+    assert_eq!(parse_duration("+P1W"), Some(604800));
+    assert_eq!(parse_duration("P1DT"), None);
+    assert_eq!(parse_duration("P"), None);
+    assert_eq!(parse_duration("PT1D"), None);
+    assert_eq!(parse_duration("P1H"), None);
+    assert_eq!(parse_duration("PT1M1H"), None);
+    assert_eq!(parse_duration("15M"), None);
+  }
+
+  /// Check that we can format and parse UTC `DATE-TIME` values.
+  #[test]
+  fn format_parse_utc_date_time() {
+    let time = Utc.with_ymd_and_hms(2023, 4, 1, 12, 30, 5).unwrap();
+    let string = format_utc_date_time(time);
+    assert_eq!(string, "20230401T123005Z");
+    assert_eq!(parse_utc_date_time(&string), Some(time));
+    assert_eq!(parse_utc_date_time("20230401T123005"), None);
+  }
+
+  /// Check that we can escape and unescape `TEXT` values.
+  #[test]
+  fn escape_unescape_text() {
+    let text = "line 1\nline 2; with, special \\ characters";
+    let escaped = escape_text(text);
+    assert_eq!(
+      escaped,
+      "line 1\\nline 2\\; with\\, special \\\\ characters"
+    );
+    assert!(!escaped.contains('\n'));
+    assert_eq!(unescape_text(&escaped), text);
+  }
+
   /// Check that we fail conversion from a `Calendar` object if it does
   /// not meet certain requirements.
   #[test]
@@ -133,7 +546,7 @@ mod tests {
 
     assert_eq!(
       err.root_cause().to_string(),
-      "calendar contains no components"
+      "calendar contains no TODO components"
     );
 
     let todo1 = Todo::new();
@@ -144,7 +557,22 @@ mod tests {
 
     assert_eq!(
       err.root_cause().to_string(),
-      "calendar contains multiple components"
+      "calendar contains multiple TODO components"
     );
   }
+
+  /// Check that components other than `VTODO`s do not get in the way
+  /// of the conversion from a `Calendar` object.
+  #[test]
+  fn try_from_calendar_with_other_components() {
+    let mut todo = Todo::new();
+    let todo = todo.summary("todo").done();
+    let calendar = Calendar::from_iter([
+      CalendarComponent::from(Event::new()),
+      CalendarComponent::from(todo),
+    ]);
+
+    let task = try_from_calendar_with_single_todo::<Task>(&calendar).unwrap();
+    assert_eq!(task.summary, "todo");
+  }
 }
@@ -0,0 +1,113 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing serialization and deserialization support for the
+//! history of operations that can be undone and redone.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::ser::tasks::Id;
+use crate::ser::tasks::Task;
+
+
+/// A task removed along with others, together with its former index
+/// and position.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Removed {
+  /// The removed task.
+  #[serde(with = "crate::ser::journal::ical_task")]
+  pub task: Task,
+  /// The task's former index.
+  pub index: usize,
+  /// The task's former position.
+  pub position: f64,
+}
+
+
+/// A task moved along with others, together with its former index and
+/// position.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Moved {
+  /// The ID of the moved task.
+  pub id: Id,
+  /// The task's former index.
+  pub index: usize,
+  /// The task's former position.
+  pub position: f64,
+}
+
+
+/// A task whose dependencies got adjusted, along with its former
+/// dependencies.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Dependent {
+  /// The ID of the task.
+  pub id: Id,
+  /// The IDs of the tasks it formerly depended on.
+  pub depends_on: Vec<Id>,
+}
+
+
+/// The spot a task got moved to.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+  /// The spot before the task with the given ID.
+  Before(Id),
+  /// The spot after the task with the given ID.
+  After(Id),
+}
+
+
+/// An operation performed on tasks, along with the state required to
+/// undo it.
+// Steps are kept in a bounded history, so their size is of no real
+// concern.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Step {
+  /// A task got added, after the task with the given ID or at the end.
+  Add {
+    #[serde(with = "crate::ser::journal::ical_task")]
+    task: Task,
+    after: Option<Id>,
+  },
+  /// The task with the given ID got removed, along with its sub-tasks.
+  Remove {
+    id: Id,
+    removed: Vec<Removed>,
+    dependents: Vec<Dependent>,
+  },
+  /// The task with the given ID got updated.
+  Update {
+    id: Id,
+    #[serde(with = "crate::ser::journal::ical_task")]
+    updated: Task,
+    #[serde(with = "crate::ser::journal::ical_task")]
+    before: Task,
+  },
+  /// The task with the given ID got moved, along with its sub-tasks.
+  /// Recorded are the tasks' former spots and the former parent, if
+  /// the move is to be undone.
+  Move {
+    id: Id,
+    to: Target,
+    before: Option<(Vec<Moved>, Option<Id>)>,
+  },
+}
+
+
+/// The history of operations that can be undone and redone.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct History {
+  /// A fingerprint of the tasks the history applies to.
+  pub fingerprint: u64,
+  /// The operations that can be undone, from the least to the most
+  /// recently performed one.
+  pub undo: Vec<Step>,
+  /// The operations that can be redone, starting with the next one to
+  /// redo.
+  pub redo: Vec<Step>,
+}
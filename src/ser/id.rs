@@ -22,6 +22,9 @@ use serde::de::Unexpected;
 use serde::ser::Serialize;
 use serde::ser::Serializer;
 
+use uuid::Builder;
+use uuid::Uuid;
+
 
 /// An ID that can be serialized and deserialized.
 #[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -38,11 +41,6 @@ impl<T> Id<T> {
       phantom: PhantomData,
     }
   }
-
-  /// Retrieve the underlying integer value.
-  pub fn get(&self) -> usize {
-    self.id.get()
-  }
 }
 
 impl<T> TryFrom<usize> for Id<T> {
@@ -106,6 +104,24 @@ impl<'de, T> Deserialize<'de> for Id<T> {
 }
 
 
+/// Derive a UUID from the given name.
+///
+/// The same name always results in the same UUID, meaning that objects
+/// identified by name on different devices share an identity.
+pub fn uuid_from_name(name: &str) -> Uuid {
+  // We use a 128 bit FNV-1a hash of the name as the basis of a "custom"
+  // (version 8) UUID. Contrary to the hashers provided by the standard
+  // library its output is guaranteed to be stable.
+  const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+  const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+  let hash = name.bytes().fold(OFFSET_BASIS, |hash, byte| {
+    (hash ^ u128::from(byte)).wrapping_mul(PRIME)
+  });
+  Builder::from_custom_bytes(hash.to_be_bytes()).into_uuid()
+}
+
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -0,0 +1,100 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing serialization and deserialization support for the
+//! journal of operations performed on tasks.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::ser::tasks::Id;
+use crate::ser::tasks::Task;
+
+
+/// An operation performed on tasks, as recorded in the journal.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Entry {
+  /// A task got added, after the task with the given ID or at the end.
+  Add {
+    #[serde(with = "ical_task")]
+    task: Task,
+    after: Option<Id>,
+  },
+  /// The task with the given ID got removed, along with its sub-tasks.
+  Remove { id: Id },
+  /// A task got updated to the given state.
+  Update {
+    #[serde(with = "ical_task")]
+    task: Task,
+  },
+  /// The task with ID `id` got moved before the one with ID `other`.
+  MoveBefore { id: Id, other: Id },
+  /// The task with ID `id` got moved after the one with ID `other`.
+  MoveAfter { id: Id, other: Id },
+}
+
+
+/// Serialization support for tasks contained in journal entries and
+/// the undo history.
+///
+/// Tasks are stored in their iCalendar representation, which does not
+/// capture the file a task is stored in or the collection it belongs
+/// to. Both are recorded alongside.
+pub(super) mod ical_task {
+  use std::ffi::OsString;
+
+  use serde::de::Error as _;
+  use serde::ser::Error as _;
+  use serde::Deserialize;
+  use serde::Deserializer;
+  use serde::Serialize;
+  use serde::Serializer;
+
+  use crate::ser::backends::iCal;
+  use crate::ser::backends::Backend;
+  use crate::ser::tasks::Task;
+
+
+  #[derive(Deserialize, Serialize)]
+  struct Repr {
+    ical: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collection: Option<String>,
+  }
+
+
+  pub fn serialize<S>(task: &Task, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    let ical = <iCal as Backend<Task>>::serialize(task).map_err(S::Error::custom)?;
+    let repr = Repr {
+      ical: String::from_utf8(ical).map_err(S::Error::custom)?,
+      file_name: task
+        .file_name
+        .as_ref()
+        .map(|file_name| file_name.to_string_lossy().into_owned()),
+      collection: task.collection.clone(),
+    };
+    repr.serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D>(deserializer: D) -> Result<Task, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let Repr {
+      ical,
+      file_name,
+      collection,
+    } = Repr::deserialize(deserializer)?;
+    let mut task =
+      <iCal as Backend<Task>>::deserialize(ical.as_bytes()).map_err(D::Error::custom)?;
+    task.file_name = file_name.map(OsString::from);
+    task.collection = collection;
+    Ok(task)
+  }
+}
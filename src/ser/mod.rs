@@ -4,9 +4,12 @@
 //! A module housing serialization related definitions.
 
 pub mod backends;
+pub mod history;
 pub mod id;
+pub mod journal;
 #[allow(missing_docs)]
 pub mod state;
+pub mod sync;
 #[allow(missing_docs)]
 pub mod tags;
 #[allow(missing_docs)]
@@ -8,6 +8,7 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use crate::colors::Colors;
+use crate::ser::sync::SyncConfig;
 use crate::ser::tags::Tag;
 use crate::ser::tasks::Tasks;
 use crate::ser::tasks::TasksMeta;
@@ -25,6 +26,18 @@ pub struct UiConfig {
   /// The tag to toggle on user initiated action.
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub toggle_tag: Option<Tag>,
+  /// The command to run when a reminder fires.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub reminder_command: Option<String>,
+  /// The configuration of task synchronization.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sync: Option<SyncConfig>,
+  /// The number of days after which completed tasks get archived.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub archive_after_days: Option<u32>,
+  /// The maximum number of operations that can be undone.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_undo_steps: Option<usize>,
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub views: Vec<View>,
 }
@@ -37,6 +50,10 @@ pub struct UiState {
   pub selected_tasks: Vec<Option<usize>>,
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub selected_view: Option<usize>,
+  /// The time, in seconds since the Unix epoch, up to which reminders
+  /// have been checked.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub reminders_checked: Option<i64>,
 }
 
 
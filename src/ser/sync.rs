@@ -0,0 +1,56 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A module providing serialization and deserialization support for
+//! task synchronization related objects.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+
+/// The configuration of task synchronization with a CalDAV server.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SyncConfig {
+  /// The URL of the server, of a principal, or of a calendar.
+  pub url: String,
+  /// The user name to authenticate with.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub username: Option<String>,
+  /// The command printing the password to authenticate with.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub password_command: Option<String>,
+  /// The URLs of the calendars to synchronize the collections other
+  /// than the default one with, keyed by collection name.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub collections: BTreeMap<String, String>,
+}
+
+
+/// The synchronization state of a single task file.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SyncItem {
+  /// The `href` of the corresponding item on the server.
+  pub href: String,
+  /// The entity tag of the item as of the last synchronization.
+  pub etag: String,
+  /// A hash of the file's contents as of the last synchronization.
+  pub hash: u64,
+  /// The file's contents as of the last synchronization, serving as
+  /// the base when merging changes made on both sides.
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub data: String,
+}
+
+
+/// The state of the last synchronization, used for determining what
+/// changed on either side since.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SyncStatus {
+  /// The URL of the calendar collection synchronized with.
+  pub collection: String,
+  /// The synchronization state of all task files, keyed by file name.
+  #[serde(default)]
+  pub items: BTreeMap<String, SyncItem>,
+}
@@ -4,24 +4,166 @@
 //! A module providing serialization and deserialization support for
 //! task templates and tags.
 
+use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
+use std::num::NonZeroUsize;
 use std::str::FromStr;
 
 use anyhow::Context as _;
 use anyhow::Error;
 
+use serde::de::Error as DeError;
+use serde::de::Unexpected;
+use serde::de::Visitor;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use serde::Serializer;
 
-use crate::ser::id::Id as IdT;
+use uuid::Uuid;
+
+use crate::ser::id::uuid_from_name;
 
 
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct T(());
 
-pub type Id = IdT<T>;
+
+/// The identity of a tag template.
+///
+/// Templates are identified by UUIDs, which, contrary to the small
+/// integers allocated on a per-device basis that earlier versions of
+/// the program used, do not collide between different devices. Such
+/// legacy IDs are still understood, but only for the purpose of
+/// migrating existing data.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Id(Uuid);
+
+impl Id {
+  /// Create the `Id` of a newly created template with the given name.
+  ///
+  /// The `Id` is derived from the name, meaning that tags of the same
+  /// name created independently on different devices share an identity.
+  pub fn from_name(name: &str) -> Self {
+    Self(uuid_from_name(name))
+  }
+
+  /// Create a random `Id`.
+  pub fn random() -> Self {
+    Self(Uuid::new_v4())
+  }
+
+  /// Create a legacy `Id` from a "raw" integer.
+  fn legacy(id: NonZeroUsize) -> Self {
+    // Legacy IDs are mapped onto the otherwise unused range of UUIDs
+    // with all upper bits being zero.
+    Self(Uuid::from_u128(id.get() as u128))
+  }
+
+  /// Check whether this `Id` is a legacy one.
+  pub fn is_legacy(&self) -> bool {
+    self.0.as_u128() >> 64 == 0
+  }
+
+  /// Retrieve the "raw" integer of a legacy `Id`.
+  pub fn to_legacy(self) -> Option<usize> {
+    if self.is_legacy() {
+      usize::try_from(self.0.as_u128()).ok()
+    } else {
+      None
+    }
+  }
+}
+
+impl TryFrom<usize> for Id {
+  type Error = Error;
+
+  /// Create a legacy `Id` from a "raw" integer.
+  fn try_from(other: usize) -> Result<Self, Self::Error> {
+    let id = NonZeroUsize::new(other).context("encountered an ID of reserved value 0")?;
+    Ok(Self::legacy(id))
+  }
+}
+
+impl Debug for Id {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(f, "Id {{ id: {self} }}")
+  }
+}
+
+impl Display for Id {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    if self.is_legacy() {
+      write!(f, "{}", self.0.as_u128())
+    } else {
+      write!(f, "{}", self.0.as_hyphenated())
+    }
+  }
+}
+
+impl FromStr for Id {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Ok(id) = usize::from_str(s) {
+      Self::try_from(id)
+    } else {
+      let uuid = Uuid::try_parse(s).with_context(|| format!("'{s}' is not a valid tag ID"))?;
+      Ok(Self(uuid))
+    }
+  }
+}
+
+// We manually implement Serialize and Deserialize in order to have the
+// ID represented as a literal value, and not some structured type.
+impl Serialize for Id {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    if self.is_legacy() {
+      serializer.serialize_u64(self.0.as_u128() as u64)
+    } else {
+      serializer.collect_str(self)
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for Id {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    struct IdVisitor;
+
+    impl Visitor<'_> for IdVisitor {
+      type Value = Id;
+
+      fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("a UUID or a non-zero unsigned integer")
+      }
+
+      fn visit_u64<E>(self, id: u64) -> Result<Self::Value, E>
+      where
+        E: DeError,
+      {
+        Id::try_from(id as usize)
+          .map_err(|_| E::invalid_value(Unexpected::Unsigned(id), &self))
+      }
+
+      fn visit_str<E>(self, id: &str) -> Result<Self::Value, E>
+      where
+        E: DeError,
+      {
+        Id::from_str(id).map_err(|_| E::invalid_value(Unexpected::Str(id), &self))
+      }
+    }
+
+    deserializer.deserialize_any(IdVisitor)
+  }
+}
 
 
 /// The separator to use for separating the components of a `Template`
@@ -96,6 +238,38 @@ impl Display for Tag {
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Templates(pub Vec<Template>);
 
+impl Templates {
+  /// Merge the templates of `other`, e.g., as stored in a copy of the
+  /// task meta data that diverged on a different device, into this
+  /// object.
+  ///
+  /// Templates are matched up by their identity. Legacy IDs are only
+  /// meaningful on the device that allocated them and so templates
+  /// having one are matched up by name instead. Templates already
+  /// present take precedence.
+  pub fn merge(&mut self, other: Templates) {
+    for template in other.0 {
+      let id = if template.id.is_legacy() {
+        Id::from_name(&template.name)
+      } else {
+        template.id
+      };
+
+      let present = self.0.iter().any(|existing| {
+        existing.id == id
+          || (existing.name == template.name
+            && (existing.id.is_legacy() || template.id.is_legacy()))
+      });
+      if !present {
+        let () = self.0.push(Template {
+          id,
+          name: template.name,
+        });
+      }
+    }
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -144,4 +318,70 @@ mod tests {
 
     assert_eq!(deserialized, tag);
   }
+
+  /// Check that legacy IDs are serialized as numbers and all others as
+  /// UUID strings.
+  #[test]
+  fn serialize_deserialize_ids() {
+    let id = Id::try_from(1337).unwrap();
+    assert!(id.is_legacy());
+    assert_eq!(id.to_legacy(), Some(1337));
+    let serialized = Json::serialize(&id).unwrap();
+    assert_eq!(serialized, b"1337");
+    let deserialized = <Json as Backend<Id>>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, id);
+
+    let id = Id::random();
+    assert!(!id.is_legacy());
+    assert_eq!(id.to_legacy(), None);
+    let serialized = Json::serialize(&id).unwrap();
+    assert_eq!(serialized, format!(r#""{id}""#).as_bytes());
+    let deserialized = <Json as Backend<Id>>::deserialize(&serialized).unwrap();
+    assert_eq!(deserialized, id);
+
+    assert!(<Json as Backend<Id>>::deserialize(b"0").is_err());
+    assert!(<Json as Backend<Id>>::deserialize(br#""foo""#).is_err());
+  }
+
+  /// Check that `Id`s derived from names are stable and distinct for
+  /// different names.
+  #[test]
+  fn id_from_name() {
+    let id = Id::from_name("work");
+    assert!(!id.is_legacy());
+    assert_eq!(id, Id::from_name("work"));
+    assert_ne!(id, Id::from_name("home"));
+    // The `Id` must not change between versions of the program.
+    assert_eq!(id.to_string(), "69daf6a0-5c75-8277-b806-e997a7b9c5d8");
+  }
+
+  /// Check that we can merge two diverged sets of templates.
+  #[test]
+  fn merge_templates() {
+    let template = |id, name: &str| Template {
+      id,
+      name: name.to_string(),
+    };
+
+    let work = Id::from_name("work");
+    let home = Id::from_name("home");
+    let renamed = Id::random();
+    let mut templates1 = Templates(vec![
+      template(work, "work"),
+      template(renamed, "renamed"),
+    ]);
+    let templates2 = Templates(vec![
+      template(Id::try_from(1).unwrap(), "work"),
+      template(Id::try_from(2).unwrap(), "home"),
+      template(renamed, "original"),
+    ]);
+
+    let () = templates1.merge(templates2);
+    let expected = Templates(vec![
+      template(work, "work"),
+      template(renamed, "renamed"),
+      template(home, "home"),
+    ]);
+    assert_eq!(templates1, expected);
+  }
 }
@@ -4,6 +4,17 @@
 //! A module providing serialization and deserialization support for
 //! task objects.
 
+use std::ffi::OsString;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+#[cfg(any(test, feature = "test"))]
+use chrono::SubsecRound as _;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+
 use uuid::Uuid;
 
 use crate::ser::tags::Tag;
@@ -14,6 +25,100 @@ use crate::ser::tags::Templates;
 pub type Id = Uuid;
 
 
+/// The priority of a task.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+  Low,
+  Medium,
+  High,
+}
+
+
+/// A property of a task that we do not interpret ourselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+  /// The property's name.
+  pub name: String,
+  /// The property's parameters, as name-value pairs.
+  pub params: Vec<(String, Option<String>)>,
+  /// The property's value, in its raw (escaped) form.
+  pub value: String,
+}
+
+
+/// A sub-component of a task that we do not interpret ourselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+  /// The component's name, e.g., `VALARM`.
+  pub name: String,
+  /// The component's properties, in their original order.
+  pub properties: Vec<Property>,
+  /// The component's own sub-components.
+  pub components: Vec<Component>,
+}
+
+
+/// The point in time relative to which a relative alarm trigger is
+/// specified.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Related {
+  /// The trigger is relative to the task's start date.
+  Start,
+  /// The trigger is relative to the task's due date.
+  End,
+}
+
+
+/// A description of when an alarm fires.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+  /// The alarm fires at the given point in time.
+  Absolute(DateTime<Utc>),
+  /// The alarm fires `offset` seconds after (or, if negative, before)
+  /// the beginning of the task's start or due date.
+  Relative { offset: i64, related: Related },
+}
+
+
+/// An alarm reminding of a task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alarm {
+  /// When the alarm fires.
+  pub trigger: Trigger,
+  /// The alarm's remaining properties (e.g., `ACTION`), which we do not
+  /// interpret ourselves, in their original order.
+  pub properties: Vec<Property>,
+}
+
+
+/// Data attached to a task that we do not interpret ourselves, but
+/// keep around so that we can write it back unchanged.
+///
+/// Such data are typically created by other clients operating on the
+/// same tasks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Unknown {
+  /// Properties we do not know about, in their original order.
+  pub properties: Vec<Property>,
+  /// Sub-components we do not know about, in their original order.
+  pub components: Vec<Component>,
+  /// Components accompanying the task on the calendar level, such as
+  /// `VTIMEZONE` definitions, in their original order.
+  pub calendar_components: Vec<Component>,
+}
+
+impl Unknown {
+  /// Check whether there is no unknown data.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.properties.is_empty()
+      && self.components.is_empty()
+      && self.calendar_components.is_empty()
+  }
+}
+
+
 /// A task that we deserialize into and serialize from.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Task {
@@ -21,10 +126,51 @@ pub struct Task {
   pub id: Id,
   /// The task's summary.
   pub summary: String,
+  /// Free-form notes associated with the task, if any.
+  pub notes: Option<String>,
   /// The task's list of currently set tags.
   pub tags: Vec<Tag>,
+  /// The names of the task's tags. When loading a task these take
+  /// precedence over `tags`, unless empty, because other clients only
+  /// know about the former.
+  pub categories: Vec<String>,
+  /// The date before which the task cannot be acted upon, if any.
+  pub start: Option<NaiveDate>,
+  /// The date by which the task is due, if any.
+  pub due: Option<NaiveDate>,
+  /// The task's priority, if any.
+  pub priority: Option<Priority>,
+  /// The rule describing how the task recurs, if it does, as an RFC
+  /// 5545 `RRULE` value.
+  pub recurrence: Option<String>,
+  /// The alarms reminding of the task.
+  pub alarms: Vec<Alarm>,
+  /// The time at which the task got completed, if it did.
+  pub completed: Option<DateTime<Utc>>,
+  /// Whether the task's completion state got recorded, as opposed to
+  /// the task stemming from data predating the tracking of completion.
+  pub completion_recorded: bool,
+  /// The time at which the task got created, if known.
+  pub created: Option<DateTime<Utc>>,
+  /// The time at which the task got last modified, if known.
+  pub modified: Option<DateTime<Utc>>,
+  /// The time stamp of the task's serialized representation.
+  pub stamp: DateTime<Utc>,
+  /// The ID of the task's parent, if it is a sub-task.
+  pub parent: Option<Id>,
+  /// The IDs of the tasks this task depends on.
+  pub depends_on: Vec<Id>,
   /// The task's position.
   pub position: Option<f64>,
+  /// Data of the task that we do not interpret.
+  pub unknown: Unknown,
+  /// The name of the file the task is stored in, if it differs from the
+  /// one we would pick, e.g., because the file got created by a
+  /// different program.
+  pub file_name: Option<OsString>,
+  /// The name of the collection the task belongs to, if it is not the
+  /// default one.
+  pub collection: Option<String>,
 }
 
 #[cfg(any(test, feature = "test"))]
@@ -37,8 +183,25 @@ impl Task {
     Self {
       id: Id::new_v4(),
       summary: summary.into(),
+      notes: None,
       tags: Default::default(),
+      categories: Vec::new(),
+      start: None,
+      due: None,
+      priority: None,
+      recurrence: None,
+      alarms: Vec::new(),
+      completed: None,
+      completion_recorded: true,
+      created: None,
+      modified: None,
+      stamp: Utc::now().trunc_subsecs(0),
+      parent: None,
+      depends_on: Vec::new(),
       position: None,
+      unknown: Unknown::default(),
+      file_name: None,
+      collection: None,
     }
   }
 
@@ -60,9 +223,17 @@ pub struct TasksMeta {
   pub templates: Templates,
 }
 
+impl TasksMeta {
+  /// Merge a diverged copy of task meta data into this object.
+  #[inline]
+  pub fn merge(&mut self, other: TasksMeta) {
+    self.templates.merge(other.templates)
+  }
+}
+
 
 /// A struct comprising a list of tasks.
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Tasks(pub Vec<Task>);
 
 #[cfg(test)]
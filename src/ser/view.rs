@@ -7,32 +7,76 @@
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::ser::tags::Id;
 use crate::ser::tags::Tag;
+use crate::ser::tasks::Priority;
+
+
+/// A term that a literal may refer to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Term {
+  /// The term is matched by tasks having the tag set.
+  Tag(Tag),
+  /// The term is matched by tasks having the given priority.
+  Priority { priority: Priority },
+  /// The term is matched by tasks created within the given number of
+  /// days.
+  CreatedWithin { created_within_days: u32 },
+  /// The term is matched by tasks modified within the given number of
+  /// days.
+  ModifiedWithin { modified_within_days: u32 },
+  /// The term is matched by tasks that are (or are not) blocked on
+  /// another task.
+  Blocked { blocked: bool },
+  /// The term is matched by tasks that are (or are not) snoozed, i.e.,
+  /// have a start date in the future.
+  Snoozed { snoozed: bool },
+  /// The term is matched by tasks belonging to the collection with the
+  /// given name. The empty name refers to the default collection.
+  Collection { collection: String },
+}
+
+impl From<Tag> for Term {
+  fn from(tag: Tag) -> Self {
+    Term::Tag(tag)
+  }
+}
 
 
 /// A literal that can be serialized and deserialized.
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TagLit {
-  Pos(Tag),
-  Neg(Tag),
+  Pos(Term),
+  Neg(Term),
 }
 
 impl TagLit {
-  /// Retrieve the ID of the wrapped tag.
-  pub fn id(&self) -> Id {
+  /// Retrieve the wrapped term.
+  pub fn term(&self) -> &Term {
     match self {
-      TagLit::Pos(tag) | TagLit::Neg(tag) => tag.id,
+      TagLit::Pos(term) | TagLit::Neg(term) => term,
     }
   }
 }
 
 
+/// The criterion by which to sort the tasks of a view.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+  Created,
+  Modified,
+  Priority,
+}
+
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 struct ViewImpl {
   name: String,
   lits: Vec<Vec<TagLit>>,
+  #[serde(default)]
+  sort: Option<Sort>,
 }
 
 
@@ -51,8 +95,8 @@ impl From<ViewTuple> for View {
   fn from(other: ViewTuple) -> Self {
     match other {
       ViewTuple::View(view) | ViewTuple::Tuple((view, ..)) => {
-        let ViewImpl { name, lits } = view;
-        View { name, lits }
+        let ViewImpl { name, lits, sort } = view;
+        View { name, lits, sort }
       },
     }
   }
@@ -67,6 +111,8 @@ impl From<ViewTuple> for View {
 pub struct View {
   pub name: String,
   pub lits: Vec<Vec<TagLit>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sort: Option<Sort>,
 }
 
 
@@ -77,7 +123,7 @@ mod tests {
   use crate::ser::backends::Backend;
   use crate::ser::backends::Json;
 
-  use crate::ser::id::Id;
+  use crate::ser::tags::Id;
 
 
   /// Check that we can serialize and deserialize a `View`.
@@ -99,10 +145,39 @@ mod tests {
     let view = View {
       name: "test-view".to_string(),
       lits: vec![
-        vec![TagLit::Pos(tag1)],
-        vec![TagLit::Pos(tag2), TagLit::Neg(tag3)],
-        vec![TagLit::Neg(tag4), TagLit::Pos(tag2)],
+        vec![TagLit::Pos(tag1.into())],
+        vec![TagLit::Pos(tag2.into()), TagLit::Neg(tag3.into())],
+        vec![TagLit::Neg(tag4.into()), TagLit::Pos(tag2.into())],
+        vec![
+          TagLit::Pos(Term::Priority {
+            priority: Priority::High,
+          }),
+          TagLit::Neg(Term::Priority {
+            priority: Priority::Low,
+          }),
+        ],
+        vec![
+          TagLit::Pos(Term::CreatedWithin {
+            created_within_days: 7,
+          }),
+          TagLit::Neg(Term::ModifiedWithin {
+            modified_within_days: 1,
+          }),
+        ],
+        vec![
+          TagLit::Pos(Term::Blocked { blocked: false }),
+          TagLit::Neg(Term::Snoozed { snoozed: true }),
+        ],
+        vec![
+          TagLit::Pos(Term::Collection {
+            collection: "work".to_string(),
+          }),
+          TagLit::Neg(Term::Collection {
+            collection: String::new(),
+          }),
+        ],
       ],
+      sort: Some(Sort::Modified),
     };
 
     let serialized = Json::serialize(&view).unwrap();
@@ -110,4 +185,25 @@ mod tests {
 
     assert_eq!(deserialized, view);
   }
+
+  /// Check that views stored before literals could refer to anything
+  /// but tags can still be deserialized.
+  #[test]
+  fn deserialize_tag_only_view() {
+    let serialized = br#"{"name":"test-view","lits":[[{"pos":1},{"neg":2}]]}"#;
+    let view = <Json as Backend<View>>::deserialize(serialized).unwrap();
+
+    let tag1 = Tag {
+      id: Id::try_from(1).unwrap(),
+    };
+    let tag2 = Tag {
+      id: Id::try_from(2).unwrap(),
+    };
+    let expected = View {
+      name: "test-view".to_string(),
+      lits: vec![vec![TagLit::Pos(tag1.into()), TagLit::Neg(tag2.into())]],
+      sort: None,
+    };
+    assert_eq!(view, expected);
+  }
 }
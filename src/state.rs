@@ -4,34 +4,55 @@
 //! Definitions pertaining UI configuration and task state of the
 //! program.
 
+use std::cell::Cell;
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::collections::btree_map::Entry;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::hash::Hasher as _;
 use std::io::ErrorKind;
+use std::os::unix::ffi::OsStrExt as _;
 use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Context as _;
+use anyhow::Error;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::Utc;
+
 use crate::ser::backends::iCal;
 use crate::ser::backends::Backend;
 
+use serde_json::from_slice as from_json;
+use serde_json::to_writer as to_json_writer;
+
 use tokio::fs::create_dir_all;
+use tokio::fs::read;
 use tokio::fs::read_dir;
+use tokio::fs::remove_dir_all;
 use tokio::fs::remove_file;
-use tokio::fs::DirEntry;
+use tokio::fs::try_exists;
 use tokio::fs::File;
-use tokio::fs::OpenOptions;
 use tokio::fs::ReadDir;
 use tokio::io::AsyncReadExt as _;
-use tokio::io::AsyncWriteExt as _;
 
 use uuid::uuid;
 
 use crate::cap::DirCap;
 use crate::cap::FileCap;
 use crate::cap::WriteGuard;
+use crate::ser::journal::Entry as JournalEntry;
 use crate::ser::state::TaskState as SerTaskState;
 use crate::ser::tasks::Id as SerTaskId;
 use crate::ser::tasks::Task as SerTask;
@@ -44,7 +65,8 @@ use crate::tasks::Tasks;
 /// The ID we use for storing task meta data.
 // We use the "special" UUID 00000000-0000-0000-0000-000000000000 for
 // storing task meta data.
-const TASKS_META_ID: SerTaskId = uuid!("00000000-0000-0000-0000-000000000000");
+pub(crate) const TASKS_META_ID: SerTaskId = uuid!("00000000-0000-0000-0000-000000000000");
+
 
 
 /// Load some serialized state from a file.
@@ -67,41 +89,161 @@ where
   }
 }
 
-/// Load a task from a directory entry.
-async fn load_task_from_dir_entry(entry: &DirEntry) -> Result<Option<SerTask>> {
-  let file_name = entry.file_name();
-  let path = entry.path();
-  let id = file_name
-    .to_str()
-    .and_then(|id| SerTaskId::try_parse(id).ok())
-    .ok_or_else(|| anyhow!("filename {} is not a valid UUID", path.display()))?;
+/// Retrieve the path of the file storing the given task, relative to
+/// the tasks root directory.
+///
+/// Tasks of the default collection are stored in the root directory
+/// itself, those of other collections in a sub-directory named after
+/// the collection.
+fn task_file_path(task: &SerTask) -> PathBuf {
+  let file_name = task
+    .file_name
+    .clone()
+    .unwrap_or_else(|| OsString::from(task.id.to_string()));
+
+  match &task.collection {
+    Some(collection) => Path::new(collection).join(file_name),
+    None => PathBuf::from(file_name),
+  }
+}
 
-  let result = load_state_from_file::<iCal, SerTask>(&path)
-    .await
+/// Retrieve the name of the collection a task file, as identified by
+/// its path relative to the tasks root directory, belongs to.
+fn file_collection(file: &Path) -> Option<&str> {
+  file
+    .parent()
+    .filter(|parent| !parent.as_os_str().is_empty())
+    .and_then(Path::to_str)
+}
+
+/// Group tasks by the files they are stored in.
+fn group_tasks_by_file(tasks: &SerTasks) -> BTreeMap<PathBuf, SerTasks> {
+  tasks
+    .0
+    .iter()
+    .fold(BTreeMap::new(), |mut files, task| {
+      let () = files
+        .entry(task_file_path(task))
+        .or_insert_with(SerTasks::default)
+        .0
+        .push(task.clone());
+      files
+    })
+}
+
+/// A fingerprint of the contents of a file.
+type Fingerprint = u64;
+
+/// Calculate the fingerprint of the given file contents.
+fn fingerprint(content: &[u8]) -> Fingerprint {
+  let mut hasher = DefaultHasher::new();
+  let () = hasher.write(content);
+  hasher.finish()
+}
+
+/// Read a file, if it exists.
+async fn read_file(path: &Path) -> Result<Option<Vec<u8>>> {
+  match read(path).await {
+    Ok(content) => Ok(Some(content)),
+    Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+  }
+}
+
+/// Calculate the fingerprint of a file, if it exists.
+async fn fingerprint_file(path: &Path) -> Result<Option<Fingerprint>> {
+  let content = read_file(path).await?;
+  Ok(content.as_deref().map(fingerprint))
+}
+
+/// Load the tasks stored in the file at the given path, along with the
+/// fingerprint of the file, if it exists.
+async fn load_tasks_from_file(path: &Path) -> Result<Option<(Vec<SerTask>, Fingerprint)>> {
+  let content = if let Some(content) = read_file(path).await? {
+    content
+  } else {
+    return Ok(None)
+  };
+
+  let mut tasks = <iCal as Backend<SerTasks>>::deserialize(&content)
+    .context("failed to decode state")
     .with_context(|| format!("failed to load state from {}", path.display()))?
-    .map(|mut task| {
-      // TODO: Silently overwriting the ID may not be the best choice,
-      //       but it depends on whether it is actually serialized and
-      //       deserialized to begin with. If there is a discrepancy,
-      //       that may indicate a problem. On the other hand, we want
-      //       to give precedence to the file name, because it is the
-      //       most user visible and it would be confusing to not honor
-      //       it.
-      task.id = id;
-      task
-    });
+    .0;
+
+  // The ID of a task is taken from its `UID` property. Files created by
+  // other programs may be named arbitrarily and may contain multiple
+  // tasks, in which case we remember the name in order to save the
+  // tasks under it again.
+  let file_name = path.file_name().unwrap_or_default();
+  let is_default = matches!(
+    tasks.as_slice(),
+    [task] if file_name == OsStr::new(&task.id.to_string())
+  );
+  if !is_default {
+    let () = tasks
+      .iter_mut()
+      .for_each(|task| task.file_name = Some(file_name.to_os_string()));
+  }
+  Ok(Some((tasks, fingerprint(&content))))
+}
 
-  Ok(result)
+/// Load the task meta data stored in the file at the given path, along
+/// with the fingerprint of the file, if it exists.
+async fn load_tasks_meta_from_file(path: &Path) -> Result<Option<(SerTasksMeta, Fingerprint)>> {
+  let content = if let Some(content) = read_file(path).await? {
+    content
+  } else {
+    return Ok(None)
+  };
+
+  let tasks_meta = <iCal as Backend<SerTasksMeta>>::deserialize(&content)
+    .context("failed to decode state")
+    .with_context(|| format!("failed to load state from {}", path.display()))?;
+  Ok(Some((tasks_meta, fingerprint(&content))))
+}
+
+/// Task state as loaded from a directory.
+#[derive(Debug, Default)]
+struct Loaded {
+  /// The state that could be loaded.
+  state: SerTaskState,
+  /// The fingerprints of the task files loaded, keyed by their path
+  /// relative to the tasks root directory.
+  fingerprints: HashMap<PathBuf, Fingerprint>,
+  /// The fingerprint of the task meta data file, if it was loaded.
+  meta_fingerprint: Option<Fingerprint>,
+  /// Problems encountered while loading individual task files.
+  problems: Vec<Problem>,
+  /// The names of the collections found.
+  collections: Vec<String>,
 }
 
 /// Load tasks by iterating over the entries of a `ReadDir` object.
-#[allow(clippy::type_complexity)]
-async fn load_tasks_from_read_dir(dir: ReadDir) -> Result<(Vec<SerTask>, Option<SerTasksMeta>)> {
+///
+/// `collection` is the name of the collection the directory represents,
+/// if it is not the tasks root directory. Only the latter contains task
+/// meta data and sub-directories for further collections.
+///
+/// `ids` contains the IDs of all tasks loaded so far and is updated
+/// accordingly. A file containing a task with an ID that was already
+/// encountered is treated as broken.
+///
+/// Task files that fail to load do not cause the overall operation to
+/// fail. Rather, a [`Problem`] is recorded for each of them.
+async fn load_tasks_from_read_dir(
+  dir: ReadDir,
+  collection: Option<&str>,
+  ids: &mut HashSet<SerTaskId>,
+) -> Result<Loaded> {
   let mut dir = dir;
   // Ideally we'd size the `Vec` as per the number of directory entries,
   // but `fs::ReadDir` does not currently expose that number.
   let mut tasks = Vec::new();
   let mut tasks_meta = None;
+  let mut fingerprints = HashMap::new();
+  let mut meta_fingerprint = None;
+  let mut problems = Vec::new();
+  let mut collections = Vec::new();
 
   let mut buffer = SerTaskId::encode_buffer();
   let tasks_meta_uuid = TASKS_META_ID.as_hyphenated().encode_lower(&mut buffer);
@@ -111,37 +253,189 @@ async fn load_tasks_from_read_dir(dir: ReadDir) -> Result<(Vec<SerTask>, Option<
     .await
     .context("failed to iterate directory contents")?
   {
-    if entry.file_name() == OsStr::new(tasks_meta_uuid) {
+    let file_name = entry.file_name();
+    // Hidden files are not considered part of a vdir.
+    if file_name.as_bytes().starts_with(b".") {
+      continue
+    }
+
+    let file_type = entry
+      .file_type()
+      .await
+      .with_context(|| format!("failed to inquire file type of {}", entry.path().display()))?;
+    if file_type.is_dir() {
+      // Directories below the tasks root are collections of their own.
+      // We do not descend any further than that.
+      if collection.is_none() {
+        match file_name.into_string() {
+          Ok(name) => collections.push(name),
+          Err(_) => problems.push(Problem {
+            path: entry.path(),
+            error: anyhow!("collection name is not valid UTF-8"),
+          }),
+        }
+      }
+      continue
+    }
+
+    if file_name == OsStr::new(tasks_meta_uuid) {
+      // Task meta data are shared by all collections and only ever
+      // stored in the tasks root directory.
+      if collection.is_some() {
+        continue
+      }
+
       debug_assert_eq!(
         tasks_meta, None,
         "encountered multiple task meta data files"
       );
-      tasks_meta = load_state_from_file::<iCal, SerTasksMeta>(&entry.path()).await?;
-    } else if let Some(data) = load_task_from_dir_entry(&entry).await? {
-      let () = tasks.push(data);
+      // A broken meta data file is reported like any other broken
+      // file. We continue with default meta data, then.
+      match load_tasks_meta_from_file(&entry.path()).await {
+        Ok(Some((loaded, fingerprint))) => {
+          tasks_meta = Some(loaded);
+          meta_fingerprint = Some(fingerprint);
+        },
+        // The file vanished in the meantime.
+        Ok(None) => (),
+        Err(error) => problems.push(Problem {
+          path: entry.path(),
+          error,
+        }),
+      }
+    } else {
+      match load_tasks_from_file(&entry.path()).await {
+        Ok(Some((mut loaded, fingerprint))) => {
+          if let Some(task) = loaded.iter().find(|task| ids.contains(&task.id)) {
+            let () = problems.push(Problem {
+              path: entry.path(),
+              error: anyhow!("task {} is already stored in a different file", task.id),
+            });
+            continue
+          }
+
+          let () = ids.extend(loaded.iter().map(|task| task.id));
+          let () = loaded
+            .iter_mut()
+            .for_each(|task| task.collection = collection.map(str::to_string));
+          let file = match collection {
+            Some(collection) => Path::new(collection).join(file_name),
+            None => PathBuf::from(file_name),
+          };
+          let () = tasks.extend(loaded);
+          let _prev = fingerprints.insert(file, fingerprint);
+        },
+        // The file vanished in the meantime.
+        Ok(None) => (),
+        Err(error) => problems.push(Problem {
+          path: entry.path(),
+          error,
+        }),
+      }
     }
   }
 
-  Ok((tasks, tasks_meta))
+  let loaded = Loaded {
+    state: SerTaskState {
+      tasks_meta: tasks_meta.unwrap_or_default(),
+      tasks: SerTasks(tasks),
+    },
+    fingerprints,
+    meta_fingerprint,
+    problems,
+    collections,
+  };
+  Ok(loaded)
 }
 
 /// Load tasks from a directory.
 ///
 /// The function assumes that the directory *only* contains files
-/// representing tasks (along with one file for meta data).
-async fn load_tasks_from_dir(root: &Path) -> Result<SerTaskState> {
+/// representing tasks (along with one file for meta data) and
+/// directories representing further collections of tasks.
+async fn load_tasks_from_dir(root: &Path) -> Result<Loaded> {
   let dir = match read_dir(root).await {
     Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Default::default()),
     result => result,
   }?;
 
-  let (tasks, tasks_meta) = load_tasks_from_read_dir(dir).await?;
-  let tasks_meta = tasks_meta.unwrap_or_default();
+  let mut ids = HashSet::new();
+  let mut loaded = load_tasks_from_read_dir(dir, None, &mut ids).await?;
+  for collection in loaded.collections.iter() {
+    let path = root.join(collection);
+    let dir = read_dir(&path)
+      .await
+      .with_context(|| format!("failed to read directory {}", path.display()))?;
+    let Loaded {
+      state,
+      fingerprints,
+      problems,
+      ..
+    } = load_tasks_from_read_dir(dir, Some(collection), &mut ids).await?;
+
+    let () = loaded.state.tasks.0.extend(state.tasks.0);
+    let () = loaded.fingerprints.extend(fingerprints);
+    let () = loaded.problems.extend(problems);
+  }
+  Ok(loaded)
+}
+
+/// Replace the tags of all tasks for which tag names were loaded (e.g.,
+/// as set by other clients) with the tags of the same names, creating
+/// templates for names that are not yet known.
+fn apply_categories(tasks: &mut SerTasks, templates: &mut Templates) {
+  for task in tasks.0.iter_mut().filter(|task| !task.categories.is_empty()) {
+    let mut tags = Vec::with_capacity(task.categories.len());
+    for name in &task.categories {
+      // Should multiple templates share a name, we prefer the tag that
+      // the task already has.
+      let tag = task
+        .tags
+        .iter()
+        .find(|tag| matches!(templates.instantiate(tag.id), Some(t) if t.name() == name))
+        .copied()
+        .unwrap_or_else(|| templates.instantiate_or_create(name).to_serde());
+
+      if !tags.contains(&tag) {
+        let () = tags.push(tag);
+      }
+    }
+    task.tags = tags;
+  }
+}
+
+/// Merge two diverged copies of the task meta data file, writing the
+/// result back to both files.
+///
+/// The signature of this function is such that it can be used for
+/// resolving conflicts reported by tools such as `vdirsyncer`.
+pub(crate) async fn merge_tasks_meta_files(path1: &Path, path2: &Path) -> Result<()> {
+  let mut tasks_meta = load_tasks_meta_file(path1).await?;
+  let () = tasks_meta.merge(load_tasks_meta_file(path2).await?);
+  let serialized = iCal::serialize(&tasks_meta)?;
+
+  for path in [path1, path2] {
+    let (dir, file) = match (path.parent(), path.file_name()) {
+      (Some(dir), Some(file)) => (dir, file),
+      _ => bail!("{} does not refer to a file", path.display()),
+    };
+    let mut dir_cap = DirCap::for_dir(dir.to_path_buf()).await?;
+    let write_guard = dir_cap.write().await?;
+    let () = write_guard
+      .file_cap(file)
+      .write(&serialized)
+      .await
+      .with_context(|| format!("failed to write task meta data to {}", path.display()))?;
+  }
+  Ok(())
+}
 
-  Ok(SerTaskState {
-    tasks_meta,
-    tasks: SerTasks(tasks),
-  })
+/// Load task meta data from the given file.
+async fn load_tasks_meta_file(path: &Path) -> Result<SerTasksMeta> {
+  load_state_from_file::<iCal, SerTasksMeta>(path)
+    .await
+    .with_context(|| format!("failed to load task meta data from {}", path.display()))
+    .map(Option::unwrap_or_default)
 }
 
 /// Check whether some state should be saved.
@@ -174,41 +468,94 @@ where
   }
 
   let serialized = B::serialize(state)?;
+  file_cap.write(serialized.as_ref()).await
+}
 
-  let () = file_cap
-    .with_writeable_path(|path| async {
-      let () = OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(path)
-        .await?
-        .write_all(serialized.as_ref())
-        .await?;
-      Ok(())
-    })
-    .await?;
+/// Load the entries of the journal stored in the file at `path`.
+///
+/// The journal contains one JSON encoded entry per line. A missing file
+/// is treated as an empty journal. Because the program may have
+/// terminated while appending to the journal, a final line that fails
+/// to decode is ignored.
+pub(crate) async fn load_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+  let content = match read(path).await {
+    Ok(content) => content,
+    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+  };
+
+  let mut lines = content
+    .split(|byte| *byte == b'\n')
+    .filter(|line| !line.is_empty())
+    .peekable();
+  let mut entries = Vec::new();
+  while let Some(line) = lines.next() {
+    match from_json::<JournalEntry>(line) {
+      Ok(entry) => entries.push(entry),
+      Err(_) if lines.peek().is_none() => break,
+      Err(err) => {
+        return Err(err).with_context(|| format!("failed to decode journal {}", path.display()))
+      },
+    }
+  }
+  Ok(entries)
+}
 
-  Ok(())
+/// Append `entries` to the journal file represented by `file_cap`.
+pub(crate) async fn append_journal(
+  file_cap: &mut FileCap<'_>,
+  entries: &[JournalEntry],
+) -> Result<()> {
+  let mut data = Vec::new();
+  for entry in entries {
+    let () = to_json_writer(&mut data, entry).context("failed to encode journal entry")?;
+    let () = data.push(b'\n');
+  }
+  file_cap.append(&data).await
 }
 
 /// Check whether we should save a task.
-async fn should_save_task(dir: &Path, task: &SerTask) -> Result<bool> {
-  let path = dir.join(OsStr::new(&task.id.to_string()));
-  should_save_state::<iCal, _>(&path, task).await
+async fn should_save_task_file(dir: &Path, file: &Path, tasks: &SerTasks) -> Result<bool> {
+  let path = dir.join(file);
+  load_state_from_file::<iCal, SerTasks>(&path)
+    .await
+    .map(|existing| {
+      // Neither the file name nor the collection are part of the
+      // serialized representation and so we have to fill them in before
+      // comparing. All tasks stored in a file share them.
+      let file_name = tasks.0.first().and_then(|task| task.file_name.clone());
+      let collection = tasks.0.first().and_then(|task| task.collection.clone());
+      let existing = existing.map(|mut existing| {
+        let () = existing.0.iter_mut().for_each(|task| {
+          task.file_name = file_name.clone();
+          task.collection = collection.clone();
+        });
+        existing
+      });
+      existing.as_ref() != Some(tasks)
+    })
 }
 
-/// Save a task into a file in the given directory.
-async fn save_task_to_file(write_guard: &mut WriteGuard<'_>, task: &SerTask) -> Result<()> {
-  let mut file_cap = write_guard.file_cap(OsStr::new(&task.id.to_string()));
-  // TODO: It would be better if we were create a temporary file first
-  //       if one already exists and then rename atomically. But even
-  //       nicer would be if we somehow wrapped all saving in a
-  //       transaction of sorts. That would allow us to eliminate the
-  //       chance for *any* inconsistency, e.g., when saving UI
-  //       configuration before task state and the latter failing the
-  //       operation.
-  save_state_to_file::<iCal, _>(&mut file_cap, task).await
+/// Save tasks into a file in the given directory.
+async fn save_task_file(
+  write_guard: &mut WriteGuard<'_>,
+  file: &OsStr,
+  tasks: &SerTasks,
+) -> Result<()> {
+  let dir = write_guard.path().to_path_buf();
+  // A file that we fail to read or parse may hold somebody's work in
+  // progress and is never overwritten.
+  if !should_save_task_file(&dir, Path::new(file), tasks).await? {
+    return Ok(())
+  }
+
+  let mut file_cap = write_guard.file_cap(file);
+  // TODO: Individual files are written atomically, but it would be
+  //       nicer if we somehow wrapped all saving in a transaction of
+  //       sorts. That would allow us to eliminate the chance for *any*
+  //       inconsistency, e.g., when saving UI configuration before task
+  //       state and the latter failing the operation.
+  save_state_to_file::<iCal, _>(&mut file_cap, tasks).await
 }
 
 /// Check whether we should save some tasks meta state.
@@ -226,88 +573,549 @@ async fn save_tasks_meta_to_dir(
   save_state_to_file::<iCal, _>(&mut file_cap, tasks_meta).await
 }
 
+/// The state of a task file as we last saw it, i.e., when loading or
+/// saving it.
+#[derive(Clone, Debug)]
+struct FileState {
+  /// The fingerprint of the file's contents, if known.
+  fingerprint: Option<Fingerprint>,
+  /// The tasks stored in the file, in our representation.
+  tasks: SerTasks,
+}
+
+/// The task files we manage, keyed by their path relative to the tasks
+/// root directory, along with their state.
+type Files = HashMap<PathBuf, FileState>;
+
+
+/// Take over changes made to the fields of a task in `from` relative to
+/// `base` into `into`, unless `into` changed the respective field
+/// itself.
+fn merge_task_fields(base: &SerTask, from: &SerTask, into: &mut SerTask) {
+  macro_rules! merge {
+    ($($field:ident),* $(,)?) => {
+      $(
+        if into.$field == base.$field {
+          into.$field = from.$field.clone();
+        }
+      )*
+    };
+  }
+
+  // The position of a task in the hierarchy is not subject to merging,
+  // we always keep ours. Tags are represented by categories in this
+  // context.
+  merge!(
+    summary,
+    notes,
+    categories,
+    start,
+    due,
+    priority,
+    recurrence,
+    alarms,
+    completed,
+    created,
+    modified,
+    depends_on,
+    unknown,
+  );
+}
+
+/// Check whether any of the mergeable fields of `task` differ from
+/// those of `base`.
+fn is_modified(base: &SerTask, task: &SerTask) -> bool {
+  let mut merged = base.clone();
+  let () = merge_task_fields(base, task, &mut merged);
+  merged != *base
+}
+
+/// Merge two diverged versions, `ours` and `theirs`, of the tasks
+/// stored in a file, field by field.
+///
+/// Changes made on either side relative to `base` are retained. Should
+/// both sides have changed the same field of a task, our change wins.
+pub(crate) fn merge_tasks(base: &[SerTask], ours: &[SerTask], theirs: &[SerTask]) -> Vec<SerTask> {
+  fn find(tasks: &[SerTask], id: SerTaskId) -> Option<&SerTask> {
+    tasks.iter().find(|task| task.id == id)
+  }
+
+  let mut ids = HashSet::new();
+
+  ours
+    .iter()
+    .chain(theirs)
+    .map(|task| task.id)
+    .filter(|id| ids.insert(*id))
+    .collect::<Vec<_>>()
+    .into_iter()
+    .filter_map(|id| match (find(base, id), find(ours, id), find(theirs, id)) {
+      (Some(base), Some(ours), Some(theirs)) => {
+        let mut merged = ours.clone();
+        let () = merge_task_fields(base, theirs, &mut merged);
+        Some(merged)
+      },
+      // Both sides added the same task, which should only happen if
+      // the file got copied around. Just keep ours.
+      (None, Some(ours), Some(_theirs)) => Some(ours.clone()),
+      // A task removed on one side is only retained if it was modified
+      // on the other.
+      (Some(base), Some(ours), None) => is_modified(base, ours).then(|| ours.clone()),
+      (Some(base), None, Some(theirs)) => is_modified(base, theirs).then(|| theirs.clone()),
+      (None, Some(task), None) | (None, None, Some(task)) => Some(task.clone()),
+      (_, None, None) => None,
+    })
+    .collect()
+}
+
+
 /// Check whether we should save task state.
+///
+/// `files` contains the task files that we manage, i.e., the ones we
+/// loaded or saved earlier. Files contained in `broken` failed to load
+/// and are not considered.
 // TODO: The way we "walk" the task tree is duplicated with
 //       `save_tasks_to_dir`, but it's unclear how to deduplicate.
-async fn should_save_tasks(dir: &Path, tasks: &SerTaskState) -> Result<bool> {
-  for task in tasks.tasks.0.iter() {
-    if should_save_task(dir, task).await? {
+async fn should_save_tasks(
+  dir: &Path,
+  tasks: &SerTaskState,
+  files: &Files,
+  broken: &BTreeSet<PathBuf>,
+) -> Result<bool> {
+  let groups = group_tasks_by_file(&tasks.tasks);
+  for (file, tasks) in groups.iter().filter(|(file, _)| !broken.contains(*file)) {
+    if should_save_task_file(dir, file, tasks).await? {
       return Ok(true)
     }
   }
 
-  if should_save_tasks_meta(dir, &tasks.tasks_meta).await? {
+  let save_meta = !broken.contains(Path::new(&TASKS_META_ID.to_string()));
+  if save_meta && should_save_tasks_meta(dir, &tasks.tasks_meta).await? {
     return Ok(true)
   }
 
+  // Check whether there are files of tasks that no longer exist and
+  // which should be removed.
+  for file in files
+    .keys()
+    .filter(|file| !groups.contains_key(*file) && !broken.contains(*file))
+  {
+    if try_exists(dir.join(file)).await? {
+      return Ok(true)
+    }
+  }
+  Ok(false)
+}
+
+/// Retrieve the capability to the directory of the collection with the
+/// given name, creating the directory and the capability as necessary.
+///
+/// A newly created directory gets picked up by the watch of the tasks
+/// root, which then watches it as well.
+async fn collection_dir_cap<'caps>(
+  root_dir_cap: &mut DirCap,
+  collection_dir_caps: &'caps mut BTreeMap<String, DirCap>,
+  collection: &str,
+) -> Result<&'caps mut DirCap> {
+  match collection_dir_caps.entry(collection.to_string()) {
+    Entry::Occupied(entry) => Ok(entry.into_mut()),
+    Entry::Vacant(entry) => {
+      let path = root_dir_cap.path().join(collection);
+      if !try_exists(&path).await? {
+        let _write_guard = root_dir_cap.write().await?;
+        let () = create_dir_all(&path)
+          .await
+          .with_context(|| format!("failed to create directory {}", path.display()))?;
+      }
+
+      let dir_cap = DirCap::for_dir(path).await?;
+      Ok(entry.insert(dir_cap))
+    },
+  }
+}
+
+/// Save tasks into files in the provided directory and the directories
+/// of the collections below it.
+///
+/// `files` contains the task files that we manage. Files contained in
+/// it that no longer correspond to a task get removed, or moved into
+/// `trash`, if provided. Files of tasks restored from one of the
+/// `stores` are removed from it. On success, `files` is updated to
+/// reflect the files just saved.
+///
+/// `broken` contains the files, relative to the tasks root directory,
+/// that failed to load. They are neither overwritten nor removed, and
+/// that includes the task meta data file.
+async fn save_tasks_to_dir(
+  root_dir_cap: &mut DirCap,
+  collection_dir_caps: &mut BTreeMap<String, DirCap>,
+  mut trash: Option<&mut Store>,
+  stores: &mut [&mut Store],
+  tasks: &SerTaskState,
+  files: &mut Files,
+  broken: &BTreeSet<PathBuf>,
+) -> Result<()> {
+  let groups = group_tasks_by_file(&tasks.tasks);
+  let save_meta = !broken.contains(Path::new(&TASKS_META_ID.to_string()));
+  // Remove the files of all tasks that no longer exist. Files that we
+  // do not know about, e.g., because they were not created by us or
+  // only appeared after we loaded tasks, are left alone.
+  let removed = files
+    .keys()
+    .filter(|file| !groups.contains_key(*file) && !broken.contains(*file))
+    .collect::<Vec<_>>();
+  // Only files containing tasks that are gone are moved to the trash.
+  // Files whose tasks all still exist merely got superseded, e.g.,
+  // because their tasks moved to a different collection.
   let ids = tasks
     .tasks
     .0
     .iter()
     .map(|task| task.id)
     .collect::<HashSet<_>>();
-
-  // Check whether there are files that do not correspond to a task we
-  // manage and which should be removed.
-  let mut dir = read_dir(dir).await?;
-  while let Some(entry) = dir.next_entry().await? {
-    let id = entry
-      .file_name()
-      .to_str()
-      .and_then(|id| SerTaskId::try_parse(id).ok());
-
-    let remove = if let Some(id) = id {
-      id != TASKS_META_ID && ids.get(&id).is_none()
-    } else {
-      true
+  let is_superseded = |file: &Path| {
+    files
+      .get(file)
+      .map(|state| state.tasks.0.iter().all(|task| ids.contains(&task.id)))
+      .unwrap_or(false)
+  };
+  // Files of tasks that got added. These may have been restored from
+  // the trash or the archive.
+  let added = groups
+    .keys()
+    .filter(|file| !files.contains_key(*file))
+    .collect::<Vec<_>>();
+
+  // The default collection, represented by `None`, always comes first,
+  // as it also hosts the task meta data.
+  let collections = groups
+    .keys()
+    .chain(removed.iter().copied())
+    .map(|file| file_collection(file))
+    .chain([None])
+    .collect::<BTreeSet<_>>();
+
+  for collection in collections {
+    let dir_cap = match collection {
+      None => &mut *root_dir_cap,
+      Some(collection) => {
+        collection_dir_cap(root_dir_cap, collection_dir_caps, collection).await?
+      },
     };
+    let mut write_guard = dir_cap.write().await?;
 
-    if remove {
-      return Ok(true)
+    for (file, tasks) in groups
+      .iter()
+      .filter(|(file, _)| file_collection(file) == collection && !broken.contains(*file))
+    {
+      let file_name = file.file_name().unwrap_or_default();
+      let () = save_task_file(&mut write_guard, file_name, tasks).await?;
+    }
+
+    if collection.is_none() && save_meta {
+      let () = save_tasks_meta_to_dir(&mut write_guard, &tasks.tasks_meta).await?;
+    }
+
+    for file in removed
+      .iter()
+      .filter(|file| file_collection(file) == collection)
+    {
+      let path = write_guard.path().join(file.file_name().unwrap_or_default());
+      if let Some(trash) = trash.as_mut().filter(|_| !is_superseded(file)) {
+        match read(&path).await {
+          Ok(data) => {
+            let () = trash
+              .put(file, &data)
+              .await
+              .with_context(|| format!("failed to move {} to trash", path.display()))?;
+          },
+          Err(err) if err.kind() == ErrorKind::NotFound => continue,
+          Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()))
+          },
+        }
+      }
+
+      match remove_file(&path).await {
+        Ok(()) => (),
+        Err(err) if err.kind() == ErrorKind::NotFound => (),
+        Err(err) => {
+          return Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        },
+      }
     }
   }
-  Ok(false)
-}
 
-/// Save tasks into files in the provided directory.
-async fn save_tasks_to_dir(dir_cap: &mut DirCap, tasks: &SerTaskState) -> Result<()> {
-  let mut write_guard = dir_cap.write().await?;
+  let stores = trash.into_iter().chain(stores.iter_mut().map(|store| &mut **store));
+  for store in stores {
+    for file in added.iter() {
+      let () = store.remove(file).await?;
+    }
+  }
 
-  for task in tasks.tasks.0.iter() {
-    let () = save_task_to_file(&mut write_guard, task).await?;
+  let mut saved = Files::with_capacity(groups.len());
+  for (file, tasks) in groups
+    .into_iter()
+    .filter(|(file, _)| !broken.contains(file))
+  {
+    let fingerprint = fingerprint_file(&root_dir_cap.path().join(&file)).await?;
+    let _prev = saved.insert(file, FileState { fingerprint, tasks });
   }
+  // We did not touch broken files and so our view of them stays as it
+  // was.
+  for file in broken {
+    if let Some(state) = files.get(file) {
+      let _prev = saved.insert(file.clone(), state.clone());
+    }
+  }
+  *files = saved;
+  Ok(())
+}
 
-  let () = save_tasks_meta_to_dir(&mut write_guard, &tasks.tasks_meta).await?;
-  let ids = tasks
-    .tasks
-    .0
+
+/// Move the files of tasks completed before `cutoff` from the tasks
+/// directory into `archive`, returning the paths of the archived files
+/// relative to the tasks directory.
+///
+/// A file is only archived if all the tasks it contains got completed
+/// before `cutoff` and none of them is the parent or a dependency of a
+/// task that stays, as such references would get lost otherwise.
+async fn archive_task_files(
+  root_dir_cap: &mut DirCap,
+  archive: &mut Store,
+  tasks: &SerTasks,
+  cutoff: DateTime<Utc>,
+) -> Result<BTreeSet<PathBuf>> {
+  let groups = group_tasks_by_file(tasks);
+  let mut archived = groups
     .iter()
-    .map(|task| task.id)
-    .collect::<HashSet<_>>();
+    .filter(|(_, tasks)| {
+      tasks
+        .0
+        .iter()
+        .all(|task| matches!(task.completed, Some(completed) if completed < cutoff))
+    })
+    .map(|(file, _)| file.clone())
+    .collect::<BTreeSet<_>>();
 
-  // Remove all files that do not correspond to an ID we just saved.
-  let mut dir = read_dir(write_guard.path()).await?;
-  while let Some(entry) = dir.next_entry().await? {
-    let id = entry
-      .file_name()
-      .to_str()
-      .and_then(|id| SerTaskId::try_parse(id).ok());
+  // Retaining a task may mean that we have to retain the tasks it
+  // references, and so on.
+  loop {
+    let referenced = groups
+      .iter()
+      .filter(|(file, _)| !archived.contains(*file))
+      .flat_map(|(_, tasks)| tasks.0.iter())
+      .flat_map(|task| task.parent.iter().chain(task.depends_on.iter()).copied())
+      .collect::<HashSet<_>>();
+    let count = archived.len();
+    let () = archived.retain(|file| {
+      groups[file]
+        .0
+        .iter()
+        .all(|task| !referenced.contains(&task.id))
+    });
+    if archived.len() == count {
+      break
+    }
+  }
 
-    let remove = if let Some(id) = id {
-      id != TASKS_META_ID && ids.get(&id).is_none()
-    } else {
-      true
+  let mut collection_dir_caps = BTreeMap::new();
+  for file in archived.iter() {
+    let dir_cap = match file_collection(file) {
+      None => &mut *root_dir_cap,
+      Some(collection) => {
+        collection_dir_cap(root_dir_cap, &mut collection_dir_caps, collection).await?
+      },
     };
+    let path = dir_cap.path().join(file.file_name().unwrap_or_default());
+    let data = read(&path)
+      .await
+      .with_context(|| format!("failed to read {}", path.display()))?;
+    let () = archive
+      .put(file, &data)
+      .await
+      .with_context(|| format!("failed to archive {}", path.display()))?;
+
+    let _write_guard = dir_cap.write().await?;
+    let () = remove_file(&path)
+      .await
+      .with_context(|| format!("failed to remove {}", path.display()))?;
+  }
+  Ok(archived)
+}
 
-    if remove {
-      // Note that we purposefully do not support the case of having a
-      // directory inside the root directory, as we'd never create one
-      // there programmatically.
-      let () = remove_file(entry.path()).await?;
+
+/// A directory storing task files outside of the tasks directory, such
+/// as the trash, holding the files of removed tasks, or the archive,
+/// holding those of tasks completed long ago.
+///
+/// A store mirrors the layout of the tasks directory: files of tasks
+/// belonging to a collection other than the default one are stored in
+/// a sub-directory named after said collection.
+#[derive(Debug)]
+pub struct Store {
+  /// The capability to the store's directory.
+  root_dir_cap: DirCap,
+  /// The capabilities to the directories of the collections in the
+  /// store, keyed by collection name.
+  collection_dir_caps: BTreeMap<String, DirCap>,
+}
+
+impl Store {
+  /// Create a `Store` object for the directory referenced by
+  /// `root_dir_cap`.
+  pub fn new(root_dir_cap: DirCap) -> Self {
+    Self {
+      root_dir_cap,
+      collection_dir_caps: BTreeMap::new(),
     }
   }
 
-  Ok(())
+  /// Retrieve the capability to the directory hosting `file`, given
+  /// relative to the store's directory.
+  async fn dir_cap(&mut self, file: &Path) -> Result<&mut DirCap> {
+    match file_collection(file) {
+      None => Ok(&mut self.root_dir_cap),
+      Some(collection) => {
+        collection_dir_cap(
+          &mut self.root_dir_cap,
+          &mut self.collection_dir_caps,
+          collection,
+        )
+        .await
+      },
+    }
+  }
+
+  /// Store `data` as `file`, replacing any file of the same name.
+  async fn put(&mut self, file: &Path, data: &[u8]) -> Result<()> {
+    let dir_cap = self.dir_cap(file).await?;
+    let write_guard = dir_cap.write().await?;
+    let mut file_cap = write_guard.file_cap(file.file_name().unwrap_or_default());
+    file_cap.write(data).await
+  }
+
+  /// Remove `file` from the store, if present.
+  async fn remove(&mut self, file: &Path) -> Result<()> {
+    let path = self.root_dir_cap.path().join(file);
+    if !try_exists(&path).await? {
+      return Ok(())
+    }
+
+    let dir_cap = self.dir_cap(file).await?;
+    let _write_guard = dir_cap.write().await?;
+    remove_file(&path)
+      .await
+      .with_context(|| format!("failed to remove {}", path.display()))
+  }
+
+  /// Retrieve the path to the store's directory.
+  pub fn path(&self) -> &Path {
+    self.root_dir_cap.path()
+  }
+
+  /// Load the tasks contained in the store.
+  ///
+  /// Files that fail to load are skipped.
+  pub async fn load(&self) -> Result<Vec<SerTask>> {
+    let root = self.root_dir_cap.path();
+    let loaded = load_tasks_from_dir(root)
+      .await
+      .with_context(|| format!("failed to load tasks from {}", root.display()))?;
+    Ok(loaded.state.tasks.0)
+  }
+
+  /// Remove all files from the store.
+  pub async fn empty(&mut self) -> Result<()> {
+    // Dropping the capabilities to the collection directories restores
+    // write access to them, so that they can be removed as a whole.
+    let () = self.collection_dir_caps.clear();
+
+    let write_guard = self.root_dir_cap.write().await?;
+    let root = write_guard.path();
+    let mut entries = read_dir(root)
+      .await
+      .with_context(|| format!("failed to read directory {}", root.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      let result = if entry.file_type().await?.is_dir() {
+        remove_dir_all(&path).await
+      } else {
+        remove_file(&path).await
+      };
+      let () = result.with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+    Ok(())
+  }
+}
+
+
+/// A problem encountered while loading a task file.
+///
+/// Files that could not be loaded are left untouched. They are neither
+/// overwritten nor removed when saving tasks.
+#[derive(Debug)]
+pub struct Problem {
+  /// The path to the file that could not be loaded.
+  path: PathBuf,
+  /// The error encountered while loading the file.
+  error: Error,
+}
+
+impl Problem {
+  /// Retrieve the path to the file that could not be loaded.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Retrieve the error encountered while loading the file.
+  pub fn error(&self) -> &Error {
+    &self.error
+  }
+}
+
+
+/// A task file that got modified externally since we loaded or last
+/// saved it.
+#[derive(Debug)]
+pub struct Conflict {
+  /// The path of the file, relative to the tasks root directory.
+  file: PathBuf,
+  /// The current contents of the file.
+  theirs: Theirs,
+}
+
+impl Conflict {
+  /// Retrieve the path of the affected file, relative to the tasks
+  /// root directory.
+  pub fn file(&self) -> &Path {
+    &self.file
+  }
+}
+
+
+/// The current contents of a file involved in a [`Conflict`].
+#[derive(Debug)]
+enum Theirs {
+  /// The tasks currently stored in a task file, along with the file's
+  /// fingerprint, or `None` if the file got removed.
+  Tasks(Option<(Vec<SerTask>, Fingerprint)>),
+  /// The task meta data currently stored, along with the file's
+  /// fingerprint.
+  Meta(SerTasksMeta, Fingerprint),
+}
+
+
+/// The ways in which a [`Conflict`] can be resolved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+  /// Keep our version of the tasks, overwriting the file.
+  Ours,
+  /// Take over the tasks as stored in the file.
+  Theirs,
+  /// Merge both versions field by field, preferring ours for fields
+  /// changed on both sides.
+  Merge,
 }
 
 
@@ -318,56 +1126,465 @@ pub struct TaskState {
   templates: Rc<Templates>,
   /// The shared task database.
   tasks: Rc<Tasks>,
+  /// The task files we manage.
+  files: RefCell<Files>,
+  /// The fingerprint of the task meta data file as we last saw it, if
+  /// known.
+  meta_fingerprint: Cell<Option<Fingerprint>>,
+  /// The capabilities to the directories of the collections other than
+  /// the default one, keyed by collection name.
+  collection_dir_caps: BTreeMap<String, DirCap>,
+  /// The trash to move the files of removed tasks into, if any.
+  trash: Option<Store>,
+  /// The archive holding the files of tasks completed long ago, if
+  /// any.
+  archive: Option<Store>,
+  /// Problems encountered while loading task files. The files in
+  /// question are never overwritten or removed.
+  problems: RefCell<Vec<Problem>>,
 }
 
 impl TaskState {
   /// Load `TaskState` from a directory.
+  ///
+  /// Task files that cannot be loaded are skipped and reported via
+  /// [`TaskState::problems`].
   pub async fn load(tasks_root: &Path) -> Result<Self> {
-    let task_state = load_tasks_from_dir(tasks_root).await.with_context(|| {
+    let loaded = load_tasks_from_dir(tasks_root).await.with_context(|| {
       format!(
         "failed to load tasks from directory {}",
         tasks_root.display()
       )
     })?;
+    Self::with_loaded(tasks_root, loaded).await
+  }
 
-    let templates = Templates::with_serde(task_state.tasks_meta.templates)
-      .map_err(|id| anyhow!("encountered duplicate tag ID {}", id))?;
-    let templates = Rc::new(templates);
-    let tasks = Tasks::with_serde(task_state.tasks, templates.clone())
-      .context("failed to instantiate task database")?;
-    let tasks = Rc::new(tasks);
+  /// Load `TaskState` from the directory referenced by `root_dir_cap`,
+  /// moving the files of tasks completed before `cutoff` into `archive`
+  /// in the process.
+  pub async fn load_and_archive(
+    root_dir_cap: &mut DirCap,
+    archive: &mut Store,
+    cutoff: DateTime<Utc>,
+  ) -> Result<Self> {
+    let tasks_root = root_dir_cap.path().to_path_buf();
+    let mut loaded = load_tasks_from_dir(&tasks_root).await.with_context(|| {
+      format!(
+        "failed to load tasks from directory {}",
+        tasks_root.display()
+      )
+    })?;
 
-    let slf = Self { templates, tasks };
+    let archived = archive_task_files(root_dir_cap, archive, &loaded.state.tasks, cutoff)
+      .await
+      .context("failed to archive completed tasks")?;
+    let () = loaded
+      .state
+      .tasks
+      .0
+      .retain(|task| !archived.contains(&task_file_path(task)));
+    let () = loaded
+      .fingerprints
+      .retain(|file, _| !archived.contains(file));
+
+    Self::with_loaded(&tasks_root, loaded).await
+  }
+
+  /// Create a `TaskState` object from tasks loaded from `tasks_root`.
+  async fn with_loaded(tasks_root: &Path, loaded: Loaded) -> Result<Self> {
+    let Loaded {
+      mut state,
+      fingerprints,
+      meta_fingerprint,
+      problems,
+      collections,
+    } = loaded;
+
+    if problems
+      .iter()
+      .any(|problem| problem.path == tasks_root.join(TASKS_META_ID.to_string()))
+    {
+      // Without the tag templates, tags can only be restored from the
+      // names stored alongside them.
+      let () = state
+        .tasks
+        .0
+        .iter_mut()
+        .filter(|task| task.categories.is_empty())
+        .for_each(|task| task.tags.clear());
+    }
+
+    let mut slf = Self::with_serde(state)?;
+    let () = slf
+      .files
+      .get_mut()
+      .iter_mut()
+      .for_each(|(file, state)| state.fingerprint = fingerprints.get(file).copied());
+    let () = slf.meta_fingerprint.set(meta_fingerprint);
+    for collection in collections {
+      let dir_cap = DirCap::for_dir(tasks_root.join(&collection)).await?;
+      let _prev = slf.collection_dir_caps.insert(collection, dir_cap);
+    }
+    *slf.problems.get_mut() = problems;
     Ok(slf)
   }
 
   /// Create a `TaskState` object from serialized state.
   pub fn with_serde(state: SerTaskState) -> Result<Self> {
-    let templates = Templates::with_serde(state.tasks_meta.templates)
+    let mut templates = Templates::with_serde(state.tasks_meta.templates)
       .map_err(|id| anyhow!("encountered duplicate tag ID {}", id))?;
+    let mut tasks = state.tasks;
+    let () = apply_categories(&mut tasks, &mut templates);
+
     let templates = Rc::new(templates);
-    let tasks = Tasks::with_serde(state.tasks, templates.clone())
+    let tasks = Tasks::with_serde(tasks, templates.clone())
       .context("failed to instantiate task database")?;
+    let files = group_tasks_by_file(&tasks.to_serde())
+      .into_iter()
+      .map(|(file, tasks)| {
+        let state = FileState {
+          fingerprint: None,
+          tasks,
+        };
+        (file, state)
+      })
+      .collect();
 
     let slf = Self {
       templates,
       tasks: Rc::new(tasks),
+      files: RefCell::new(files),
+      meta_fingerprint: Cell::new(None),
+      collection_dir_caps: BTreeMap::new(),
+      trash: None,
+      archive: None,
+      problems: RefCell::new(Vec::new()),
     };
     Ok(slf)
   }
 
 
-  /// Check whether any of the tasks were changed from the state in the
-  /// given `root_dir`.
-  pub async fn is_changed(&self, root_dir: &Path) -> bool {
-    should_save_tasks(root_dir, &self.to_serde())
-      .await
-      .unwrap_or(true)
+  /// Check whether any of the tasks were changed from the state in the
+  /// given `root_dir`.
+  pub async fn is_changed(&self, root_dir: &Path) -> bool {
+    let files = self.files.borrow().clone();
+    let broken = self.broken_files(root_dir);
+    should_save_tasks(root_dir, &self.to_serde(), &files, &broken)
+      .await
+      .unwrap_or(true)
+  }
+
+  /// Check for task files and the task meta data file in `root_dir`
+  /// that got modified externally since we loaded or last saved them.
+  ///
+  /// Saving the state would overwrite or remove such files, and so the
+  /// conflicts should be resolved first. Files that fail to load are
+  /// recorded as problems instead and left alone.
+  pub async fn conflicts(&self, root_dir: &Path) -> Result<Vec<Conflict>> {
+    let files = self.files.borrow().clone();
+    let groups = group_tasks_by_file(&self.tasks.to_serde());
+    let mut conflicts = Vec::new();
+
+    for (file, state) in files.iter() {
+      let fingerprint = if let Some(fingerprint) = state.fingerprint {
+        fingerprint
+      } else {
+        continue
+      };
+
+      let path = root_dir.join(file);
+      let theirs = match load_tasks_from_file(&path).await {
+        Ok(theirs) => theirs,
+        Err(error) => {
+          let () = self.add_problem(path, error);
+          continue
+        },
+      };
+      let conflict = match &theirs {
+        Some((_, theirs)) => *theirs != fingerprint,
+        // A file removed on both sides is not a conflict.
+        None => groups.contains_key(file),
+      };
+
+      if conflict {
+        let conflict = Conflict {
+          file: file.clone(),
+          theirs: Theirs::Tasks(theirs),
+        };
+        let () = conflicts.push(conflict);
+      }
+    }
+
+    // Files we are about to create may have been created by somebody
+    // else in the meantime.
+    for file in groups.keys().filter(|file| !files.contains_key(*file)) {
+      let path = root_dir.join(file);
+      match load_tasks_from_file(&path).await {
+        Ok(Some(theirs)) => {
+          let conflict = Conflict {
+            file: file.clone(),
+            theirs: Theirs::Tasks(Some(theirs)),
+          };
+          let () = conflicts.push(conflict);
+        },
+        Ok(None) => (),
+        Err(error) => self.add_problem(path, error),
+      }
+    }
+
+    // The task meta data may have changed as well, e.g., because a
+    // synchronization tool pulled in tags created on another device.
+    if let Some(fingerprint) = self.meta_fingerprint.get() {
+      let file = PathBuf::from(TASKS_META_ID.to_string());
+      let path = root_dir.join(&file);
+      match load_tasks_meta_from_file(&path).await {
+        Ok(Some((tasks_meta, theirs))) if theirs != fingerprint => {
+          let conflict = Conflict {
+            file,
+            theirs: Theirs::Meta(tasks_meta, theirs),
+          };
+          let () = conflicts.push(conflict);
+        },
+        Ok(_) => (),
+        Err(error) => self.add_problem(path, error),
+      }
+    }
+    Ok(conflicts)
+  }
+
+  /// Resolve a conflict in the provided way.
+  ///
+  /// Our tasks are updated to reflect the resolution and the conflict
+  /// is considered settled for the next save.
+  pub fn resolve(&self, conflict: Conflict, resolution: Resolution) {
+    let Conflict { file, theirs } = conflict;
+    let theirs = match theirs {
+      Theirs::Tasks(theirs) => theirs,
+      Theirs::Meta(tasks_meta, fingerprint) => {
+        return self.resolve_meta(tasks_meta, fingerprint, resolution)
+      },
+    };
+    let mut files = self.files.borrow_mut();
+
+    let ours = group_tasks_by_file(&self.tasks.to_serde())
+      .remove(&file)
+      .unwrap_or_default()
+      .0;
+    let base = files
+      .get(&file)
+      .map(|state| state.tasks.0.as_slice())
+      .unwrap_or_default();
+    let their_tasks = theirs
+      .as_ref()
+      .map(|(tasks, _)| tasks.as_slice())
+      .unwrap_or_default();
+
+    let resolved = match resolution {
+      Resolution::Ours => ours.clone(),
+      Resolution::Theirs => their_tasks.to_vec(),
+      Resolution::Merge => merge_tasks(base, &ours, their_tasks),
+    };
+
+    let find = |id| self.tasks.iter(|mut iter| iter.find(|task| task.id() == id).cloned());
+
+    for task in resolved.iter() {
+      match find(task.id) {
+        Some(existing) => {
+          let modified = ours
+            .iter()
+            .find(|ours| ours.id == task.id)
+            .map(|ours| is_modified(ours, task))
+            .unwrap_or(true);
+          if modified {
+            let () = self.tasks.update_external(existing, task.clone());
+          }
+        },
+        None => {
+          let _task = self.tasks.add_external(task.clone());
+        },
+      }
+    }
+
+    for task in ours.iter() {
+      if !resolved.iter().any(|resolved| resolved.id == task.id) {
+        if let Some(existing) = find(task.id) {
+          let () = self.tasks.remove_external(existing);
+        }
+      }
+    }
+
+    // The file now is expected to contain their version of the tasks.
+    match theirs {
+      Some((tasks, fingerprint)) => {
+        let state = FileState {
+          fingerprint: Some(fingerprint),
+          tasks: SerTasks(tasks),
+        };
+        let _prev = files.insert(file, state);
+      },
+      None => {
+        let _prev = files.remove(&file);
+      },
+    }
+  }
+
+  /// Resolve a conflict concerning the task meta data file.
+  ///
+  /// Tasks refer to our tag templates and so none of them can be
+  /// removed. Taking over their version of the meta data hence amounts
+  /// to merging it.
+  fn resolve_meta(
+    &self,
+    tasks_meta: SerTasksMeta,
+    fingerprint: Fingerprint,
+    resolution: Resolution,
+  ) {
+    match resolution {
+      Resolution::Ours => (),
+      Resolution::Theirs | Resolution::Merge => self.templates.merge_serde(tasks_meta.templates),
+    }
+    let () = self.meta_fingerprint.set(Some(fingerprint));
+  }
+
+  /// Merge changes made to the task files in `root_dir` by other
+  /// programs into our tasks.
+  ///
+  /// Tasks added, changed, or removed externally are taken over, while
+  /// local changes that have not yet been saved are retained. The same
+  /// goes for tags in the task meta data. Files that fail to load are
+  /// recorded as problems, replacing the ones found earlier. Returned
+  /// is whether any file had changed.
+  pub async fn reload(&self, root_dir: &Path) -> Result<bool> {
+    let Loaded {
+      state,
+      fingerprints,
+      meta_fingerprint,
+      problems,
+      ..
+    } = load_tasks_from_dir(root_dir).await?;
+
+    // Tasks may refer to tags that were newly created alongside them,
+    // and so the meta data have to be merged first.
+    let meta_changed = match meta_fingerprint {
+      Some(fingerprint) if self.meta_fingerprint.get() != Some(fingerprint) => {
+        let () = self.resolve_meta(state.tasks_meta, fingerprint, Resolution::Merge);
+        true
+      },
+      _ => false,
+    };
+
+    let mut changes = Vec::new();
+    {
+      let files = self.files.borrow();
+      // Files that we know but that no longer exist got removed. Files
+      // that merely failed to load are left alone, though.
+      for file in files.iter().filter_map(|(file, state)| {
+        let removed = state.fingerprint.is_some()
+          && !fingerprints.contains_key(file)
+          && !problems
+            .iter()
+            .any(|problem| problem.path() == root_dir.join(file));
+        removed.then_some(file)
+      }) {
+        let change = Conflict {
+          file: file.clone(),
+          theirs: Theirs::Tasks(None),
+        };
+        let () = changes.push(change);
+      }
+
+      let mut theirs = group_tasks_by_file(&state.tasks);
+      for (file, fingerprint) in fingerprints {
+        if files.get(&file).and_then(|state| state.fingerprint) != Some(fingerprint) {
+          let tasks = theirs.remove(&file).unwrap_or_default().0;
+          let change = Conflict {
+            file,
+            theirs: Theirs::Tasks(Some((tasks, fingerprint))),
+          };
+          let () = changes.push(change);
+        }
+      }
+    }
+
+    let changed = meta_changed || !changes.is_empty();
+    for change in changes {
+      let () = self.resolve(change, Resolution::Merge);
+    }
+    *self.problems.borrow_mut() = problems;
+    Ok(changed)
+  }
+
+  /// Persist the state into the directory referenced by `root_dir_cap`
+  /// and the directories of the collections below it.
+  pub async fn save(&mut self, root_dir_cap: &mut DirCap) -> Result<()> {
+    let mut files = self.files.borrow().clone();
+    let state = self.to_serde();
+    let broken = self.broken_files(root_dir_cap.path());
+    let () = save_tasks_to_dir(
+      root_dir_cap,
+      &mut self.collection_dir_caps,
+      self.trash.as_mut(),
+      &mut self.archive.iter_mut().collect::<Vec<_>>(),
+      &state,
+      &mut files,
+      &broken,
+    )
+    .await?;
+    *self.files.borrow_mut() = files;
+
+    if !broken.contains(Path::new(&TASKS_META_ID.to_string())) {
+      let path = root_dir_cap.path().join(TASKS_META_ID.to_string());
+      let () = self.meta_fingerprint.set(fingerprint_file(&path).await?);
+    }
+    Ok(())
+  }
+
+  /// Record a problem with the file at `path`, unless one is known
+  /// already.
+  fn add_problem(&self, path: PathBuf, error: Error) {
+    let mut problems = self.problems.borrow_mut();
+    if !problems.iter().any(|problem| problem.path == path) {
+      let () = problems.push(Problem { path, error });
+    }
+  }
+
+  /// Retrieve the paths, relative to `root_dir`, of the files that
+  /// failed to load and which we hence must not overwrite or remove.
+  fn broken_files(&self, root_dir: &Path) -> BTreeSet<PathBuf> {
+    self
+      .problems
+      .borrow()
+      .iter()
+      .filter_map(|problem| problem.path.strip_prefix(root_dir).ok())
+      .map(Path::to_path_buf)
+      .collect()
+  }
+
+  /// Set the trash to move the files of removed tasks into when
+  /// saving. Without one, such files are removed for good.
+  pub fn set_trash(&mut self, trash: Store) {
+    self.trash = Some(trash)
+  }
+
+  /// Retrieve the trash, if any.
+  pub fn trash(&self) -> Option<&Store> {
+    self.trash.as_ref()
+  }
+
+  /// Set the archive, holding the files of tasks completed long ago.
+  /// Tasks restored from it get removed from it when saving.
+  pub fn set_archive(&mut self, archive: Store) {
+    self.archive = Some(archive)
+  }
+
+  /// Retrieve the archive, if any.
+  pub fn archive(&self) -> Option<&Store> {
+    self.archive.as_ref()
   }
 
-  /// Persist the state into a file.
-  pub async fn save(&self, root_dir_cap: &mut DirCap) -> Result<()> {
-    save_tasks_to_dir(root_dir_cap, &self.to_serde()).await
+  /// Retrieve the names of the collections other than the default one.
+  pub fn collections(&self) -> impl Iterator<Item = &str> {
+    self.collection_dir_caps.keys().map(String::as_str)
   }
 
   /// Retrieve the `Templates` object associated with this `TaskState`
@@ -381,6 +1598,11 @@ impl TaskState {
   pub fn tasks(&self) -> &Rc<Tasks> {
     &self.tasks
   }
+
+  /// Retrieve the problems encountered while loading task files.
+  pub fn problems(&self) -> Ref<'_, [Problem]> {
+    Ref::map(self.problems.borrow(), Vec::as_slice)
+  }
 }
 
 impl ToSerde for TaskState {
@@ -403,10 +1625,14 @@ pub mod tests {
   use super::*;
 
   use std::env::temp_dir;
+  use std::ops::Deref as _;
+
+  use chrono::Duration;
 
   use tempfile::TempDir;
 
   use tokio::fs::remove_dir_all;
+  use tokio::fs::write;
   use tokio::test;
 
   use crate::ser::backends::Json;
@@ -414,7 +1640,10 @@ pub mod tests {
   use crate::ser::tags::Tag as SerTag;
   use crate::ser::tags::Template as SerTemplate;
   use crate::ser::tags::Templates as SerTemplates;
+  use crate::tasks::Task;
   use crate::test::make_tasks;
+  use crate::test::make_tasks_with_tags;
+  use crate::test::COMPLETE_TAG;
 
 
   /// Create a `TaskState` object.
@@ -431,7 +1660,12 @@ pub mod tests {
   /// from there.
   #[test]
   async fn save_load_tasks() {
-    async fn test(root: &Path, tasks: Vec<SerTask>, templates: Option<SerTemplates>) {
+    async fn test(
+      root: &Path,
+      files: &mut Files,
+      tasks: Vec<SerTask>,
+      templates: Option<SerTemplates>,
+    ) {
       let task_state = SerTaskState {
         tasks_meta: SerTasksMeta {
           templates: templates.unwrap_or_default(),
@@ -440,10 +1674,19 @@ pub mod tests {
       };
       let task_state = TaskState::with_serde(task_state).unwrap().to_serde();
       let mut tasks_root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
-      let () = save_tasks_to_dir(&mut tasks_root_cap, &task_state)
-        .await
-        .unwrap();
-      let mut loaded = load_tasks_from_dir(root).await.unwrap();
+      let mut collection_dir_caps = BTreeMap::new();
+      let () = save_tasks_to_dir(
+        &mut tasks_root_cap,
+        &mut collection_dir_caps,
+        None,
+        &mut [],
+        &task_state,
+        files,
+        &BTreeSet::new(),
+      )
+      .await
+      .unwrap();
+      let mut loaded = load_tasks_from_dir(root).await.unwrap().state;
 
       // The order of tasks is undefined at this point of the loading
       // process. Sort them according to their position as is done
@@ -461,12 +1704,13 @@ pub mod tests {
     // Doing so tests that the task saving logic removes files of tasks
     // that have been deleted.
     let root = TempDir::new().unwrap();
+    let mut files = Files::new();
     let tasks = Vec::new();
     // Check that things work out even when no task is provided.
-    let () = test(root.path(), tasks, None).await;
+    let () = test(root.path(), &mut files, tasks, None).await;
 
     let tasks = make_tasks(3);
-    let () = test(root.path(), tasks, None).await;
+    let () = test(root.path(), &mut files, tasks, None).await;
 
     let id_tag = SerId::try_from(42).unwrap();
     let templates = SerTemplates(vec![SerTemplate {
@@ -475,12 +1719,13 @@ pub mod tests {
     }]);
     // Test with a task with a tag as well.
     let tasks = vec![SerTask::new("a task!").with_tags([SerTag { id: id_tag }])];
-    let () = test(root.path(), tasks, Some(templates)).await;
+    let () = test(root.path(), &mut files, tasks, Some(templates)).await;
 
     let tasks = make_tasks(25);
     // Make sure that directories not yet present are created.
     let () = test(
       &root.path().join("not").join("yet").join("present"),
+      &mut files,
       tasks,
       None,
     )
@@ -503,11 +1748,11 @@ pub mod tests {
       tasks_meta: SerTasksMeta::default(),
       tasks: SerTasks::from(task_vec.clone()),
     };
-    let task_state = TaskState::with_serde(task_state).unwrap();
+    let mut task_state = TaskState::with_serde(task_state).unwrap();
     let mut tasks_root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
     let () = task_state.save(&mut tasks_root_cap).await.unwrap();
 
-    let mut task_state = load_tasks_from_dir(root).await.unwrap();
+    let mut task_state = load_tasks_from_dir(root).await.unwrap().state;
     let () = task_state.tasks.0.sort_by(|first, second| {
       let first = first.position.unwrap_or(f64::MAX);
       let second = second.position.unwrap_or(f64::MAX);
@@ -528,7 +1773,7 @@ pub mod tests {
       tasks_meta: SerTasksMeta::default(),
       tasks: SerTasks::from(tasks),
     };
-    let task_state = TaskState::with_serde(task_state).unwrap();
+    let mut task_state = TaskState::with_serde(task_state).unwrap();
 
     let tasks = {
       let tasks = task_state.tasks();
@@ -551,7 +1796,7 @@ pub mod tests {
 
     let mut tasks_root_cap = DirCap::for_dir(root.to_path_buf()).await.unwrap();
     let () = task_state.save(&mut tasks_root_cap).await.unwrap();
-    let task_state = load_tasks_from_dir(root).await.unwrap();
+    let task_state = load_tasks_from_dir(root).await.unwrap().state;
     let templates = Rc::new(Templates::with_serde(task_state.tasks_meta.templates).unwrap());
     let loaded = Tasks::with_serde(task_state.tasks, templates).unwrap();
     let loaded = loaded.iter(|iter| iter.cloned().collect::<Vec<_>>());
@@ -588,7 +1833,7 @@ pub mod tests {
   #[test]
   async fn save_and_load_task_state() {
     let task_vec = make_tasks(3);
-    let task_state = make_task_state(task_vec.clone());
+    let mut task_state = make_task_state(task_vec.clone());
 
     let tasks_dir = TempDir::new().unwrap();
     let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
@@ -607,7 +1852,7 @@ pub mod tests {
   async fn load_task_state_file_not_found() {
     let tasks_root = {
       let task_vec = make_tasks(1);
-      let task_state = make_task_state(task_vec.clone());
+      let mut task_state = make_task_state(task_vec.clone());
 
       let tasks_dir = TempDir::new().unwrap();
       let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
@@ -701,4 +1946,810 @@ pub mod tests {
       assert!(iter.next().is_none());
     });
   }
+
+  /// Check that tag names set by other clients are mapped onto existing
+  /// templates, with new templates being created as needed.
+  #[test]
+  async fn load_state_with_categories() {
+    let id_work = SerId::try_from(3).unwrap();
+    let id_home = SerId::try_from(4).unwrap();
+    let templates = SerTemplates(vec![
+      SerTemplate {
+        id: id_work,
+        name: "work".to_string(),
+      },
+      SerTemplate {
+        id: id_home,
+        name: "home".to_string(),
+      },
+    ]);
+
+    let mut task1 = SerTask::new("task1");
+    task1.categories = vec!["work".to_string(), "errands".to_string()];
+    // A tag removed by another client is removed from the task as well.
+    let mut task2 = SerTask::new("task2").with_tags([SerTag { id: id_home }]);
+    task2.categories = vec!["work".to_string()];
+    // Without categories, the tags are used as-is.
+    let task3 = SerTask::new("task3").with_tags([SerTag { id: id_home }]);
+
+    let task_state = SerTaskState {
+      tasks_meta: SerTasksMeta { templates },
+      tasks: SerTasks::from(vec![task1, task2, task3]),
+    };
+    let state = TaskState::with_serde(task_state).unwrap().to_serde();
+
+    // Legacy numeric IDs are migrated to name based ones, but retained
+    // for the benefit of devices still using them.
+    let legacy = [id_work, id_home];
+    let id_work = SerId::from_name("work");
+    let id_home = SerId::from_name("home");
+    let id_errands = SerId::from_name("errands");
+    let templates = &state.tasks_meta.templates.0;
+    assert_eq!(templates.len(), 5);
+    assert!(templates[..3].iter().all(|template| !template.id.is_legacy()));
+    let retained = templates[3..]
+      .iter()
+      .map(|template| template.id)
+      .collect::<Vec<_>>();
+    assert_eq!(retained, legacy);
+
+    let tags = state
+      .tasks
+      .0
+      .iter()
+      .map(|task| task.tags.iter().map(|tag| tag.id).collect::<Vec<_>>())
+      .collect::<Vec<_>>();
+    // Tags are ordered by their template's ID.
+    assert_eq!(
+      tags,
+      vec![vec![id_errands, id_work], vec![id_work], vec![id_home]]
+    );
+    assert_eq!(state.tasks.0[2].categories, vec!["home"]);
+  }
+
+  /// Check that we can merge two diverged task meta data files.
+  #[test]
+  async fn merge_diverged_tasks_meta_files() {
+    let template = |name: &str| SerTemplate {
+      id: SerId::from_name(name),
+      name: name.to_string(),
+    };
+    let meta = |templates| SerTasksMeta {
+      templates: SerTemplates(templates),
+    };
+
+    let dir = TempDir::new().unwrap();
+    let path1 = dir.path().join("meta1");
+    let path2 = dir.path().join("meta2");
+    let meta1 = meta(vec![template("work"), template("home")]);
+    let meta2 = meta(vec![template("work"), template("errands")]);
+    let () = write(&path1, iCal::serialize(&meta1).unwrap()).await.unwrap();
+    let () = write(&path2, iCal::serialize(&meta2).unwrap()).await.unwrap();
+
+    let () = merge_tasks_meta_files(&path1, &path2).await.unwrap();
+
+    let expected = meta(vec![template("work"), template("home"), template("errands")]);
+    for path in [&path1, &path2] {
+      let merged = load_state_from_file::<iCal, SerTasksMeta>(path)
+        .await
+        .unwrap()
+        .unwrap();
+      assert_eq!(merged, expected);
+    }
+  }
+
+  /// Check that data of tasks that we do not interpret, e.g., because
+  /// they were written by other clients, survive a load-modify-save
+  /// cycle.
+  #[test]
+  async fn save_preserves_unknown_task_data() {
+    let id = "4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1";
+    let data = format!(
+      "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//other client//EN\r
+BEGIN:VTODO\r
+UID:{id}\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:foreign task\r
+CATEGORIES:work\r
+X-OTHER-CLIENT:value\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+TRIGGER:-PT15M\r
+END:VALARM\r
+END:VTODO\r
+END:VCALENDAR\r
+"
+    );
+
+    let tasks_dir = TempDir::new().unwrap();
+    let () = std::fs::write(tasks_dir.path().join(id), data).unwrap();
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let tasks = task_state.tasks();
+    let task = tasks.iter(|mut iter| iter.next().unwrap().clone());
+    let mut updated = task.deref().clone();
+    let () = updated.set_summary("changed task".to_string());
+    let () = tasks.update(task, updated);
+
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let content = std::fs::read_to_string(tasks_dir.path().join(id)).unwrap();
+    assert!(content.contains("SUMMARY:changed task\r\n"), "{content}");
+    assert!(content.contains("CATEGORIES:work\r\n"), "{content}");
+    assert!(content.contains("X-OTHER-CLIENT:value\r\n"), "{content}");
+    assert!(
+      content.contains("BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\n"),
+      "{content}"
+    );
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+  }
+
+  /// Check that we can work with vdir items with arbitrary file names
+  /// and that we leave alone files that we do not manage.
+  #[test]
+  async fn save_load_foreign_file_names() {
+    let task = |id, summary| {
+      format!(
+        "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//other client//EN\r
+BEGIN:VTODO\r
+UID:{id}\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:{summary}\r
+END:VTODO\r
+END:VCALENDAR\r
+"
+      )
+    };
+    let id1 = "4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1";
+    let id2 = "9f0b2a7c-0f7e-4b55-a1d7-3c1f2b8e6d40";
+    let id3 = "b5e8a0c4-2d7a-4e39-9c6e-5f8a1d2c3b47";
+
+    let tasks_dir = TempDir::new().unwrap();
+    let path = |name: &str| tasks_dir.path().join(name);
+    let () = std::fs::write(path(&format!("{id1}.ics")), task(id1, "task1")).unwrap();
+    let () = std::fs::write(path("some-task.ics"), task(id2, "task2")).unwrap();
+    let () = std::fs::write(path(".hidden"), "not a task").unwrap();
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let tasks = task_state.tasks();
+    let vec = tasks.iter(|iter| iter.cloned().collect::<Vec<_>>());
+    let mut ids = vec
+      .iter()
+      .map(|task| task.id().to_string())
+      .collect::<Vec<_>>();
+    let () = ids.sort();
+    assert_eq!(ids, vec![id1, id2]);
+
+    // A file showing up after loading, e.g., as a result of
+    // synchronization, is not touched.
+    let () = std::fs::write(path("new.ics"), task(id3, "task3")).unwrap();
+
+    let task2 = vec
+      .iter()
+      .find(|task| task.summary() == "task2")
+      .unwrap()
+      .clone();
+    let () = tasks.remove(task2);
+
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let content = std::fs::read_to_string(path(&format!("{id1}.ics"))).unwrap();
+    assert!(content.contains("SUMMARY:task1\r\n"), "{content}");
+    assert!(!path(id1).exists());
+    assert!(!path("some-task.ics").exists());
+    assert!(path("new.ics").exists());
+    assert!(path(".hidden").exists());
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+  }
+
+  /// Make sure that task files that fail to load are reported as
+  /// problems and left alone when saving.
+  #[test]
+  async fn load_save_with_broken_files() {
+    let tasks_dir = TempDir::new().unwrap();
+    let path = |name: &str| tasks_dir.path().join(name);
+    let tasks = make_tasks(2);
+    let mut task_state = make_task_state(tasks.clone());
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let broken = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\n";
+    let () = std::fs::write(path("broken.ics"), broken).unwrap();
+    let () = std::fs::write(path(&tasks[1].id.to_string()), broken).unwrap();
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let summaries = task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>());
+    assert_eq!(summaries, vec!["1"]);
+
+    let mut problems = task_state
+      .problems()
+      .iter()
+      .map(|problem| problem.path().to_path_buf())
+      .collect::<Vec<_>>();
+    let () = problems.sort();
+    let mut expected = vec![path("broken.ics"), path(&tasks[1].id.to_string())];
+    let () = expected.sort();
+    assert_eq!(problems, expected);
+
+    let error = format!("{:#}", task_state.problems()[0].error());
+    assert!(error.starts_with("failed to load state from"), "{error}");
+
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert_eq!(std::fs::read_to_string(path("broken.ics")).unwrap(), broken);
+    assert_eq!(
+      std::fs::read_to_string(path(&tasks[1].id.to_string())).unwrap(),
+      broken
+    );
+  }
+
+  /// Make sure that a task file containing a task already loaded from
+  /// a different file is reported as a problem.
+  #[test]
+  async fn load_save_with_duplicate_task_ids() {
+    let tasks_dir = TempDir::new().unwrap();
+    let path = |name: &str| tasks_dir.path().join(name);
+    let tasks = make_tasks(1);
+    let mut task_state = make_task_state(tasks.clone());
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    let _count = std::fs::copy(path(&tasks[0].id.to_string()), path("copy.ics")).unwrap();
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let summaries = task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>());
+    assert_eq!(summaries, vec!["1"]);
+    assert_eq!(task_state.problems().len(), 1);
+
+    let error = task_state.problems()[0].error().to_string();
+    assert!(error.contains("already stored in a different file"), "{error}");
+
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(path(&tasks[0].id.to_string()).exists());
+    assert!(path("copy.ics").exists());
+  }
+
+  /// Check that a broken task meta data file is reported as a problem,
+  /// but does not prevent loading of tasks.
+  #[test]
+  async fn load_save_with_broken_tasks_meta() {
+    let tasks_dir = TempDir::new().unwrap();
+    let meta = tasks_dir.path().join(TASKS_META_ID.to_string());
+    let (_tags, templates, tasks) = make_tasks_with_tags(2);
+    let task_state = SerTaskState {
+      tasks_meta: SerTasksMeta {
+        templates: SerTemplates(templates),
+      },
+      tasks: SerTasks::from(tasks),
+    };
+    let mut task_state = TaskState::with_serde(task_state).unwrap();
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let broken = "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n";
+    let () = std::fs::write(&meta, broken).unwrap();
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let problems = task_state
+      .problems()
+      .iter()
+      .map(|problem| problem.path().to_path_buf())
+      .collect::<Vec<_>>();
+    assert_eq!(problems, vec![meta.clone()]);
+
+    // Tags are restored by name.
+    let tags = task_state.tasks().iter(|mut iter| {
+      iter
+        .nth(1)
+        .unwrap()
+        .tags(|iter| iter.map(|tag| tag.name().to_string()).collect::<Vec<_>>())
+    });
+    assert_eq!(tags, vec![COMPLETE_TAG]);
+
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&meta).unwrap(), broken);
+  }
+
+  /// Check that tasks are stored in and loaded from the directories of
+  /// the collections they belong to.
+  #[test]
+  async fn save_load_collections() {
+    let tasks_dir = TempDir::new().unwrap();
+    let path = |name: &str| tasks_dir.path().join(name);
+    let mut tasks = make_tasks(3);
+    tasks[1].collection = Some("work".to_string());
+    tasks[2].collection = Some("home".to_string());
+    let id = |idx: usize| tasks[idx].id.to_string();
+
+    let mut task_state = make_task_state(tasks.clone());
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(path(&id(0)).exists());
+    assert!(path(&format!("work/{}", id(1))).exists());
+    assert!(path(&format!("home/{}", id(2))).exists());
+    // Meta data are only stored once, in the tasks root.
+    assert!(!path(&format!("work/{TASKS_META_ID}")).exists());
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    assert_eq!(
+      task_state.collections().collect::<Vec<_>>(),
+      vec!["home", "work"]
+    );
+    let vec = task_state
+      .tasks()
+      .iter(|iter| iter.cloned().collect::<Vec<_>>());
+    let collections = vec
+      .iter()
+      .map(|task| task.collection())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      collections,
+      vec![None, Some("work".to_string()), Some("home".to_string())]
+    );
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+
+    // Moving a task to a different collection moves its file.
+    let mut moved = vec[1].deref().clone();
+    let () = moved.set_collection(Some("home".to_string()));
+    let () = task_state.tasks().update(vec[1].clone(), moved);
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(!path(&format!("work/{}", id(1))).exists());
+    assert!(path(&format!("home/{}", id(1))).exists());
+
+    let task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let collections = task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.collection()).collect::<Vec<_>>());
+    assert_eq!(
+      collections,
+      vec![None, Some("home".to_string()), Some("home".to_string())]
+    );
+  }
+
+  /// Check that the files of removed tasks are moved into the trash,
+  /// from where tasks can be restored.
+  #[test]
+  async fn save_with_trash() {
+    let tasks_dir = TempDir::new().unwrap();
+    let trash_dir = TempDir::new().unwrap();
+    let trash_path = |name: &str| trash_dir.path().join(name);
+    let mut tasks = make_tasks(3);
+    tasks[2].collection = Some("work".to_string());
+    let id = |idx: usize| tasks[idx].id.to_string();
+
+    let mut task_state = make_task_state(tasks.clone());
+    let trash_root_cap = DirCap::for_dir(trash_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.set_trash(Store::new(trash_root_cap));
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let vec = task_state
+      .tasks()
+      .iter(|iter| iter.cloned().collect::<Vec<_>>());
+    let () = task_state.tasks().remove(vec[1].clone());
+    let () = task_state.tasks().remove(vec[2].clone());
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(!tasks_dir.path().join(id(1)).exists());
+    assert!(trash_path(&id(1)).exists());
+    assert!(trash_path(&format!("work/{}", id(2))).exists());
+
+    let trash = task_state.trash().unwrap();
+    let mut trashed = trash.load().await.unwrap();
+    let () = trashed.sort_by_key(|task| task.summary.clone());
+    let summaries = trashed
+      .iter()
+      .map(|task| task.summary.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(summaries, vec!["2", "3"]);
+    assert_eq!(trashed[1].collection.as_deref(), Some("work"));
+
+    // Moving a task to a different collection does not put its old
+    // file into the trash.
+    let mut moved = vec[0].deref().clone();
+    let () = moved.set_collection(Some("work".to_string()));
+    let () = task_state.tasks().update(vec[0].clone(), moved);
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(tasks_dir.path().join(format!("work/{}", id(0))).exists());
+    assert!(!tasks_dir.path().join(id(0)).exists());
+    assert!(!trash_path(&id(0)).exists());
+
+    // Once saved, a restored task is no longer contained in the trash.
+    let _task = task_state.tasks().add_from_serde(trashed.remove(1));
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(tasks_dir.path().join(format!("work/{}", id(2))).exists());
+    assert!(!trash_path(&format!("work/{}", id(2))).exists());
+    assert!(trash_path(&id(1)).exists());
+
+    let mut trash = Store::new(DirCap::for_dir(trash_dir.path().to_path_buf()).await.unwrap());
+    let () = trash.empty().await.unwrap();
+    assert!(trash.load().await.unwrap().is_empty());
+    assert!(!trash_path("work").exists());
+  }
+
+  /// Check that tasks completed long ago get archived when loading and
+  /// that they can be restored from the archive.
+  #[test]
+  async fn load_and_archive_tasks() {
+    let tasks_dir = TempDir::new().unwrap();
+    let archive_dir = TempDir::new().unwrap();
+    let archive_path = |name: &str| archive_dir.path().join(name);
+    let now = Utc::now();
+    let cutoff = now - Duration::days(30);
+    let old = now - Duration::days(31);
+
+    let mut tasks = make_tasks(5);
+    tasks[0].completed = Some(old);
+    tasks[1].completed = Some(old);
+    tasks[1].collection = Some("work".to_string());
+    // Completed long ago, but the parent of a task that stays.
+    tasks[2].completed = Some(old);
+    tasks[3].parent = Some(tasks[2].id);
+    tasks[4].completed = Some(now);
+    let id = |idx: usize| tasks[idx].id.to_string();
+
+    let mut task_state = make_task_state(tasks.clone());
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let mut archive = Store::new(DirCap::for_dir(archive_dir.path().to_path_buf()).await.unwrap());
+    let mut task_state = TaskState::load_and_archive(&mut tasks_root_cap, &mut archive, cutoff)
+      .await
+      .unwrap();
+    let () = task_state.set_archive(archive);
+    let summaries = task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>());
+    assert_eq!(summaries, vec!["3", "4", "5"]);
+    assert!(!tasks_dir.path().join(id(0)).exists());
+    assert!(archive_path(&id(0)).exists());
+    assert!(archive_path(&format!("work/{}", id(1))).exists());
+    assert!(!archive_path(&id(2)).exists());
+    // Archived tasks must not be considered removed.
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+
+    let archive = task_state.archive().unwrap();
+    let mut archived = archive.load().await.unwrap();
+    let () = archived.sort_by_key(|task| task.summary.clone());
+    let summaries = archived
+      .iter()
+      .map(|task| task.summary.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(summaries, vec!["1", "2"]);
+
+    // Once saved, a restored task is no longer contained in the
+    // archive.
+    let _task = task_state.tasks().add_from_serde(archived.remove(0));
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(tasks_dir.path().join(id(0)).exists());
+    assert!(!archive_path(&id(0)).exists());
+  }
+
+  /// Check that we detect task files modified externally and can
+  /// resolve the resulting conflicts in all supported ways.
+  #[test]
+  async fn detect_and_resolve_conflicts() {
+    fn modify<F>(task_state: &TaskState, index: usize, f: F)
+    where
+      F: FnOnce(&mut Task),
+    {
+      let task = task_state
+        .tasks()
+        .iter(|mut iter| iter.nth(index).unwrap().clone());
+      let mut updated = task.deref().clone();
+      let () = f(&mut updated);
+      let () = task_state.tasks().update(task, updated);
+    }
+
+    async fn test(resolution: Resolution) -> (String, Option<String>) {
+      let tasks_dir = TempDir::new().unwrap();
+      let tasks = make_tasks(3);
+      let mut ours = make_task_state(tasks.clone());
+      let mut ours_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+        .await
+        .unwrap();
+      let () = ours.save(&mut ours_cap).await.unwrap();
+      assert!(ours.conflicts(tasks_dir.path()).await.unwrap().is_empty());
+
+      let mut theirs = TaskState::load(tasks_dir.path()).await.unwrap();
+      let () = modify(&theirs, 1, |task| task.set_summary("theirs".to_string()));
+      let mut theirs_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+        .await
+        .unwrap();
+      let () = theirs.save(&mut theirs_cap).await.unwrap();
+
+      let () = modify(&ours, 1, |task| task.set_notes(Some("ours".to_string())));
+      let mut conflicts = ours.conflicts(tasks_dir.path()).await.unwrap();
+      assert_eq!(conflicts.len(), 1);
+      let conflict = conflicts.remove(0);
+      assert_eq!(conflict.file(), OsStr::new(&tasks[1].id.to_string()));
+
+      let () = ours.resolve(conflict, resolution);
+      assert!(ours.conflicts(tasks_dir.path()).await.unwrap().is_empty());
+      let () = ours.save(&mut ours_cap).await.unwrap();
+
+      let loaded = TaskState::load(tasks_dir.path()).await.unwrap();
+      let summaries = loaded
+        .tasks()
+        .iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>());
+      assert_eq!(summaries.len(), 3);
+
+      let task = loaded.tasks().iter(|mut iter| iter.nth(1).unwrap().clone());
+      (task.summary(), task.notes())
+    }
+
+    let ours = (String::from("2"), Some(String::from("ours")));
+    assert_eq!(test(Resolution::Ours).await, ours);
+    let theirs = (String::from("theirs"), None);
+    assert_eq!(test(Resolution::Theirs).await, theirs);
+    let merged = (String::from("theirs"), Some(String::from("ours")));
+    assert_eq!(test(Resolution::Merge).await, merged);
+  }
+
+  /// Check that we detect a task meta data file modified externally
+  /// and can resolve the resulting conflict.
+  #[test]
+  async fn detect_and_resolve_tasks_meta_conflict() {
+    async fn test(resolution: Resolution) -> Vec<String> {
+      let tasks_dir = TempDir::new().unwrap();
+      let (_tags, templates, tasks) = make_tasks_with_tags(2);
+      let task_state = SerTaskState {
+        tasks_meta: SerTasksMeta {
+          templates: SerTemplates(templates),
+        },
+        tasks: SerTasks::from(tasks),
+      };
+      let mut ours = TaskState::with_serde(task_state).unwrap();
+      let mut ours_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+        .await
+        .unwrap();
+      let () = ours.save(&mut ours_cap).await.unwrap();
+
+      // Another device created a tag and the new meta data got synced
+      // in.
+      let mut theirs = TaskState::load(tasks_dir.path()).await.unwrap();
+      let _tag = theirs.templates().instantiate_or_create("theirs");
+      let mut theirs_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+        .await
+        .unwrap();
+      let () = theirs.save(&mut theirs_cap).await.unwrap();
+
+      let _tag = ours.templates().instantiate_or_create("ours");
+      let mut conflicts = ours.conflicts(tasks_dir.path()).await.unwrap();
+      assert_eq!(conflicts.len(), 1);
+      let conflict = conflicts.remove(0);
+      assert_eq!(conflict.file(), OsStr::new(&TASKS_META_ID.to_string()));
+
+      let () = ours.resolve(conflict, resolution);
+      assert!(ours.conflicts(tasks_dir.path()).await.unwrap().is_empty());
+      let () = ours.save(&mut ours_cap).await.unwrap();
+
+      let loaded = TaskState::load(tasks_dir.path()).await.unwrap();
+      let mut names = loaded
+        .templates()
+        .iter()
+        .map(|template| template.name().to_string())
+        .filter(|name| name == "ours" || name == "theirs")
+        .collect::<Vec<_>>();
+      let () = names.sort();
+      names
+    }
+
+    assert_eq!(test(Resolution::Ours).await, vec!["ours"]);
+    assert_eq!(test(Resolution::Theirs).await, vec!["ours", "theirs"]);
+    assert_eq!(test(Resolution::Merge).await, vec!["ours", "theirs"]);
+  }
+
+  /// Check that reloading picks up changes to task files as well as to
+  /// the task meta data file.
+  #[test]
+  async fn reload_tasks_and_tasks_meta() {
+    let tasks_dir = TempDir::new().unwrap();
+    let (_tags, templates, tasks) = make_tasks_with_tags(2);
+    let task_state = SerTaskState {
+      tasks_meta: SerTasksMeta {
+        templates: SerTemplates(templates),
+      },
+      tasks: SerTasks::from(tasks),
+    };
+    let mut ours = TaskState::with_serde(task_state).unwrap();
+    let mut ours_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = ours.save(&mut ours_cap).await.unwrap();
+    assert!(!ours.reload(tasks_dir.path()).await.unwrap());
+
+    let mut theirs = TaskState::load(tasks_dir.path()).await.unwrap();
+    let tag = theirs.templates().instantiate_or_create("theirs");
+    let task = theirs.tasks().iter(|mut iter| iter.next().unwrap().clone());
+    let mut updated = task.deref().clone();
+    let _added = updated.set_tag(tag);
+    let () = theirs.tasks().update(task, updated);
+    let mut theirs_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = theirs.save(&mut theirs_cap).await.unwrap();
+
+    assert!(ours.reload(tasks_dir.path()).await.unwrap());
+    assert!(ours.conflicts(tasks_dir.path()).await.unwrap().is_empty());
+    let task = ours.tasks().iter(|mut iter| iter.next().unwrap().clone());
+    assert!(task.tags(|mut iter| iter.any(|tag| tag.name() == "theirs")));
+  }
+
+  /// Make sure that a known task file that got broken externally is
+  /// recorded as a problem and never overwritten.
+  #[test]
+  async fn reload_save_with_broken_file() {
+    let tasks_dir = TempDir::new().unwrap();
+    let tasks = make_tasks(2);
+    let path = tasks_dir.path().join(tasks[1].id.to_string());
+    let mut task_state = make_task_state(tasks);
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+
+    let broken = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\n";
+    let () = std::fs::write(&path, broken).unwrap();
+    let _changed = task_state.reload(tasks_dir.path()).await.unwrap();
+    let problems = task_state
+      .problems()
+      .iter()
+      .map(|problem| problem.path().to_path_buf())
+      .collect::<Vec<_>>();
+    assert_eq!(problems, vec![path.clone()]);
+
+    // Our version of the tasks is retained, but not saved.
+    let task = task_state
+      .tasks()
+      .iter(|mut iter| iter.nth(1).unwrap().clone());
+    let mut updated = task.deref().clone();
+    let () = updated.set_summary("ours".to_string());
+    let () = task_state.tasks().update(task, updated);
+    assert!(task_state.conflicts(tasks_dir.path()).await.unwrap().is_empty());
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), broken);
+
+    // Once the file is restored, the problem is gone and the file is
+    // treated as usual again.
+    let () = std::fs::write(&path, content).unwrap();
+    assert!(!task_state.reload(tasks_dir.path()).await.unwrap());
+    assert!(task_state.problems().is_empty());
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("SUMMARY:ours\r\n"), "{content}");
+  }
+
+  /// Make sure that checking for conflicts reports task files that
+  /// fail to load as problems instead of failing.
+  #[test]
+  async fn conflicts_with_broken_file() {
+    let tasks_dir = TempDir::new().unwrap();
+    let tasks = make_tasks(2);
+    let path = tasks_dir.path().join(tasks[0].id.to_string());
+    let mut task_state = make_task_state(tasks);
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    let broken = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\n";
+    let () = std::fs::write(&path, broken).unwrap();
+    assert!(task_state.conflicts(tasks_dir.path()).await.unwrap().is_empty());
+    assert_eq!(task_state.problems().len(), 1);
+    assert_eq!(task_state.problems()[0].path(), path);
+
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), broken);
+  }
+
+  /// Check that merging tasks retains additions and removals from both
+  /// sides.
+  #[test]
+  async fn merge_added_and_removed_tasks() {
+    let base = make_tasks(3);
+    let mut ours = base.clone();
+    let mut theirs = base.clone();
+
+    // We modified the second task, they removed it.
+    ours[1].summary = "modified".to_string();
+    let _removed = theirs.remove(1);
+    // They removed the first task, we left it alone.
+    let _removed = theirs.remove(0);
+    // We both added a task.
+    let () = ours.push(SerTask::new("ours"));
+    let () = theirs.push(SerTask::new("theirs"));
+
+    let merged = merge_tasks(&base, &ours, &theirs);
+    let summaries = merged
+      .iter()
+      .map(|task| task.summary.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(summaries, vec!["modified", "3", "ours", "theirs"]);
+  }
+
+  /// Check that we correctly handle files containing multiple tasks
+  /// along with other components.
+  #[test]
+  async fn save_load_multi_task_file() {
+    let data = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//other client//EN\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+END:VTIMEZONE\r
+BEGIN:VTODO\r
+UID:4ec4e2c6-5e74-4ec2-8f8b-a8d6d8c3e2a1\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:task1\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:9f0b2a7c-0f7e-4b55-a1d7-3c1f2b8e6d40\r
+DTSTAMP:20230401T120000Z\r
+SUMMARY:task2\r
+END:VTODO\r
+END:VCALENDAR\r
+";
+    let tasks_dir = TempDir::new().unwrap();
+    let path = tasks_dir.path().join("tasks.ics");
+    let () = std::fs::write(&path, data).unwrap();
+
+    let mut task_state = TaskState::load(tasks_dir.path()).await.unwrap();
+    let tasks = task_state.tasks().clone();
+    let vec = tasks.iter(|iter| iter.cloned().collect::<Vec<_>>());
+    assert_eq!(vec.len(), 2);
+
+    let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
+      .await
+      .unwrap();
+    let task1 = vec
+      .iter()
+      .find(|task| task.summary() == "task1")
+      .unwrap()
+      .clone();
+    let mut updated = task1.deref().clone();
+    let () = updated.set_summary("changed task".to_string());
+    let () = tasks.update(task1, updated);
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    // Both tasks are still stored in the same file.
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("SUMMARY:changed task\r\n"), "{content}");
+    assert!(content.contains("SUMMARY:task2\r\n"), "{content}");
+    assert_eq!(content.matches("BEGIN:VTIMEZONE\r\n").count(), 1, "{content}");
+    assert_eq!(std::fs::read_dir(tasks_dir.path()).unwrap().count(), 2);
+    assert!(!task_state.is_changed(tasks_dir.path()).await);
+
+    let () = vec.into_iter().for_each(|task| tasks.remove(task));
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+    assert!(!path.exists());
+  }
 }
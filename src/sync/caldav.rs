@@ -0,0 +1,345 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! The CalDAV (RFC 4791) protocol operations we rely on.
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use super::http::Client;
+use super::http::Response;
+use super::xml::escape;
+use super::xml::Element;
+
+
+/// The content type of XML request bodies.
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+/// The content type of calendar objects.
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+
+/// Resolve a (potentially relative) `href` against `base`.
+pub fn resolve(base: &str, href: &str) -> String {
+  if href.starts_with("http://") || href.starts_with("https://") {
+    return href.to_string()
+  }
+
+  let authority_end = base
+    .find("://")
+    .and_then(|scheme_end| {
+      let start = scheme_end + 3;
+      base[start..].find('/').map(|end| start + end)
+    })
+    .unwrap_or(base.len());
+
+  if href.starts_with('/') {
+    format!("{}{}", &base[..authority_end], href)
+  } else {
+    let dir_end = base[authority_end..]
+      .rfind('/')
+      .map(|end| authority_end + end + 1)
+      .unwrap_or(base.len());
+    if dir_end == base.len() && !base.ends_with('/') {
+      format!("{base}/{href}")
+    } else {
+      format!("{}{}", &base[..dir_end], href)
+    }
+  }
+}
+
+/// Extract the path component of a URL, which is what servers report
+/// as `href`.
+pub fn path(url: &str) -> &str {
+  match url.find("://") {
+    Some(scheme_end) => {
+      let start = scheme_end + 3;
+      url[start..]
+        .find('/')
+        .map(|end| &url[start + end..])
+        .unwrap_or("/")
+    },
+    None => url,
+  }
+}
+
+/// Check that a response indicates success.
+fn check(response: Response, method: &str, url: &str) -> Result<Response> {
+  if response.is_success() {
+    Ok(response)
+  } else {
+    bail!(
+      "{method} request to {url} failed with status {}",
+      response.status
+    )
+  }
+}
+
+/// Perform a request with an XML body and parse the `multistatus`
+/// response, returning the contained `response` elements.
+async fn multistatus(
+  client: &Client,
+  method: &str,
+  url: &str,
+  depth: &str,
+  body: &str,
+) -> Result<Vec<Element>> {
+  let headers = [("Content-Type", XML_CONTENT_TYPE), ("Depth", depth)];
+  let response = client.request(method, url, &headers, Some(body)).await?;
+  let response = check(response, method, url)?;
+  let root = Element::parse(&response.body)
+    .with_context(|| format!("failed to parse response to {method} request to {url}"))?;
+  if root.name() != "multistatus" {
+    bail!("{method} request to {url} did not return a multistatus response")
+  }
+  Ok(root.into_children("response"))
+}
+
+/// Retrieve the successfully retrieved properties of a `response`
+/// element.
+fn props(response: &Element) -> impl Iterator<Item = &Element> {
+  response
+    .children("propstat")
+    .filter(|propstat| {
+      propstat
+        .child("status")
+        .map(|status| status.text().split(' ').nth(1) == Some("200"))
+        .unwrap_or(true)
+    })
+    .filter_map(|propstat| propstat.child("prop"))
+}
+
+/// Retrieve the property with the given name from a `response` element.
+fn prop<'e>(response: &'e Element, name: &str) -> Option<&'e Element> {
+  props(response).find_map(|prop| prop.child(name))
+}
+
+/// Retrieve the `href` of a `response` element.
+fn href(response: &Element) -> Result<String> {
+  response
+    .child("href")
+    .map(Element::text)
+    .context("multistatus response lacks href")
+}
+
+
+/// Find the calendar collection to synchronize with, starting from
+/// `url`.
+///
+/// `url` may refer to a calendar collection directly, to a principal,
+/// or to the server's root, in which case we discover the calendar
+/// home of the current user as per RFC 6764 and pick the first
+/// calendar supporting tasks (`VTODO` components).
+pub async fn discover(client: &Client, url: &str) -> Result<String> {
+  let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <d:resourcetype/>
+    <d:current-user-principal/>
+    <c:calendar-home-set/>
+  </d:prop>
+</d:propfind>"#;
+
+  let responses = multistatus(client, "PROPFIND", url, "0", body).await?;
+  let response = responses
+    .first()
+    .with_context(|| format!("PROPFIND request to {url} returned no response"))?;
+
+  if prop(response, "resourcetype")
+    .and_then(|types| types.child("calendar"))
+    .is_some()
+  {
+    return Ok(url.to_string())
+  }
+
+  let home = match prop(response, "calendar-home-set").and_then(|set| set.child("href")) {
+    Some(href) => resolve(url, &href.text()),
+    None => {
+      let principal = prop(response, "current-user-principal")
+        .and_then(|principal| principal.child("href"))
+        .with_context(|| format!("failed to find principal for {url}"))?;
+      let principal = resolve(url, &principal.text());
+
+      let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <c:calendar-home-set/>
+  </d:prop>
+</d:propfind>"#;
+      let responses = multistatus(client, "PROPFIND", &principal, "0", body).await?;
+      let href = responses
+        .first()
+        .and_then(|response| prop(response, "calendar-home-set"))
+        .and_then(|set| set.child("href"))
+        .with_context(|| format!("failed to find calendar home set of {principal}"))?;
+      resolve(&principal, &href.text())
+    },
+  };
+
+  let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <d:resourcetype/>
+    <c:supported-calendar-component-set/>
+  </d:prop>
+</d:propfind>"#;
+  let responses = multistatus(client, "PROPFIND", &home, "1", body).await?;
+  for response in responses.iter() {
+    let is_calendar = prop(response, "resourcetype")
+      .and_then(|types| types.child("calendar"))
+      .is_some();
+    // Servers not reporting supported components support all of them.
+    let supports_tasks = prop(response, "supported-calendar-component-set")
+      .map(|set| {
+        let mut comps = set.children("comp").peekable();
+        comps.peek().is_none() || comps.any(|comp| comp.attribute("name") == Some("VTODO"))
+      })
+      .unwrap_or(true);
+
+    if is_calendar && supports_tasks {
+      return Ok(resolve(&home, &href(response)?))
+    }
+  }
+  bail!("failed to find calendar supporting tasks below {home}")
+}
+
+
+/// An item (calendar object resource) stored on the server.
+#[derive(Debug)]
+pub struct Item {
+  /// The item's `href`.
+  pub href: String,
+  /// The item's entity tag.
+  pub etag: String,
+  /// The item's data, if retrieved.
+  pub data: Option<String>,
+}
+
+
+/// Parse the `response` elements of a `REPORT` into `Item` objects.
+fn items(responses: Vec<Element>, with_data: bool) -> Result<Vec<Item>> {
+  responses
+    .iter()
+    // Responses lacking properties are typically "404 Not Found" ones,
+    // for items that got removed in the meantime.
+    .filter(|response| prop(response, "getetag").is_some())
+    .map(|response| {
+      let href = href(response)?;
+      // SANITY: We filtered out responses without entity tag above.
+      let etag = prop(response, "getetag").unwrap().text();
+      let data = if with_data {
+        let data = prop(response, "calendar-data")
+          .map(Element::content)
+          .with_context(|| format!("response for {href} lacks calendar data"))?;
+        Some(data)
+      } else {
+        None
+      };
+
+      Ok(Item { href, etag, data })
+    })
+    .collect()
+}
+
+/// List the tasks contained in the given calendar collection, without
+/// their data.
+pub async fn list(client: &Client, collection: &str) -> Result<Vec<Item>> {
+  let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <d:getetag/>
+  </d:prop>
+  <c:filter>
+    <c:comp-filter name="VCALENDAR">
+      <c:comp-filter name="VTODO"/>
+    </c:comp-filter>
+  </c:filter>
+</c:calendar-query>"#;
+
+  let responses = multistatus(client, "REPORT", collection, "1", body).await?;
+  let collection = path(collection).trim_end_matches('/');
+  let items = items(responses, false)?
+    .into_iter()
+    // Some servers report the collection itself as well.
+    .filter(|item| item.href.trim_end_matches('/') != collection)
+    .collect();
+  Ok(items)
+}
+
+/// Fetch the items with the given `hrefs`, including their data.
+pub async fn fetch(client: &Client, collection: &str, hrefs: &[&str]) -> Result<Vec<Item>> {
+  if hrefs.is_empty() {
+    return Ok(Vec::new())
+  }
+
+  let hrefs = hrefs
+    .iter()
+    .map(|href| format!("  <d:href>{}</d:href>\n", escape(href)))
+    .collect::<String>();
+  let body = format!(
+    r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <d:getetag/>
+    <c:calendar-data/>
+  </d:prop>
+{hrefs}</c:calendar-multiget>"#
+  );
+
+  let responses = multistatus(client, "REPORT", collection, "1", &body).await?;
+  items(responses, true)
+}
+
+/// Store an item at the given `url`.
+///
+/// If `etag` is provided, the item is only replaced if it is still in
+/// the state referenced by it. Otherwise the item must not exist yet.
+/// Returned is the new entity tag of the item, if reported by the
+/// server.
+pub async fn put(
+  client: &Client,
+  url: &str,
+  data: &str,
+  etag: Option<&str>,
+) -> Result<Option<String>> {
+  let condition = match etag {
+    Some(etag) => ("If-Match", etag),
+    None => ("If-None-Match", "*"),
+  };
+  let headers = [("Content-Type", CALENDAR_CONTENT_TYPE), condition];
+  let response = client.request("PUT", url, &headers, Some(data)).await?;
+  let response = check(response, "PUT", url)?;
+  Ok(response.header("ETag").map(str::to_string))
+}
+
+/// Delete the item at the given `url`, provided it is still in the
+/// state referenced by `etag`.
+pub async fn delete(client: &Client, url: &str, etag: &str) -> Result<()> {
+  let headers = [("If-Match", etag)];
+  let response = client.request("DELETE", url, &headers, None).await?;
+  // An item that is gone already is just fine.
+  if response.status != 404 {
+    let _response = check(response, "DELETE", url)?;
+  }
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we resolve `href`s correctly.
+  #[test]
+  fn resolve_hrefs() {
+    let base = "http://localhost:8080/dav/calendars/";
+    assert_eq!(resolve(base, "/a/b.ics"), "http://localhost:8080/a/b.ics");
+    assert_eq!(resolve(base, "b.ics"), "http://localhost:8080/dav/calendars/b.ics");
+    assert_eq!(resolve(base, "https://other/x"), "https://other/x");
+    assert_eq!(resolve("http://localhost", "/a"), "http://localhost/a");
+    assert_eq!(resolve("http://localhost/dav", "a"), "http://localhost/a");
+    assert_eq!(path(base), "/dav/calendars/");
+    assert_eq!(path("http://localhost"), "/");
+  }
+}
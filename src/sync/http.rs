@@ -0,0 +1,230 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A minimal HTTP client.
+//!
+//! Requests are performed by means of the `curl` program, which takes
+//! care of the transport (including TLS) for us. The request, including
+//! any credentials and the body, is passed to it as a configuration
+//! file via standard input, so that nothing sensitive ends up on the
+//! command line.
+//!
+//! `curl` runs on a thread dedicated to blocking operations, so that
+//! requests do not stall the asynchronous runtime they are issued from.
+
+use std::io::Write as _;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+use std::str::from_utf8;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use tokio::task::spawn_blocking;
+
+
+/// The program we use for performing HTTP requests.
+const CURL: &str = "curl";
+/// The maximum time in seconds we allow for establishing a connection.
+const CONNECT_TIMEOUT: u32 = 15;
+/// The maximum time in seconds we allow a request to take as a whole.
+const MAX_TIME: u32 = 120;
+
+
+/// Escape a string for usage as a quoted value in a `curl`
+/// configuration file.
+fn escape(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len() + 2);
+  let () = escaped.push('"');
+  for c in value.chars() {
+    match c {
+      '\\' => escaped.push_str(r"\\"),
+      '"' => escaped.push_str(r#"\""#),
+      '\n' => escaped.push_str(r"\n"),
+      '\r' => escaped.push_str(r"\r"),
+      '\t' => escaped.push_str(r"\t"),
+      c => escaped.push(c),
+    }
+  }
+  let () = escaped.push('"');
+  escaped
+}
+
+/// Run `curl` with the provided configuration.
+fn curl(config: &str) -> Result<Output> {
+  let mut child = Command::new(CURL)
+    .arg("--config")
+    .arg("-")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .with_context(|| format!("failed to run `{CURL}`"))?;
+
+  // SANITY: We requested standard input to be piped.
+  let mut stdin = child.stdin.take().unwrap();
+  let () = stdin
+    .write_all(config.as_bytes())
+    .with_context(|| format!("failed to provide request to `{CURL}`"))?;
+  let () = drop(stdin);
+
+  child
+    .wait_with_output()
+    .with_context(|| format!("failed to wait for `{CURL}`"))
+}
+
+
+/// An HTTP response.
+#[derive(Debug)]
+pub struct Response {
+  /// The status code.
+  pub status: u16,
+  /// The response headers, in the order received.
+  pub headers: Vec<(String, String)>,
+  /// The response body.
+  pub body: String,
+}
+
+impl Response {
+  /// Parse a response as printed by `curl --include`.
+  fn parse(output: &[u8]) -> Result<Self> {
+    let mut rest = output;
+    loop {
+      let end = rest
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .context("HTTP response lacks end of header")?;
+      let head = from_utf8(&rest[..end]).context("HTTP response header is not valid UTF-8")?;
+      rest = &rest[end + 4..];
+
+      let mut lines = head.split("\r\n");
+      // SANITY: `split` always yields at least one item.
+      let status_line = lines.next().unwrap();
+      let status = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .with_context(|| format!("encountered invalid HTTP status line: {status_line}"))?;
+
+      // Informational responses are followed by the actual one.
+      if (100..200).contains(&status) {
+        continue
+      }
+
+      let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+      let body = String::from_utf8_lossy(rest).into_owned();
+
+      let slf = Self {
+        status,
+        headers,
+        body,
+      };
+      break Ok(slf)
+    }
+  }
+
+  /// Retrieve the value of the header with the given name.
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(header, _)| header.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Check whether the status code indicates success.
+  pub fn is_success(&self) -> bool {
+    (200..300).contains(&self.status)
+  }
+}
+
+
+/// An HTTP client.
+#[derive(Debug, Default)]
+pub struct Client {
+  /// The user name and password to authenticate with, if any.
+  credentials: Option<(String, String)>,
+}
+
+impl Client {
+  /// Create a new client authenticating with the given credentials.
+  pub fn new(credentials: Option<(String, String)>) -> Self {
+    Self { credentials }
+  }
+
+  /// Perform a request.
+  pub async fn request(
+    &self,
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+  ) -> Result<Response> {
+    let mut config = String::new();
+    let () = config.push_str("silent\nshow-error\ninclude\ngloboff\n");
+    let () = config.push_str(&format!("connect-timeout = {CONNECT_TIMEOUT}\n"));
+    let () = config.push_str(&format!("max-time = {MAX_TIME}\n"));
+    let () = config.push_str(&format!("url = {}\n", escape(url)));
+    let () = config.push_str(&format!("request = {}\n", escape(method)));
+    // We do not want to deal with "100 Continue" responses.
+    let () = config.push_str("header = \"Expect:\"\n");
+    for (name, value) in headers {
+      let () = config.push_str(&format!("header = {}\n", escape(&format!("{name}: {value}"))));
+    }
+    if let Some((user, password)) = &self.credentials {
+      let () = config.push_str(&format!("user = {}\n", escape(&format!("{user}:{password}"))));
+    }
+    if let Some(body) = body {
+      let () = config.push_str(&format!("data-raw = {}\n", escape(body)));
+    }
+
+    let output = spawn_blocking(move || curl(&config))
+      .await
+      .with_context(|| format!("failed to run `{CURL}`"))??;
+    if !output.status.success() {
+      let error = String::from_utf8_lossy(&output.stderr);
+      bail!("{method} request to {url} failed: {}", error.trim())
+    }
+
+    Response::parse(&output.stdout)
+      .with_context(|| format!("failed to parse response to {method} request to {url}"))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we escape values for `curl` configuration files
+  /// correctly.
+  #[test]
+  fn escape_config_value() {
+    assert_eq!(escape("abc"), r#""abc""#);
+    assert_eq!(escape("a\"b\\c\r\n"), r#""a\"b\\c\r\n""#);
+  }
+
+  /// Make sure that we can parse an HTTP response, skipping any
+  /// informational ones.
+  #[test]
+  fn parse_response() {
+    let output = b"HTTP/1.1 100 Continue\r\n\r\n\
+      HTTP/1.1 201 Created\r\nETag: \"42\"\r\nContent-Length: 4\r\n\r\nbody";
+    let response = Response::parse(output).unwrap();
+    assert_eq!(response.status, 201);
+    assert!(response.is_success());
+    assert_eq!(response.header("etag"), Some("\"42\""));
+    assert_eq!(response.body, "body");
+
+    let output = b"HTTP/2 207\r\n\r\n";
+    let response = Response::parse(output).unwrap();
+    assert_eq!(response.status, 207);
+    assert_eq!(response.body, "");
+  }
+}
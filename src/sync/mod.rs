@@ -0,0 +1,1065 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Synchronization of task files with a CalDAV server.
+//!
+//! Synchronization works on the files in the tasks directory, i.e., on
+//! saved state. For each file we remember the `href` and entity tag of
+//! the corresponding item on the server as well as a hash of the file's
+//! contents as of the last synchronization. Based on this status, we
+//! can tell which side changed an item and propagate the change to the
+//! other one. Should both sides have changed the same item, the two
+//! versions are merged field by field, relative to the file's contents
+//! as of the last synchronization. Without such a common base, as is
+//! the case when synchronizing for the first time, differing versions
+//! are reported as conflicting and left alone.
+//!
+//! The tasks root directory and each collection below it correspond to
+//! a calendar of their own, with a status file in each directory.
+//!
+//! Files moved into the archive are gone from the tasks directory, but
+//! the user did not delete them. We leave the corresponding items on
+//! the server alone and keep their status, so that restoring a file
+//! from the archive makes synchronization pick up where it left off.
+
+mod caldav;
+mod http;
+mod xml;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io::ErrorKind;
+use std::ops::AddAssign;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use tokio::fs::read;
+use tokio::fs::read_dir;
+use tokio::fs::remove_file;
+use tokio::fs::try_exists;
+use tokio::task::spawn_blocking;
+
+use crate::cap::DirCap;
+use crate::ser::backends::iCal;
+use crate::ser::backends::Backend;
+use crate::ser::backends::Json;
+use crate::ser::sync::SyncConfig;
+use crate::ser::sync::SyncItem;
+use crate::ser::sync::SyncStatus;
+use crate::ser::tasks::Tasks as SerTasks;
+use crate::state::load_state_from_file;
+use crate::state::merge_tasks;
+use crate::state::save_state_to_file;
+use crate::state::TASKS_META_ID;
+
+use self::caldav::Item;
+use self::http::Client;
+
+
+/// The name of the file storing the synchronization status. Being a
+/// hidden file, it is not considered a task file.
+const STATUS_FILE: &str = ".sync-status.json";
+
+
+/// Calculate a hash of the provided data.
+///
+/// The hash is persisted and so we use FNV-1a, which, as opposed to the
+/// standard library's hasher, is guaranteed to be stable.
+fn hash(data: &str) -> u64 {
+  data.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+  })
+}
+
+/// Percent-encode a file name for usage in an `href`.
+fn encode(name: &str) -> String {
+  name
+    .bytes()
+    .map(|byte| match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        char::from(byte).to_string()
+      },
+      _ => format!("%{byte:02X}"),
+    })
+    .collect()
+}
+
+/// Decode a percent-encoded string.
+fn decode(string: &str) -> String {
+  let bytes = string.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut idx = 0;
+  while idx < bytes.len() {
+    let byte = bytes
+      .get(idx + 1..idx + 3)
+      .filter(|_| bytes[idx] == b'%')
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match byte {
+      Some(byte) => {
+        let () = decoded.push(byte);
+        idx += 3;
+      },
+      None => {
+        let () = decoded.push(bytes[idx]);
+        idx += 1;
+      },
+    }
+  }
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Decode the tasks contained in some iCalendar data.
+fn decode_tasks(data: &str) -> Result<SerTasks> {
+  <iCal as Backend<SerTasks>>::deserialize(data.as_bytes()).context("failed to decode task data")
+}
+
+/// Retrieve the IDs of the tasks contained in some iCalendar data.
+fn task_ids(data: &str) -> Result<Vec<String>> {
+  let tasks = decode_tasks(data)?;
+  let ids = tasks.0.iter().map(|task| task.id.to_string()).collect();
+  Ok(ids)
+}
+
+/// Merge the diverged local and remote versions of a task file, given
+/// their common `base`.
+fn merge(base: &str, ours: &str, theirs: &str) -> Result<String> {
+  let base = decode_tasks(base)?;
+  let ours = decode_tasks(ours)?;
+  let theirs = decode_tasks(theirs)?;
+  let merged = SerTasks(merge_tasks(&base.0, &ours.0, &theirs.0));
+  let data = <iCal as Backend<SerTasks>>::serialize(&merged).context("failed to encode tasks")?;
+  String::from_utf8(data).context("encoded tasks are not valid UTF-8")
+}
+
+/// Check whether the task file with the given name got archived.
+async fn is_archived(archive: Option<&Path>, file: &str) -> bool {
+  match archive {
+    Some(archive) => try_exists(archive.join(file)).await.unwrap_or(false),
+    None => false,
+  }
+}
+
+/// Retrieve the credentials to use as per the configuration.
+async fn credentials(config: &SyncConfig) -> Result<Option<(String, String)>> {
+  let user = if let Some(user) = &config.username {
+    user.clone()
+  } else {
+    return Ok(None)
+  };
+
+  let password = if let Some(command) = config.password_command.clone() {
+    let output = spawn_blocking({
+      let command = command.clone();
+      move || Command::new("sh").arg("-c").arg(command).output()
+    })
+    .await
+    .context("failed to run password command")?
+    .with_context(|| format!("failed to run password command `{command}`"))?;
+    if !output.status.success() {
+      bail!("password command `{command}` exited with {}", output.status)
+    }
+    let password = String::from_utf8(output.stdout)
+      .with_context(|| format!("password command `{command}` printed invalid UTF-8"))?;
+    password.lines().next().unwrap_or_default().to_string()
+  } else {
+    String::new()
+  };
+  Ok(Some((user, password)))
+}
+
+/// Read all task files from the given directory.
+async fn read_task_files(dir: &DirCap) -> Result<BTreeMap<String, String>> {
+  let path = dir.path();
+  let mut entries = read_dir(path)
+    .await
+    .with_context(|| format!("failed to read directory {}", path.display()))?;
+  let meta = TASKS_META_ID.to_string();
+  let mut files = BTreeMap::new();
+
+  while let Some(entry) = entries
+    .next_entry()
+    .await
+    .context("failed to iterate directory contents")?
+  {
+    let name = entry.file_name();
+    if name.as_bytes().starts_with(b".") || name == OsStr::new(&meta) {
+      continue
+    }
+
+    let file_type = entry
+      .file_type()
+      .await
+      .with_context(|| format!("failed to inquire file type of {}", entry.path().display()))?;
+    if file_type.is_dir() {
+      continue
+    }
+
+    let name = name
+      .into_string()
+      .map_err(|name| anyhow::anyhow!("encountered non-UTF-8 file name {name:?}"))?;
+    let data = match read(entry.path()).await {
+      Ok(data) => data,
+      // The file vanished in the meantime.
+      Err(err) if err.kind() == ErrorKind::NotFound => continue,
+      Err(err) => {
+        return Err(err).with_context(|| format!("failed to read {}", entry.path().display()))
+      },
+    };
+    let data = String::from_utf8(data)
+      .with_context(|| format!("{} does not contain valid UTF-8", entry.path().display()))?;
+    let _prev = files.insert(name, data);
+  }
+  Ok(files)
+}
+
+/// Retrieve the names of the collections below the tasks root
+/// directory `root`, i.e., of its sub-directories.
+pub async fn read_collections(root: &Path) -> Result<Vec<String>> {
+  let mut entries = read_dir(root)
+    .await
+    .with_context(|| format!("failed to read directory {}", root.display()))?;
+  let mut collections = Vec::new();
+
+  while let Some(entry) = entries
+    .next_entry()
+    .await
+    .context("failed to iterate directory contents")?
+  {
+    let name = entry.file_name();
+    if name.as_bytes().starts_with(b".") {
+      continue
+    }
+
+    let file_type = entry
+      .file_type()
+      .await
+      .with_context(|| format!("failed to inquire file type of {}", entry.path().display()))?;
+    if file_type.is_dir() {
+      let name = name
+        .into_string()
+        .map_err(|name| anyhow::anyhow!("encountered non-UTF-8 collection name {name:?}"))?;
+      let () = collections.push(name);
+    }
+  }
+  Ok(collections)
+}
+
+
+/// A summary of the changes made during a synchronization.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+  /// The number of items downloaded from the server.
+  pub downloaded: usize,
+  /// The number of task files uploaded to the server.
+  pub uploaded: usize,
+  /// The number of task files removed locally.
+  pub removed_local: usize,
+  /// The number of items removed from the server.
+  pub removed_remote: usize,
+  /// The number of task files changed on both sides that got merged.
+  pub merged: usize,
+  /// The number of task files that differ from the corresponding item
+  /// on the server, but could not be merged with it.
+  pub conflicting: usize,
+  /// The names of the collections not synchronized, because no
+  /// calendar is configured for them.
+  pub skipped: Vec<String>,
+}
+
+impl AddAssign for Summary {
+  fn add_assign(&mut self, other: Self) {
+    self.downloaded += other.downloaded;
+    self.uploaded += other.uploaded;
+    self.removed_local += other.removed_local;
+    self.removed_remote += other.removed_remote;
+    self.merged += other.merged;
+    self.conflicting += other.conflicting;
+    let () = self.skipped.extend(other.skipped);
+  }
+}
+
+impl Display for Summary {
+  fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+    write!(
+      f,
+      "{} downloaded, {} uploaded, {} removed locally, {} removed remotely, {} merged",
+      self.downloaded, self.uploaded, self.removed_local, self.removed_remote, self.merged
+    )?;
+    if self.conflicting > 0 {
+      write!(f, ", {} conflicting", self.conflicting)?;
+    }
+    if !self.skipped.is_empty() {
+      write!(
+        f,
+        "; skipped collections without calendar: {}",
+        self.skipped.join(", ")
+      )?;
+    }
+    Ok(())
+  }
+}
+
+
+/// Synchronize the task files in the directory referenced by `dir_cap`
+/// with the calendar found at `url`.
+///
+/// `archive` is the directory task files get archived into, if any.
+async fn sync_dir(
+  client: &Client,
+  url: &str,
+  dir_cap: &mut DirCap,
+  archive: Option<&Path>,
+) -> Result<Summary> {
+  let mut collection = caldav::discover(client, url).await?;
+  if !collection.ends_with('/') {
+    let () = collection.push('/');
+  }
+
+  let status_path = dir_cap.path().join(STATUS_FILE);
+  let status = load_state_from_file::<Json, SyncStatus>(&status_path)
+    .await
+    .with_context(|| {
+      format!(
+        "failed to load synchronization status from {}",
+        status_path.display()
+      )
+    })?
+    .filter(|status| status.collection == collection)
+    .unwrap_or_default();
+
+  let mut local = read_task_files(dir_cap).await?;
+  let mut remote = caldav::list(client, &collection).await?
+    .into_iter()
+    .map(|item| (item.href, item.etag))
+    .collect::<BTreeMap<_, _>>();
+
+  let mut items = BTreeMap::new();
+  // Downloads, as (file, href) tuples. New items do not have a file yet.
+  let mut downloads = Vec::<(Option<String>, String)>::new();
+  // Uploads, as (file, href, etag) tuples. New items have no entity tag.
+  let mut uploads = Vec::<(String, String, Option<String>)>::new();
+  // Items changed on both sides, as (file, href, base, data) tuples.
+  // Items lacking a common base cannot be merged.
+  let mut merges = Vec::<(String, String, Option<String>, String)>::new();
+  let mut remove_local = Vec::new();
+  let mut remove_remote = Vec::new();
+
+  for (file, item) in status.items {
+    let data = local.remove(&file);
+    let etag = remote.remove(&item.href);
+    let SyncItem { href, .. } = &item;
+
+    match (data, etag) {
+      (Some(data), Some(etag)) => {
+        let local_changed = hash(&data) != item.hash;
+        let remote_changed = etag != item.etag;
+        match (local_changed, remote_changed) {
+          (false, false) => {
+            let _prev = items.insert(file, item);
+          },
+          (false, true) => downloads.push((Some(file), href.clone())),
+          (true, false) => uploads.push((file, href.clone(), Some(etag))),
+          (true, true) => {
+            let base = (!item.data.is_empty()).then(|| item.data.clone());
+            let () = merges.push((file, href.clone(), base, data));
+          },
+        }
+      },
+      (Some(data), None) => {
+        if hash(&data) != item.hash {
+          // Changed locally but removed remotely: bring it back.
+          let () = uploads.push((file, href.clone(), None));
+        } else {
+          let () = remove_local.push(file);
+        }
+      },
+      (None, Some(_)) if is_archived(archive, &file).await => {
+        let _prev = items.insert(file, item);
+      },
+      (None, Some(etag)) => {
+        if etag != item.etag {
+          // Changed remotely but removed locally: bring it back.
+          let () = downloads.push((Some(file), href.clone()));
+        } else {
+          let () = remove_remote.push((href.clone(), etag));
+        }
+      },
+      (None, None) => (),
+    }
+  }
+
+  let mut hrefs = downloads
+    .iter()
+    .map(|(_, href)| href.as_str())
+    .collect::<Vec<_>>();
+  let () = hrefs.extend(merges.iter().map(|(_, href, ..)| href.as_str()));
+  let () = hrefs.extend(remote.keys().map(String::as_str));
+  let fetched = caldav::fetch(client, &collection, &hrefs).await?
+    .into_iter()
+    .map(|item| (item.href.clone(), item))
+    .collect::<HashMap<_, _>>();
+
+  // Items neither of the sides knows about may still correspond to each
+  // other, e.g., when synchronizing for the first time. We match them
+  // up by the IDs of the tasks they contain.
+  let mut unmatched = local
+    .iter()
+    .filter_map(|(file, data)| task_ids(data).ok().map(|ids| (ids, file.clone())))
+    .collect::<HashMap<_, _>>();
+  for href in remote.into_keys() {
+    let item = fetched
+      .get(&href)
+      .with_context(|| format!("server did not provide data for {href}"))?;
+    // SANITY: `fetch` always retrieves data.
+    let ids = task_ids(item.data.as_deref().unwrap())
+      .with_context(|| format!("received invalid task data for {href}"))?;
+
+    match unmatched.remove(&ids) {
+      Some(file) => {
+        // SANITY: We got the file name from the map.
+        let data = local.remove(&file).unwrap();
+        // SANITY: `fetch` always retrieves data.
+        let remote_data = item.data.as_deref().unwrap();
+        let is_same = data.trim() == remote_data.trim()
+          || matches!(
+            (decode_tasks(&data), decode_tasks(remote_data)),
+            (Ok(ours), Ok(theirs)) if ours == theirs
+          );
+        if is_same {
+          let item = SyncItem {
+            href,
+            etag: item.etag.clone(),
+            hash: hash(&data),
+            data,
+          };
+          let _prev = items.insert(file, item);
+        } else {
+          let () = merges.push((file, href, None, data));
+        }
+      },
+      None => downloads.push((None, href)),
+    }
+  }
+
+  for file in local.into_keys() {
+    let name = if file.ends_with(".ics") {
+      encode(&file)
+    } else {
+      format!("{}.ics", encode(&file))
+    };
+    let href = caldav::path(&caldav::resolve(&collection, &name)).to_string();
+    let () = uploads.push((file, href, None));
+  }
+
+  let mut summary = Summary::default();
+  let write_guard = dir_cap.write().await?;
+
+  for (file, href) in downloads {
+    let Item { etag, data, .. } = fetched
+      .get(&href)
+      .with_context(|| format!("server did not provide data for {href}"))?;
+    // SANITY: `fetch` always retrieves data.
+    let data = data.as_deref().unwrap();
+    let ids = task_ids(data).with_context(|| format!("received invalid task data for {href}"))?;
+
+    let file = match file {
+      Some(file) => file,
+      // Files containing a single task are named after it, which is
+      // what we do for tasks created locally as well.
+      None => match ids.as_slice() {
+        [id] => id.clone(),
+        _ => decode(href.trim_end_matches('/').rsplit('/').next().unwrap_or_default()),
+      },
+    };
+    if file.is_empty() || file.starts_with('.') || file.contains('/') {
+      bail!("unable to derive file name for {href}")
+    }
+
+    let () = write_guard
+      .file_cap(OsStr::new(&file))
+      .write(data.as_bytes())
+      .await?;
+    let item = SyncItem {
+      href,
+      etag: etag.clone(),
+      hash: hash(data),
+      data: data.to_string(),
+    };
+    let _prev = items.insert(file, item);
+    summary.downloaded += 1;
+  }
+
+  let mut missing_etags = false;
+  for (file, href, base, data) in merges {
+    let Item { etag, data: theirs, .. } = fetched
+      .get(&href)
+      .with_context(|| format!("server did not provide data for {href}"))?;
+    // SANITY: `fetch` always retrieves data.
+    let theirs = theirs.as_deref().unwrap();
+    let base = if let Some(base) = base {
+      base
+    } else {
+      // We keep neither version. The item is reported again on the
+      // next synchronization, until the user resolved the conflict.
+      summary.conflicting += 1;
+      continue
+    };
+
+    let merged = merge(&base, &data, theirs)
+      .with_context(|| format!("failed to merge {file} with {href}"))?;
+    let () = write_guard
+      .file_cap(OsStr::new(&file))
+      .write(merged.as_bytes())
+      .await?;
+
+    let url = caldav::resolve(&collection, &href);
+    let etag = caldav::put(client, &url, &merged, Some(etag)).await?;
+    missing_etags |= etag.is_none();
+
+    let item = SyncItem {
+      href,
+      etag: etag.unwrap_or_default(),
+      hash: hash(&merged),
+      data: merged,
+    };
+    let _prev = items.insert(file, item);
+    summary.merged += 1;
+  }
+
+  for (file, href, etag) in uploads {
+    let path = write_guard.path().join(&file);
+    let data = String::from_utf8(
+      read(&path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))?,
+    )
+    .with_context(|| format!("{} does not contain valid UTF-8", path.display()))?;
+    let url = caldav::resolve(&collection, &href);
+    let etag = caldav::put(client, &url, &data, etag.as_deref()).await?;
+    missing_etags |= etag.is_none();
+
+    let item = SyncItem {
+      href,
+      etag: etag.unwrap_or_default(),
+      hash: hash(&data),
+      data,
+    };
+    let _prev = items.insert(file, item);
+    summary.uploaded += 1;
+  }
+
+  for file in remove_local {
+    let path = write_guard.path().join(&file);
+    match remove_file(&path).await {
+      Ok(()) => (),
+      Err(err) if err.kind() == ErrorKind::NotFound => (),
+      Err(err) => {
+        return Err(err).with_context(|| format!("failed to remove {}", path.display()))
+      },
+    }
+    summary.removed_local += 1;
+  }
+
+  for (href, etag) in remove_remote {
+    let url = caldav::resolve(&collection, &href);
+    let () = caldav::delete(client, &url, &etag).await?;
+    summary.removed_remote += 1;
+  }
+
+  // Not all servers report the entity tag of an item stored. Retrieve
+  // them separately in that case.
+  if missing_etags {
+    let etags = caldav::list(client, &collection).await?
+      .into_iter()
+      .map(|item| (item.href, item.etag))
+      .collect::<HashMap<_, _>>();
+    let () = items
+      .values_mut()
+      .filter(|item| item.etag.is_empty())
+      .for_each(|item| item.etag = etags.get(&item.href).cloned().unwrap_or_default());
+  }
+
+  let status = SyncStatus { collection, items };
+  let mut file_cap = write_guard.file_cap(OsStr::new(STATUS_FILE));
+  let () = save_state_to_file::<Json, _>(&mut file_cap, &status)
+    .await
+    .context("failed to save synchronization status")?;
+  Ok(summary)
+}
+
+/// Synchronize the task files in the tasks root directory referenced
+/// by `dir_cap` with the server described by `config`.
+///
+/// Each of the `collections` below the tasks root directory is
+/// synchronized with the calendar configured for it, keeping its own
+/// synchronization status. Collections without a calendar are skipped
+/// and reported as such.
+///
+/// `archive` is the directory task files get archived into, if any.
+pub async fn sync(
+  config: &SyncConfig,
+  dir_cap: &mut DirCap,
+  collections: &[String],
+  archive: Option<&Path>,
+) -> Result<Summary> {
+  let client = Client::new(credentials(config).await?);
+  let mut summary = sync_dir(&client, &config.url, dir_cap, archive).await?;
+
+  for collection in collections {
+    let url = if let Some(url) = config.collections.get(collection) {
+      url
+    } else {
+      let () = summary.skipped.push(collection.clone());
+      continue
+    };
+
+    let mut collection_dir_cap = DirCap::for_dir(dir_cap.path().join(collection)).await?;
+    let archive = archive.map(|archive| archive.join(collection));
+    summary += sync_dir(&client, url, &mut collection_dir_cap, archive.as_deref())
+      .await
+      .with_context(|| format!("failed to synchronize collection {collection}"))?;
+  }
+  Ok(summary)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::BTreeMap;
+  use std::fs;
+  use std::io::BufRead as _;
+  use std::io::BufReader;
+  use std::io::Read as _;
+  use std::io::Write as _;
+  use std::net::TcpListener;
+  use std::net::TcpStream;
+  use std::sync::Arc;
+  use std::sync::Mutex;
+  use std::thread;
+
+  use tempfile::TempDir;
+
+  use tokio::test;
+
+  use crate::ser::tasks::Task as SerTask;
+
+  use super::xml::escape;
+
+
+  /// The items stored by a `Server`, keyed by `href`, as (entity tag,
+  /// data) tuples.
+  type Items = Arc<Mutex<BTreeMap<String, (String, String)>>>;
+
+
+  /// A stand-in for a CalDAV server, supporting just enough of the
+  /// protocol for our synchronization logic.
+  struct Server {
+    /// The URL of the server.
+    url: String,
+    /// The items stored on the server.
+    items: Items,
+  }
+
+  impl Server {
+    /// The path of the calendar collection containing tasks.
+    const COLLECTION: &'static str = "/calendars/tasks/";
+
+    fn new() -> Self {
+      let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+      let url = format!("http://{}/", listener.local_addr().unwrap());
+      let items = Items::default();
+      let items_ = items.clone();
+
+      let _handle = thread::spawn(move || {
+        let mut etag = 0;
+        for stream in listener.incoming() {
+          let () = Self::handle(stream.unwrap(), &items_, &mut etag);
+        }
+      });
+      Self { url, items }
+    }
+
+    /// Store an item directly, bypassing any protocol.
+    fn insert(&self, name: &str, data: &str, etag: &str) {
+      let href = format!("{}{name}", Self::COLLECTION);
+      let _prev = self
+        .items
+        .lock()
+        .unwrap()
+        .insert(href, (etag.to_string(), data.to_string()));
+    }
+
+    /// Retrieve the data of all items, keyed by `href`.
+    fn items(&self) -> BTreeMap<String, String> {
+      self
+        .items
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(href, (_, data))| (href.clone(), data.clone()))
+        .collect()
+    }
+
+    fn handle(stream: TcpStream, items: &Items, etag: &mut usize) {
+      let mut reader = BufReader::new(stream);
+      let mut line = String::new();
+      let _count = reader.read_line(&mut line).unwrap();
+      let mut parts = line.split(' ');
+      let method = parts.next().unwrap().to_string();
+      let path = parts.next().unwrap().to_string();
+
+      let mut headers = HashMap::new();
+      loop {
+        let mut line = String::new();
+        let _count = reader.read_line(&mut line).unwrap();
+        match line.trim_end().split_once(':') {
+          Some((name, value)) => {
+            let _prev = headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+          },
+          None => break,
+        }
+      }
+      let length = headers
+        .get("content-length")
+        .map(|length| length.parse::<usize>().unwrap())
+        .unwrap_or(0);
+      let mut body = vec![0; length];
+      let () = reader.read_exact(&mut body).unwrap();
+      let body = String::from_utf8(body).unwrap();
+
+      let response = |href: &str, props: &str| {
+        format!(
+          "<d:response><d:href>{href}</d:href><d:propstat><d:prop>{props}</d:prop>\
+           <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+        )
+      };
+      let mut items = items.lock().unwrap();
+      let current = items.get(&path).map(|(etag, _)| etag.clone());
+      let precondition = match (headers.get("if-match"), headers.get("if-none-match")) {
+        (Some(etag), _) => current.as_ref() == Some(etag),
+        (None, Some(_)) => current.is_none(),
+        (None, None) => true,
+      };
+
+      let (status, extra, body) = match (method.as_str(), path.as_str()) {
+        ("PROPFIND", "/") => (
+          207,
+          String::new(),
+          response(
+            "/",
+            "<d:current-user-principal><d:href>/principal/</d:href></d:current-user-principal>",
+          ),
+        ),
+        ("PROPFIND", "/principal/") => (
+          207,
+          String::new(),
+          response(
+            "/principal/",
+            "<c:calendar-home-set><d:href>/calendars/</d:href></c:calendar-home-set>",
+          ),
+        ),
+        ("PROPFIND", "/calendars/") => {
+          let body = [
+            response("/calendars/", "<d:resourcetype><d:collection/></d:resourcetype>"),
+            response(
+              "/calendars/events/",
+              "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+               <c:supported-calendar-component-set><c:comp name=\"VEVENT\"/>\
+               </c:supported-calendar-component-set>",
+            ),
+            response(
+              Self::COLLECTION,
+              "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+               <c:supported-calendar-component-set><c:comp name=\"VEVENT\"/>\
+               <c:comp name=\"VTODO\"/></c:supported-calendar-component-set>",
+            ),
+          ]
+          .concat();
+          (207, String::new(), body)
+        },
+        // Any other path below the calendar home is a calendar of its
+        // own, as may be configured for a collection.
+        ("PROPFIND", calendar) if calendar.starts_with("/calendars/") => (
+          207,
+          String::new(),
+          response(
+            calendar,
+            "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>",
+          ),
+        ),
+        ("REPORT", calendar) if calendar.starts_with("/calendars/") => {
+          let multiget = body.contains("calendar-multiget");
+          let body = items
+            .iter()
+            .filter(|(href, _)| href.starts_with(calendar))
+            .filter(|(href, _)| !multiget || body.contains(&format!("<d:href>{href}</d:href>")))
+            .map(|(href, (etag, data))| {
+              let mut props = format!("<d:getetag>{}</d:getetag>", escape(etag));
+              if multiget {
+                props += &format!("<c:calendar-data>{}</c:calendar-data>", escape(data));
+              }
+              response(href, &props)
+            })
+            .collect::<String>();
+          (207, String::new(), body)
+        },
+        ("PUT", _) if precondition => {
+          *etag += 1;
+          let etag = format!("\"{etag}\"");
+          let status = if current.is_some() { 204 } else { 201 };
+          let _prev = items.insert(path.clone(), (etag.clone(), body));
+          (status, format!("ETag: {etag}\r\n"), String::new())
+        },
+        ("DELETE", _) if current.is_none() => (404, String::new(), String::new()),
+        ("DELETE", _) if precondition => {
+          let _prev = items.remove(&path);
+          (204, String::new(), String::new())
+        },
+        ("PUT", _) | ("DELETE", _) => (412, String::new(), String::new()),
+        _ => (404, String::new(), String::new()),
+      };
+
+      let body = if status == 207 {
+        format!(
+          concat!(
+            r#"<?xml version="1.0"?>"#,
+            r#"<d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">"#,
+            "{body}</d:multistatus>",
+          ),
+          body = body
+        )
+      } else {
+        body
+      };
+      let mut stream = reader.into_inner();
+      let () = write!(
+        stream,
+        "HTTP/1.1 {status} Status\r\nContent-Length: {}\r\nConnection: close\r\n{extra}\r\n{body}",
+        body.len()
+      )
+      .unwrap();
+    }
+  }
+
+
+  /// Serialize a task into iCalendar data, returning it along with the
+  /// task's ID.
+  fn make_task(summary: &str) -> (String, String) {
+    let task = SerTask::new(summary.to_string());
+    let id = task.id.to_string();
+    let data = <iCal as Backend<SerTasks>>::serialize(&SerTasks(vec![task])).unwrap();
+    (id, String::from_utf8(data).unwrap())
+  }
+
+  /// Check that we can synchronize task files with a CalDAV server.
+  #[test]
+  async fn sync_with_server() {
+    let server = Server::new();
+    let config = SyncConfig {
+      url: server.url.clone(),
+      username: Some("user".to_string()),
+      password_command: Some("echo secret".to_string()),
+      collections: BTreeMap::new(),
+    };
+    let dir = TempDir::new().unwrap();
+    let mut dir_cap = DirCap::for_dir(dir.path().to_path_buf()).await.unwrap();
+    let local = |file: &str| fs::read_to_string(dir.path().join(file)).ok();
+    let remote = |file: &str| {
+      server
+        .items()
+        .get(&format!("{}{file}.ics", Server::COLLECTION))
+        .cloned()
+    };
+
+    let (a, a_data) = make_task("a");
+    let (b, b_data) = make_task("b");
+    let (c, c_data) = make_task("c");
+    let () = fs::write(dir.path().join(&a), &a_data).unwrap();
+    let () = fs::write(dir.path().join(&b), &b_data).unwrap();
+    let () = server.insert(&format!("{c}.ics"), &c_data, "\"c\"");
+
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    let expected = Summary {
+      downloaded: 1,
+      uploaded: 2,
+      ..Default::default()
+    };
+    assert_eq!(summary, expected);
+    assert_eq!(local(&c), Some(c_data.clone()));
+    assert_eq!(remote(&a), Some(a_data.clone()));
+    assert_eq!(remote(&b), Some(b_data));
+
+    // Without any changes, there is nothing to do.
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    assert_eq!(summary, Summary::default());
+
+    // Even without any status, we should be able to match up all items.
+    let () = fs::remove_file(dir.path().join(STATUS_FILE)).unwrap();
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    assert_eq!(summary, Summary::default());
+
+    // Modify and add a task on the server and remove one locally.
+    let (d, d_data) = make_task("d");
+    let a_data = a_data.replace("SUMMARY:a", "SUMMARY:a2");
+    let () = server.insert(&format!("{a}.ics"), &a_data, "\"a2\"");
+    let () = server.insert(&format!("{d}.ics"), &d_data, "\"d\"");
+    let () = fs::remove_file(dir.path().join(&b)).unwrap();
+
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    let expected = Summary {
+      downloaded: 2,
+      removed_remote: 1,
+      ..Default::default()
+    };
+    assert_eq!(summary, expected);
+    assert_eq!(local(&a), Some(a_data));
+    assert_eq!(local(&d), Some(d_data));
+    assert_eq!(remote(&b), None);
+
+    // Remove a task on the server and modify one on both sides, in
+    // which case the changes get merged.
+    let a_data = local(&a).unwrap();
+    let a_remote = a_data.replace("SUMMARY:a2", "SUMMARY:a2\r\nDESCRIPTION:remote");
+    let () = server.items.lock().unwrap().clear();
+    let () = server.insert(&format!("{a}.ics"), &a_remote, "\"a3\"");
+    let c_data = c_data.replace("SUMMARY:c", "SUMMARY:c2");
+    let () = fs::write(dir.path().join(&c), &c_data).unwrap();
+    let a_data = a_data.replace("SUMMARY:a2", "SUMMARY:a3");
+    let () = fs::write(dir.path().join(&a), &a_data).unwrap();
+
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    let expected = Summary {
+      uploaded: 1,
+      removed_local: 1,
+      merged: 1,
+      ..Default::default()
+    };
+    assert_eq!(summary, expected);
+    let a_data = remote(&a).unwrap();
+    assert!(a_data.contains("SUMMARY:a3"), "{a_data}");
+    assert!(a_data.contains("DESCRIPTION:remote"), "{a_data}");
+    assert_eq!(local(&a), Some(a_data.clone()));
+    assert_eq!(remote(&c), Some(c_data));
+    assert_eq!(local(&d), None);
+
+    // Without a common base, differing versions of a task are left
+    // alone.
+    let () = fs::remove_file(dir.path().join(STATUS_FILE)).unwrap();
+    let a_local = a_data.replace("SUMMARY:a3", "SUMMARY:a4");
+    let () = fs::write(dir.path().join(&a), &a_local).unwrap();
+
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    let expected = Summary {
+      conflicting: 1,
+      ..Default::default()
+    };
+    assert_eq!(summary, expected);
+    assert_eq!(local(&a), Some(a_local));
+    assert_eq!(remote(&a), Some(a_data));
+  }
+
+  /// Check that archiving a task file does not remove the task from the
+  /// server.
+  #[test]
+  async fn sync_archived() {
+    let server = Server::new();
+    let config = SyncConfig {
+      url: server.url.clone(),
+      username: None,
+      password_command: None,
+      collections: BTreeMap::new(),
+    };
+    let dir = TempDir::new().unwrap();
+    let archive = TempDir::new().unwrap();
+    let mut dir_cap = DirCap::for_dir(dir.path().to_path_buf()).await.unwrap();
+
+    let (a, a_data) = make_task("a");
+    let () = fs::write(dir.path().join(&a), &a_data).unwrap();
+    let summary = sync(&config, &mut dir_cap, &[], Some(archive.path())).await.unwrap();
+    assert_eq!(summary.uploaded, 1);
+
+    let () = fs::rename(dir.path().join(&a), archive.path().join(&a)).unwrap();
+    let summary = sync(&config, &mut dir_cap, &[], Some(archive.path())).await.unwrap();
+    assert_eq!(summary, Summary::default());
+    assert_eq!(server.items().len(), 1);
+
+    // Once restored from the archive, the file is synchronized as
+    // before.
+    let () = fs::rename(archive.path().join(&a), dir.path().join(&a)).unwrap();
+    let summary = sync(&config, &mut dir_cap, &[], Some(archive.path())).await.unwrap();
+    assert_eq!(summary, Summary::default());
+
+    // Without knowledge of the archive, the file would be considered
+    // deleted.
+    let () = fs::rename(dir.path().join(&a), archive.path().join(&a)).unwrap();
+    let summary = sync(&config, &mut dir_cap, &[], None).await.unwrap();
+    assert_eq!(summary.removed_remote, 1);
+    assert!(server.items().is_empty());
+  }
+
+  /// Check that collections are synchronized with the calendars
+  /// configured for them.
+  #[test]
+  async fn sync_collections() {
+    let server = Server::new();
+    let work = "/calendars/work/";
+    let config = SyncConfig {
+      url: server.url.clone(),
+      username: None,
+      password_command: None,
+      collections: BTreeMap::from([("work".to_string(), format!("{}{}", server.url, &work[1..]))]),
+    };
+    let dir = TempDir::new().unwrap();
+    let mut dir_cap = DirCap::for_dir(dir.path().to_path_buf()).await.unwrap();
+    let () = fs::create_dir(dir.path().join("home")).unwrap();
+    let () = fs::create_dir(dir.path().join("work")).unwrap();
+    let collections = read_collections(dir.path()).await.unwrap();
+    assert_eq!(collections.len(), 2);
+
+    let (a, a_data) = make_task("a");
+    let (b, b_data) = make_task("b");
+    let (c, c_data) = make_task("c");
+    let () = fs::write(dir.path().join(&a), &a_data).unwrap();
+    let () = fs::write(dir.path().join("work").join(&b), &b_data).unwrap();
+    let () = fs::write(dir.path().join("home").join(&c), c_data).unwrap();
+
+    let summary = sync(&config, &mut dir_cap, &collections, None).await.unwrap();
+    let expected = Summary {
+      uploaded: 2,
+      skipped: vec!["home".to_string()],
+      ..Default::default()
+    };
+    assert_eq!(summary, expected);
+    let items = server.items();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items.get(&format!("{}{a}.ics", Server::COLLECTION)), Some(&a_data));
+    assert_eq!(items.get(&format!("{work}{b}.ics")), Some(&b_data));
+    assert!(dir.path().join("work").join(STATUS_FILE).exists());
+
+    // Items added to the calendar of a collection end up in its
+    // directory.
+    let (d, d_data) = make_task("d");
+    let _prev = server
+      .items
+      .lock()
+      .unwrap()
+      .insert(format!("{work}{d}.ics"), ("\"d\"".to_string(), d_data.clone()));
+    let summary = sync(&config, &mut dir_cap, &collections, None).await.unwrap();
+    assert_eq!(summary.downloaded, 1);
+    let local = fs::read_to_string(dir.path().join("work").join(&d)).ok();
+    assert_eq!(local, Some(d_data));
+    assert!(!dir.path().join(&d).exists());
+  }
+}
@@ -0,0 +1,355 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! A minimal XML parser, sufficient for interpreting WebDAV
+//! `multistatus` responses.
+//!
+//! Namespaces are not resolved. Rather, element names are reduced to
+//! their local part, which is unambiguous for the small set of elements
+//! we are interested in.
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+
+/// Escape text for inclusion in an XML document.
+pub fn escape(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+/// Replace entity and character references in `text`.
+fn unescape(text: &str) -> Result<String> {
+  let mut unescaped = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('&') {
+    let () = unescaped.push_str(&rest[..start]);
+    rest = &rest[start + 1..];
+    let end = rest
+      .find(';')
+      .with_context(|| format!("encountered unterminated reference in `{text}`"))?;
+    let reference = &rest[..end];
+    let c = match reference {
+      "amp" => '&',
+      "lt" => '<',
+      "gt" => '>',
+      "quot" => '"',
+      "apos" => '\'',
+      _ => {
+        let code = if let Some(hex) = reference.strip_prefix("#x") {
+          u32::from_str_radix(hex, 16).ok()
+        } else if let Some(dec) = reference.strip_prefix('#') {
+          dec.parse::<u32>().ok()
+        } else {
+          None
+        };
+        code
+          .and_then(char::from_u32)
+          .with_context(|| format!("encountered unsupported reference `&{reference};`"))?
+      },
+    };
+    let () = unescaped.push(c);
+    rest = &rest[end + 1..];
+  }
+  let () = unescaped.push_str(rest);
+  Ok(unescaped)
+}
+
+/// Reduce a potentially prefixed name to its local part.
+fn local_name(name: &str) -> &str {
+  name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Parse the attributes contained in the remainder of a start tag.
+fn parse_attributes(mut rest: &str) -> Result<Vec<(String, String)>> {
+  let mut attributes = Vec::new();
+  loop {
+    rest = rest.trim_start();
+    if rest.is_empty() {
+      break Ok(attributes)
+    }
+
+    let (name, value) = rest
+      .split_once('=')
+      .with_context(|| format!("encountered invalid attribute `{rest}`"))?;
+    let value = value.trim_start();
+    let quote = value
+      .chars()
+      .next()
+      .filter(|c| *c == '"' || *c == '\'')
+      .with_context(|| format!("encountered unquoted attribute value `{value}`"))?;
+    let end = value[1..]
+      .find(quote)
+      .with_context(|| format!("encountered unterminated attribute value `{value}`"))?;
+    let () = attributes.push((
+      local_name(name.trim()).to_string(),
+      unescape(&value[1..1 + end])?,
+    ));
+    rest = &value[end + 2..];
+  }
+}
+
+
+/// A node in an XML document.
+#[derive(Debug, PartialEq)]
+enum Node {
+  Element(Element),
+  Text(String),
+}
+
+
+/// An XML element.
+#[derive(Debug, PartialEq)]
+pub struct Element {
+  /// The local name of the element.
+  name: String,
+  /// The element's attributes, with names reduced to their local part.
+  attributes: Vec<(String, String)>,
+  /// The element's child nodes.
+  children: Vec<Node>,
+}
+
+impl Element {
+  /// Parse an XML document, returning its root element.
+  pub fn parse(document: &str) -> Result<Self> {
+    let mut stack = vec![Element {
+      name: String::new(),
+      attributes: Vec::new(),
+      children: Vec::new(),
+    }];
+    let mut rest = document;
+
+    while !rest.is_empty() {
+      if let Some(markup) = rest.strip_prefix('<') {
+        if let Some(markup) = markup.strip_prefix("!--") {
+          let end = markup.find("-->").context("encountered unterminated comment")?;
+          rest = &markup[end + 3..];
+        } else if let Some(markup) = markup.strip_prefix("![CDATA[") {
+          let end = markup.find("]]>").context("encountered unterminated CDATA section")?;
+          // SANITY: There is always at least the document element.
+          let parent = stack.last_mut().unwrap();
+          let () = parent.children.push(Node::Text(markup[..end].to_string()));
+          rest = &markup[end + 3..];
+        } else if markup.starts_with('?') || markup.starts_with('!') {
+          let end = markup.find('>').context("encountered unterminated declaration")?;
+          rest = &markup[end + 1..];
+        } else if let Some(markup) = markup.strip_prefix('/') {
+          let end = markup.find('>').context("encountered unterminated end tag")?;
+          let name = local_name(markup[..end].trim());
+          let element = stack
+            .pop()
+            .filter(|_| !stack.is_empty())
+            .with_context(|| format!("encountered unexpected end tag `{name}`"))?;
+          if element.name != name {
+            bail!(
+              "encountered end tag `{name}` for element `{}`",
+              element.name
+            )
+          }
+          // SANITY: We checked above that the stack is not empty.
+          let () = stack.last_mut().unwrap().children.push(Node::Element(element));
+          rest = &markup[end + 1..];
+        } else {
+          // Find the end of the tag, skipping over any quoted attribute
+          // values, which may contain `>`.
+          let mut quote = None;
+          let end = markup
+            .char_indices()
+            .find(|(_, c)| match quote {
+              Some(q) if *c == q => {
+                quote = None;
+                false
+              },
+              Some(_) => false,
+              None if *c == '"' || *c == '\'' => {
+                quote = Some(*c);
+                false
+              },
+              None => *c == '>',
+            })
+            .map(|(idx, _)| idx)
+            .context("encountered unterminated start tag")?;
+          let tag = &markup[..end];
+          let (tag, empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+          };
+          let (name, attributes) = tag
+            .split_once(char::is_whitespace)
+            .unwrap_or((tag, ""));
+          let element = Element {
+            name: local_name(name).to_string(),
+            attributes: parse_attributes(attributes)?,
+            children: Vec::new(),
+          };
+
+          if empty {
+            // SANITY: There is always at least the document element.
+            let () = stack.last_mut().unwrap().children.push(Node::Element(element));
+          } else {
+            let () = stack.push(element);
+          }
+          rest = &markup[end + 1..];
+        }
+      } else {
+        let end = rest.find('<').unwrap_or(rest.len());
+        let text = unescape(&rest[..end])?;
+        // SANITY: There is always at least the document element.
+        let () = stack.last_mut().unwrap().children.push(Node::Text(text));
+        rest = &rest[end..];
+      }
+    }
+
+    let mut document = stack.pop().filter(|_| stack.is_empty()).with_context(|| {
+      "encountered unterminated element".to_string()
+    })?;
+    let root = document
+      .children
+      .drain(..)
+      .find_map(|node| match node {
+        Node::Element(element) => Some(element),
+        Node::Text(_) => None,
+      })
+      .context("XML document does not contain an element")?;
+    Ok(root)
+  }
+
+  /// Retrieve the local name of the element.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Retrieve the value of the attribute with the given local name.
+  pub fn attribute(&self, name: &str) -> Option<&str> {
+    self
+      .attributes
+      .iter()
+      .find(|(attribute, _)| attribute == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Retrieve an iterator over all child elements with the given name.
+  pub fn children<'slf>(&'slf self, name: &'slf str) -> impl Iterator<Item = &'slf Element> {
+    self.children.iter().filter_map(move |node| match node {
+      Node::Element(element) if element.name == name => Some(element),
+      _ => None,
+    })
+  }
+
+  /// Convert the element into its child elements with the given name.
+  pub fn into_children(self, name: &str) -> Vec<Element> {
+    self
+      .children
+      .into_iter()
+      .filter_map(|node| match node {
+        Node::Element(element) if element.name == name => Some(element),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// Retrieve the first child element with the given name.
+  pub fn child(&self, name: &str) -> Option<&Element> {
+    self.children.iter().find_map(|node| match node {
+      Node::Element(element) if element.name == name => Some(element),
+      _ => None,
+    })
+  }
+
+  /// Retrieve the text contained in the element, including that of all
+  /// descendants, verbatim.
+  pub fn content(&self) -> String {
+    fn collect(element: &Element, text: &mut String) {
+      for node in &element.children {
+        match node {
+          Node::Element(element) => collect(element, text),
+          Node::Text(string) => text.push_str(string),
+        }
+      }
+    }
+
+    let mut text = String::new();
+    let () = collect(self, &mut text);
+    text
+  }
+
+  /// Retrieve the text contained in the element, including that of all
+  /// descendants, with surrounding white space removed.
+  pub fn text(&self) -> String {
+    self.content().trim().to_string()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we can parse a typical `multistatus` response.
+  #[test]
+  fn parse_multistatus() {
+    let document = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <!-- a comment -->
+  <d:response>
+    <d:href>/calendars/a%20b.ics</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:getetag>&quot;1&quot;</d:getetag>
+        <c:calendar-data><![CDATA[BEGIN:VCALENDAR]]>&#13;&#x0A;END:VCALENDAR</c:calendar-data>
+        <d:resourcetype><d:collection/><c:calendar /></d:resourcetype>
+      </d:prop>
+      <d:status attr='a>b' other = "&lt;">HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    let root = Element::parse(document).unwrap();
+    assert_eq!(root.name(), "multistatus");
+    let response = root.child("response").unwrap();
+    assert_eq!(response.child("href").unwrap().text(), "/calendars/a%20b.ics");
+    let propstat = response.child("propstat").unwrap();
+    let prop = propstat.child("prop").unwrap();
+    assert_eq!(prop.child("getetag").unwrap().text(), r#""1""#);
+    assert_eq!(
+      prop.child("calendar-data").unwrap().text(),
+      "BEGIN:VCALENDAR\r\nEND:VCALENDAR"
+    );
+    let types = prop.child("resourcetype").unwrap();
+    assert!(types.child("collection").is_some());
+    assert!(types.child("calendar").is_some());
+    let status = propstat.child("status").unwrap();
+    assert_eq!(status.text(), "HTTP/1.1 200 OK");
+    assert_eq!(status.attribute("attr"), Some("a>b"));
+    assert_eq!(status.attribute("other"), Some("<"));
+  }
+
+  /// Make sure that we reject malformed documents.
+  #[test]
+  fn parse_malformed() {
+    assert!(Element::parse("<a><b></a>").is_err());
+    assert!(Element::parse("<a>").is_err());
+    assert!(Element::parse("</a>").is_err());
+    assert!(Element::parse("text").is_err());
+    assert!(Element::parse("<a>&unknown;</a>").is_err());
+  }
+
+  /// Check that escaping and unescaping text round trips.
+  #[test]
+  fn escape_unescape() {
+    let text = r#"<a href="x">&'</a>"#;
+    assert_eq!(unescape(&escape(text)).unwrap(), text);
+  }
+}
@@ -1,8 +1,11 @@
 // Copyright (C) 2018-2022 Daniel Mueller (deso@posteo.net)
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::rc::Rc;
@@ -22,27 +25,27 @@ type Id = IdT<T>;
 /// A type representing a template for a tag.
 #[derive(Debug, Eq)]
 pub struct Template {
+  /// The template's `Id`, which is only valid for the lifetime of the
+  /// program.
   id: Id,
+  /// The template's persistent and globally unique identity.
+  ser_id: SerTagId,
   name: String,
 }
 
 impl Template {
   /// Create a new tag template with the given name.
-  fn new<S>(id: Id, name: S) -> Self
+  fn new<S>(id: Id, ser_id: SerTagId, name: S) -> Self
   where
     S: Into<String>,
   {
     Self {
       id,
+      ser_id,
       name: name.into(),
     }
   }
 
-  /// Create a `Template` object from a `SerTemplate`.
-  fn with_serde(id: Id, template: SerTemplate) -> Self {
-    Self::new(id, template.name)
-  }
-
   /// Retrieve the tag template's name.
   #[inline]
   pub fn name(&self) -> &str {
@@ -85,7 +88,7 @@ impl ToSerde for Template {
   /// Convert the template into a serializable one.
   fn to_serde(&self) -> Self::Output {
     SerTemplate {
-      id: self.id.to_serde(),
+      id: self.ser_id,
       name: self.name.clone(),
     }
   }
@@ -122,18 +125,25 @@ impl ToSerde for Tag {
   /// Convert the tag into a serializable one.
   fn to_serde(&self) -> Self::Output {
     SerTag {
-      id: self.template.id.to_serde(),
+      id: self.template.ser_id,
     }
   }
 }
 
 
 /// A management structure for tag templates.
+///
+/// Templates are shared between all tasks, but new ones may get created
+/// at any time when tasks changed by other clients refer to unknown tag
+/// names. Hence, the object is internally mutable.
 #[derive(Debug)]
 pub struct Templates {
   /// A mapping of all the tag templates, indexed by each one's `Id`,
   /// converted to `usize`.
-  templates: BTreeMap<usize, Rc<Template>>,
+  templates: RefCell<BTreeMap<usize, Rc<Template>>>,
+  /// A mapping from serialized IDs, including legacy ones that have
+  /// been migrated, to templates.
+  index: RefCell<HashMap<SerTagId, Rc<Template>>>,
 }
 
 impl Templates {
@@ -144,22 +154,71 @@ impl Templates {
   }
 
   /// Create a `Templates` object from a `SerTemplates` object.
+  ///
+  /// Templates with a legacy ID are migrated to one derived from their
+  /// name, but remain accessible by their legacy ID as well.
   pub fn with_serde(templates: SerTemplates) -> Result<Self, SerTagId> {
-    let templates =
-      templates
-        .0
-        .into_iter()
-        .try_fold(BTreeMap::new(), |mut templates, template| {
-          let (id, entry) = templates
-            .try_reserve_id(template.id.get())
-            .ok_or(template.id)?;
-          let template = Rc::new(Template::with_serde(id, template));
-          let _value_ref = entry.insert(template);
+    let slf = Self {
+      templates: RefCell::new(BTreeMap::new()),
+      index: RefCell::new(HashMap::new()),
+    };
 
-          Ok(templates)
-        })?;
+    for SerTemplate { id, name } in templates.0 {
+      let ser_id = if id.is_legacy() {
+        SerTagId::from_name(&name)
+      } else {
+        id
+      };
+
+      let existing = slf.index.borrow().get(&ser_id).cloned();
+      let template = match existing {
+        // Legacy templates of the same name map to the same identity
+        // and are merged, also with the migrated template itself.
+        Some(template) if template.name() == name => template.clone(),
+        Some(..) => return Err(id),
+        // We attempt to keep the legacy ID as the run time one, so that
+        // tags keep their relative order.
+        None => slf.insert_at(id.to_legacy(), ser_id, name),
+      };
+
+      if id != ser_id {
+        match slf.index.borrow_mut().entry(id) {
+          Entry::Vacant(entry) => {
+            let _template = entry.insert(template);
+          },
+          Entry::Occupied(entry) if Rc::ptr_eq(entry.get(), &template) => (),
+          Entry::Occupied(..) => return Err(id),
+        }
+      }
+    }
 
-    Ok(Self { templates })
+    Ok(slf)
+  }
+
+  /// Create and insert a new template.
+  #[inline]
+  fn insert<S>(&self, ser_id: SerTagId, name: S) -> Rc<Template>
+  where
+    S: Into<String>,
+  {
+    self.insert_at(None, ser_id, name)
+  }
+
+  /// Create and insert a new template, using the provided run time ID
+  /// if it is available.
+  fn insert_at<S>(&self, id: Option<usize>, ser_id: SerTagId, name: S) -> Rc<Template>
+  where
+    S: Into<String>,
+  {
+    let mut templates = self.templates.borrow_mut();
+    let (id, entry) = match id.and_then(|id| templates.try_reserve_id(id)) {
+      Some((id, entry)) => (id, entry),
+      None => templates.allocate_id(),
+    };
+    let template = Rc::new(Template::new(id, ser_id, name));
+    let _value_ref = entry.insert(template.clone());
+    let _prev = self.index.borrow_mut().insert(ser_id, template.clone());
+    template
   }
 
   /// Instantiate a tag from the given serialized tag ID.
@@ -168,8 +227,9 @@ impl Templates {
   /// a known tag.
   pub fn instantiate(&self, id: SerTagId) -> Option<Tag> {
     self
-      .templates
-      .get(&id.get())
+      .index
+      .borrow()
+      .get(&id)
       .map(|template| Tag::new(template.clone()))
   }
 
@@ -177,16 +237,47 @@ impl Templates {
   #[cfg(test)]
   pub fn instantiate_from_name(&self, name: &str) -> Tag {
     self
-      .templates
-      .values()
+      .iter()
       .find(|template| template.name() == name)
-      .map(|template| Tag::new(template.clone()))
+      .map(Tag::new)
       .unwrap_or_else(|| panic!("Attempt to create tag from invalid name: {}", name))
   }
 
+  /// Instantiate a tag based on a name, creating a new template if
+  /// none with the given name exists yet.
+  pub fn instantiate_or_create(&self, name: &str) -> Tag {
+    let template = self
+      .iter()
+      .find(|template| template.name() == name)
+      .unwrap_or_else(|| {
+        let mut ser_id = SerTagId::from_name(name);
+        // The name derived ID may be taken by a template that was
+        // renamed elsewhere, in which case we fall back to a random one.
+        while self.index.borrow().contains_key(&ser_id) {
+          ser_id = SerTagId::random();
+        }
+        self.insert(ser_id, name)
+      });
+
+    Tag::new(template)
+  }
+
+  /// Add the templates of a diverged copy of serialized templates,
+  /// e.g., as created by other clients, that are not yet known.
+  pub fn merge_serde(&self, templates: SerTemplates) {
+    let mut merged = self.to_serde();
+    let count = merged.0.len();
+    let () = merged.merge(templates);
+
+    for SerTemplate { id, name } in merged.0.drain(count..) {
+      let _template = self.insert(id, name);
+    }
+  }
+
   /// Retrieve an iterator over all the tag templates.
-  pub fn iter(&self) -> impl Iterator<Item = Rc<Template>> + '_ {
-    self.templates.values().cloned()
+  pub fn iter(&self) -> impl Iterator<Item = Rc<Template>> {
+    let templates = self.templates.borrow().values().cloned().collect::<Vec<_>>();
+    templates.into_iter()
   }
 }
 
@@ -200,9 +291,8 @@ where
     I: IntoIterator<Item = S>,
   {
     let () = iter.into_iter().for_each(|name| {
-      let (id, entry) = self.templates.allocate_id();
-      let template = Rc::new(Template::new(id, name));
-      let _value_ref = entry.insert(template);
+      let name = name.into();
+      let _template = self.insert(SerTagId::from_name(&name), name);
     });
   }
 }
@@ -211,12 +301,30 @@ impl ToSerde for Templates {
   type Output = SerTemplates;
 
   /// Convert the tag templates object into a serializable form.
+  ///
+  /// Legacy IDs of migrated templates are retained after all others,
+  /// so that tags referring to them, e.g., as stored by devices not yet
+  /// upgraded, continue to be understood.
   fn to_serde(&self) -> Self::Output {
+    let mut legacy = self
+      .index
+      .borrow()
+      .iter()
+      .filter(|(id, _template)| id.is_legacy())
+      .map(|(id, template)| SerTemplate {
+        id: *id,
+        name: template.name().to_string(),
+      })
+      .collect::<Vec<_>>();
+    let () = legacy.sort_by_key(|template| template.id.to_legacy());
+
     SerTemplates(
       self
         .templates
+        .borrow()
         .values()
         .map(|template| template.to_serde())
+        .chain(legacy)
         .collect(),
     )
   }
@@ -243,4 +351,72 @@ mod tests {
 
     assert_eq!(tag1, tag2)
   }
+
+  /// Check that templates with legacy IDs are migrated to name based
+  /// ones while remaining accessible by their legacy ID.
+  #[test]
+  fn migrate_legacy_template_ids() {
+    let id1 = SerTagId::try_from(1).unwrap();
+    let id2 = SerTagId::try_from(2).unwrap();
+    let id3 = SerTagId::random();
+    let templates = SerTemplates(vec![
+      SerTemplate {
+        id: id1,
+        name: "tag1".to_string(),
+      },
+      // A template of the same name, e.g., stemming from a different
+      // device, is merged with the first one.
+      SerTemplate {
+        id: id2,
+        name: "tag1".to_string(),
+      },
+      SerTemplate {
+        id: id3,
+        name: "tag3".to_string(),
+      },
+    ]);
+
+    let templates = Templates::with_serde(templates).unwrap();
+    let tag1 = templates.instantiate(id1).unwrap();
+    let tag2 = templates.instantiate(id2).unwrap();
+    assert_eq!(tag1, tag2);
+    assert_eq!(tag1.to_serde().id, SerTagId::from_name("tag1"));
+    assert_eq!(
+      templates.instantiate(SerTagId::from_name("tag1")),
+      Some(tag1)
+    );
+    assert_eq!(templates.instantiate(id3).unwrap().name(), "tag3");
+
+    // Legacy IDs are retained and still understood after a round
+    // trip.
+    let templates = templates.to_serde();
+    assert_eq!(templates.0.len(), 4);
+    assert!(templates.0[..2].iter().all(|template| !template.id.is_legacy()));
+    assert_eq!(templates.0[2].id, id1);
+    assert_eq!(templates.0[3].id, id2);
+
+    let templates = Templates::with_serde(templates).unwrap();
+    assert_eq!(templates.iter().count(), 2);
+    let id = |id| templates.instantiate(id).map(|tag| tag.to_serde().id);
+    assert_eq!(id(id1), Some(SerTagId::from_name("tag1")));
+    assert_eq!(id(id2), Some(SerTagId::from_name("tag1")));
+  }
+
+  /// Make sure that we reject templates with conflicting IDs.
+  #[test]
+  fn reject_conflicting_template_ids() {
+    let id = SerTagId::random();
+    let templates = SerTemplates(vec![
+      SerTemplate {
+        id,
+        name: "tag1".to_string(),
+      },
+      SerTemplate {
+        id,
+        name: "tag2".to_string(),
+      },
+    ]);
+
+    assert_eq!(Templates::with_serde(templates).unwrap_err(), id);
+  }
 }
@@ -3,14 +3,33 @@
 
 use std::cell::RefCell;
 use std::collections::btree_set::Iter as BTreeSetIter;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::hash_map::Entry as HashMapEntry;
 use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::hash::Hasher as _;
+use std::mem::replace;
+use std::mem::take;
 use std::ops::Deref as _;
 use std::ops::DerefMut as _;
+use std::ptr;
 use std::rc::Rc;
+use std::str::FromStr as _;
 
 use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::SubsecRound as _;
+use chrono::TimeZone as _;
+use chrono::Utc;
+
 use uuid::Uuid;
 
 use crate::db::Db;
@@ -19,20 +38,53 @@ use crate::db::Iter as DbIter;
 use crate::ops::Op;
 use crate::ops::Ops;
 use crate::position::Position;
+use crate::priority::Priority;
+use crate::recurrence::Recurrence;
+use crate::ser::backends::iCal;
+use crate::ser::backends::Backend;
+use crate::ser::history::Dependent as SerDependent;
+use crate::ser::history::History as SerHistory;
+use crate::ser::history::Moved as SerMoved;
+use crate::ser::history::Removed as SerRemoved;
+use crate::ser::history::Step as SerStep;
+use crate::ser::history::Target as SerTarget;
+use crate::ser::journal::Entry as JournalEntry;
+use crate::ser::tasks::Alarm as SerAlarm;
+use crate::ser::tasks::Related as SerRelated;
 use crate::ser::tasks::Task as SerTask;
 use crate::ser::tasks::Tasks as SerTasks;
+use crate::ser::tasks::Trigger as SerTrigger;
+use crate::ser::tasks::Unknown as SerUnknown;
 use crate::ser::ToSerde;
 use crate::tags::Tag;
 use crate::tags::Templates;
 
 
-/// The maximum number of undo steps that we keep record of.
-// TODO: We may consider making this value user-configurable.
-const MAX_UNDO_STEP_COUNT: usize = 64;
+/// The maximum number of undo steps that we keep record of, unless
+/// configured otherwise.
+const DEFAULT_MAX_UNDO_STEP_COUNT: usize = 64;
 
 
 type Id = Uuid;
 
+/// Tasks removed from a `Db`, along with their former index and
+/// position.
+type Removed = Vec<(Rc<Task>, usize, Position)>;
+
+/// Tasks whose dependencies got adjusted, along with their former
+/// dependencies.
+type Dependents = Vec<(Rc<Task>, Vec<Rc<Task>>)>;
+
+
+/// Retrieve the current time, in the precision we persist it with.
+///
+/// iCalendar time stamps have a granularity of seconds. We truncate
+/// anything more precise right away, to not end up with a different
+/// value when loading the time back in.
+fn now() -> DateTime<Utc> {
+  Utc::now().trunc_subsecs(0)
+}
+
 
 #[derive(Clone, Debug)]
 struct TaskInner {
@@ -40,8 +92,51 @@ struct TaskInner {
   id: Id,
   /// The task's summary.
   summary: String,
+  /// Free-form notes associated with the task, if any.
+  notes: Option<String>,
   /// The task's tags.
   tags: BTreeSet<Tag>,
+  /// The date before which the task cannot be acted upon, if any.
+  start: Option<NaiveDate>,
+  /// The date by which the task is due, if any.
+  due: Option<NaiveDate>,
+  /// The task's priority, if any.
+  priority: Option<Priority>,
+  /// The rule describing how the task recurs, if it does.
+  recurrence: Option<String>,
+  /// The alarms reminding of the task.
+  alarms: Vec<SerAlarm>,
+  /// The time at which the task got completed, if it did.
+  completed: Option<DateTime<Utc>>,
+  /// Whether the task's completion state got recorded, as opposed to
+  /// being conveyed by the completion tag only.
+  completion_recorded: bool,
+  /// The time at which the task got created, if known.
+  created: Option<DateTime<Utc>>,
+  /// The time at which the task got last modified, if known.
+  modified: Option<DateTime<Utc>>,
+  /// The time stamp of the task's serialized representation.
+  stamp: DateTime<Utc>,
+  /// Data of the task that we do not interpret but preserve.
+  unknown: SerUnknown,
+  /// The name of the file the task is stored in, if it is not the
+  /// default one.
+  file_name: Option<OsString>,
+  /// The name of the collection the task belongs to, if it is not the
+  /// default one.
+  collection: Option<String>,
+  /// The task's parent, if it is a sub-task.
+  parent: Option<Rc<Task>>,
+  /// The tasks this task depends on.
+  depends_on: Vec<Rc<Task>>,
+  /// The ID of the task's parent, if it could not be resolved, e.g.,
+  /// because the file storing it failed to load. It is kept so that
+  /// the relation survives a save.
+  unresolved_parent: Option<Id>,
+  /// The IDs of tasks this task depends on that could not be resolved.
+  unresolved_depends_on: Vec<Id>,
+  /// Whether the task's sub-tasks are hidden from view.
+  collapsed: bool,
   /// Reference to the shared `Templates` object from which tags were
   /// instantiated.
   templates: Rc<Templates>,
@@ -67,7 +162,26 @@ impl Task {
     let inner = TaskInner {
       id: Id::new_v4(),
       summary: summary.into(),
+      notes: None,
       tags: Default::default(),
+      start: None,
+      due: None,
+      priority: None,
+      recurrence: None,
+      alarms: Vec::new(),
+      completed: None,
+      completion_recorded: true,
+      created: None,
+      modified: None,
+      stamp: now(),
+      unknown: SerUnknown::default(),
+      file_name: None,
+      collection: None,
+      parent: None,
+      depends_on: Vec::new(),
+      unresolved_parent: None,
+      unresolved_depends_on: Vec::new(),
+      collapsed: false,
       templates: Rc::new(Templates::new()),
     };
 
@@ -82,7 +196,26 @@ impl Task {
     let inner = TaskInner {
       id: Id::new_v4(),
       summary: summary.into(),
+      notes: None,
       tags: tags.into_iter().collect(),
+      start: None,
+      due: None,
+      priority: None,
+      recurrence: None,
+      alarms: Vec::new(),
+      completed: None,
+      completion_recorded: true,
+      created: None,
+      modified: None,
+      stamp: now(),
+      unknown: SerUnknown::default(),
+      file_name: None,
+      collection: None,
+      parent: None,
+      depends_on: Vec::new(),
+      unresolved_parent: None,
+      unresolved_depends_on: Vec::new(),
+      collapsed: false,
       templates,
     };
 
@@ -102,14 +235,34 @@ impl Task {
     let inner = TaskInner {
       id: task.id,
       summary: task.summary,
+      notes: task.notes,
       tags,
+      start: task.start,
+      due: task.due,
+      priority: task.priority.map(Priority::from),
+      recurrence: task.recurrence,
+      alarms: task.alarms,
+      completed: task.completed,
+      completion_recorded: task.completion_recorded,
+      created: task.created,
+      modified: task.modified,
+      stamp: task.stamp,
+      unknown: task.unknown,
+      file_name: task.file_name,
+      collection: task.collection,
+      // Relations to other tasks can only be resolved once all tasks
+      // are known.
+      parent: None,
+      depends_on: Vec::new(),
+      unresolved_parent: None,
+      unresolved_depends_on: Vec::new(),
+      collapsed: false,
       templates,
     };
     Ok(Self(RefCell::new(inner)))
   }
 
   /// Retrieve the [`Task`]'s ID.
-  #[cfg(test)]
   #[inline]
   pub fn id(&self) -> Id {
     // SANITY: The type's API surface prevents any borrows from escaping
@@ -136,6 +289,24 @@ impl Task {
     self.0.try_borrow_mut().unwrap().summary = summary
   }
 
+  /// Retrieve the [`Task`]'s notes, if any.
+  #[inline]
+  pub fn notes(&self) -> Option<String> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().notes.clone()
+  }
+
+  /// Change this [`Task`]'s notes.
+  #[inline]
+  pub fn set_notes(&mut self, notes: Option<String>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().notes = notes
+  }
+
   /// Invoke a user-provided function on an iterator over all the task's
   /// tags.
   #[inline]
@@ -187,13 +358,456 @@ impl Task {
     self.0.try_borrow_mut().unwrap().tags.remove(tag)
   }
 
+  /// Retrieve the date before which the [`Task`] cannot be acted upon,
+  /// if any.
+  #[inline]
+  pub fn start(&self) -> Option<NaiveDate> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().start
+  }
+
+  /// Set or clear this [`Task`]'s start date.
+  #[inline]
+  pub fn set_start(&mut self, start: Option<NaiveDate>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().start = start
+  }
+
+  /// Check whether the [`Task`] is snoozed, i.e., whether its start
+  /// date lies in the future.
+  pub fn is_snoozed(&self) -> bool {
+    self
+      .start()
+      .map(|start| start > Local::now().date_naive())
+      .unwrap_or(false)
+  }
+
+  /// Retrieve the date by which the [`Task`] is due, if any.
+  #[inline]
+  pub fn due(&self) -> Option<NaiveDate> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().due
+  }
+
+  /// Set or clear this [`Task`]'s due date.
+  #[inline]
+  pub fn set_due(&mut self, due: Option<NaiveDate>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().due = due
+  }
+
+  /// Retrieve the [`Task`]'s priority, if any.
+  #[inline]
+  pub fn priority(&self) -> Option<Priority> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().priority
+  }
+
+  /// Set or clear this [`Task`]'s priority.
+  #[inline]
+  pub fn set_priority(&mut self, priority: Option<Priority>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().priority = priority
+  }
+
+  /// Retrieve the [`Task`]'s recurrence rule, if any.
+  #[inline]
+  pub fn recurrence(&self) -> Option<String> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().recurrence.clone()
+  }
+
+  /// Set or clear this [`Task`]'s recurrence rule.
+  #[inline]
+  pub fn set_recurrence(&mut self, recurrence: Option<String>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().recurrence = recurrence
+  }
+
+  /// Advance a recurring [`Task`] to its next occurrence, by moving its
+  /// due date forward. The start date, if any, is moved along by the
+  /// same amount.
+  ///
+  /// A task without due date is treated as being due today. `false` is
+  /// returned if the task does not recur (anymore), including when its
+  /// rule is not supported. The UI refuses to complete tasks with such
+  /// a rule to begin with.
+  fn advance_recurrence(&mut self) -> bool {
+    let mut recurrence = match self.recurrence().as_deref().map(Recurrence::from_str) {
+      Some(Ok(recurrence)) => recurrence,
+      Some(Err(..)) | None => return false,
+    };
+    let original = recurrence.clone();
+    let due = self.due().unwrap_or_else(|| Local::now().date_naive());
+
+    if let Some(next) = recurrence.advance(due) {
+      let () = self.set_due(Some(next));
+      if let Some(start) = self.start() {
+        let () = self.set_start(start.checked_add_signed(next - due));
+      }
+      // Only write back the rule if it actually changed, to not
+      // needlessly normalize the user's formatting.
+      if recurrence != original {
+        let () = self.set_recurrence(Some(recurrence.to_string()));
+      }
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Retrieve the points in time at which the [`Task`]'s reminders
+  /// fire, in no particular order.
+  ///
+  /// Reminders relative to the task's start or due date refer to the
+  /// beginning of the respective day. Ones relative to a date that is
+  /// not set are ignored.
+  pub fn reminders(&self) -> Vec<DateTime<Utc>> {
+    let start = self.start();
+    let due = self.due();
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let borrow = self.0.try_borrow().unwrap();
+
+    borrow
+      .alarms
+      .iter()
+      .filter_map(|alarm| match alarm.trigger {
+        SerTrigger::Absolute(time) => Some(time),
+        SerTrigger::Relative { offset, related } => {
+          let date = match related {
+            SerRelated::Start => start.or(due),
+            SerRelated::End => due.or(start),
+          }?;
+          let time = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()?;
+          time
+            .with_timezone(&Utc)
+            .checked_add_signed(Duration::seconds(offset))
+        },
+      })
+      .collect()
+  }
+
+  /// Set or clear this [`Task`]'s reminder.
+  ///
+  /// Any existing reminders, including ones set by other clients, are
+  /// replaced.
+  pub fn set_reminder(&mut self, reminder: Option<DateTime<Utc>>) {
+    let alarms = reminder
+      .map(|time| SerAlarm {
+        trigger: SerTrigger::Absolute(time),
+        properties: Vec::new(),
+      })
+      .into_iter()
+      .collect();
+
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().alarms = alarms
+  }
+
+  /// Retrieve the time at which the [`Task`] got completed, if it did.
+  #[inline]
+  pub fn completed(&self) -> Option<DateTime<Utc>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().completed
+  }
+
+  /// Set or clear the time at which this [`Task`] got completed.
+  #[inline]
+  fn set_completed(&mut self, completed: Option<DateTime<Utc>>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.completed = completed;
+    borrow.completion_recorded = true;
+  }
+
+  /// Retrieve the time at which the [`Task`] got created, if known.
+  #[inline]
+  pub fn created(&self) -> Option<DateTime<Utc>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().created
+  }
+
+  /// Retrieve the time at which the [`Task`] got last modified, if
+  /// known.
+  #[inline]
+  pub fn modified(&self) -> Option<DateTime<Utc>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().modified
+  }
+
+  /// Mark the [`Task`] as created at the given time.
+  fn set_created(&mut self, time: DateTime<Utc>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.created = Some(time);
+    borrow.modified = Some(time);
+    borrow.stamp = time;
+  }
+
+  /// Mark the [`Task`] as modified at the given time.
+  fn set_modified(&mut self, time: DateTime<Utc>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.modified = Some(time);
+    borrow.stamp = time;
+  }
+
+  /// Retrieve the name of the collection the [`Task`] belongs to, if it
+  /// is not the default one.
+  #[inline]
+  pub fn collection(&self) -> Option<String> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().collection.clone()
+  }
+
+  /// Move the [`Task`] into the collection with the given name, or into
+  /// the default one.
+  ///
+  /// In its new collection the task is always stored in a file of its
+  /// own, named after it.
+  pub fn set_collection(&mut self, collection: Option<String>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    if borrow.collection != collection {
+      borrow.collection = collection;
+      borrow.file_name = None;
+    }
+  }
+
+  /// Bring the task's completion state in line with whether or not it
+  /// has the provided completion tag set.
+  ///
+  /// If `before` is provided, it represents the state of the task prior
+  /// to a change and the completion time is only adjusted if the tag
+  /// changed. Otherwise the two are reconciled, with a completion state
+  /// recorded by another client taking precedence over the tag. Only
+  /// for data predating the recording of completion is the tag
+  /// authoritative.
+  fn sync_completion(&mut self, completion_tag: &Tag, before: Option<&Task>) {
+    let tagged = self.has_tag(completion_tag);
+    match before {
+      Some(before) => {
+        if tagged != before.has_tag(completion_tag) {
+          let completed = if tagged { Some(now()) } else { None };
+          let () = self.set_completed(completed);
+        }
+      },
+      None => {
+        if self.completed().is_some() {
+          let _set = self.set_tag(completion_tag.clone());
+        } else if tagged {
+          // SANITY: The type's API surface prevents any borrows from
+          //         escaping a function call and we don't call methods
+          //         on `self` while a borrow is active.
+          let recorded = self.0.try_borrow().unwrap().completion_recorded;
+          if recorded {
+            // The task got reopened by another client that does not
+            // know about the tag.
+            let _unset = self.unset_tag(completion_tag);
+          } else {
+            let () = self.set_completed(Some(now()));
+          }
+        }
+      },
+    }
+  }
+
+  /// Retrieve the [`Task`]'s parent, if it is a sub-task.
+  #[inline]
+  pub fn parent(&self) -> Option<Rc<Task>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().parent.clone()
+  }
+
+  /// Replace the [`Task`]'s parent, returning the previous one.
+  ///
+  /// Changing the parent changes the task's place in the hierarchy.
+  /// Hence, it is reserved for operations that move the task
+  /// accordingly.
+  fn replace_parent(&self, parent: Option<Rc<Task>>) -> Option<Rc<Task>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    replace(&mut borrow.parent, parent)
+  }
+
+  /// Check whether the [`Task`] has the same parent as `other`.
+  pub fn is_sibling_of(&self, other: &Task) -> bool {
+    match (self.parent(), other.parent()) {
+      (Some(parent), Some(other)) => Rc::ptr_eq(&parent, &other),
+      (None, None) => true,
+      _ => false,
+    }
+  }
+
+  /// Check whether the [`Task`] is a (transitive) sub-task of
+  /// `ancestor`.
+  pub fn is_descendant_of(&self, ancestor: &Task) -> bool {
+    let mut parent = self.parent();
+    while let Some(task) = parent {
+      if ptr::eq(task.as_ref(), ancestor) {
+        return true
+      }
+      parent = task.parent();
+    }
+    false
+  }
+
+  /// Retrieve the [`Task`]'s depth in the task hierarchy, with `0`
+  /// being the depth of top-level tasks.
+  pub fn depth(&self) -> usize {
+    let mut depth = 0;
+    let mut parent = self.parent();
+    while let Some(task) = parent {
+      depth += 1;
+      parent = task.parent();
+    }
+    depth
+  }
+
+  /// Retrieve the tasks this [`Task`] depends on.
+  #[inline]
+  pub fn depends_on(&self) -> Vec<Rc<Task>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().depends_on.clone()
+  }
+
+  /// Set the tasks this [`Task`] depends on.
+  #[inline]
+  pub fn set_depends_on(&mut self, depends_on: Vec<Rc<Task>>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow_mut().unwrap().depends_on = depends_on
+  }
+
+  /// Replace the tasks this [`Task`] depends on, bypassing the
+  /// [`Tasks::update`] path, and return the previous ones.
+  fn replace_depends_on(&self, depends_on: Vec<Rc<Task>>) -> Vec<Rc<Task>> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    replace(&mut borrow.depends_on, depends_on)
+  }
+
+  /// Set the IDs of the parent and the dependencies of the [`Task`]
+  /// that could not be resolved to tasks.
+  fn set_unresolved(&self, parent: Option<Id>, depends_on: Vec<Id>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    borrow.unresolved_parent = parent;
+    borrow.unresolved_depends_on = depends_on;
+  }
+
+  /// Check whether the [`Task`] (transitively) depends on `other`.
+  pub fn depends_on_task(&self, other: &Task) -> bool {
+    let mut pending = self.depends_on();
+    let mut visited = HashSet::new();
+    while let Some(task) = pending.pop() {
+      if ptr::eq(task.as_ref(), other) {
+        return true
+      }
+      if visited.insert(Rc::as_ptr(&task)) {
+        let () = pending.extend(task.depends_on());
+      }
+    }
+    false
+  }
+
+  /// Check whether the [`Task`] is blocked, because at least one of the
+  /// tasks it depends on has not been completed yet.
+  pub fn is_blocked(&self) -> bool {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self
+      .0
+      .try_borrow()
+      .unwrap()
+      .depends_on
+      .iter()
+      .any(|task| task.completed().is_none())
+  }
+
+  /// Check whether the [`Task`]'s sub-tasks are hidden from view.
+  #[inline]
+  pub fn is_collapsed(&self) -> bool {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    self.0.try_borrow().unwrap().collapsed
+  }
+
+  /// Check whether the [`Task`] is hidden, because one of its
+  /// ancestors is collapsed.
+  pub fn is_hidden(&self) -> bool {
+    let mut parent = self.parent();
+    while let Some(task) = parent {
+      if task.is_collapsed() {
+        return true
+      }
+      parent = task.parent();
+    }
+    false
+  }
+
   /// Update this task with the contents of `other`.
   fn update_from(&self, other: Task) {
     // SANITY: The type's API surface prevents any borrows from escaping
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
+    // Whether the task is collapsed is not part of its data and should
+    // not be affected by updates, including undoing them.
+    let collapsed = borrow.collapsed;
     *borrow.deref_mut() = other.0.into_inner();
+    borrow.collapsed = collapsed;
   }
 
   /// Retrieve the `Templates` object associated with this task.
@@ -209,15 +823,58 @@ fn task_to_serde(task: &Task, position: Option<&Position>) -> SerTask {
   let TaskInner {
     ref id,
     ref summary,
+    ref notes,
     ref tags,
+    ref start,
+    ref due,
+    ref priority,
+    ref recurrence,
+    ref alarms,
+    ref completed,
+    ref created,
+    ref modified,
+    ref stamp,
+    ref unknown,
+    ref file_name,
+    ref collection,
+    ref parent,
+    ref depends_on,
+    ref unresolved_parent,
+    ref unresolved_depends_on,
     ..
   } = borrow.deref();
 
   let task = SerTask {
     id: *id,
     summary: summary.clone(),
+    notes: notes.clone(),
     tags: tags.iter().map(Tag::to_serde).collect(),
+    categories: tags.iter().map(|tag| tag.name().to_string()).collect(),
+    start: *start,
+    due: *due,
+    priority: priority.as_ref().map(Priority::to_serde),
+    recurrence: recurrence.clone(),
+    alarms: alarms.clone(),
+    parent: parent
+      .as_ref()
+      .map(|parent| parent.id())
+      .or(*unresolved_parent),
+    depends_on: depends_on
+      .iter()
+      .map(|task| task.id())
+      .chain(unresolved_depends_on.iter().copied())
+      .collect(),
+    completed: *completed,
+    // The completion state got reconciled with the tag on load and is
+    // recorded from here on.
+    completion_recorded: true,
+    created: *created,
+    modified: *modified,
+    stamp: *stamp,
     position: position.map(Position::to_serde),
+    unknown: unknown.clone(),
+    file_name: file_name.clone(),
+    collection: collection.clone(),
   };
 
   task
@@ -288,13 +945,55 @@ fn find_position(
   }
 }
 
+/// Find the entry of the last (transitive) sub-task of the task
+/// represented by `entry`, or `entry` itself if it has none.
+fn last_descendant<'db>(entry: DbEntry<'db, Task, Position>) -> DbEntry<'db, Task, Position> {
+  let mut last = entry.clone();
+  while let Some(next) = last.next() {
+    if !next.is_descendant_of(&entry) {
+      break
+    }
+    last = next;
+  }
+  last
+}
+
+/// Find the siblings directly preceding and following the given task,
+/// if any.
+fn siblings(tasks: &Db<Task, Position>, task: &Rc<Task>) -> (Option<Rc<Task>>, Option<Rc<Task>>) {
+  // SANITY: The task is known to be present.
+  let entry = tasks.find(task).unwrap();
+  let depth = task.depth();
+  let mut prev = entry.prev();
+  // Walk backwards, skipping over the sub-tasks of the preceding
+  // sibling, until we find the sibling or reach the parent.
+  let prev = loop {
+    match prev {
+      Some(entry) if entry.is_sibling_of(task) => break Some(entry.deref().clone()),
+      Some(entry) if entry.depth() > depth => prev = entry.prev(),
+      _ => break None,
+    }
+  };
+  let next = last_descendant(entry)
+    .next()
+    .filter(|next| next.is_sibling_of(task))
+    .map(|next| next.deref().clone());
+  (prev, next)
+}
+
 /// Add a task to a vector of tasks.
+///
+/// A task added after another one is placed after all of the other
+/// task's sub-tasks, to keep each task's sub-tasks directly behind it.
 fn add_task(tasks: &mut Db<Task, Position>, task: Rc<Task>, target: Option<Target>) -> Rc<Task> {
   let (before, after) = if let Some(target) = target {
     let entry = tasks.find(target.task()).unwrap();
     match target {
       Target::Before(..) => (entry.prev(), Some(entry)),
-      Target::After(..) => (Some(entry.clone()), entry.next()),
+      Target::After(..) => {
+        let last = last_descendant(entry);
+        (Some(last.clone()), last.next())
+      },
     }
   } else {
     (tasks.last(), None)
@@ -334,6 +1033,69 @@ fn remove_task(tasks: &mut Db<Task, Position>, task: &Rc<Task>) -> (Rc<Task>, Po
   (task, aux, idx)
 }
 
+/// Remove a task along with all its sub-tasks from a vector of tasks.
+///
+/// The removed tasks are returned in order, along with their former
+/// index and position.
+fn remove_subtree(tasks: &mut Db<Task, Position>, task: &Rc<Task>) -> Removed {
+  let entry = tasks.find(task).unwrap();
+  let idx = entry.index();
+  let count = last_descendant(entry).index() - idx + 1;
+
+  (idx..idx + count)
+    .map(|orig_idx| {
+      let (task, aux) = tasks.remove(idx);
+      (task, orig_idx, aux)
+    })
+    .collect()
+}
+
+/// Reinsert tasks previously removed via [`remove_subtree`] at their
+/// original positions.
+fn reinsert_subtree(tasks: &mut Db<Task, Position>, removed: &[(Rc<Task>, usize, Position)]) {
+  for (task, _idx, aux) in removed {
+    // Tasks may have been added or removed by other clients in the
+    // meantime, rendering the original index invalid. Positions are
+    // not affected by that.
+    let idx = (0..tasks.len())
+      // SANITY: The index is guaranteed to be valid.
+      .find(|idx| tasks.get(*idx).unwrap().aux().get() > aux.get())
+      .unwrap_or_else(|| tasks.len());
+    // SANITY: The tasks had been removed earlier, so they are not
+    //         currently present.
+    let _entry = tasks
+      .try_insert_with_aux(idx, task.clone(), *aux)
+      .unwrap();
+  }
+}
+
+/// Remove all dependencies on the given (removed) tasks from the
+/// tasks remaining in `tasks`.
+///
+/// The tasks that got adjusted are returned, along with their former
+/// dependencies.
+fn remove_dependencies(tasks: &Db<Task, Position>, removed: &Removed) -> Dependents {
+  let is_removed = |task: &Rc<Task>| {
+    removed
+      .iter()
+      .any(|(removed, _idx, _aux)| Rc::ptr_eq(removed, task))
+  };
+
+  tasks
+    .iter()
+    .filter(|task| task.depends_on().iter().any(is_removed))
+    .map(|task| {
+      let depends_on = task
+        .depends_on()
+        .into_iter()
+        .filter(|dependency| !is_removed(dependency))
+        .collect();
+      let before = task.replace_depends_on(depends_on);
+      (task.clone(), before)
+    })
+    .collect()
+}
+
 /// Update a task in a vector of tasks.
 fn update_task(task: &Rc<Task>, other: Task) -> Task {
   // Make a deep copy of the task.
@@ -343,6 +1105,374 @@ fn update_task(task: &Rc<Task>, other: Task) -> Task {
 }
 
 
+/// Create a task from a serializable one as provided by a different
+/// client, e.g., because it was loaded from a file modified externally.
+///
+/// Tags are looked up by the names stored as categories, with templates
+/// being created for names not known yet. Lacking categories, the tags
+/// stored by ID are used, dropping unknown ones. Dependencies are
+/// resolved against `tasks`, skipping those that would introduce a
+/// cycle involving `existing`. The task's parent is taken over from
+/// `existing`, if provided, as its position in the hierarchy is not
+/// subject to change. Otherwise it is resolved against `tasks` as well.
+/// Relations to tasks that cannot be found are kept by ID.
+fn task_from_foreign_serde(
+  mut task: SerTask,
+  templates: &Rc<Templates>,
+  tasks: &Db<Task, Position>,
+  existing: Option<&Rc<Task>>,
+) -> Task {
+  let parent = task.parent;
+  let depends_on = take(&mut task.depends_on);
+  let categories = take(&mut task.categories);
+  let () = task
+    .tags
+    .retain(|tag| templates.instantiate(tag.id).is_some());
+
+  // SANITY: Creating a task can only fail because of invalid tags, of
+  //         which there are none at this point.
+  let mut new = Task::with_serde(task, templates.clone()).unwrap();
+  if !categories.is_empty() {
+    let tags = categories.iter().fold(Vec::new(), |mut tags, name| {
+      let tag = templates.instantiate_or_create(name);
+      if !tags.contains(&tag) {
+        let () = tags.push(tag);
+      }
+      tags
+    });
+    let () = new.set_tags(tags.into_iter());
+  }
+
+  let find = |id: Id| tasks.iter().find(|task| task.id() == id);
+  let unresolved = depends_on
+    .iter()
+    .copied()
+    .filter(|id| find(*id).is_none())
+    .collect();
+  let depends_on = depends_on
+    .into_iter()
+    .filter_map(find)
+    .filter(|dependency| match existing {
+      Some(existing) => {
+        !Rc::ptr_eq(existing, dependency) && !dependency.depends_on_task(existing)
+      },
+      None => true,
+    })
+    .cloned()
+    .collect();
+  let _depends_on = new.replace_depends_on(depends_on);
+  let (parent, unresolved_parent) = match existing {
+    Some(existing) => (existing.parent(), existing.0.borrow().unresolved_parent),
+    None => {
+      let resolved = parent.and_then(|parent| find(parent).cloned());
+      (resolved, parent.filter(|parent| find(*parent).is_none()))
+    },
+  };
+  let _parent = new.replace_parent(parent);
+  let () = new.set_unresolved(unresolved_parent, unresolved);
+  new
+}
+
+
+/// Create a task from a serialized one, resolving its relations to
+/// other tasks by ID via `find`.
+///
+/// Relations to tasks that cannot be found are kept by ID.
+fn task_with_relations<F>(mut task: SerTask, templates: &Rc<Templates>, find: F) -> Result<Task>
+where
+  F: Fn(Id) -> Option<Rc<Task>>,
+{
+  let parent = task.parent;
+  let depends_on = take(&mut task.depends_on);
+  let new = Task::with_serde(task, templates.clone())?;
+
+  let _parent = new.replace_parent(parent.and_then(&find));
+  let unresolved_parent = parent.filter(|parent| find(*parent).is_none());
+  let (depends_on, unresolved) = depends_on.into_iter().fold(
+    (Vec::new(), Vec::new()),
+    |(mut depends_on, mut unresolved), id| {
+      match find(id) {
+        Some(dependency) => depends_on.push(dependency),
+        None => unresolved.push(id),
+      }
+      (depends_on, unresolved)
+    },
+  );
+  let _depends_on = new.replace_depends_on(depends_on);
+  let () = new.set_unresolved(unresolved_parent, unresolved);
+  Ok(new)
+}
+
+/// Create a task from one recorded in the journal, resolving its
+/// relations to other tasks by ID.
+fn task_from_journal(
+  task: SerTask,
+  templates: &Rc<Templates>,
+  tasks: &Db<Task, Position>,
+) -> Result<Task> {
+  task_with_relations(task, templates, |id| {
+    tasks.iter().find(|task| task.id() == id).cloned()
+  })
+}
+
+
+/// Calculate a fingerprint of the given tasks.
+fn fingerprint(tasks: &SerTasks) -> Result<u64> {
+  let mut hasher = DefaultHasher::new();
+  for task in tasks.0.iter() {
+    let serialized = <iCal as Backend<SerTask>>::serialize(task)?;
+    let () = hasher.write(&serialized);
+  }
+  Ok(hasher.finish())
+}
+
+
+/// Convert an operation into a step of the serializable undo history.
+fn op_to_serde(op: &TaskOp) -> SerStep {
+  match op {
+    TaskOp::Add { task, after } => SerStep::Add {
+      task: task_to_serde(task, None),
+      after: after.as_ref().map(|after| after.id()),
+    },
+    TaskOp::Remove {
+      task,
+      removed,
+      dependents,
+    } => SerStep::Remove {
+      id: task.id(),
+      removed: removed
+        .iter()
+        .map(|(task, index, position)| SerRemoved {
+          task: task_to_serde(task, None),
+          index: *index,
+          position: position.get(),
+        })
+        .collect(),
+      dependents: dependents
+        .iter()
+        .map(|(task, depends_on)| SerDependent {
+          id: task.id(),
+          depends_on: depends_on.iter().map(|task| task.id()).collect(),
+        })
+        .collect(),
+    },
+    TaskOp::Update { updated, before } => SerStep::Update {
+      id: updated.0.id(),
+      updated: task_to_serde(&updated.1, None),
+      // SANITY: Operations contained in the history have been executed,
+      //         which sets `before`.
+      before: task_to_serde(before.as_ref().unwrap(), None),
+    },
+    TaskOp::Move { task, to, before } => SerStep::Move {
+      id: task.id(),
+      to: match to {
+        Target::Before(task) => SerTarget::Before(task.id()),
+        Target::After(task) => SerTarget::After(task.id()),
+      },
+      before: before.as_ref().map(|(moved, parent)| {
+        let moved = moved
+          .iter()
+          .map(|(task, index, position)| SerMoved {
+            id: task.id(),
+            index: *index,
+            position: position.get(),
+          })
+          .collect();
+        (moved, parent.as_ref().map(|parent| parent.id()))
+      }),
+    },
+  }
+}
+
+/// Create the operations recorded in a serialized undo history.
+///
+/// Tasks are resolved by ID. Tasks not currently present, e.g., because
+/// their removal is yet to be undone, are created from the state
+/// recorded in the history.
+fn ops_from_serde(
+  steps: Vec<SerStep>,
+  templates: &Rc<Templates>,
+  tasks: &Db<Task, Position>,
+) -> Result<Vec<TaskOp>> {
+  let mut pool = tasks
+    .iter()
+    .map(|task| (task.id(), task.clone()))
+    .collect::<HashMap<_, _>>();
+
+  let mut created = Vec::new();
+  for step in steps.iter() {
+    let snapshots = match step {
+      SerStep::Add { task, .. } => vec![task],
+      SerStep::Remove { removed, .. } => removed.iter().map(|removed| &removed.task).collect(),
+      SerStep::Update { .. } | SerStep::Move { .. } => Vec::new(),
+    };
+
+    for task in snapshots {
+      if let HashMapEntry::Vacant(entry) = pool.entry(task.id) {
+        let mut task = task.clone();
+        let parent = task.parent;
+        let depends_on = take(&mut task.depends_on);
+        let task = Rc::new(Task::with_serde(task, templates.clone())?);
+        let _task = entry.insert(task.clone());
+        let () = created.push((task, parent, depends_on));
+      }
+    }
+  }
+
+  // Relations can only be resolved once all tasks are known.
+  for (task, parent, depends_on) in created {
+    let _parent = task.replace_parent(parent.and_then(|id| pool.get(&id).cloned()));
+    let unresolved_parent = parent.filter(|id| !pool.contains_key(id));
+    let unresolved = depends_on
+      .iter()
+      .copied()
+      .filter(|id| !pool.contains_key(id))
+      .collect();
+    let depends_on = depends_on
+      .into_iter()
+      .filter_map(|id| pool.get(&id).cloned())
+      .collect();
+    let _depends_on = task.replace_depends_on(depends_on);
+    let () = task.set_unresolved(unresolved_parent, unresolved);
+  }
+
+  let find = |id: Id| {
+    pool
+      .get(&id)
+      .cloned()
+      .ok_or_else(|| anyhow!("task {id} referenced by undo history not found"))
+  };
+  let with_relations = |task| task_with_relations(task, templates, |id| pool.get(&id).cloned());
+
+  steps
+    .into_iter()
+    .map(|step| {
+      let op = match step {
+        SerStep::Add { task, after } => TaskOp::Add {
+          task: find(task.id)?,
+          after: after.map(find).transpose()?,
+        },
+        SerStep::Remove {
+          id,
+          removed,
+          dependents,
+        } => TaskOp::Remove {
+          task: find(id)?,
+          removed: removed
+            .into_iter()
+            .map(|removed| {
+              let task = find(removed.task.id)?;
+              Ok((task, removed.index, Position::new(removed.position)))
+            })
+            .collect::<Result<_>>()?,
+          dependents: dependents
+            .into_iter()
+            .map(|dependent| {
+              let depends_on = dependent
+                .depends_on
+                .into_iter()
+                .map(find)
+                .collect::<Result<_>>()?;
+              Ok((find(dependent.id)?, depends_on))
+            })
+            .collect::<Result<_>>()?,
+        },
+        SerStep::Update {
+          id,
+          updated,
+          before,
+        } => TaskOp::Update {
+          updated: (find(id)?, with_relations(updated)?),
+          before: Some(with_relations(before)?),
+        },
+        SerStep::Move { id, to, before } => TaskOp::Move {
+          task: find(id)?,
+          to: match to {
+            SerTarget::Before(id) => Target::Before(find(id)?),
+            SerTarget::After(id) => Target::After(find(id)?),
+          },
+          before: before
+            .map(|(moved, parent)| -> Result<_> {
+              let moved = moved
+                .into_iter()
+                .map(|moved| Ok((find(moved.id)?, moved.index, Position::new(moved.position))))
+                .collect::<Result<_>>()?;
+              Ok((moved, parent.map(find).transpose()?))
+            })
+            .transpose()?,
+        },
+      };
+      Ok(op)
+    })
+    .collect()
+}
+
+
+/// Order tasks hierarchically, such that every task is directly
+/// followed by its (transitive) sub-tasks, while otherwise retaining
+/// their relative order.
+///
+/// Each task is accompanied by its ID and the ID of its parent. Parent
+/// IDs that cannot be resolved or that would result in a cycle are
+/// cleared.
+fn order_hierarchically<T>(tasks: Vec<(T, Id, Option<Id>)>) -> Vec<(T, Id, Option<Id>)> {
+  fn visit(root: usize, children: &[Vec<usize>], visited: &mut [bool], order: &mut Vec<usize>) {
+    let mut stack = vec![root];
+    while let Some(idx) = stack.pop() {
+      if !visited[idx] {
+        visited[idx] = true;
+        order.push(idx);
+        stack.extend(children[idx].iter().rev());
+      }
+    }
+  }
+
+  let len = tasks.len();
+  let indices = tasks
+    .iter()
+    .enumerate()
+    .map(|(idx, (_task, id, _parent))| (*id, idx))
+    .collect::<HashMap<_, _>>();
+
+  let mut is_root = vec![false; len];
+  let mut children = vec![Vec::new(); len];
+  for (idx, (_task, _id, parent)) in tasks.iter().enumerate() {
+    match parent.and_then(|parent| indices.get(&parent)) {
+      Some(parent) if *parent != idx => children[*parent].push(idx),
+      _ => is_root[idx] = true,
+    }
+  }
+
+  let mut order = Vec::with_capacity(len);
+  let mut visited = vec![false; len];
+  for (idx, is_root) in is_root.iter().enumerate() {
+    if *is_root {
+      let () = visit(idx, &children, &mut visited, &mut order);
+    }
+  }
+
+  // Tasks we have not reached yet are part of a cycle. We break it up
+  // by turning the first such task into a top-level one.
+  for idx in 0..len {
+    if !visited[idx] {
+      is_root[idx] = true;
+      let () = visit(idx, &children, &mut visited, &mut order);
+    }
+  }
+
+  let mut tasks = tasks.into_iter().map(Some).collect::<Vec<_>>();
+  order
+    .into_iter()
+    .map(|idx| {
+      // SANITY: Every index is contained in `order` exactly once.
+      let (task, id, parent) = tasks[idx].take().unwrap();
+      let parent = if is_root[idx] { None } else { parent };
+      (task, id, parent)
+    })
+    .collect()
+}
+
+
 /// An enum encoding the target location of a task: before or after a
 /// task with a given ID.
 #[derive(Clone, Debug)]
@@ -363,6 +1493,9 @@ impl Target {
 
 
 /// An operation to be performed on a task in a `Tasks` object.
+// Operations are short lived or kept in a bounded buffer, so their size
+// is of no real concern.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 enum TaskOp {
   /// An operation adding a task.
@@ -370,21 +1503,24 @@ enum TaskOp {
     task: Rc<Task>,
     after: Option<Rc<Task>>,
   },
-  /// An operation removing a task.
+  /// An operation removing a task, along with its sub-tasks. Other
+  /// tasks no longer depend on the removed ones.
   Remove {
     task: Rc<Task>,
-    position: Option<(usize, Position)>,
+    removed: Removed,
+    dependents: Dependents,
   },
   /// An operation updating a task.
   Update {
     updated: (Rc<Task>, Task),
     before: Option<Task>,
   },
-  /// An operation changing a task's position.
+  /// An operation changing a task's position, along with that of its
+  /// sub-tasks. The task adopts the parent of the target task.
   Move {
     task: Rc<Task>,
     to: Target,
-    position: Option<(usize, Position)>,
+    before: Option<(Removed, Option<Rc<Task>>)>,
   },
 }
 
@@ -396,7 +1532,8 @@ impl TaskOp {
   fn remove(task: Rc<Task>) -> Self {
     Self::Remove {
       task,
-      position: None,
+      removed: Vec::new(),
+      dependents: Vec::new(),
     }
   }
 
@@ -411,9 +1548,161 @@ impl TaskOp {
     Self::Move {
       task,
       to,
-      position: None,
+      before: None,
+    }
+  }
+
+  /// Create the journal entry describing the effect of executing the
+  /// operation.
+  fn exec_journal(&self) -> JournalEntry {
+    match self {
+      Self::Add { task, after } => JournalEntry::Add {
+        task: task_to_serde(task, None),
+        after: after.as_ref().map(|after| after.id()),
+      },
+      Self::Remove { task, .. } => JournalEntry::Remove { id: task.id() },
+      Self::Update { updated, .. } => JournalEntry::Update {
+        task: task_to_serde(&updated.0, None),
+      },
+      Self::Move { task, to, .. } => match to {
+        Target::Before(other) => JournalEntry::MoveBefore {
+          id: task.id(),
+          other: other.id(),
+        },
+        Target::After(other) => JournalEntry::MoveAfter {
+          id: task.id(),
+          other: other.id(),
+        },
+      },
     }
   }
+
+  /// Create the journal entries describing the effect of undoing the
+  /// operation, given the tasks in the state after the undo.
+  ///
+  /// Tasks restored by the undo are described by their state and their
+  /// place relative to their siblings, which is what replaying the
+  /// entries later on reproduces.
+  fn undo_journal(&self, tasks: &Db<Task, Position>) -> Vec<JournalEntry> {
+    let mut entries = Vec::new();
+    match self {
+      Self::Add { task, .. } => {
+        let () = entries.push(JournalEntry::Remove { id: task.id() });
+      },
+      Self::Remove {
+        removed,
+        dependents,
+        ..
+      } => {
+        for (idx, (task, _idx, _aux)) in removed.iter().enumerate() {
+          // Sub-tasks are restored in order, so they always end up
+          // last among their siblings at the time they are added.
+          let (prev, next) = siblings(tasks, task);
+          let after = prev.clone().or_else(|| task.parent());
+          let () = entries.push(JournalEntry::Add {
+            task: task_to_serde(task, None),
+            after: after.map(|after| after.id()),
+          });
+
+          if let (0, None, Some(next)) = (idx, &prev, next) {
+            let () = entries.push(JournalEntry::MoveBefore {
+              id: task.id(),
+              other: next.id(),
+            });
+          }
+        }
+
+        let () = entries.extend(dependents.iter().map(|(task, _depends_on)| {
+          JournalEntry::Update {
+            task: task_to_serde(task, None),
+          }
+        }));
+      },
+      Self::Update { updated, .. } => {
+        let () = entries.push(JournalEntry::Update {
+          task: task_to_serde(&updated.0, None),
+        });
+      },
+      Self::Move { task, .. } => match siblings(tasks, task) {
+        (_, Some(next)) => {
+          let () = entries.push(JournalEntry::MoveBefore {
+            id: task.id(),
+            other: next.id(),
+          });
+        },
+        (Some(prev), None) => {
+          let () = entries.push(JournalEntry::MoveAfter {
+            id: task.id(),
+            other: prev.id(),
+          });
+        },
+        (None, None) => {
+          // Being its parent's only sub-task, the task is moved behind
+          // the parent and then turned into a sub-task of it.
+          if let Some(parent) = task.parent() {
+            let () = entries.push(JournalEntry::MoveAfter {
+              id: task.id(),
+              other: parent.id(),
+            });
+            let () = entries.push(JournalEntry::Update {
+              task: task_to_serde(task, None),
+            });
+          }
+        },
+      },
+    }
+    entries
+  }
+
+  /// Retrieve the IDs of all tasks the operation refers to, including
+  /// those referenced by the relations of the tasks it stores.
+  fn task_ids(&self) -> HashSet<Id> {
+    fn insert(ids: &mut HashSet<Id>, task: &Task) {
+      let _inserted = ids.insert(task.id());
+      if let Some(parent) = task.parent() {
+        let _inserted = ids.insert(parent.id());
+      }
+      let () = ids.extend(task.depends_on().iter().map(|task| task.id()));
+    }
+
+    let mut ids = HashSet::new();
+    match self {
+      Self::Add { task, after } => {
+        let () = insert(&mut ids, task);
+        let () = ids.extend(after.iter().map(|after| after.id()));
+      },
+      Self::Remove {
+        task,
+        removed,
+        dependents,
+      } => {
+        let () = insert(&mut ids, task);
+        let () = removed
+          .iter()
+          .for_each(|(task, _idx, _aux)| insert(&mut ids, task));
+        let () = dependents.iter().for_each(|(task, depends_on)| {
+          let () = insert(&mut ids, task);
+          let () = ids.extend(depends_on.iter().map(|task| task.id()));
+        });
+      },
+      Self::Update { updated, before } => {
+        let () = insert(&mut ids, &updated.0);
+        let () = insert(&mut ids, &updated.1);
+        let () = before.iter().for_each(|before| insert(&mut ids, before));
+      },
+      Self::Move { task, to, before } => {
+        let () = insert(&mut ids, task);
+        let _inserted = ids.insert(to.task().id());
+        if let Some((removed, parent)) = before {
+          let () = removed
+            .iter()
+            .for_each(|(task, _idx, _aux)| insert(&mut ids, task));
+          let () = ids.extend(parent.iter().map(|parent| parent.id()));
+        }
+      },
+    }
+    ids
+  }
 }
 
 impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
@@ -426,9 +1715,13 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
         let added = add_task(tasks, task.clone(), after.clone().map(Target::After));
         Some(added)
       },
-      Self::Remove { task, position } => {
-        let (_task, aux, idx) = remove_task(tasks, task);
-        *position = Some((idx, aux));
+      Self::Remove {
+        task,
+        removed,
+        dependents,
+      } => {
+        *removed = remove_subtree(tasks, task);
+        *dependents = remove_dependencies(tasks, removed);
         None
       },
       Self::Update { updated, before } => {
@@ -437,19 +1730,24 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
         *before = Some(_task);
         Some(task.clone())
       },
-      Self::Move { task, to, position } => {
-        // SANITY: The task really should be in our `Tasks` object or we
-        //         are in trouble.
-        let idx = tasks.find(task).unwrap().index();
-        let (removed, aux) = tasks.remove(idx);
-        // We do not support the case of moving a task with itself as a
-        // target. Doing so should be prevented at a higher layer,
-        // though.
-        debug_assert!(!Rc::ptr_eq(&removed, to.task()));
-        *position = Some((idx, aux));
-
-        let task = add_task(tasks, removed, Some(to.clone()));
-        Some(task)
+      Self::Move { task, to, before } => {
+        // We do not support the case of moving a task relative to
+        // itself or one of its sub-tasks. Doing so should be prevented
+        // at a higher layer, though.
+        debug_assert!(!Rc::ptr_eq(task, to.task()));
+        debug_assert!(!to.task().is_descendant_of(task));
+
+        let removed = remove_subtree(tasks, task);
+        let parent = task.replace_parent(to.task().parent());
+
+        let mut target = to.clone();
+        for (removed, _idx, _aux) in &removed {
+          let added = add_task(tasks, removed.clone(), Some(target));
+          target = Target::After(added);
+        }
+
+        *before = Some((removed, parent));
+        Some(task.clone())
       },
     }
   }
@@ -460,12 +1758,15 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
         let (_task, _aux, _idx) = remove_task(tasks, task);
         None
       },
-      Self::Remove { task, position } => {
-        // SANITY: The position will always be set at this point.
-        let (idx, aux) = position.unwrap();
-        // SANITY: The task had been removed earlier, so it is not
-        //         currently present.
-        tasks.try_insert_with_aux(idx, task.clone(), aux).unwrap();
+      Self::Remove {
+        task,
+        removed,
+        dependents,
+      } => {
+        let () = dependents.iter().for_each(|(task, depends_on)| {
+          let _depends_on = task.replace_depends_on(depends_on.clone());
+        });
+        let () = reinsert_subtree(tasks, removed);
         Some(task.clone())
       },
       Self::Update { updated, before } => {
@@ -476,16 +1777,13 @@ impl Op<Db<Task, Position>, Option<Rc<Task>>> for TaskOp {
         let entry = tasks.find(task).unwrap();
         Some(entry.deref().clone())
       },
-      Self::Move { task, position, .. } => {
-        // SANITY: `position` is guaranteed to be set on this path.
-        let (position, aux) = position.unwrap();
-        let idx = tasks.find(task).unwrap().index();
-        let (removed, _aux) = tasks.remove(idx);
-        // SANITY: We just removed the task, so it can't be present.
-        let _entry = tasks
-          .try_insert_with_aux(position, removed.clone(), aux)
-          .unwrap();
-        Some(removed)
+      Self::Move { task, before, .. } => {
+        // SANITY: `before` is guaranteed to be set on this path.
+        let (removed, parent) = before.take().unwrap();
+        let _removed = remove_subtree(tasks, task);
+        let _parent = task.replace_parent(parent);
+        let () = reinsert_subtree(tasks, &removed);
+        Some(task.clone())
       },
     }
   }
@@ -499,10 +1797,15 @@ pub type TaskIter<'tasks> = DbIter<'tasks, Task, Position>;
 #[derive(Debug)]
 struct TasksInner {
   templates: Rc<Templates>,
+  /// The tag marking a task as completed, if any.
+  completion_tag: Option<Tag>,
   /// The managed tasks.
   tasks: Db<Task, Position>,
   /// A record of operations in the order they were performed.
   operations: Ops<TaskOp, Db<Task, Position>, Option<Rc<Task>>>,
+  /// Journal entries for operations performed since the journal was
+  /// last taken.
+  journal: Vec<JournalEntry>,
 }
 
 
@@ -512,7 +1815,14 @@ pub struct Tasks(RefCell<TasksInner>);
 
 impl Tasks {
   /// Create a new `Tasks` object from a serializable one.
+  ///
+  /// Task IDs are required to be unique.
   pub fn with_serde(mut tasks: SerTasks, templates: Rc<Templates>) -> Result<Self> {
+    let mut ids = HashSet::with_capacity(tasks.0.len());
+    if let Some(task) = tasks.0.iter().find(|task| !ids.insert(task.id)) {
+      bail!("encountered duplicate task ID {}", task.id)
+    }
+
     // If a task has no position we will just silently sort it last.
     tasks.0.sort_by(|first, second| {
       let first = first.position.unwrap_or(f64::MAX);
@@ -520,29 +1830,85 @@ impl Tasks {
       first.total_cmp(&second)
     });
 
-    let len = tasks.0.len();
-    let tasks =
-      tasks
-        .0
-        .into_iter()
-        .try_fold(Vec::with_capacity(len), |mut vec, task| -> Result<_> {
-          let position = task.position;
-          let task = Task::with_serde(task, templates.clone())?;
-          let position = position.map(Position::new).unwrap_or_else(|| {
-            let prev_pos = vec.last().map(|(_task, position)| position);
-            // SANITY: Under real world scenarios we shall always find
-            //         another free position.
-            Position::between(prev_pos.cloned(), None).unwrap()
+    let tasks = tasks
+      .0
+      .into_iter()
+      .map(|task| {
+        let id = task.id;
+        let parent = task.parent;
+        (task, id, parent)
+      })
+      .collect();
+    // Sub-tasks are always kept right behind their parent. Tasks
+    // created by other clients may not adhere to that, though.
+    let tasks = order_hierarchically(tasks);
+
+    let mut relations = Vec::with_capacity(tasks.len());
+    let mut vec = Vec::<(Task, Position)>::with_capacity(tasks.len());
+    for (mut task, _id, parent) in tasks {
+      let position = task.position;
+      let unresolved_parent = task.parent;
+      let depends_on = take(&mut task.depends_on);
+      let task = Task::with_serde(task, templates.clone())?;
+      let prev_pos = vec.last().map(|(_task, position)| *position);
+      let position = position
+        .map(Position::new)
+        // Hierarchical ordering may have rendered positions unsorted,
+        // in which case we need to assign new ones.
+        .filter(|position| {
+          prev_pos
+            .map(|prev_pos| prev_pos.get() <= position.get())
+            .unwrap_or(true)
+        })
+        .unwrap_or_else(|| {
+          // SANITY: Under real world scenarios we shall always find
+          //         another free position.
+          Position::between(prev_pos, None).unwrap()
+        });
+      let () = vec.push((task, position));
+      let () = relations.push((parent, unresolved_parent, depends_on));
+    }
+    let tasks = Db::from_iter_with_aux(vec);
+
+    let by_id = tasks
+      .iter()
+      .map(|task| (task.id(), task.clone()))
+      .collect::<HashMap<_, _>>();
+    let () = tasks
+      .iter()
+      .zip(relations)
+      .for_each(|(task, (parent, unresolved_parent, depends_on))| {
+        let parent = parent.and_then(|parent| by_id.get(&parent).cloned());
+        let _parent = task.replace_parent(parent);
+
+        // Relations to tasks that failed to load (or never existed) are
+        // kept by ID, so that they are not lost on the next save.
+        // Dependencies that would result in a cycle are dropped.
+        let unresolved_parent = unresolved_parent.filter(|id| !by_id.contains_key(id));
+        let unresolved = depends_on
+          .iter()
+          .copied()
+          .filter(|id| !by_id.contains_key(id))
+          .collect();
+        let () = task.set_unresolved(unresolved_parent, unresolved);
+        let depends_on = depends_on
+          .into_iter()
+          .filter_map(|id| by_id.get(&id))
+          .fold(Vec::new(), |mut depends_on, dependency| {
+            if !Rc::ptr_eq(task, dependency) && !dependency.depends_on_task(task) {
+              let () = depends_on.push(dependency.clone());
+            }
+            depends_on
           });
-          let () = vec.push((task, position));
-          Result::Ok(vec)
-        })?;
-    let tasks = Db::from_iter_with_aux(tasks);
+        let _depends_on = task.replace_depends_on(depends_on);
+      });
 
     let inner = TasksInner {
       templates,
+      completion_tag: None,
       tasks,
-      operations: Ops::new(MAX_UNDO_STEP_COUNT),
+      operations: Ops::new(DEFAULT_MAX_UNDO_STEP_COUNT),
+      journal: Vec::new(),
     };
 
     Ok(Self(RefCell::new(inner)))
@@ -583,6 +1949,35 @@ impl Tasks {
     SerTasks(tasks)
   }
 
+  /// Set the tag used for marking tasks as completed.
+  ///
+  /// Tasks having this tag set are persisted as completed and tasks
+  /// found to be completed (e.g., because they were marked as such by
+  /// a different client) will get the tag set. Setting the tag
+  /// reconciles the completion state of all tasks with it, which is
+  /// not an undoable operation.
+  pub fn set_completion_tag(&self, completion_tag: Option<Tag>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let TasksInner {
+      completion_tag: ref mut tag,
+      ref tasks,
+      ..
+    } = borrow.deref_mut();
+
+    if let Some(completion_tag) = &completion_tag {
+      let () = tasks.iter().for_each(|task| {
+        // Make a deep copy of the task.
+        let mut updated = task.deref().clone();
+        let () = updated.sync_completion(completion_tag, None);
+        let () = task.update_from(updated);
+      });
+    }
+    *tag = completion_tag;
+  }
+
   /// Invoke a user-provided function on an iterator over all tasks.
   #[inline]
   pub fn iter<F, R>(&self, mut f: F) -> R
@@ -592,37 +1987,171 @@ impl Tasks {
     // SANITY: The type's API surface prevents any borrows from escaping
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
-    f(self.0.try_borrow().unwrap().tasks.iter())
+    f(self.0.try_borrow().unwrap().tasks.iter())
+  }
+
+  /// Add a new task.
+  ///
+  /// A task added after another one becomes its sibling, i.e., it
+  /// shares the other task's parent.
+  pub fn add(&self, summary: String, tags: Vec<Tag>, after: Option<Rc<Task>>) -> Rc<Task> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let TasksInner {
+      ref mut templates,
+      ref completion_tag,
+      ref mut operations,
+      ref mut tasks,
+      ref mut journal,
+    } = borrow.deref_mut();
+
+    let mut task = Task::with_summary_and_tags(summary, tags, templates.clone());
+    let () = task.set_created(now());
+    let _parent = task.replace_parent(after.as_ref().and_then(|after| after.parent()));
+    if let Some(completion_tag) = completion_tag {
+      let () = task.sync_completion(completion_tag, None);
+    }
+
+    let task = Rc::new(task);
+    let after_id = after.as_ref().map(|after| after.id());
+    let op = TaskOp::add(task, after);
+    // SANITY: We know that an "add" operation always returns a task, so
+    //         this unwrap will never panic.
+    let task = operations.exec(op, tasks).unwrap();
+    let () = journal.push(JournalEntry::Add {
+      task: task_to_serde(&task, None),
+      after: after_id,
+    });
+
+    task
+  }
+
+  /// Remove a task, along with all its sub-tasks.
+  pub fn remove(&self, task: Rc<Task>) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let TasksInner {
+      ref mut operations,
+      ref mut tasks,
+      ref mut journal,
+      ..
+    } = borrow.deref_mut();
+
+    let id = task.id();
+    let op = TaskOp::remove(task);
+    operations.exec(op, tasks);
+    let () = journal.push(JournalEntry::Remove { id });
+  }
+
+  /// Update a task.
+  ///
+  /// If a completion tag is set and the update toggles it, the task's
+  /// completion time is adjusted accordingly.
+  pub fn update(&self, task: Rc<Task>, mut updated: Task) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let TasksInner {
+      ref completion_tag,
+      ref mut operations,
+      ref mut tasks,
+      ref mut journal,
+      ..
+    } = borrow.deref_mut();
+
+    if let Some(completion_tag) = completion_tag {
+      // A recurring task marked as completed moves on to its next
+      // occurrence instead, as part of the same operation.
+      if !task.has_tag(completion_tag)
+        && updated.has_tag(completion_tag)
+        && updated.advance_recurrence()
+      {
+        let _unset = updated.unset_tag(completion_tag);
+      }
+      let () = updated.sync_completion(completion_tag, Some(&task));
+    }
+
+    // Only bump the modification time if something actually changed.
+    // The previous time stamps are part of the task state captured by
+    // the operation and get restored on undo.
+    if task_to_serde(&task, None) != task_to_serde(&updated, None) {
+      let () = updated.set_modified(now());
+    }
+
+    let op = TaskOp::update(task.clone(), updated);
+    operations.exec(op, tasks);
+    let () = journal.push(JournalEntry::Update {
+      task: task_to_serde(&task, None),
+    });
+  }
+
+  /// Create the operation adding a task from a serializable one
+  /// provided by a different client.
+  fn add_from_serde_op(&self, task: SerTask) -> TaskOp {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let borrow = self.0.try_borrow().unwrap();
+    let TasksInner {
+      ref templates,
+      ref completion_tag,
+      ref tasks,
+      ..
+    } = borrow.deref();
+
+    let mut task = task_from_foreign_serde(task, templates, tasks, None);
+    if let Some(completion_tag) = completion_tag {
+      let () = task.sync_completion(completion_tag, None);
+    }
+
+    let after = task.parent();
+    TaskOp::add(Rc::new(task), after)
   }
 
-  /// Add a new task.
-  pub fn add(&self, summary: String, tags: Vec<Tag>, after: Option<Rc<Task>>) -> Rc<Task> {
+  /// Add a task from a serializable one, e.g., as restored from the
+  /// trash.
+  ///
+  /// The task is added as the last sub-task of its parent or, lacking
+  /// one, as a top-level task at the end. Tags are matched by name
+  /// against the known templates.
+  pub fn add_from_serde(&self, task: SerTask) -> Rc<Task> {
+    let op = self.add_from_serde_op(task);
     // SANITY: The type's API surface prevents any borrows from escaping
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
     let TasksInner {
-      ref mut templates,
       ref mut operations,
       ref mut tasks,
+      ref mut journal,
       ..
     } = borrow.deref_mut();
 
-    let task = Rc::new(Task::with_summary_and_tags(
-      summary,
-      tags,
-      templates.clone(),
-    ));
-    let op = TaskOp::add(task, after);
     // SANITY: We know that an "add" operation always returns a task, so
     //         this unwrap will never panic.
     let task = operations.exec(op, tasks).unwrap();
+    let () = journal.push(JournalEntry::Add {
+      task: task_to_serde(&task, None),
+      after: task.parent().map(|parent| parent.id()),
+    });
 
     task
   }
 
-  /// Remove a task.
-  pub fn remove(&self, task: Rc<Task>) {
+  /// Execute an operation reflecting a change that a different client
+  /// made.
+  ///
+  /// Such a change is reflected on disk already. Hence, the operation
+  /// is neither journaled nor recorded for undo. Recorded operations
+  /// involving any of the tasks it touched are forgotten, as undoing or
+  /// redoing them could revert the change or refer to tasks that no
+  /// longer exist.
+  fn exec_external(&self, mut op: TaskOp) -> Option<Rc<Task>> {
     // SANITY: The type's API surface prevents any borrows from escaping
     //         a function call and we don't call methods on `self` while
     //         a borrow is active.
@@ -633,29 +2162,67 @@ impl Tasks {
       ..
     } = borrow.deref_mut();
 
-    let op = TaskOp::remove(task);
-    operations.exec(op, tasks);
+    let result = op.exec(tasks);
+    let touched = op.task_ids();
+    let () = operations.forget(|op| !op.task_ids().is_disjoint(&touched));
+    result
   }
 
-  /// Update a task.
-  pub fn update(&self, task: Rc<Task>, updated: Task) {
-    // SANITY: The type's API surface prevents any borrows from escaping
-    //         a function call and we don't call methods on `self` while
-    //         a borrow is active.
-    let mut borrow = self.0.try_borrow_mut().unwrap();
-    let TasksInner {
-      ref mut operations,
-      ref mut tasks,
-      ..
-    } = borrow.deref_mut();
+  /// Add a task that a different client added.
+  ///
+  /// The task is placed as by [`Tasks::add_from_serde`], but the change
+  /// is not subject to undo.
+  pub fn add_external(&self, task: SerTask) -> Rc<Task> {
+    let op = self.add_from_serde_op(task);
+    // SANITY: We know that an "add" operation always returns a task, so
+    //         this unwrap will never panic.
+    self.exec_external(op).unwrap()
+  }
 
-    let op = TaskOp::update(task, updated);
-    operations.exec(op, tasks);
+  /// Update a task with the data of a serializable one provided by a
+  /// different client.
+  ///
+  /// Tags are matched by name against the known templates. The task
+  /// retains its position in the hierarchy. As opposed to
+  /// [`Tasks::update`], the modification time is taken over as-is and
+  /// the change is not subject to undo, because it was made elsewhere.
+  pub fn update_external(&self, task: Rc<Task>, updated: SerTask) {
+    let updated = {
+      // SANITY: The type's API surface prevents any borrows from
+      //         escaping a function call and we don't call methods on
+      //         `self` while a borrow is active.
+      let borrow = self.0.try_borrow().unwrap();
+      let TasksInner {
+        ref templates,
+        ref completion_tag,
+        ref tasks,
+        ..
+      } = borrow.deref();
+
+      let mut updated = task_from_foreign_serde(updated, templates, tasks, Some(&task));
+      if let Some(completion_tag) = completion_tag {
+        let () = updated.sync_completion(completion_tag, None);
+      }
+      updated
+    };
+
+    let _task = self.exec_external(TaskOp::update(task, updated));
+  }
+
+  /// Remove a task, along with all its sub-tasks, that a different
+  /// client removed.
+  ///
+  /// The change is not subject to undo.
+  pub fn remove_external(&self, task: Rc<Task>) {
+    let _task = self.exec_external(TaskOp::remove(task));
   }
 
   /// Reorder the task referenced by `to_move` before `other`.
+  ///
+  /// The task is moved along with its sub-tasks and becomes a sibling
+  /// of `other`.
   pub fn move_before(&self, to_move: Rc<Task>, other: Rc<Task>) {
-    if !Rc::ptr_eq(&to_move, &other) {
+    if !Rc::ptr_eq(&to_move, &other) && !other.is_descendant_of(&to_move) {
       // SANITY: The type's API surface prevents any borrows from escaping
       //         a function call and we don't call methods on `self` while
       //         a borrow is active.
@@ -663,18 +2230,28 @@ impl Tasks {
       let TasksInner {
         ref mut operations,
         ref mut tasks,
+        ref mut journal,
         ..
       } = borrow.deref_mut();
 
+      let entry = JournalEntry::MoveBefore {
+        id: to_move.id(),
+        other: other.id(),
+      };
       let to = Target::Before(other);
       let op = TaskOp::move_(to_move, to);
       operations.exec(op, tasks);
+      let () = journal.push(entry);
     }
   }
 
-  /// Reorder the tasks referenced by `to_move` after `other`.
+  /// Reorder the tasks referenced by `to_move` after `other` and its
+  /// sub-tasks.
+  ///
+  /// The task is moved along with its sub-tasks and becomes a sibling
+  /// of `other`.
   pub fn move_after(&self, to_move: Rc<Task>, other: Rc<Task>) {
-    if !Rc::ptr_eq(&to_move, &other) {
+    if !Rc::ptr_eq(&to_move, &other) && !other.is_descendant_of(&to_move) {
       // SANITY: The type's API surface prevents any borrows from escaping
       //         a function call and we don't call methods on `self` while
       //         a borrow is active.
@@ -682,12 +2259,92 @@ impl Tasks {
       let TasksInner {
         ref mut operations,
         ref mut tasks,
+        ref mut journal,
         ..
       } = borrow.deref_mut();
 
+      let entry = JournalEntry::MoveAfter {
+        id: to_move.id(),
+        other: other.id(),
+      };
       let to = Target::After(other);
       let op = TaskOp::move_(to_move, to);
       operations.exec(op, tasks);
+      let () = journal.push(entry);
+    }
+  }
+
+  /// Check whether the provided task has sub-tasks.
+  pub fn has_children(&self, task: &Rc<Task>) -> bool {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let borrow = self.0.try_borrow().unwrap();
+    // Sub-tasks always directly follow their parent.
+    borrow
+      .tasks
+      .find(task)
+      .and_then(|entry| entry.next())
+      .map(|next| next.is_descendant_of(task))
+      .unwrap_or(false)
+  }
+
+  /// Show or hide the sub-tasks of the provided task.
+  ///
+  /// Collapsing is merely a matter of presentation. As such, it is not
+  /// undoable and does not go through [`Tasks::update`].
+  pub fn set_collapsed(&self, task: &Rc<Task>, collapsed: bool) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `task` while
+    //         a borrow is active.
+    task.0.try_borrow_mut().unwrap().collapsed = collapsed
+  }
+
+  /// Turn the provided task into a sub-task of its preceding sibling,
+  /// if it has one.
+  ///
+  /// Returns whether the task got indented.
+  pub fn indent(&self, task: Rc<Task>) -> bool {
+    let sibling = {
+      // SANITY: The type's API surface prevents any borrows from
+      //         escaping a function call and we don't call methods on
+      //         `self` while a borrow is active.
+      let borrow = self.0.try_borrow().unwrap();
+      let depth = task.depth();
+      let mut entry = borrow.tasks.find(&task).and_then(|entry| entry.prev());
+      // Walk backwards, skipping over the sub-tasks of the preceding
+      // sibling, until we find the sibling or reach the parent.
+      loop {
+        match entry {
+          Some(prev) if prev.is_sibling_of(&task) => break Some(prev.deref().clone()),
+          Some(prev) if prev.depth() > depth => entry = prev.prev(),
+          _ => break None,
+        }
+      }
+    };
+
+    if let Some(sibling) = sibling {
+      // The task already directly follows the sibling's sub-tasks, so
+      // only its parent has to change.
+      let updated = task.deref().clone();
+      let _parent = updated.replace_parent(Some(sibling));
+      let () = self.update(task, updated);
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Turn the provided sub-task into a sibling of its parent, placing
+  /// it after the parent's other sub-tasks.
+  ///
+  /// Returns whether the task got outdented.
+  pub fn outdent(&self, task: Rc<Task>) -> bool {
+    if let Some(parent) = task.parent() {
+      let () = self.move_after(task, parent);
+      true
+    } else {
+      false
     }
   }
 
@@ -700,10 +2357,19 @@ impl Tasks {
     let TasksInner {
       ref mut operations,
       ref mut tasks,
+      ref mut journal,
       ..
     } = borrow.deref_mut();
 
-    operations.undo(tasks)
+    let result = operations.undo(tasks);
+    if result.is_some() {
+      // We journal the effect of the undo rather than the undo itself,
+      // as the undo history may differ once the journal gets replayed.
+      let (_done, undone) = operations.history();
+      // SANITY: We just undid an operation, so there is one to redo.
+      let () = journal.extend(undone[0].undo_journal(tasks));
+    }
+    result
   }
 
   /// Redo the last undone operation.
@@ -713,12 +2379,161 @@ impl Tasks {
     //         a borrow is active.
     let mut borrow = self.0.try_borrow_mut().unwrap();
     let TasksInner {
+      ref mut operations,
+      ref mut tasks,
+      ref mut journal,
+      ..
+    } = borrow.deref_mut();
+
+    let result = operations.redo(tasks);
+    if result.is_some() {
+      let (done, _undone) = operations.history();
+      // SANITY: We just redid an operation, so there is one to undo.
+      let () = journal.push(done.last().unwrap().exec_journal());
+    }
+    result
+  }
+
+  /// Set the maximum number of operations that can be undone, dropping
+  /// the oldest ones if necessary.
+  pub fn set_max_undo_step_count(&self, count: usize) {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let () = self
+      .0
+      .try_borrow_mut()
+      .unwrap()
+      .operations
+      .set_max_count(count);
+  }
+
+  /// Convert the history of operations that can be undone and redone
+  /// into a serializable one.
+  pub fn history_to_serde(&self) -> Result<SerHistory> {
+    let fingerprint = fingerprint(&self.to_serde())?;
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let borrow = self.0.try_borrow().unwrap();
+    let (undo, redo) = borrow.operations.history();
+    let history = SerHistory {
+      fingerprint,
+      undo: undo.into_iter().map(op_to_serde).collect(),
+      redo: redo.into_iter().map(op_to_serde).collect(),
+    };
+    Ok(history)
+  }
+
+  /// Restore a history of operations retrieved earlier via
+  /// `history_to_serde`, replacing the current one.
+  ///
+  /// The history is only restored if it belongs to the tasks in their
+  /// current state. Returned is whether that is the case.
+  pub fn restore_history(&self, history: SerHistory) -> Result<bool> {
+    if history.fingerprint != fingerprint(&self.to_serde())? {
+      return Ok(false)
+    }
+
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let TasksInner {
+      ref templates,
+      ref mut operations,
+      ref tasks,
+      ..
+    } = borrow.deref_mut();
+
+    let SerHistory { undo, redo, .. } = history;
+    let count = undo.len();
+    let mut ops = ops_from_serde(undo.into_iter().chain(redo).collect(), templates, tasks)?;
+    let redo = ops.split_off(count);
+    *operations = Ops::with_history(operations.max_count(), ops, redo);
+    Ok(true)
+  }
+
+  /// Take the journal entries recorded for the operations performed
+  /// since the journal was last taken.
+  pub fn take_journal(&self) -> Vec<JournalEntry> {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    take(&mut self.0.try_borrow_mut().unwrap().journal)
+  }
+
+  /// Replay operations recorded in a journal, e.g., by a previous
+  /// program instance that terminated without saving.
+  ///
+  /// Replayed operations can be undone, but they are not recorded in
+  /// the journal again. Returned is the number of entries that could
+  /// not be applied, for example, because they refer to tasks that no
+  /// longer exist.
+  pub fn replay(&self, entries: Vec<JournalEntry>) -> usize {
+    // SANITY: The type's API surface prevents any borrows from escaping
+    //         a function call and we don't call methods on `self` while
+    //         a borrow is active.
+    let mut borrow = self.0.try_borrow_mut().unwrap();
+    let TasksInner {
+      ref templates,
       ref mut operations,
       ref mut tasks,
       ..
     } = borrow.deref_mut();
 
-    operations.redo(tasks)
+    let find = |tasks: &Db<Task, Position>, id: Id| {
+      tasks.iter().find(|task| task.id() == id).cloned()
+    };
+
+    let mut failed = 0;
+    for entry in entries {
+      let op = match entry {
+        JournalEntry::Add { task, after } => {
+          // The task may have been loaded from disk already, e.g., if
+          // it got saved by a different program instance.
+          if find(tasks, task.id).is_some() {
+            continue
+          }
+          let after = after.and_then(|after| find(tasks, after));
+          task_from_journal(task, templates, tasks)
+            .ok()
+            .map(|task| TaskOp::add(Rc::new(task), after))
+        },
+        JournalEntry::Remove { id } => find(tasks, id).map(TaskOp::remove),
+        JournalEntry::Update { task } => {
+          let existing = find(tasks, task.id);
+          let updated = task_from_journal(task, templates, tasks).ok();
+          existing
+            .zip(updated)
+            .map(|(existing, updated)| TaskOp::update(existing, updated))
+        },
+        JournalEntry::MoveBefore { id, other } | JournalEntry::MoveAfter { id, other } => {
+          let before = matches!(entry, JournalEntry::MoveBefore { .. });
+          match (find(tasks, id), find(tasks, other)) {
+            (Some(task), Some(other))
+              if !Rc::ptr_eq(&task, &other) && !other.is_descendant_of(&task) =>
+            {
+              let to = if before {
+                Target::Before(other)
+              } else {
+                Target::After(other)
+              };
+              Some(TaskOp::move_(task, to))
+            },
+            _ => None,
+          }
+        },
+      };
+
+      match op {
+        Some(op) => {
+          let _task = operations.exec(op, tasks);
+        },
+        None => failed += 1,
+      }
+    }
+    failed
   }
 }
 
@@ -727,9 +2542,8 @@ impl Tasks {
 pub mod tests {
   use super::*;
 
-  use std::num::NonZeroUsize;
-
   use crate::ser::tags::Id as SerTemplateId;
+  use crate::ser::tags::Tag as SerTag;
   use crate::ser::tags::Template as SerTemplate;
   use crate::ser::tags::Templates as SerTemplates;
   use crate::test::make_tasks;
@@ -740,7 +2554,7 @@ pub mod tests {
   #[test]
   fn task_tag_query_and_adjustment() {
     let templates = vec![SerTemplate {
-      id: SerTemplateId::new(NonZeroUsize::new(42).unwrap()),
+      id: SerTemplateId::try_from(42).unwrap(),
       name: COMPLETE_TAG.to_string(),
     }];
     let templates = Templates::with_serde(SerTemplates(templates)).unwrap();
@@ -998,12 +2812,159 @@ pub mod tests {
     tasks.update(task, updated);
 
     let tasks = tasks.to_serde().into_task_vec();
+    assert!(tasks[1].modified.is_some());
+
     let mut expected = task_vec;
     expected[1].summary = "amended".to_string();
+    expected[1].modified = tasks[1].modified;
+    expected[1].stamp = tasks[1].stamp;
 
     assert_eq!(tasks, expected);
   }
 
+  /// Check that adding and updating tasks maintains their creation and
+  /// modification times, and that undo restores previous values.
+  #[test]
+  fn update_task_timestamps() {
+    let tasks = Tasks::with_serde_tasks(make_tasks(1)).unwrap();
+    let task = tasks.add("new".to_string(), Vec::new(), None);
+    let created = task.created();
+    assert!(created.is_some());
+    assert_eq!(task.modified(), created);
+
+    // An update not changing anything should leave the task untouched.
+    let updated = task.deref().clone();
+    tasks.update(task.clone(), updated);
+    assert_eq!(task.modified(), created);
+
+    // Fake an older modification time to be able to detect changes.
+    let before = Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0).unwrap();
+    let mut updated = task.deref().clone();
+    let () = updated.set_modified(before);
+    let () = task.update_from(updated);
+
+    let mut updated = task.deref().clone();
+    updated.set_summary("amended".to_string());
+    tasks.update(task.clone(), updated);
+    assert_eq!(task.created(), created);
+    assert_ne!(task.modified(), Some(before));
+
+    let _task = tasks.undo();
+    assert_eq!(task.summary(), "new");
+    assert_eq!(task.modified(), Some(before));
+    assert_eq!(task.to_serde().stamp, before);
+  }
+
+  /// Check that toggling the completion tag on a task updates its
+  /// completion time, and that undo restores the previous state.
+  #[test]
+  fn update_task_completion() {
+    let templates = vec![SerTemplate {
+      id: SerTemplateId::try_from(42).unwrap(),
+      name: COMPLETE_TAG.to_string(),
+    }];
+    let templates = Templates::with_serde(SerTemplates(templates)).unwrap();
+    let templates = Rc::new(templates);
+    let complete = templates.instantiate_from_name(COMPLETE_TAG);
+
+    let tasks = Tasks::with_serde(SerTasks::from(make_tasks(2)), templates).unwrap();
+    let () = tasks.set_completion_tag(Some(complete.clone()));
+
+    let task = tasks.iter(|mut iter| iter.next().unwrap().clone());
+    assert_eq!(task.completed(), None);
+
+    let mut updated = task.deref().clone();
+    assert!(updated.set_tag(complete.clone()));
+    tasks.update(task.clone(), updated);
+    assert!(task.completed().is_some());
+
+    let mut updated = task.deref().clone();
+    updated.set_summary("amended".to_string());
+    let completed = task.completed();
+    tasks.update(task.clone(), updated);
+    assert_eq!(task.completed(), completed);
+
+    let _task = tasks.undo();
+    let _task = tasks.undo();
+    assert_eq!(task.completed(), None);
+    assert!(!task.has_tag(&complete));
+  }
+
+  /// Check that completing a recurring task advances it to its next
+  /// occurrence until the recurrence runs out.
+  #[test]
+  fn update_recurring_task_completion() {
+    let templates = vec![SerTemplate {
+      id: SerTemplateId::try_from(42).unwrap(),
+      name: COMPLETE_TAG.to_string(),
+    }];
+    let templates = Templates::with_serde(SerTemplates(templates)).unwrap();
+    let templates = Rc::new(templates);
+    let complete = templates.instantiate_from_name(COMPLETE_TAG);
+
+    let mut task_vec = make_tasks(1);
+    task_vec[0].due = NaiveDate::from_ymd_opt(2023, 4, 1);
+    task_vec[0].recurrence = Some("FREQ=DAILY;COUNT=2".to_string());
+
+    let tasks = Tasks::with_serde(SerTasks::from(task_vec), templates).unwrap();
+    let () = tasks.set_completion_tag(Some(complete.clone()));
+    let task = tasks.iter(|mut iter| iter.next().unwrap().clone());
+
+    let mut updated = task.deref().clone();
+    assert!(updated.set_tag(complete.clone()));
+    tasks.update(task.clone(), updated);
+    assert!(!task.has_tag(&complete));
+    assert_eq!(task.completed(), None);
+    assert_eq!(task.due(), NaiveDate::from_ymd_opt(2023, 4, 2));
+    assert_eq!(task.recurrence().as_deref(), Some("FREQ=DAILY;UNTIL=20230402"));
+
+    // This was the last occurrence, so the task actually completes.
+    let mut updated = task.deref().clone();
+    assert!(updated.set_tag(complete.clone()));
+    tasks.update(task.clone(), updated);
+    assert!(task.has_tag(&complete));
+    assert!(task.completed().is_some());
+    assert_eq!(task.due(), NaiveDate::from_ymd_opt(2023, 4, 2));
+
+    let _task = tasks.undo();
+    let _task = tasks.undo();
+    assert!(!task.has_tag(&complete));
+    assert_eq!(task.due(), NaiveDate::from_ymd_opt(2023, 4, 1));
+    assert_eq!(task.recurrence().as_deref(), Some("FREQ=DAILY;COUNT=2"));
+  }
+
+  /// Check that setting the completion tag reconciles the completion
+  /// state of all tasks with it.
+  #[test]
+  fn set_completion_tag() {
+    let templates = vec![SerTemplate {
+      id: SerTemplateId::try_from(42).unwrap(),
+      name: COMPLETE_TAG.to_string(),
+    }];
+    let templates = Templates::with_serde(SerTemplates(templates)).unwrap();
+    let templates = Rc::new(templates);
+    let complete = templates.instantiate_from_name(COMPLETE_TAG);
+
+    let mut task_vec = make_tasks(4);
+    task_vec[0].completed = Some(now());
+    // A task stemming from data predating the recording of completion.
+    task_vec[1].tags = vec![complete.to_serde()];
+    task_vec[1].completion_recorded = false;
+    // A task that got reopened by a client unaware of the tag.
+    task_vec[3].tags = vec![complete.to_serde()];
+
+    let tasks = Tasks::with_serde(SerTasks::from(task_vec), templates).unwrap();
+    let () = tasks.set_completion_tag(Some(complete.clone()));
+
+    let tasks = tasks.iter(|iter| iter.cloned().collect::<Vec<_>>());
+    assert!(tasks[0].has_tag(&complete));
+    assert!(tasks[1].completed().is_some());
+    assert!(!tasks[2].has_tag(&complete));
+    assert_eq!(tasks[2].completed(), None);
+    assert!(!tasks[3].has_tag(&complete));
+    assert_eq!(tasks[3].completed(), None);
+  }
+
   /// Check that moving a task before the first one works as expected.
   #[test]
   fn move_before_for_first() {
@@ -1085,4 +3046,510 @@ pub mod tests {
       tasks.move_after(task1, task2);
     })
   }
+
+  /// Retrieve the summaries of all tasks, along with their depth.
+  fn summaries_with_depth(tasks: &Tasks) -> Vec<(String, usize)> {
+    tasks.iter(|iter| {
+      iter
+        .map(|task| (task.summary(), task.depth()))
+        .collect::<Vec<_>>()
+    })
+  }
+
+  /// Retrieve the task at the given index.
+  fn nth_task(tasks: &Tasks, idx: usize) -> Rc<Task> {
+    tasks.iter(|mut iter| iter.nth(idx).unwrap().clone())
+  }
+
+
+  /// Check that sub-tasks get placed right behind their parent on
+  /// load, that cyclic parent references are ignored, and that
+  /// references to unknown parents are retained.
+  #[test]
+  fn load_hierarchy() {
+    let mut task_vec = make_tasks(5);
+    // "1" is a sub-task of "4".
+    task_vec[0].parent = Some(task_vec[3].id);
+    // "2" and "3" form a cycle.
+    task_vec[1].parent = Some(task_vec[2].id);
+    task_vec[2].parent = Some(task_vec[1].id);
+    // "5" refers to a task that does not exist.
+    let unknown = Id::new_v4();
+    task_vec[4].parent = Some(unknown);
+
+    let tasks = Tasks::with_serde_tasks(task_vec.clone()).unwrap();
+    // Tasks caught in a cycle are placed after all others.
+    let expected = vec![
+      ("4".to_string(), 0),
+      ("1".to_string(), 1),
+      ("5".to_string(), 0),
+      ("2".to_string(), 0),
+      ("3".to_string(), 1),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+
+    let serialized = tasks.to_serde().0;
+    assert_eq!(serialized[1].parent, Some(task_vec[3].id));
+    assert_eq!(serialized[2].parent, Some(unknown));
+    assert_eq!(serialized[3].parent, None);
+    assert_eq!(serialized[4].parent, Some(task_vec[1].id));
+
+    // Positions reflect the new order.
+    let positions = serialized
+      .iter()
+      .map(|task| task.position.unwrap())
+      .collect::<Vec<_>>();
+    assert!(positions.windows(2).all(|pair| pair[0] <= pair[1]));
+  }
+
+  /// Check that we can indent and outdent tasks and undo doing so.
+  #[test]
+  fn indent_outdent() {
+    let tasks = Tasks::with_serde_tasks(make_tasks(4)).unwrap();
+    // The first task has no preceding sibling.
+    assert!(!tasks.indent(nth_task(&tasks, 0)));
+
+    assert!(tasks.indent(nth_task(&tasks, 1)));
+    assert!(tasks.indent(nth_task(&tasks, 2)));
+    assert!(tasks.indent(nth_task(&tasks, 2)));
+    let expected = vec![
+      ("1".to_string(), 0),
+      ("2".to_string(), 1),
+      ("3".to_string(), 2),
+      ("4".to_string(), 0),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+    assert!(tasks.has_children(&nth_task(&tasks, 0)));
+    assert!(!tasks.has_children(&nth_task(&tasks, 2)));
+
+    // Outdenting "2" moves it, along with "3", behind "1".
+    assert!(tasks.indent(nth_task(&tasks, 3)));
+    assert!(tasks.outdent(nth_task(&tasks, 1)));
+    let expected = vec![
+      ("1".to_string(), 0),
+      ("4".to_string(), 1),
+      ("2".to_string(), 0),
+      ("3".to_string(), 1),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+    assert!(!tasks.outdent(nth_task(&tasks, 0)));
+
+    let _task = tasks.undo();
+    let expected = vec![
+      ("1".to_string(), 0),
+      ("2".to_string(), 1),
+      ("3".to_string(), 2),
+      ("4".to_string(), 1),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+  }
+
+  /// Check that moving, adding, and removing tasks takes sub-tasks
+  /// into account.
+  #[test]
+  fn move_add_remove_with_sub_tasks() {
+    let tasks = Tasks::with_serde_tasks(make_tasks(4)).unwrap();
+    assert!(tasks.indent(nth_task(&tasks, 1)));
+    assert!(tasks.indent(nth_task(&tasks, 3)));
+
+    // Move "1" along with "2" after "3" and its sub-task "4".
+    tasks.move_after(nth_task(&tasks, 0), nth_task(&tasks, 2));
+    let expected = vec![
+      ("3".to_string(), 0),
+      ("4".to_string(), 1),
+      ("1".to_string(), 0),
+      ("2".to_string(), 1),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+
+    // Moving a task relative to one of its sub-tasks is not possible.
+    tasks.move_before(nth_task(&tasks, 0), nth_task(&tasks, 1));
+    assert_eq!(summaries_with_depth(&tasks), expected);
+
+    // A task added after "3" becomes its sibling.
+    let _task = tasks.add("5".to_string(), Vec::new(), Some(nth_task(&tasks, 0)));
+    let expected = vec![
+      ("3".to_string(), 0),
+      ("4".to_string(), 1),
+      ("5".to_string(), 0),
+      ("1".to_string(), 0),
+      ("2".to_string(), 1),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+
+    tasks.remove(nth_task(&tasks, 3));
+    let expected = vec![
+      ("3".to_string(), 0),
+      ("4".to_string(), 1),
+      ("5".to_string(), 0),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+
+    let _task = tasks.undo();
+    let _task = tasks.undo();
+    let _task = tasks.undo();
+    let expected = vec![
+      ("1".to_string(), 0),
+      ("2".to_string(), 1),
+      ("3".to_string(), 0),
+      ("4".to_string(), 1),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+  }
+
+  /// Check that tasks sharing an ID are rejected on load.
+  #[test]
+  fn load_duplicate_ids() {
+    let mut task_vec = make_tasks(3);
+    task_vec[2].id = task_vec[0].id;
+
+    let err = Tasks::with_serde_tasks(task_vec.clone()).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      format!("encountered duplicate task ID {}", task_vec[0].id)
+    );
+  }
+
+  /// Check that dependencies get resolved on load and that tasks are
+  /// blocked for as long as one of their dependencies is incomplete.
+  #[test]
+  fn load_dependencies() {
+    let mut task_vec = make_tasks(4);
+    task_vec[0].depends_on = vec![task_vec[1].id, task_vec[2].id];
+    // A dependency resulting in a cycle should be dropped.
+    task_vec[1].depends_on = vec![task_vec[0].id];
+    task_vec[2].completed = Some(Utc::now());
+    // As should references to the task itself. References to unknown
+    // tasks are retained, though.
+    let unknown = Id::new_v4();
+    task_vec[3].depends_on = vec![task_vec[3].id, unknown, task_vec[2].id];
+
+    let tasks = Tasks::with_serde_tasks(task_vec.clone()).unwrap();
+    let serialized = tasks.to_serde().into_task_vec();
+    assert_eq!(serialized[0].depends_on, vec![task_vec[1].id, task_vec[2].id]);
+    assert!(serialized[1].depends_on.is_empty());
+    assert_eq!(serialized[3].depends_on, vec![task_vec[2].id, unknown]);
+
+    let blocked = tasks.iter(|iter| iter.map(|task| task.is_blocked()).collect::<Vec<_>>());
+    assert_eq!(blocked, vec![true, false, false, false]);
+
+    let first = nth_task(&tasks, 0);
+    assert!(first.depends_on_task(&nth_task(&tasks, 2)));
+    assert!(!nth_task(&tasks, 1).depends_on_task(&first));
+  }
+
+  /// Check that removing a task removes any dependencies on it, in an
+  /// undoable fashion.
+  #[test]
+  fn remove_dependency() {
+    let tasks = Tasks::with_serde_tasks(make_tasks(3)).unwrap();
+    let first = nth_task(&tasks, 0);
+    let second = nth_task(&tasks, 1);
+
+    let mut updated = first.deref().clone();
+    let () = updated.set_depends_on(vec![second.clone()]);
+    tasks.update(first.clone(), updated);
+    assert!(first.is_blocked());
+
+    tasks.remove(second);
+    assert!(!first.is_blocked());
+    assert!(first.depends_on().is_empty());
+
+    let _task = tasks.undo();
+    assert!(first.is_blocked());
+    assert_eq!(first.depends_on().len(), 1);
+  }
+
+  /// Check that changes made by other clients are not subject to undo
+  /// and that recorded operations involving the affected tasks are
+  /// forgotten.
+  #[test]
+  fn external_changes() {
+    let tasks = Tasks::with_serde_tasks(make_tasks(4)).unwrap();
+    let first = nth_task(&tasks, 0);
+    let second = nth_task(&tasks, 1);
+    let third = nth_task(&tasks, 2);
+
+    for task in [&first, &second] {
+      let mut updated = task.deref().clone();
+      let () = updated.set_summary("local".to_string());
+      tasks.update(task.clone(), updated);
+    }
+
+    let mut task = task_to_serde(&first, None);
+    task.summary = "remote".to_string();
+    let () = tasks.update_external(first.clone(), task);
+    assert_eq!(first.summary(), "remote");
+
+    // Only the update of the second task can still be undone.
+    assert!(tasks.undo().is_some());
+    assert_eq!(second.summary(), "2");
+    assert_eq!(first.summary(), "remote");
+    assert!(tasks.undo().is_none());
+    assert!(tasks.redo().is_some());
+    assert_eq!(second.summary(), "local");
+
+    tasks.remove(third.clone());
+    let _task = tasks.add_external(SerTask::new("added".to_string()));
+    let () = tasks.remove_external(nth_task(&tasks, 0));
+    assert_eq!(
+      summaries_with_depth(&tasks),
+      vec![("local".to_string(), 0), ("4".to_string(), 0), ("added".to_string(), 0)]
+    );
+
+    // Undoing the removal reinserts the task at its former position,
+    // even though other tasks got added and removed since.
+    assert!(tasks.undo().is_some());
+    assert_eq!(
+      summaries_with_depth(&tasks),
+      vec![
+        ("local".to_string(), 0),
+        ("3".to_string(), 0),
+        ("4".to_string(), 0),
+        ("added".to_string(), 0),
+      ]
+    );
+    assert!(tasks.undo().is_some());
+    assert!(tasks.undo().is_none());
+    assert!(tasks.take_journal().iter().all(|entry| !matches!(
+      entry,
+      JournalEntry::Add { task, .. } if task.summary == "added"
+    )));
+  }
+
+  /// Check that tasks provided by other clients get their tags and
+  /// their parent resolved.
+  #[test]
+  fn add_task_from_foreign_serde() {
+    let templates = vec![SerTemplate {
+      id: SerTemplateId::from_name("a"),
+      name: "a".to_string(),
+    }];
+    let templates = Rc::new(Templates::with_serde(SerTemplates(templates)).unwrap());
+    let tag_a = templates.instantiate_from_name("a");
+    let tasks = make_tasks(2);
+    let tasks = Tasks::with_serde(SerTasks::from(tasks), templates.clone()).unwrap();
+    let parent = nth_task(&tasks, 0);
+
+    let mut task = SerTask::new("child".to_string());
+    task.parent = Some(parent.id());
+    task.categories = vec!["a".to_string(), "b".to_string(), "a".to_string()];
+    let child = tasks.add_from_serde(task);
+    assert!(Rc::ptr_eq(&child.parent().unwrap(), &parent));
+    assert!(Rc::ptr_eq(&nth_task(&tasks, 1), &child));
+    let names = child.tags(|iter| iter.map(|tag| tag.name().to_string()).collect::<Vec<_>>());
+    assert_eq!(names, vec!["a", "b"]);
+    assert!(templates.iter().any(|template| template.name() == "b"));
+
+    // Lacking categories, tags stored by ID are used.
+    let mut task = SerTask::new("tagged".to_string());
+    task.tags = vec![tag_a.to_serde(), SerTag::from(SerTemplateId::from_name("unknown"))];
+    let tagged = tasks.add_from_serde(task);
+    assert!(tagged.parent().is_none());
+    let names = tagged.tags(|iter| iter.map(|tag| tag.name().to_string()).collect::<Vec<_>>());
+    assert_eq!(names, vec!["a"]);
+  }
+
+  /// Check that advancing a recurring task moves its start date along.
+  #[test]
+  fn update_recurring_task_with_start() {
+    let templates = vec![SerTemplate {
+      id: SerTemplateId::try_from(42).unwrap(),
+      name: COMPLETE_TAG.to_string(),
+    }];
+    let templates = Templates::with_serde(SerTemplates(templates)).unwrap();
+    let templates = Rc::new(templates);
+    let complete = templates.instantiate_from_name(COMPLETE_TAG);
+
+    let mut task_vec = make_tasks(1);
+    task_vec[0].start = NaiveDate::from_ymd_opt(2023, 3, 29);
+    task_vec[0].due = NaiveDate::from_ymd_opt(2023, 4, 1);
+    task_vec[0].recurrence = Some("FREQ=WEEKLY".to_string());
+
+    let tasks = Tasks::with_serde(SerTasks::from(task_vec), templates).unwrap();
+    let () = tasks.set_completion_tag(Some(complete.clone()));
+    let task = tasks.iter(|mut iter| iter.next().unwrap().clone());
+
+    let mut updated = task.deref().clone();
+    assert!(updated.set_tag(complete));
+    tasks.update(task.clone(), updated);
+    assert_eq!(task.start(), NaiveDate::from_ymd_opt(2023, 4, 5));
+    assert_eq!(task.due(), NaiveDate::from_ymd_opt(2023, 4, 8));
+  }
+
+  /// Check that we correctly compute the times at which a task's
+  /// reminders fire.
+  #[test]
+  fn task_reminders() {
+    let absolute = Utc.with_ymd_and_hms(2023, 4, 1, 9, 30, 0).unwrap();
+    let relative = |offset, related| SerAlarm {
+      trigger: SerTrigger::Relative { offset, related },
+      properties: Vec::new(),
+    };
+
+    let mut task_vec = make_tasks(1);
+    task_vec[0].alarms = vec![
+      SerAlarm {
+        trigger: SerTrigger::Absolute(absolute),
+        properties: Vec::new(),
+      },
+      relative(-60 * 60, SerRelated::Start),
+      relative(9 * 60 * 60, SerRelated::End),
+    ];
+
+    let tasks = Tasks::with_serde_tasks(task_vec).unwrap();
+    let mut task = tasks.iter(|mut iter| iter.next().unwrap().deref().clone());
+    // Relative reminders are ignored for as long as the task has
+    // neither a start nor a due date.
+    assert_eq!(task.reminders(), vec![absolute]);
+
+    let midnight = |year, month, day| {
+      Local
+        .with_ymd_and_hms(year, month, day, 0, 0, 0)
+        .unwrap()
+        .with_timezone(&Utc)
+    };
+
+    task.set_due(NaiveDate::from_ymd_opt(2023, 4, 10));
+    let expected = vec![
+      absolute,
+      midnight(2023, 4, 10) - Duration::hours(1),
+      midnight(2023, 4, 10) + Duration::hours(9),
+    ];
+    assert_eq!(task.reminders(), expected);
+
+    task.set_start(NaiveDate::from_ymd_opt(2023, 4, 3));
+    let expected = vec![
+      absolute,
+      midnight(2023, 4, 3) - Duration::hours(1),
+      midnight(2023, 4, 10) + Duration::hours(9),
+    ];
+    assert_eq!(task.reminders(), expected);
+
+    let () = task.set_reminder(None);
+    assert!(task.reminders().is_empty());
+    let () = task.set_reminder(Some(absolute));
+    assert_eq!(task.reminders(), vec![absolute]);
+  }
+
+  /// Check that operations recorded in the journal survive a
+  /// serialization round trip and can be replayed onto the original
+  /// set of tasks.
+  #[test]
+  fn journal_replay() {
+    let task_vec = make_tasks(4);
+    let tasks = Tasks::with_serde_tasks(task_vec.clone()).unwrap();
+
+    let first = nth_task(&tasks, 0);
+    let added = tasks.add("new".to_string(), Default::default(), Some(first.clone()));
+    let mut updated = added.deref().clone();
+    let () = updated.set_summary("amended".to_string());
+    tasks.update(added, updated);
+    assert!(tasks.indent(nth_task(&tasks, 1)));
+    tasks.remove(nth_task(&tasks, 3));
+    let _moved = tasks.move_after(first, nth_task(&tasks, 3));
+    tasks.remove(nth_task(&tasks, 0));
+    let _task = tasks.undo();
+
+    let journal = tasks.take_journal();
+    // Undoing the removal of the first task re-adds it and moves it
+    // back in front of its sibling.
+    assert_eq!(journal.len(), 8);
+    assert!(tasks.take_journal().is_empty());
+
+    let journal = journal
+      .into_iter()
+      .map(|entry| {
+        let json = serde_json::to_string(&entry).unwrap();
+        serde_json::from_str::<JournalEntry>(&json).unwrap()
+      })
+      .collect::<Vec<_>>();
+
+    let replayed = Tasks::with_serde_tasks(task_vec).unwrap();
+    assert_eq!(replayed.replay(journal), 0);
+    assert_eq!(summaries_with_depth(&replayed), summaries_with_depth(&tasks));
+    // Replayed operations are undoable but not journaled again.
+    assert!(replayed.take_journal().is_empty());
+    assert!(replayed.undo().is_some());
+  }
+
+  /// Check that undoing and redoing operations is journaled by its
+  /// effect, so that replaying the journal does not depend on the undo
+  /// history.
+  #[test]
+  fn journal_replay_undo_redo() {
+    let tasks = Tasks::with_serde_tasks(make_tasks(4)).unwrap();
+    assert!(tasks.indent(nth_task(&tasks, 1)));
+    assert!(tasks.outdent(nth_task(&tasks, 1)));
+    tasks.remove(nth_task(&tasks, 2));
+    let _moved = tasks.move_before(nth_task(&tasks, 2), nth_task(&tasks, 0));
+    let _journal = tasks.take_journal();
+    let task_vec = tasks.to_serde().into_task_vec();
+
+    for _ in 0..4 {
+      assert!(tasks.undo().is_some());
+    }
+    assert!(tasks.redo().is_some());
+    let expected = vec![
+      ("1".to_string(), 0),
+      ("2".to_string(), 1),
+      ("3".to_string(), 0),
+      ("4".to_string(), 0),
+    ];
+    assert_eq!(summaries_with_depth(&tasks), expected);
+
+    // The replayed tasks lack any undo history.
+    let journal = tasks.take_journal();
+    let replayed = Tasks::with_serde_tasks(task_vec).unwrap();
+    assert_eq!(replayed.replay(journal), 0);
+    assert_eq!(summaries_with_depth(&replayed), expected);
+  }
+
+  /// Check that the undo history survives a serialization round trip
+  /// and can be restored onto tasks in the state it belongs to.
+  #[test]
+  fn history_round_trip() {
+    let task_vec = make_tasks(5);
+    let tasks = Tasks::with_serde_tasks(task_vec.clone()).unwrap();
+
+    let first = nth_task(&tasks, 0);
+    let added = tasks.add("new".to_string(), Default::default(), Some(first.clone()));
+    assert!(tasks.indent(added));
+    let mut updated = nth_task(&tasks, 2).deref().clone();
+    let () = updated.set_depends_on(vec![nth_task(&tasks, 1)]);
+    tasks.update(nth_task(&tasks, 2), updated);
+    tasks.remove(first);
+    tasks.move_after(nth_task(&tasks, 0), nth_task(&tasks, 2));
+    tasks.remove(nth_task(&tasks, 3));
+    let _task = tasks.undo();
+
+    let history = tasks.history_to_serde().unwrap();
+    assert_eq!(history.undo.len(), 5);
+    assert_eq!(history.redo.len(), 1);
+    let json = serde_json::to_string(&history).unwrap();
+    let history = serde_json::from_str::<SerHistory>(&json).unwrap();
+
+    // The history does not apply to tasks in a different state.
+    let other = Tasks::with_serde_tasks(task_vec).unwrap();
+    assert!(!other.restore_history(history.clone()).unwrap());
+    assert!(other.undo().is_none());
+
+    let restored = Tasks::with_serde(tasks.to_serde(), Rc::new(Templates::new())).unwrap();
+    assert!(restored.restore_history(history).unwrap());
+
+    assert!(restored.redo().is_some());
+    assert!(tasks.redo().is_some());
+    assert_eq!(summaries_with_depth(&restored), summaries_with_depth(&tasks));
+
+    for _ in 0..6 {
+      assert!(restored.undo().is_some());
+      assert!(tasks.undo().is_some());
+      assert_eq!(summaries_with_depth(&restored), summaries_with_depth(&tasks));
+    }
+    assert!(restored.undo().is_none());
+    assert_eq!(restored.to_serde(), tasks.to_serde());
+
+    let _task = restored.redo();
+    assert!(restored.iter(|mut iter| iter.any(|task| task.summary() == "new")));
+  }
 }
@@ -3,6 +3,9 @@
 
 //! A module providing testing related utility functionality.
 
+use chrono::TimeZone as _;
+use chrono::Utc;
+
 use crate::ser::state::TaskState as SerTaskState;
 use crate::ser::state::UiConfig as SerUiConfig;
 use crate::ser::tags::Id as SerId;
@@ -79,9 +82,12 @@ pub fn make_tasks_with_tags(count: usize) -> (Vec<SerTag>, Vec<SerTemplate>, Vec
   let tasks = (0..count)
     .map(|x| {
       let mut task_tags = Vec::new();
-      // Add 'complete' tag for uneven tasks.
+      let mut completed = None;
+      // Add 'complete' tag for uneven tasks and mark them as completed
+      // accordingly.
       if x % 2 == 1 {
-        task_tags.push(tags[0])
+        task_tags.push(tags[0]);
+        completed = Some(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap());
       }
       // Add the "newest" tag.
       if x >= 4 {
@@ -91,7 +97,9 @@ pub fn make_tasks_with_tags(count: usize) -> (Vec<SerTag>, Vec<SerTemplate>, Vec
       if x >= 8 && x % 4 >= 2 {
         task_tags.extend_from_slice(&tags[1..x / 4])
       }
-      SerTask::new(format!("{}", x + 1)).with_tags(task_tags)
+      let mut task = SerTask::new(format!("{}", x + 1)).with_tags(task_tags);
+      task.completed = completed;
+      task
     })
     .collect();
 
@@ -119,23 +127,31 @@ pub fn default_tasks_and_tags() -> (SerUiConfig, SerTaskState) {
       SerView {
         name: "all".to_string(),
         lits: vec![],
+        sort: None,
       },
       SerView {
         name: "tag complete".to_string(),
-        lits: vec![vec![SerTagLit::Pos(tags[0])]],
+        lits: vec![vec![SerTagLit::Pos(tags[0].into())]],
+        sort: None,
       },
       SerView {
         name: "tag2 || tag3".to_string(),
-        lits: vec![vec![SerTagLit::Pos(tags[2]), SerTagLit::Pos(tags[3])]],
+        lits: vec![vec![SerTagLit::Pos(tags[2].into()), SerTagLit::Pos(tags[3].into())]],
+        sort: None,
       },
       SerView {
         name: "tag1 && tag3".to_string(),
-        lits: vec![vec![SerTagLit::Pos(tags[1])], vec![SerTagLit::Pos(tags[3])]],
+        lits: vec![vec![SerTagLit::Pos(tags[1].into())], vec![SerTagLit::Pos(tags[3].into())]],
+        sort: None,
       },
     ],
     colors: Default::default(),
     // The UI can be used to toggle completion state.
     toggle_tag: Some(tags[0]),
+    reminder_command: None,
+    sync: None,
+    archive_after_days: None,
+    max_undo_steps: None,
   };
   let task_state = SerTaskState {
     tasks_meta: SerTasksMeta {
@@ -4,6 +4,7 @@
 use std::path::Path;
 
 use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Context as _;
 use anyhow::Result;
 
@@ -11,6 +12,7 @@ use crate::cap::FileCap;
 use crate::colors::Colors;
 use crate::ser::backends::Json;
 use crate::ser::state::UiConfig as SerUiConfig;
+use crate::ser::sync::SyncConfig;
 use crate::ser::ToSerde;
 use crate::state::load_state_from_file;
 use crate::state::save_state_to_file;
@@ -21,6 +23,12 @@ use crate::view::View;
 use crate::view::ViewBuilder;
 
 
+/// The maximum number of operations that can be configured to be
+/// undoable. Undo history is allocated eagerly, so we do not permit
+/// arbitrarily large values.
+const MAX_UNDO_STEPS: usize = 10_000;
+
+
 /// A struct encapsulating the UI's configuration.
 #[derive(Debug)]
 pub struct Config {
@@ -28,6 +36,16 @@ pub struct Config {
   pub colors: Colors,
   /// The tag to toggle on user initiated action.
   pub toggle_tag: Option<Tag>,
+  /// The command to run when a reminder fires.
+  pub reminder_command: Option<String>,
+  /// The configuration of task synchronization, if any.
+  pub sync: Option<SyncConfig>,
+  /// The number of days after which completed tasks get archived, if
+  /// at all.
+  pub archive_after_days: Option<u32>,
+  /// The maximum number of operations that can be undone, if different
+  /// from the default.
+  pub max_undo_steps: Option<usize>,
   /// The views used in the UI.
   pub views: Vec<View>,
 }
@@ -53,8 +71,19 @@ impl Config {
     let SerUiConfig {
       colors,
       toggle_tag,
+      reminder_command,
+      sync,
+      archive_after_days,
+      max_undo_steps,
       views,
     } = config;
+
+    if let Some(count) = max_undo_steps {
+      if count > MAX_UNDO_STEPS {
+        bail!("configured maximum number of undo steps ({count}) exceeds limit of {MAX_UNDO_STEPS}")
+      }
+    }
+
     let templates = task_state.templates();
     let tasks = task_state.tasks();
 
@@ -87,6 +116,10 @@ impl Config {
     let slf = Self {
       colors,
       toggle_tag,
+      reminder_command,
+      sync,
+      archive_after_days,
+      max_undo_steps,
       views,
     };
     Ok(slf)
@@ -116,6 +149,10 @@ impl ToSerde for Config {
     let config = SerUiConfig {
       colors: self.colors,
       toggle_tag: self.toggle_tag.as_ref().map(ToSerde::to_serde),
+      reminder_command: self.reminder_command.clone(),
+      sync: self.sync.clone(),
+      archive_after_days: self.archive_after_days,
+      max_undo_steps: self.max_undo_steps,
       views,
     };
     config
@@ -192,4 +229,23 @@ pub mod tests {
 
     let _new_config = Config::load(&config, &task_state).await.unwrap();
   }
+
+  /// Check that an excessive maximum number of undo steps is rejected.
+  #[test]
+  async fn reject_excessive_undo_steps() {
+    let (_config, task_state) = make_config(1);
+    let config = SerUiConfig {
+      max_undo_steps: Some(usize::MAX),
+      ..Default::default()
+    };
+    let err = Config::with_serde(config, &task_state).unwrap_err();
+    assert!(err.to_string().contains("exceeds limit"), "{err}");
+
+    let config = SerUiConfig {
+      max_undo_steps: Some(MAX_UNDO_STEPS),
+      ..Default::default()
+    };
+    let config = Config::with_serde(config, &task_state).unwrap();
+    assert_eq!(config.max_undo_steps, Some(MAX_UNDO_STEPS));
+  }
 }
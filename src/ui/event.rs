@@ -1,8 +1,13 @@
 // Copyright (C) 2018-2022 Daniel Mueller (deso@posteo.net)
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::rc::Rc;
+
+use gui::Id;
 use gui::Mergeable;
 
+use crate::tasks::Task;
+
 
 /// A key as used by the UI.
 pub use termion::event::Key;
@@ -16,6 +21,9 @@ pub enum Event {
   Updated,
   /// An indication that the application should quit.
   Quit,
+  /// A request to edit the notes of a task in an external editor. The
+  /// result is reported back to the widget with the given ID.
+  EditNotes(Id, Rc<Task>),
   /// A key press.
   #[cfg(not(feature = "readline"))]
   Key(Key, ()),
@@ -50,8 +58,15 @@ impl Mergeable for Event {
         "Attempting to merge incompatible events: {:?} & {:?}",
         self, other
       ),
+      (Self::EditNotes(..), Self::EditNotes(..)) => panic!(
+        "Attempting to merge incompatible events: {:?} & {:?}",
+        self, other
+      ),
       (Self::Updated, Self::Updated) => self,
       (Self::Quit, _) | (_, Self::Quit) => Self::Quit,
+      // An editor request always implies a re-render afterwards.
+      (Self::EditNotes(..), Self::Updated) => self,
+      (Self::Updated, Self::EditNotes(..)) => other,
     }
   }
 }
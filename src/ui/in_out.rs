@@ -31,8 +31,12 @@ use super::modal::Modal;
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum InOut {
   Saved,
+  Synced(String),
   Search(String),
+  Reminder(String),
   Error(String),
+  Conflict(String),
+  Recovery(String),
   Input(Line),
   Clear,
 }
@@ -177,7 +181,7 @@ impl InOutArea {
             data.clear_gen = Some(data.in_out.gen);
             None
           },
-          Event::Updated | Event::Quit => None,
+          Event::Updated | Event::Quit | Event::EditNotes(..) => None,
         }
       } else {
         // We only change our state to "Clear" if the generation number
@@ -185,10 +189,14 @@ impl InOutArea {
         // between pre- and post-hook.
         if data.clear_gen.take() == Some(data.in_out.gen) {
           match data.in_out.get() {
-            InOut::Saved | InOut::Search(_) | InOut::Error(_) => {
+            InOut::Saved
+            | InOut::Synced(_)
+            | InOut::Search(_)
+            | InOut::Reminder(_)
+            | InOut::Error(_) => {
               data.change_state(InOut::Clear).map(|_| Event::Updated)
             },
-            InOut::Input(..) | InOut::Clear => None,
+            InOut::Conflict(_) | InOut::Recovery(_) | InOut::Input(..) | InOut::Clear => None,
           }
         } else {
           None
@@ -390,6 +398,16 @@ impl Handleable<Event, Message> for InOutArea {
         };
 
         let data = self.data_mut::<InOutAreaData>(cap);
+        // A reminder must not interrupt text input the user is
+        // currently performing or a question awaiting an answer.
+        if matches!(in_out, InOut::Reminder(..))
+          && matches!(
+            data.in_out.get(),
+            InOut::Input(..) | InOut::Conflict(..) | InOut::Recovery(..)
+          )
+        {
+          return None
+        }
         data.change_state(in_out)
       },
       #[cfg(all(test, not(feature = "readline")))]
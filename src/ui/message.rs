@@ -3,10 +3,17 @@
 
 use std::rc::Rc;
 
+use anyhow::Result;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use crate::ser::tasks::Task as SerTask;
 use crate::tasks::Task;
 
 use super::event::Event;
 use super::in_out::InOut;
+use super::problems::ProblemText;
 use super::tab_bar::SearchState;
 use super::tab_bar::TabState;
 
@@ -28,14 +35,43 @@ pub enum Message {
   SearchTask(String, SearchState, bool, bool),
   /// Edit the tags associated with a task.
   EditTags(Rc<Task>, Task),
+  /// Show the given problems encountered while loading task files.
+  ShowProblems(Vec<ProblemText>),
+  /// Show the given tasks contained in a store, i.e., the trash or the
+  /// archive.
+  ShowStoredTasks(Vec<SerTask>),
+  /// Restore a task from a store.
+  RestoreTask(SerTask),
+  /// Merge changes made to task files by other programs.
+  ReloadTasks,
+  /// Restore the undo history saved in a previous session.
+  RestoreHistory,
+  /// Check the journal for task operations from a previous session that
+  /// never got saved.
+  CheckJournal,
+  /// Remember the currently selected task, so that it can be selected
+  /// again via `RestoreSelection` after tasks got reloaded.
+  SaveSelection,
+  /// Select the task remembered by `SaveSelection` again.
+  RestoreSelection,
   /// Update a task.
   UpdateTask(Rc<Task>, Task),
+  /// The notes of a task have been edited externally, with the given
+  /// result.
+  EditedNotes(Rc<Task>, Result<String>),
   /// Set the state of the input/output area.
   SetInOut(InOut),
   /// Text has been entered.
   EnteredText(String),
   /// Text input has been canceled.
   InputCanceled,
+  /// Check for reminders that fired since the last check, up to and
+  /// including the given time.
+  CheckReminders(DateTime<Utc>),
+  /// The response to the `CheckReminders` message, containing the time
+  /// of the next reminder to fire, if any, and an indication whether
+  /// the UI got updated.
+  CheckedReminders(Option<DateTime<Utc>>, bool),
   /// A message used to collect the state from the `TabBar`.
   CollectState,
   /// The response to the `CollectState` message.
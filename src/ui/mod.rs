@@ -7,8 +7,10 @@ mod event;
 mod in_out;
 mod message;
 mod modal;
+mod problems;
 mod selectable;
 mod state;
+mod store;
 mod tab_bar;
 mod task_list_box;
 mod term_renderer;
@@ -0,0 +1,173 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use async_trait::async_trait;
+
+use gui::derive::Widget;
+use gui::Cap;
+use gui::Handleable;
+use gui::Id;
+use gui::MutCap;
+use gui::Widget;
+
+use crate::state::Problem;
+
+use super::event::Event;
+use super::event::Key;
+use super::message::Message;
+use super::message::MessageExt;
+use super::modal::Modal;
+use super::selectable::Selectable;
+
+
+/// A textual description of a problem, as presented to the user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemText {
+  /// The path to the file that could not be loaded.
+  pub path: String,
+  /// The error encountered, including all its causes.
+  pub error: String,
+}
+
+impl From<&Problem> for ProblemText {
+  fn from(problem: &Problem) -> Self {
+    Self {
+      path: problem.path().display().to_string(),
+      error: format!("{:#}", problem.error()),
+    }
+  }
+}
+
+
+/// The data associated with a `ProblemsDialog` widget.
+#[derive(Debug)]
+pub struct ProblemsDialogData {
+  /// The ID of the previously focused widget, set while the dialog is
+  /// shown.
+  prev_focused: Option<Id>,
+  /// The problems to display.
+  problems: Vec<ProblemText>,
+  /// The currently selected problem.
+  selection: isize,
+}
+
+impl ProblemsDialogData {
+  pub fn new(problems: Vec<ProblemText>) -> Self {
+    Self {
+      prev_focused: None,
+      problems,
+      selection: 0,
+    }
+  }
+}
+
+impl Selectable for ProblemsDialogData {
+  fn selection_index(&self) -> isize {
+    self.selection
+  }
+
+  fn set_selection_index(&mut self, selection: isize) {
+    self.selection = selection
+  }
+
+  fn count(&self) -> usize {
+    self.problems.len()
+  }
+}
+
+
+/// A read-only modal dialog listing problems encountered while loading
+/// task files.
+#[derive(Debug, Widget)]
+#[gui(Event = Event, Message = Message)]
+pub struct ProblemsDialog {
+  id: Id,
+}
+
+impl ProblemsDialog {
+  /// Create a new `ProblemsDialog`.
+  ///
+  /// If there are any problems to report the dialog is shown right
+  /// away, otherwise it starts out hidden.
+  pub fn new(id: Id, cap: &mut dyn MutCap<Event, Message>) -> Self {
+    let dialog = Self { id };
+    if dialog.problems(cap).is_empty() {
+      cap.hide(id);
+    } else {
+      dialog.make_focused(cap);
+    }
+    dialog
+  }
+
+  /// Handle a key press.
+  fn handle_key(&self, cap: &mut dyn MutCap<Event, Message>, key: Key) -> Option<Message> {
+    let data = self.data_mut::<ProblemsDialogData>(cap);
+    match key {
+      Key::Esc | Key::Char('\n') | Key::Char('q') => {
+        let _widget = self.restore_focus(cap);
+        cap.hide(self.id);
+        Some(Message::Updated)
+      },
+      Key::Char('g') => MessageExt::maybe_update(None, data.select(0)),
+      Key::Char('G') => MessageExt::maybe_update(None, data.select(isize::MAX)),
+      Key::Char('j') => MessageExt::maybe_update(None, data.change_selection(1)),
+      Key::Char('k') => MessageExt::maybe_update(None, data.change_selection(-1)),
+      _ => None,
+    }
+  }
+
+  /// Retrieve the list of problems.
+  pub fn problems<'cap>(&self, cap: &'cap dyn Cap) -> &'cap [ProblemText] {
+    let data = self.data::<ProblemsDialogData>(cap);
+    &data.problems
+  }
+
+  /// Retrieve the current selection index.
+  ///
+  /// The selection index indicates the currently selected problem.
+  pub fn selection(&self, cap: &dyn Cap) -> usize {
+    let data = self.data::<ProblemsDialogData>(cap);
+    data.selection(0)
+  }
+}
+
+impl Modal for ProblemsDialog {
+  fn prev_focused(&self, cap: &dyn Cap) -> Option<Id> {
+    let data = self.data::<ProblemsDialogData>(cap);
+    data.prev_focused
+  }
+
+  fn set_prev_focused(&self, cap: &mut dyn MutCap<Event, Message>, focused: Option<Id>) {
+    let data = self.data_mut::<ProblemsDialogData>(cap);
+    data.prev_focused = focused
+  }
+}
+
+#[async_trait(?Send)]
+impl Handleable<Event, Message> for ProblemsDialog {
+  /// Handle an event.
+  async fn handle(&self, cap: &mut dyn MutCap<Event, Message>, event: Event) -> Option<Event> {
+    match event {
+      Event::Key(key, _raw) => self.handle_key(cap, key).into_event(),
+      _ => Some(event),
+    }
+  }
+
+  /// React to a message.
+  async fn react(&self, message: Message, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    match message {
+      Message::ShowProblems(problems) => {
+        if cap.is_focused(self.id) {
+          return None
+        }
+
+        let data = self.data_mut::<ProblemsDialogData>(cap);
+        data.problems = problems;
+        let _changed = data.select(0);
+        self.make_focused(cap);
+        Some(Message::Updated)
+      },
+      m => panic!("Received unexpected message: {:?}", m),
+    }
+  }
+}
@@ -6,6 +6,10 @@ use std::path::Path;
 use anyhow::Context as _;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::TimeZone as _;
+use chrono::Utc;
+
 use crate::cap::FileCap;
 use crate::ser::backends::Json;
 use crate::ser::state::UiState as SerUiState;
@@ -22,6 +26,8 @@ pub struct State {
   pub selected_tasks: Vec<Option<usize>>,
   /// The currently selected `View`.
   pub selected_view: Option<usize>,
+  /// The time up to which reminders have been checked, if known.
+  pub reminders_checked: Option<DateTime<Utc>>,
 }
 
 impl State {
@@ -40,6 +46,9 @@ impl State {
     Self {
       selected_tasks: state.selected_tasks,
       selected_view: state.selected_view,
+      reminders_checked: state
+        .reminders_checked
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single()),
     }
   }
 
@@ -57,6 +66,9 @@ impl ToSerde for State {
     let state = SerUiState {
       selected_tasks: self.selected_tasks.clone(),
       selected_view: self.selected_view,
+      // Truncating to full seconds is fine, as reminders never fire at
+      // a finer granularity.
+      reminders_checked: self.reminders_checked.map(|time| time.timestamp()),
     };
     state
   }
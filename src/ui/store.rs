@@ -0,0 +1,236 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use async_trait::async_trait;
+
+use gui::derive::Widget;
+use gui::Cap;
+use gui::Handleable;
+use gui::Id;
+use gui::MutCap;
+use gui::Widget;
+
+use crate::line::Line;
+use crate::ser::tasks::Task as SerTask;
+
+use super::event::Event;
+use super::event::Key;
+use super::in_out::InOut;
+use super::message::Message;
+use super::message::MessageExt;
+use super::modal::Modal;
+use super::selectable::Selectable;
+
+
+/// The data associated with a `StoreDialog` widget.
+#[derive(Debug)]
+pub struct StoreDialogData {
+  /// The ID of the previously focused widget, set while the dialog is
+  /// shown.
+  prev_focused: Option<Id>,
+  /// The tasks contained in the store.
+  tasks: Vec<SerTask>,
+  /// The search term tasks are filtered by, if any.
+  filter: Option<String>,
+  /// The indices of the tasks matching `filter`.
+  matches: Vec<usize>,
+  /// The currently selected task, as index into `matches`.
+  selection: isize,
+}
+
+impl StoreDialogData {
+  pub fn new() -> Self {
+    Self {
+      prev_focused: None,
+      tasks: Vec::new(),
+      filter: None,
+      matches: Vec::new(),
+      selection: 0,
+    }
+  }
+
+  /// Filter the tasks by the given search term, matched
+  /// case-insensitively against their summaries.
+  fn set_filter(&mut self, filter: Option<String>) {
+    let filter = filter.filter(|filter| !filter.is_empty());
+    self.matches = match &filter {
+      Some(filter) => {
+        let filter = filter.to_lowercase();
+        self
+          .tasks
+          .iter()
+          .enumerate()
+          .filter(|(_, task)| task.summary.to_lowercase().contains(&filter))
+          .map(|(i, _)| i)
+          .collect()
+      },
+      None => (0..self.tasks.len()).collect(),
+    };
+    self.filter = filter;
+    let _changed = self.select(0);
+  }
+}
+
+impl Selectable for StoreDialogData {
+  fn selection_index(&self) -> isize {
+    self.selection
+  }
+
+  fn set_selection_index(&mut self, selection: isize) {
+    self.selection = selection
+  }
+
+  fn count(&self) -> usize {
+    self.matches.len()
+  }
+}
+
+
+/// A modal dialog listing the tasks contained in a store, such as the
+/// trash or the archive, and allowing for searching and restoring them.
+#[derive(Debug, Widget)]
+#[gui(Event = Event, Message = Message)]
+pub struct StoreDialog {
+  id: Id,
+  /// The ID of the input/output area used for searching.
+  in_out: Id,
+  /// The ID of the widget in charge of restoring tasks.
+  restorer: Id,
+  /// The text to display when the store is empty.
+  empty_text: &'static str,
+}
+
+impl StoreDialog {
+  /// Create a new `StoreDialog`, initially hidden.
+  ///
+  /// Tasks to restore are sent to the widget with ID `restorer`.
+  pub fn new(
+    id: Id,
+    cap: &mut dyn MutCap<Event, Message>,
+    in_out: Id,
+    restorer: Id,
+    empty_text: &'static str,
+  ) -> Self {
+    cap.hide(id);
+    Self {
+      id,
+      in_out,
+      restorer,
+      empty_text,
+    }
+  }
+
+  /// Hide the dialog, giving the focus back to the previously focused
+  /// widget.
+  fn close(&self, cap: &mut dyn MutCap<Event, Message>) {
+    let _widget = self.restore_focus(cap);
+    cap.hide(self.id);
+  }
+
+  /// Handle a key press.
+  async fn handle_key(&self, cap: &mut dyn MutCap<Event, Message>, key: Key) -> Option<Message> {
+    let data = self.data_mut::<StoreDialogData>(cap);
+    match key {
+      Key::Esc | Key::Char('q') => {
+        let () = self.close(cap);
+        Some(Message::Updated)
+      },
+      Key::Char('\n') | Key::Char('r') => {
+        // With nothing to restore there is no reason to keep the dialog
+        // open.
+        if data.matches.is_empty() {
+          let () = self.close(cap);
+          return Some(Message::Updated)
+        }
+
+        let selection = data.selection(0);
+        let task = data.tasks.remove(data.matches[selection]);
+        let () = self.close(cap);
+        cap
+          .send(self.restorer, Message::RestoreTask(task))
+          .await
+          .maybe_update(true)
+      },
+      Key::Char('/') => {
+        let message = Message::SetInOut(InOut::Input(Line::default()));
+        cap.send(self.in_out, message).await
+      },
+      Key::Char('g') => MessageExt::maybe_update(None, data.select(0)),
+      Key::Char('G') => MessageExt::maybe_update(None, data.select(isize::MAX)),
+      Key::Char('j') => MessageExt::maybe_update(None, data.change_selection(1)),
+      Key::Char('k') => MessageExt::maybe_update(None, data.change_selection(-1)),
+      _ => None,
+    }
+  }
+
+  /// Retrieve the tasks to display, i.e., those matching the current
+  /// search term.
+  pub fn tasks<'cap>(&self, cap: &'cap dyn Cap) -> Vec<&'cap SerTask> {
+    let data = self.data::<StoreDialogData>(cap);
+    data.matches.iter().map(|i| &data.tasks[*i]).collect()
+  }
+
+  /// Retrieve the current selection index.
+  ///
+  /// The selection index indicates the currently selected task.
+  pub fn selection(&self, cap: &dyn Cap) -> usize {
+    let data = self.data::<StoreDialogData>(cap);
+    data.selection(0)
+  }
+
+  /// Check whether the tasks are currently filtered by a search term.
+  pub fn is_filtered(&self, cap: &dyn Cap) -> bool {
+    let data = self.data::<StoreDialogData>(cap);
+    data.filter.is_some()
+  }
+
+  /// Retrieve the text to display when the store is empty.
+  pub fn empty_text(&self) -> &'static str {
+    self.empty_text
+  }
+}
+
+impl Modal for StoreDialog {
+  fn prev_focused(&self, cap: &dyn Cap) -> Option<Id> {
+    let data = self.data::<StoreDialogData>(cap);
+    data.prev_focused
+  }
+
+  fn set_prev_focused(&self, cap: &mut dyn MutCap<Event, Message>, focused: Option<Id>) {
+    let data = self.data_mut::<StoreDialogData>(cap);
+    data.prev_focused = focused
+  }
+}
+
+#[async_trait(?Send)]
+impl Handleable<Event, Message> for StoreDialog {
+  /// Handle an event.
+  async fn handle(&self, cap: &mut dyn MutCap<Event, Message>, event: Event) -> Option<Event> {
+    match event {
+      Event::Key(key, _raw) => self.handle_key(cap, key).await.into_event(),
+      _ => Some(event),
+    }
+  }
+
+  /// React to a message.
+  async fn react(&self, message: Message, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    match message {
+      Message::ShowStoredTasks(tasks) => {
+        let data = self.data_mut::<StoreDialogData>(cap);
+        data.tasks = tasks;
+        let () = data.set_filter(None);
+        if !cap.is_focused(self.id) {
+          self.make_focused(cap);
+        }
+        Some(Message::Updated)
+      },
+      Message::EnteredText(filter) => {
+        let data = self.data_mut::<StoreDialogData>(cap);
+        let () = data.set_filter(Some(filter));
+        Some(Message::Updated)
+      },
+      Message::InputCanceled => None,
+      m => panic!("Received unexpected message: {:?}", m),
+    }
+  }
+}
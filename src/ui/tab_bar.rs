@@ -508,6 +508,27 @@ impl Handleable<Event, Message> for TabBar {
         let message = Message::CollectedState(tab_state);
         Some(message)
       },
+      Message::SaveSelection | Message::RestoreSelection => {
+        let data = self.data::<TabBarData>(cap);
+        let mut message = message;
+        let mut result = None;
+
+        let tabs = data
+          .tabs
+          .iter()
+          .map(|(_, id)| id)
+          .copied()
+          .collect::<Vec<_>>();
+        for tab in tabs {
+          let update = cap
+            .call(tab, &mut message)
+            .await
+            .map(|m| m.is_updated())
+            .unwrap_or(false);
+          result = MessageExt::maybe_update(result, update);
+        }
+        result
+      },
       Message::SelectTask(task_id, ..) => {
         let data = self.data::<TabBarData>(cap);
         let mut message = Message::SelectTask(task_id, false);
@@ -5,9 +5,18 @@ use std::cmp::min;
 use std::isize;
 use std::ops::Deref as _;
 use std::rc::Rc;
+use std::str::FromStr as _;
 
 use async_trait::async_trait;
 
+use chrono::Days;
+use chrono::Local;
+use chrono::Months;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone as _;
+use chrono::Utc;
+
 use gui::derive::Widget;
 use gui::Cap;
 use gui::Handleable;
@@ -16,6 +25,8 @@ use gui::MutCap;
 use gui::Widget;
 
 use crate::line::Line;
+use crate::priority::Priority;
+use crate::recurrence::Recurrence;
 use crate::tags::Tag;
 use crate::tasks::Task;
 use crate::tasks::Tasks;
@@ -31,11 +42,35 @@ use super::tab_bar::SearchState;
 use super::tab_bar::TabState;
 
 
+/// The format in which due dates are entered and displayed.
+pub(super) const DUE_DATE_FORMAT: &str = "%Y-%m-%d";
+/// The format in which reminder times are entered.
+const REMINDER_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+
+/// Resolve one of the presets that may be entered when snoozing a task
+/// into the date until which to snooze it.
+fn snooze_preset(preset: &str, today: NaiveDate) -> Option<NaiveDate> {
+  match preset.to_lowercase().as_str() {
+    "tomorrow" => today.checked_add_days(Days::new(1)),
+    "next week" => today.checked_add_days(Days::new(7)),
+    "next month" => today.checked_add_months(Months::new(1)),
+    _ => None,
+  }
+}
+
+
 /// An enum representing the state a `TaskListBox` can be in.
 #[derive(Debug)]
 enum State {
   Add,
   Edit { task: Rc<Task>, edited: Task },
+  EditDue { task: Rc<Task>, edited: Task },
+  EditStart { task: Rc<Task>, edited: Task },
+  EditRecurrence { task: Rc<Task>, edited: Task },
+  EditReminder { task: Rc<Task>, edited: Task },
+  ToggleDependency { task: Rc<Task>, edited: Task },
+  MoveToCollection { task: Rc<Task>, edited: Task },
 }
 
 
@@ -49,8 +84,14 @@ pub struct TaskListBoxData {
   toggle_tag: Option<Tag>,
   /// The currently selected task.
   selection: isize,
+  /// The task selected at the time a `SaveSelection` message was
+  /// received.
+  saved_selection: Option<Rc<Task>>,
   /// The state the `TaskListBox` is in.
   state: Option<State>,
+  /// A task with sub-tasks that the user asked to remove, pending
+  /// confirmation by repeating the action.
+  confirm_remove: Option<Rc<Task>>,
 }
 
 impl TaskListBoxData {
@@ -61,7 +102,9 @@ impl TaskListBoxData {
       view,
       toggle_tag,
       selection: 0,
+      saved_selection: None,
       state: None,
+      confirm_remove: None,
     }
   }
 
@@ -273,6 +316,8 @@ impl Handleable<Event, Message> for TaskListBox {
   /// Check for new input and react to it.
   async fn handle(&self, cap: &mut dyn MutCap<Event, Message>, event: Event) -> Option<Event> {
     let data = self.data_mut::<TaskListBoxData>(cap);
+    // A removal only counts as confirmed if repeated right away.
+    let confirm_remove = data.confirm_remove.take();
     match event {
       Event::Key(key, _) => match key {
         Key::Char(' ') => {
@@ -301,8 +346,18 @@ impl Handleable<Event, Message> for TaskListBox {
         },
         Key::Char('d') => {
           if let Some(task) = data.selected_task() {
-            data.tasks.remove(task);
-            MessageExt::maybe_update(None, true).into_event()
+            // Removing a task removes its sub-tasks as well, which the
+            // user has to confirm.
+            let confirmed = confirm_remove.map_or(false, |other| Rc::ptr_eq(&other, &task));
+            if data.tasks.has_children(&task) && !confirmed {
+              data.confirm_remove = Some(task);
+              let error = "task has sub-tasks; repeat action to remove them as well".to_string();
+              let message = Message::SetInOut(InOut::Error(error));
+              cap.send(self.in_out, message).await.into_event()
+            } else {
+              data.tasks.remove(task);
+              MessageExt::maybe_update(None, true).into_event()
+            }
           } else {
             None
           }
@@ -321,6 +376,125 @@ impl Handleable<Event, Message> for TaskListBox {
             None
           }
         },
+        Key::Char('D') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = task.deref().clone();
+            let string = edited
+              .due()
+              .map(|due| due.format(DUE_DATE_FORMAT).to_string())
+              .unwrap_or_default();
+            data.state = Some(State::EditDue { task, edited });
+
+            let line = Line::from_string(string).select_end();
+            let message = Message::SetInOut(InOut::Input(line));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('s') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = task.deref().clone();
+            let string = edited
+              .start()
+              .map(|start| start.format(DUE_DATE_FORMAT).to_string())
+              .unwrap_or_default();
+            data.state = Some(State::EditStart { task, edited });
+
+            let line = Line::from_string(string).select_end();
+            let message = Message::SetInOut(InOut::Input(line));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('R') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = task.deref().clone();
+            let string = edited.recurrence().unwrap_or_default();
+            data.state = Some(State::EditRecurrence { task, edited });
+
+            let line = Line::from_string(string).select_end();
+            let message = Message::SetInOut(InOut::Input(line));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('A') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = task.deref().clone();
+            let string = edited
+              .reminders()
+              .into_iter()
+              .min()
+              .map(|time| time.with_timezone(&Local).format(REMINDER_FORMAT).to_string())
+              .unwrap_or_default();
+            data.state = Some(State::EditReminder { task, edited });
+
+            let line = Line::from_string(string).select_end();
+            let message = Message::SetInOut(InOut::Input(line));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('b') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = task.deref().clone();
+            data.state = Some(State::ToggleDependency { task, edited });
+
+            let message = Message::SetInOut(InOut::Input(Line::default()));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('c') => {
+          if let Some(task) = data.selected_task() {
+            // Make a deep copy of the task.
+            let edited = task.deref().clone();
+            let string = edited.collection().unwrap_or_default();
+            data.state = Some(State::MoveToCollection { task, edited });
+
+            let line = Line::from_string(string).select_end();
+            let message = Message::SetInOut(InOut::Input(line));
+            cap.send(self.in_out, message).await.into_event()
+          } else {
+            None
+          }
+        },
+        Key::Char('E') => data
+          .selected_task()
+          .map(|task| Event::EditNotes(self.id, task)),
+        Key::Char('+') | Key::Char('-') => {
+          if let Some(task) = data.selected_task() {
+            let priority = if key == Key::Char('+') {
+              Priority::raise(task.priority())
+            } else {
+              Priority::lower(task.priority())
+            };
+
+            if priority != task.priority() {
+              // Make a deep copy of the task to work on.
+              let mut updated = task.deref().clone();
+              let () = updated.set_priority(priority);
+              cap
+                .send(self.id, Message::UpdateTask(task, updated))
+                .await
+                .into_event()
+            } else {
+              None
+            }
+          } else {
+            None
+          }
+        },
         Key::Char('t') => {
           if let Some(task) = data.selected_task() {
             // Make a deep copy of the task to work on.
@@ -333,12 +507,19 @@ impl Handleable<Event, Message> for TaskListBox {
         },
         Key::Char('J') => {
           if let Some(to_move) = data.selected_task() {
-            let other = data
-              .view
-              .iter(|mut iter| iter.nth(data.selection(1)).cloned());
+            // Tasks only ever move among their siblings, taking their
+            // sub-tasks with them.
+            let depth = to_move.depth();
+            let other = data.view.iter(|iter| {
+              iter
+                .skip(data.selection(0) + 1)
+                .take_while(|task| task.depth() >= depth)
+                .find(|task| task.is_sibling_of(&to_move))
+                .cloned()
+            });
             if let Some(other) = other {
-              data.tasks.move_after(to_move, other);
-              MessageExt::maybe_update(None, data.change_selection(1)).into_event()
+              data.tasks.move_after(to_move.clone(), other);
+              self.select_task(cap, to_move).await.into_event()
             } else {
               None
             }
@@ -348,16 +529,48 @@ impl Handleable<Event, Message> for TaskListBox {
         },
         Key::Char('K') => {
           if let Some(to_move) = data.selected_task() {
-            if data.selection(0) > 0 {
-              let other = data
-                .view
-                .iter(|mut iter| iter.nth(data.selection(-1)).cloned());
-              if let Some(other) = other {
-                data.tasks.move_before(to_move, other);
-                MessageExt::maybe_update(None, data.change_selection(-1)).into_event()
-              } else {
-                None
-              }
+            let depth = to_move.depth();
+            let other = data.view.iter(|iter| {
+              let preceding = iter.take(data.selection(0)).collect::<Vec<_>>();
+              preceding
+                .into_iter()
+                .rev()
+                .take_while(|task| task.depth() >= depth)
+                .find(|task| task.is_sibling_of(&to_move))
+                .cloned()
+            });
+            if let Some(other) = other {
+              data.tasks.move_before(to_move.clone(), other);
+              self.select_task(cap, to_move).await.into_event()
+            } else {
+              None
+            }
+          } else {
+            None
+          }
+        },
+        Key::Char('>') | Key::Char('<') => {
+          if let Some(task) = data.selected_task() {
+            let changed = if key == Key::Char('>') {
+              data.tasks.indent(task.clone())
+            } else {
+              data.tasks.outdent(task.clone())
+            };
+
+            if changed {
+              self.select_task(cap, task).await.maybe_update(true).into_event()
+            } else {
+              None
+            }
+          } else {
+            None
+          }
+        },
+        Key::Char('z') => {
+          if let Some(task) = data.selected_task() {
+            if data.tasks.has_children(&task) {
+              let () = data.tasks.set_collapsed(&task, !task.is_collapsed());
+              MessageExt::maybe_update(None, true).into_event()
             } else {
               None
             }
@@ -443,12 +656,216 @@ impl Handleable<Event, Message> for TaskListBox {
                 Some(Message::Updated)
               }
             },
+            State::EditDue { task, mut edited } => {
+              // An empty input clears the due date.
+              let due = if !text.is_empty() {
+                match NaiveDate::parse_from_str(text, DUE_DATE_FORMAT) {
+                  Ok(due) => Some(due),
+                  Err(err) => {
+                    let error = format!("Invalid due date '{text}': {err}");
+                    let message = Message::SetInOut(InOut::Error(error));
+                    return cap.send(self.in_out, message).await
+                  },
+                }
+              } else {
+                None
+              };
+
+              edited.set_due(due);
+              data.tasks.update(task.clone(), edited);
+              self.select_task(cap, task).await.maybe_update(true)
+            },
+            State::EditStart { task, mut edited } => {
+              // An empty input clears the start date, waking up the
+              // task.
+              let start = if !text.is_empty() {
+                let today = Local::now().date_naive();
+                let start = snooze_preset(text, today)
+                  .map(Ok)
+                  .unwrap_or_else(|| NaiveDate::parse_from_str(text, DUE_DATE_FORMAT));
+                match start {
+                  Ok(start) => Some(start),
+                  Err(err) => {
+                    let error = format!("Invalid snooze date '{text}': {err}");
+                    let message = Message::SetInOut(InOut::Error(error));
+                    return cap.send(self.in_out, message).await
+                  },
+                }
+              } else {
+                None
+              };
+
+              edited.set_start(start);
+              data.tasks.update(task.clone(), edited);
+              self.select_task(cap, task).await.maybe_update(true)
+            },
+            State::EditRecurrence { task, mut edited } => {
+              // An empty input clears the recurrence rule.
+              let recurrence = if !text.is_empty() {
+                match Recurrence::from_str(text) {
+                  Ok(recurrence) => Some(recurrence.to_string()),
+                  Err(err) => {
+                    let error = format!("Invalid recurrence rule '{text}': {err}");
+                    let message = Message::SetInOut(InOut::Error(error));
+                    return cap.send(self.in_out, message).await
+                  },
+                }
+              } else {
+                None
+              };
+
+              edited.set_recurrence(recurrence);
+              data.tasks.update(task.clone(), edited);
+              self.select_task(cap, task).await.maybe_update(true)
+            },
+            State::EditReminder { task, mut edited } => {
+              // An empty input clears all reminders.
+              let reminder = if !text.is_empty() {
+                let reminder = NaiveDateTime::parse_from_str(text, REMINDER_FORMAT)
+                  .ok()
+                  .and_then(|time| Local.from_local_datetime(&time).earliest());
+                match reminder {
+                  Some(reminder) => Some(reminder.with_timezone(&Utc)),
+                  None => {
+                    let error = format!("Invalid reminder time '{text}'");
+                    let message = Message::SetInOut(InOut::Error(error));
+                    return cap.send(self.in_out, message).await
+                  },
+                }
+              } else {
+                None
+              };
+
+              edited.set_reminder(reminder);
+              data.tasks.update(task.clone(), edited);
+              self.select_task(cap, task).await.maybe_update(true)
+            },
+            State::ToggleDependency { task, mut edited } => {
+              // An empty input cancels the operation.
+              if text.is_empty() {
+                return Some(Message::Updated)
+              }
+
+              // The other task has to be identified unambiguously: by
+              // its exact summary or, failing that, by text that only
+              // its summary contains.
+              let lowercase = text.to_lowercase();
+              let matches = data.tasks.iter(|iter| {
+                let candidates = iter
+                  .filter(|other| !Rc::ptr_eq(other, &task))
+                  .cloned()
+                  .collect::<Vec<_>>();
+                let exact = candidates
+                  .iter()
+                  .filter(|other| other.summary() == *text)
+                  .cloned()
+                  .collect::<Vec<_>>();
+                if !exact.is_empty() {
+                  exact
+                } else {
+                  candidates
+                    .into_iter()
+                    .filter(|other| other.summary().to_lowercase().contains(&lowercase))
+                    .collect()
+                }
+              });
+
+              let result = match matches.as_slice() {
+                [other] => {
+                  let mut depends_on = edited.depends_on();
+                  if let Some(idx) = depends_on.iter().position(|dep| Rc::ptr_eq(dep, other)) {
+                    let _removed = depends_on.remove(idx);
+                    Ok(depends_on)
+                  } else if other.depends_on_task(&task) {
+                    Err(format!(
+                      "Task '{}' already depends on the selected task",
+                      other.summary()
+                    ))
+                  } else {
+                    let () = depends_on.push(other.clone());
+                    Ok(depends_on)
+                  }
+                },
+                [] => Err(format!("No task matching '{text}' found")),
+                [..] => Err(format!("Multiple tasks match '{text}'")),
+              };
+
+              let depends_on = match result {
+                Ok(depends_on) => depends_on,
+                Err(error) => {
+                  let message = Message::SetInOut(InOut::Error(error));
+                  return cap.send(self.in_out, message).await
+                },
+              };
+
+              edited.set_depends_on(depends_on);
+              data.tasks.update(task.clone(), edited);
+              self.select_task(cap, task).await.maybe_update(true)
+            },
+            State::MoveToCollection { task, mut edited } => {
+              // An empty input moves the task to the default
+              // collection. Collections are directories, so we only
+              // accept names that map to a visible one directly
+              // inside the tasks directory.
+              let collection = if !text.is_empty() {
+                if text.contains('/') || text.starts_with('.') {
+                  let error = format!("Invalid collection name '{text}'");
+                  let message = Message::SetInOut(InOut::Error(error));
+                  return cap.send(self.in_out, message).await
+                }
+                Some(text.clone())
+              } else {
+                None
+              };
+
+              edited.set_collection(collection);
+              data.tasks.update(task.clone(), edited);
+              self.select_task(cap, task).await.maybe_update(true)
+            },
           }
         } else {
           cap.send(self.tab_bar, message).await
         }
       },
+      Message::EditedNotes(task, result) => match result {
+        Ok(notes) => {
+          // Editors commonly add a trailing line break; we don't want
+          // to persist that. Notes containing only white spaces are
+          // treated as no notes at all.
+          let notes = notes.replace('\r', "");
+          let notes = notes.trim_end();
+          let notes = (!notes.is_empty()).then(|| notes.to_string());
+
+          if notes != task.notes() {
+            // Make a deep copy of the task.
+            let mut updated = task.deref().clone();
+            updated.set_notes(notes);
+            data.tasks.update(task.clone(), updated);
+            self.select_task(cap, task).await.maybe_update(true)
+          } else {
+            None
+          }
+        },
+        Err(err) => {
+          let error = format!("Failed to edit notes: {err:#}");
+          let message = Message::SetInOut(InOut::Error(error));
+          cap.send(self.in_out, message).await
+        },
+      },
       Message::UpdateTask(task, updated) => {
+        // Completing a recurring task advances it to its next
+        // occurrence, which requires us to understand its rule. We do
+        // not want to silently end a series that we cannot advance.
+        if let Some(toggle_tag) = &data.toggle_tag {
+          if !task.has_tag(toggle_tag) && updated.has_tag(toggle_tag) {
+            if let Some(Err(err)) = updated.recurrence().as_deref().map(Recurrence::from_str) {
+              let error = format!("Failed to advance recurring task: {err:#}");
+              let message = Message::SetInOut(InOut::Error(error));
+              return cap.send(self.in_out, message).await
+            }
+          }
+        }
+
         data.tasks.update(task.clone(), updated);
 
         // Try to select the task now that something may have changed
@@ -484,6 +901,23 @@ impl Handleable<Event, Message> for TaskListBox {
           .handle_search_task(cap, string, search_state, *reverse, *exact)
           .await
       },
+      Message::SaveSelection => {
+        let data = self.data_mut::<TaskListBoxData>(cap);
+        data.saved_selection = data.selected_task();
+        None
+      },
+      Message::RestoreSelection => {
+        let data = self.data_mut::<TaskListBoxData>(cap);
+        // Tasks may have been replaced entirely, so we have to look
+        // them up by ID. If the task is gone, the selection just stays
+        // at its index.
+        let id = data.saved_selection.take()?.id();
+        let idx = data
+          .view
+          .iter(|mut iter| iter.position(|task| task.id() == id))?;
+        let update = data.select(idx as isize);
+        MessageExt::maybe_update(None, update)
+      },
       Message::GetTabState(ref mut tab_state) => {
         let TabState { ref mut views, .. } = tab_state;
         let data = self.data::<TaskListBoxData>(cap);
@@ -17,8 +17,13 @@ use termion::color::Reset;
 use termion::cursor::Goto;
 use termion::cursor::Hide;
 use termion::cursor::Show;
+use termion::raw::RawTerminal;
+use termion::screen::ToAlternateScreen;
+use termion::screen::ToMainScreen;
 use termion::terminal_size;
 
+use unicode_width::UnicodeWidthStr as _;
+
 use gui::BBox;
 use gui::Cap;
 use gui::Id;
@@ -28,28 +33,41 @@ use gui::Renderer;
 
 use crate::colors::Color;
 use crate::colors::Colors;
+use crate::priority::Priority;
 
 use super::dialog::Dialog;
 use super::dialog::SetUnsetTag;
 use super::in_out::InOut;
 use super::in_out::InOutArea;
+use super::problems::ProblemsDialog;
+use super::store::StoreDialog;
 use super::tab_bar::TabBar;
 use super::task_list_box::TaskListBox;
+use super::task_list_box::DUE_DATE_FORMAT;
 use super::termui::TermUi;
 
 const TASK_LIST_MARGIN_X: u16 = 3;
 const TASK_LIST_MARGIN_Y: u16 = 2;
 const TASK_SPACE: u16 = 2;
+const TASK_INDENT: u16 = 4;
+const TASK_COLLAPSED: &str = "+";
 const TAG_SPACE: u16 = 2;
+const PROBLEM_SPACE: u16 = 3;
 const TAB_TITLE_WIDTH: u16 = 30;
 const DIALOG_MARGIN_X: u16 = 2;
 const DIALOG_MARGIN_Y: u16 = 1;
 const DIALOG_MIN_W: u16 = 40;
 const DIALOG_MIN_H: u16 = 20;
+const NO_PROBLEMS_TEXT: &str = "No problems encountered.";
+const NO_MATCHES_TEXT: &str = "No matching tasks.";
 
 const SAVED_TEXT: &str = " Saved ";
+const SYNCED_TEXT: &str = " Synced ";
 const SEARCH_TEXT: &str = " Search ";
+const REMINDER_TEXT: &str = " Reminder ";
 const ERROR_TEXT: &str = " Error ";
+const CONFLICT_TEXT: &str = " Conflict ";
+const RECOVERY_TEXT: &str = " Recovery ";
 const INPUT_TEXT: &str = " > ";
 
 
@@ -191,6 +209,24 @@ where
   fn show(&self) -> Result<()> {
     write!(self.writer.borrow_mut(), "{}", Show)
   }
+
+  /// Switch to the terminal's main screen.
+  fn to_main_screen(&self) -> Result<()> {
+    write!(self.writer.borrow_mut(), "{}", ToMainScreen)
+  }
+
+  /// Switch to the terminal's alternate screen.
+  fn to_alternate_screen(&self) -> Result<()> {
+    write!(self.writer.borrow_mut(), "{}", ToAlternateScreen)
+  }
+
+  /// Invoke a function on the wrapped writer.
+  fn with_writer<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&W) -> R,
+  {
+    f(&self.writer.borrow())
+  }
 }
 
 
@@ -211,6 +247,16 @@ fn displayable_tags(bbox: BBox) -> usize {
   ((bbox.h.saturating_sub(2 * DIALOG_MARGIN_Y)) / TAG_SPACE) as usize
 }
 
+/// Retrieve the number of problems that fit in the given `BBox`.
+fn displayable_problems(bbox: BBox) -> usize {
+  ((bbox.h.saturating_sub(2 * DIALOG_MARGIN_Y)) / PROBLEM_SPACE) as usize
+}
+
+/// Retrieve the number of stored tasks that fit in the given `BBox`.
+fn displayable_stored_tasks(bbox: BBox) -> usize {
+  bbox.h.saturating_sub(2 * DIALOG_MARGIN_Y) as usize
+}
+
 /// Retrieve the number of tabs that fit in the given `BBox`.
 fn displayable_tabs(width: u16) -> usize {
   (width / TAB_TITLE_WIDTH) as usize
@@ -366,6 +412,8 @@ where
 
         let (task_fg, task_bg) = if i == selection {
           (self.colors.selected_task_fg, self.colors.selected_task_bg)
+        } else if task.is_blocked() {
+          (self.colors.task_blocked_fg, self.colors.task_blocked_bg)
         } else {
           (
             self.colors.unselected_task_fg,
@@ -373,9 +421,41 @@ where
           )
         };
 
+        let x = x + task.depth() as u16 * TASK_INDENT;
+        if task.is_collapsed() {
+          let marker_x = x - TASK_COLLAPSED.len() as u16 - 1;
+          self.writer.write(marker_x, y, task_fg, task_bg, TASK_COLLAPSED)?;
+        }
+
         self.writer.write(x, y, state_fg, state_bg, state)?;
-        let x = x + state.len() as u16 + 1;
-        self.writer.write(x, y, task_fg, task_bg, task.summary())?;
+        let mut x = x + state.len() as u16 + 1;
+
+        if let Some(priority) = task.priority() {
+          let priority = match priority {
+            Priority::Low => "!",
+            Priority::Medium => "!!",
+            Priority::High => "!!!",
+          };
+          let fg = self.colors.task_priority_fg;
+          let bg = self.colors.task_priority_bg;
+          self.writer.write(x, y, fg, bg, priority)?;
+          x += priority.len() as u16 + 1;
+        }
+
+        let summary = task.summary();
+        self.writer.write(x, y, task_fg, task_bg, &summary)?;
+
+        let mut suffix_x = x + summary.width() as u16 + 1;
+        if let Some(due) = task.due() {
+          let due = format!("(due {})", due.format(DUE_DATE_FORMAT));
+          self.writer.write(suffix_x, y, task_fg, task_bg, &due)?;
+          suffix_x += due.width() as u16 + 1;
+        }
+
+        if let Some(start) = task.start().filter(|_| task.is_snoozed()) {
+          let start = format!("(snoozed until {})", start.format(DUE_DATE_FORMAT));
+          self.writer.write(suffix_x, y, task_fg, task_bg, start)?;
+        }
 
         if i == selection && cap.is_focused(task_list.id()) {
           cursor = Some((x, y));
@@ -493,6 +573,105 @@ where
     Ok(bbox)
   }
 
+  /// Render a full line of a textual dialog, containing the given text.
+  fn render_dialog_text_line(
+    &self,
+    text: &str,
+    y: u16,
+    w: u16,
+    fg: Color,
+    bg: Color,
+  ) -> Result<()> {
+    let () = self.fill_dialog_line(0, y, DIALOG_MARGIN_X)?;
+    let () = self.writer.write(DIALOG_MARGIN_X, y, fg, bg, text)?;
+    self.fill_dialog_line(DIALOG_MARGIN_X + text.width() as u16, y, w)
+  }
+
+  /// Render a `ProblemsDialog`.
+  fn render_problems(&self, dialog: &ProblemsDialog, cap: &dyn Cap, bbox: BBox) -> Result<BBox> {
+    let mut map = self.data.borrow_mut();
+    let data = map.entry(dialog.id()).or_default();
+
+    let problems = dialog.problems(cap);
+    let limit = displayable_problems(bbox);
+    let selection = dialog.selection(cap);
+    let offset = sanitize_offset(data.offset, selection, limit);
+
+    let (fg, bg) = (self.colors.dialog_fg, self.colors.dialog_bg);
+
+    (0..bbox.h).try_for_each(|y| {
+      if y < DIALOG_MARGIN_Y || y >= bbox.h - DIALOG_MARGIN_Y {
+        return self.fill_dialog_line(0, y, bbox.w)
+      }
+
+      // Each problem is displayed as the path of the affected file,
+      // followed by the error and an empty line.
+      let line = y - DIALOG_MARGIN_Y;
+      let i = offset + (line / PROBLEM_SPACE) as usize;
+      match (problems.get(i), line % PROBLEM_SPACE) {
+        (Some(problem), 0) if i == selection => self.render_dialog_text_line(
+          &problem.path,
+          y,
+          bbox.w,
+          self.colors.dialog_selected_tag_fg,
+          self.colors.dialog_selected_tag_bg,
+        ),
+        (Some(problem), 0) => self.render_dialog_text_line(&problem.path, y, bbox.w, fg, bg),
+        (Some(problem), 1) => self.render_dialog_text_line(&problem.error, y, bbox.w, fg, bg),
+        (None, 0) if problems.is_empty() && line == 0 => {
+          self.render_dialog_text_line(NO_PROBLEMS_TEXT, y, bbox.w, fg, bg)
+        },
+        _ => self.fill_dialog_line(0, y, bbox.w),
+      }
+    })?;
+
+    data.offset = offset;
+    Ok(bbox)
+  }
+
+  /// Render a `StoreDialog`.
+  fn render_store(&self, dialog: &StoreDialog, cap: &dyn Cap, bbox: BBox) -> Result<BBox> {
+    let mut map = self.data.borrow_mut();
+    let data = map.entry(dialog.id()).or_default();
+
+    let tasks = dialog.tasks(cap);
+    let limit = displayable_stored_tasks(bbox);
+    let selection = dialog.selection(cap);
+    let offset = sanitize_offset(data.offset, selection, limit);
+
+    let (fg, bg) = (self.colors.dialog_fg, self.colors.dialog_bg);
+
+    (0..bbox.h).try_for_each(|y| {
+      if y < DIALOG_MARGIN_Y || y >= bbox.h - DIALOG_MARGIN_Y {
+        return self.fill_dialog_line(0, y, bbox.w)
+      }
+
+      let i = offset + (y - DIALOG_MARGIN_Y) as usize;
+      match tasks.get(i) {
+        Some(task) if i == selection => self.render_dialog_text_line(
+          &task.summary,
+          y,
+          bbox.w,
+          self.colors.dialog_selected_tag_fg,
+          self.colors.dialog_selected_tag_bg,
+        ),
+        Some(task) => self.render_dialog_text_line(&task.summary, y, bbox.w, fg, bg),
+        None if tasks.is_empty() && i == 0 => {
+          let text = if dialog.is_filtered(cap) {
+            NO_MATCHES_TEXT
+          } else {
+            dialog.empty_text()
+          };
+          self.render_dialog_text_line(text, y, bbox.w, fg, bg)
+        },
+        None => self.fill_dialog_line(0, y, bbox.w),
+      }
+    })?;
+
+    data.offset = offset;
+    Ok(bbox)
+  }
+
   /// Render an `InOutArea`.
   fn render_input_output(&self, in_out: &InOutArea, cap: &dyn Cap, bbox: BBox) -> Result<BBox> {
     let (prefix, fg, bg, string) = match in_out.state(cap) {
@@ -502,18 +681,42 @@ where
         self.colors.in_out_success_bg,
         None,
       ),
+      InOut::Synced(ref s) => (
+        SYNCED_TEXT,
+        self.colors.in_out_success_fg,
+        self.colors.in_out_success_bg,
+        Some(s.as_ref()),
+      ),
       InOut::Search(ref s) => (
         SEARCH_TEXT,
         self.colors.in_out_status_fg,
         self.colors.in_out_status_bg,
         Some(s.as_ref()),
       ),
+      InOut::Reminder(ref s) => (
+        REMINDER_TEXT,
+        self.colors.in_out_status_fg,
+        self.colors.in_out_status_bg,
+        Some(s.as_ref()),
+      ),
       InOut::Error(ref e) => (
         ERROR_TEXT,
         self.colors.in_out_error_fg,
         self.colors.in_out_error_bg,
         Some(e.as_ref()),
       ),
+      InOut::Conflict(ref s) => (
+        CONFLICT_TEXT,
+        self.colors.in_out_error_fg,
+        self.colors.in_out_error_bg,
+        Some(s.as_ref()),
+      ),
+      InOut::Recovery(ref s) => (
+        RECOVERY_TEXT,
+        self.colors.in_out_error_fg,
+        self.colors.in_out_error_bg,
+        Some(s.as_ref()),
+      ),
       InOut::Input(ref line) => (
         INPUT_TEXT,
         self.colors.in_out_success_fg,
@@ -569,6 +772,33 @@ where
   }
 }
 
+impl<W> TermRenderer<RawTerminal<W>>
+where
+  W: Write,
+{
+  /// Temporarily hand the terminal back, e.g., to run another
+  /// interactive program.
+  pub fn suspend(&self) -> Result<()> {
+    let () = self.writer.show()?;
+    let () = self.writer.to_main_screen()?;
+    let () = self.writer.flush()?;
+    self
+      .writer
+      .with_writer(|writer| writer.get_ref().suspend_raw_mode())
+  }
+
+  /// Take over the terminal again after it was suspended.
+  ///
+  /// Note that a full render is necessary afterwards.
+  pub fn resume(&self) -> Result<()> {
+    let () = self
+      .writer
+      .with_writer(|writer| writer.get_ref().activate_raw_mode())?;
+    let () = self.writer.to_alternate_screen()?;
+    self.writer.flush()
+  }
+}
+
 impl<W> Renderer for TermRenderer<W>
 where
   W: Write,
@@ -607,6 +837,26 @@ where
       self.writer.restrict(bbox);
 
       self.render_dialog(dialog, cap, bbox)
+    } else if let Some(problems) = widget.downcast_ref::<ProblemsDialog>() {
+      let w = max(DIALOG_MIN_W, bbox.w / 2);
+      let h = max(DIALOG_MIN_H, bbox.h / 2);
+      let x = w / 2;
+      let y = h / 2;
+
+      let bbox = BBox { x, y, w, h };
+      self.writer.restrict(bbox);
+
+      self.render_problems(problems, cap, bbox)
+    } else if let Some(store) = widget.downcast_ref::<StoreDialog>() {
+      let w = max(DIALOG_MIN_W, bbox.w / 2);
+      let h = max(DIALOG_MIN_H, bbox.h / 2);
+      let x = w / 2;
+      let y = h / 2;
+
+      let bbox = BBox { x, y, w, h };
+      self.writer.restrict(bbox);
+
+      self.render_store(store, cap, bbox)
     } else if let Some(in_out) = widget.downcast_ref::<InOutArea>() {
       self.render_input_output(in_out, cap, bbox)
     } else if let Some(tab_bar) = widget.downcast_ref::<TabBar>() {
@@ -1,17 +1,25 @@
 // Copyright (C) 2017-2023 Daniel Mueller (deso@posteo.net)
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::collections::HashSet;
 use std::ffi::OsString;
 use std::future::Future;
 use std::iter::repeat;
+use std::mem::replace;
 use std::pin::Pin;
+use std::thread;
 
+use anyhow::anyhow;
 use anyhow::Context as _;
 use anyhow::Result;
 
 use async_trait::async_trait;
 
+use chrono::DateTime;
+use chrono::Utc;
+
 use gui::derive::Widget;
+use gui::Cap;
 use gui::Handleable;
 use gui::Id;
 use gui::MutCap;
@@ -19,7 +27,21 @@ use gui::Widget;
 
 use crate::cap::DirCap;
 use crate::colors::Colors;
+use crate::reminder::due_reminders;
+use crate::reminder::notify;
+use crate::ser::backends::Json;
+use crate::ser::history::History as SerHistory;
+use crate::ser::journal::Entry as JournalEntry;
+use crate::ser::tasks::Task as SerTask;
+use crate::ser::sync::SyncConfig;
+use crate::state::append_journal;
+use crate::state::load_journal;
+use crate::state::load_state_from_file;
+use crate::state::save_state_to_file;
+use crate::state::Conflict;
+use crate::state::Resolution;
 use crate::state::TaskState;
+use crate::sync::sync;
 use crate::tags::Tag;
 #[cfg(all(test, not(feature = "readline")))]
 use crate::tasks::Task;
@@ -35,7 +57,13 @@ use super::in_out::InOutArea;
 use super::in_out::InOutAreaData;
 use super::message::Message;
 use super::message::MessageExt as _;
+use super::modal::Modal;
+use super::problems::ProblemText;
+use super::problems::ProblemsDialog;
+use super::problems::ProblemsDialogData;
 use super::state::State;
+use super::store::StoreDialog;
+use super::store::StoreDialogData;
 use super::tab_bar::TabBar;
 use super::tab_bar::TabBarData;
 use super::tab_bar::TabState;
@@ -45,6 +73,12 @@ use super::tab_bar::TabState;
 const CHAR_QUIT: char = 'q';
 /// The key used for quitting the program.
 const KEY_QUIT: Key = Key::Char(CHAR_QUIT);
+/// The name of the file, in the UI state directory, in which we journal
+/// task operations that have not been saved yet.
+const JOURNAL_FILE: &str = "journal.jsonl";
+/// The name of the file, in the UI state directory, in which we save
+/// the history of operations that can be undone and redone.
+const HISTORY_FILE: &str = "undo-history.json";
 
 
 /// The data associated with a `TermUi`.
@@ -65,12 +99,37 @@ pub struct TermUiData {
   colors: Colors,
   /// The tag to toggle on user initiated action.
   toggle_tag: Option<Tag>,
+  /// The command to run when a reminder fires.
+  reminder_command: Option<String>,
+  /// The configuration of task synchronization, if any.
+  sync: Option<SyncConfig>,
+  /// The number of days after which completed tasks get archived, if
+  /// at all.
+  archive_after_days: Option<u32>,
+  /// The maximum number of operations that can be undone, if different
+  /// from the default.
+  max_undo_steps: Option<usize>,
   /// Flag indicating whether we showed an "unsaved changes" warning to
   /// the user.
   displayed_unsaved_changes_warning: bool,
+  /// Conflicting external modifications of task files that the user
+  /// has yet to resolve before we can save.
+  conflicts: Vec<Conflict>,
+  /// The ID of the widget that was focused before we asked the user to
+  /// resolve conflicts.
+  prev_focused: Option<Id>,
+  /// Flag indicating that task files changed on disk but that we have
+  /// yet to reload them, because the user was busy editing.
+  reload_pending: bool,
+  /// Journaled operations from a previous session that the user has yet
+  /// to decide whether to replay.
+  replay: Vec<JournalEntry>,
+  /// The time up to which we have checked for reminders to fire.
+  reminders_checked: DateTime<Utc>,
 }
 
 impl TermUiData {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     tasks_dir_cap: DirCap,
     task_state: TaskState,
@@ -78,7 +137,18 @@ impl TermUiData {
     ui_state_path: (DirCap, OsString),
     colors: Colors,
     toggle_tag: Option<Tag>,
+    reminder_command: Option<String>,
+    sync: Option<SyncConfig>,
+    archive_after_days: Option<u32>,
+    max_undo_steps: Option<usize>,
   ) -> Self {
+    // The tag the user toggles is the one marking a task as completed.
+    // Make sure that task completion is persisted accordingly.
+    let () = task_state.tasks().set_completion_tag(toggle_tag.clone());
+    if let Some(count) = max_undo_steps {
+      let () = task_state.tasks().set_max_undo_step_count(count);
+    }
+
     Self {
       tasks_dir_cap,
       task_state,
@@ -88,7 +158,16 @@ impl TermUiData {
       ui_state_file: ui_state_path.1,
       colors,
       toggle_tag,
+      reminder_command,
+      sync,
+      archive_after_days,
+      max_undo_steps,
       displayed_unsaved_changes_warning: false,
+      conflicts: Vec::new(),
+      prev_focused: None,
+      reload_pending: false,
+      replay: Vec::new(),
+      reminders_checked: Utc::now(),
     }
   }
 }
@@ -98,8 +177,12 @@ impl TermUiData {
 #[gui(Event = Event, Message = Message)]
 pub struct TermUi {
   id: Id,
+  dialog: Id,
   in_out: Id,
   tab_bar: Id,
+  problems: Id,
+  trash: Id,
+  archive: Id,
 }
 
 
@@ -111,8 +194,16 @@ impl TermUi {
     let State {
       selected_tasks,
       selected_view,
+      reminders_checked,
     } = state;
 
+    // Reminders that came due while the program was not running fire
+    // right away.
+    if let Some(checked) = reminders_checked {
+      let data = cap.data_mut(id).downcast_mut::<TermUiData>().unwrap();
+      data.reminders_checked = checked;
+    }
+
     let selected = selected_tasks.into_iter().chain(repeat(None));
     let views = views.into_iter().zip(selected).collect();
 
@@ -153,13 +244,45 @@ impl TermUi {
         ))
       }),
     );
+    // The problems dialog is created last, so that it can take the
+    // focus from the tab bar in case there is anything to report.
+    let data = cap.data(id).downcast_ref::<TermUiData>().unwrap();
+    let problems = data
+      .task_state
+      .problems()
+      .iter()
+      .map(ProblemText::from)
+      .collect();
+    let problems = cap.add_widget(
+      id,
+      Box::new(|| Box::new(ProblemsDialogData::new(problems))),
+      Box::new(|id, cap| Box::new(ProblemsDialog::new(id, cap))),
+    );
+    let trash = cap.add_widget(
+      id,
+      Box::new(|| Box::new(StoreDialogData::new())),
+      Box::new(move |id, cap| {
+        Box::new(StoreDialog::new(id, cap, in_out, termui_id, "The trash is empty."))
+      }),
+    );
+    let archive = cap.add_widget(
+      id,
+      Box::new(|| Box::new(StoreDialogData::new())),
+      Box::new(move |id, cap| {
+        Box::new(StoreDialog::new(id, cap, in_out, termui_id, "The archive is empty."))
+      }),
+    );
 
     let _prev_hook = cap.hook_events(id, Some(&Self::handle_hooked_event));
 
     Self {
       id,
+      dialog,
       in_out,
       tab_bar,
+      problems,
+      trash,
+      archive,
     }
   }
 
@@ -181,11 +304,316 @@ impl TermUi {
           },
           _ => (),
         }
+        None
+      } else {
+        // Once the user is done editing, catch up on any reload that
+        // we had to hold off on.
+        let data = cap.data(widget.id()).downcast_ref::<TermUiData>().unwrap();
+        let reloaded = if data.reload_pending {
+          cap.send(widget.id(), Message::ReloadTasks).await
+        } else {
+          None
+        };
+
+        // Journal all operations performed while handling the event, so
+        // that they survive a crash.
+        // SANITY: We only ever hook events for the `TermUi` itself.
+        let termui = widget.downcast_ref::<TermUi>().unwrap();
+        let flushed = termui.flush_journal(cap).await;
+        reloaded.or(flushed).into_event()
       }
-      None
     })
   }
 
+  /// Append the task operations performed since the last invocation to
+  /// the journal file.
+  async fn flush_journal(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data_mut::<TermUiData>(cap);
+    let entries = data.task_state.tasks().take_journal();
+    if entries.is_empty() {
+      return None
+    }
+
+    let result = async {
+      let write_guard = data.ui_state_dir_cap.write().await?;
+      let mut file_cap = write_guard.file_cap(JOURNAL_FILE.as_ref());
+      append_journal(&mut file_cap, &entries).await
+    }
+    .await
+    .context("failed to journal task operations");
+
+    match result {
+      Ok(()) => None,
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{}", err)));
+        cap.send(self.in_out, message).await
+      },
+    }
+  }
+
+  /// Truncate the journal file, discarding all operations recorded in
+  /// it.
+  async fn clear_journal(data: &mut TermUiData) -> Result<()> {
+    let write_guard = data.ui_state_dir_cap.write().await?;
+    let mut file_cap = write_guard.file_cap(JOURNAL_FILE.as_ref());
+    file_cap
+      .write(b"")
+      .await
+      .context("failed to truncate task operation journal")
+  }
+
+  /// Check the journal for operations from a previous session that
+  /// never got saved and offer to replay them.
+  async fn check_journal(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data_mut::<TermUiData>(cap);
+    let path = data.ui_state_dir_cap.path().join(JOURNAL_FILE);
+    match load_journal(&path).await {
+      Ok(entries) if entries.is_empty() => None,
+      Ok(entries) => {
+        data.replay = entries;
+        if self.prev_focused(cap).is_none() {
+          self.make_focused(cap);
+        }
+        self.ask_replay(cap).await
+      },
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{}", err)));
+        cap.send(self.in_out, message).await
+      },
+    }
+  }
+
+  /// Ask the user whether to replay journaled operations.
+  async fn ask_replay(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let text = format!(
+      "found {} unsaved operation(s) from a previous session: (r)eplay or (d)iscard?",
+      data.replay.len()
+    );
+    let message = Message::SetInOut(InOut::Recovery(text));
+    cap.send(self.in_out, message).await
+  }
+
+  /// Handle a key press while the user has yet to decide whether to
+  /// replay journaled operations.
+  async fn handle_replay_key(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    key: Key,
+  ) -> Option<Message> {
+    let data = self.data_mut::<TermUiData>(cap);
+    let in_out = match key {
+      Key::Char('r') => {
+        let entries = data.replay.drain(..).collect::<Vec<_>>();
+        let count = entries.len();
+        let failed = data.task_state.tasks().replay(entries);
+        if failed > 0 {
+          InOut::Error(format!(
+            "failed to replay {} of {} journaled operation(s)",
+            failed, count
+          ))
+        } else {
+          InOut::Clear
+        }
+      },
+      Key::Char('d') => {
+        let () = data.replay.clear();
+        match Self::clear_journal(data).await {
+          Ok(()) => InOut::Clear,
+          Err(err) => InOut::Error(format!("{}", err)),
+        }
+      },
+      _ => return self.ask_replay(cap).await,
+    };
+
+    let _widget = self.restore_focus(cap);
+    let message = Message::SetInOut(in_out);
+    cap.send(self.in_out, message).await.maybe_update(true)
+  }
+
+  /// Check whether the user is currently busy editing or answering a
+  /// question, in which case tasks must not be changed underneath them.
+  fn is_busy(&self, cap: &dyn Cap) -> bool {
+    let data = self.data::<TermUiData>(cap);
+    !data.conflicts.is_empty()
+      || !data.replay.is_empty()
+      || cap.is_focused(self.in_out)
+      || cap.is_focused(self.dialog)
+  }
+
+  /// Merge changes made to task files by other programs into our
+  /// tasks, retaining the selection.
+  async fn reload_tasks(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    if self.is_busy(cap) {
+      let data = self.data_mut::<TermUiData>(cap);
+      data.reload_pending = true;
+      return None
+    }
+
+    match self.reload_tasks_now(cap).await {
+      Ok(message) => message,
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{}", err)));
+        cap.send(self.in_out, message).await
+      },
+    }
+  }
+
+  /// Unconditionally merge changes made to task files into our tasks.
+  async fn reload_tasks_now(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+  ) -> Result<Option<Message>> {
+    let _updated = cap.send(self.tab_bar, Message::SaveSelection).await;
+
+    let data = self.data_mut::<TermUiData>(cap);
+    data.reload_pending = false;
+    let tasks_dir = data.tasks_dir_cap.path();
+    let result = data
+      .task_state
+      .reload(tasks_dir)
+      .await
+      .context("failed to reload tasks");
+
+    let restored = cap.send(self.tab_bar, Message::RestoreSelection).await;
+    match result? {
+      true => Ok(restored.maybe_update(true)),
+      false => Ok(restored),
+    }
+  }
+
+  /// Save all changes and synchronize tasks with the configured
+  /// server.
+  async fn sync(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let config = if let Some(config) = data.sync.clone() {
+      config
+    } else {
+      let message = Message::SetInOut(InOut::Error(
+        "task synchronization is not configured".to_string(),
+      ));
+      return cap.send(self.in_out, message).await
+    };
+
+    // We only ever synchronize saved state. If saving did not work out
+    // or the user first has to resolve conflicts, we are done here.
+    let saved = self.save(cap).await;
+    let data = self.data::<TermUiData>(cap);
+    let tasks_dir = data.tasks_dir_cap.path();
+    if !data.conflicts.is_empty() || data.task_state.is_changed(tasks_dir).await {
+      return saved
+    }
+
+    let data = self.data_mut::<TermUiData>(cap);
+    let collections = data
+      .task_state
+      .collections()
+      .map(str::to_string)
+      .collect::<Vec<_>>();
+    let archive = data
+      .task_state
+      .archive()
+      .map(|archive| archive.path().to_path_buf());
+    let result = sync(&config, &mut data.tasks_dir_cap, &collections, archive.as_deref())
+      .await
+      .context("failed to synchronize tasks");
+    let result = match result {
+      Ok(summary) => self
+        .reload_tasks_now(cap)
+        .await
+        .map(|message| (summary, message)),
+      Err(err) => Err(err),
+    };
+
+    let (in_out, reloaded) = match result {
+      Ok((summary, message)) => (InOut::Synced(summary.to_string()), message),
+      Err(err) => (InOut::Error(format!("{}", err)), None),
+    };
+    let message = Message::SetInOut(in_out);
+    cap
+      .send(self.in_out, message)
+      .await
+      .maybe_update(reloaded.is_some())
+  }
+
+  /// Show the problems currently known, including those encountered
+  /// after task files were loaded initially.
+  async fn show_problems(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let problems = data
+      .task_state
+      .problems()
+      .iter()
+      .map(ProblemText::from)
+      .collect();
+    cap.send(self.problems, Message::ShowProblems(problems)).await
+  }
+
+  /// Show the tasks contained in the trash, so that the user can pick
+  /// one to restore.
+  async fn show_trash(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let result = match data.task_state.trash() {
+      Some(trash) => trash.load().await,
+      None => Err(anyhow!("no trash available")),
+    };
+    self.show_stored_tasks(cap, self.trash, result).await
+  }
+
+  /// Show the tasks contained in the archive, so that the user can
+  /// browse them and pick one to restore.
+  async fn show_archive(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let result = match data.task_state.archive() {
+      Some(archive) => archive.load().await,
+      None => Err(anyhow!("no archive available")),
+    };
+    self.show_stored_tasks(cap, self.archive, result).await
+  }
+
+  /// Show the tasks loaded from a store in the dialog with ID `dialog`.
+  async fn show_stored_tasks(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    dialog: Id,
+    result: Result<Vec<SerTask>>,
+  ) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+
+    match result {
+      Ok(mut tasks) => {
+        // Tasks restored but not yet saved are still contained in the
+        // store. We do not want to offer them again.
+        let ids = data
+          .task_state
+          .tasks()
+          .iter(|iter| iter.map(|task| task.id()).collect::<HashSet<_>>());
+        let () = tasks.retain(|task| !ids.contains(&task.id));
+        let () = tasks.sort_by_key(|task| task.summary.to_lowercase());
+        cap.send(dialog, Message::ShowStoredTasks(tasks)).await
+      },
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{}", err)));
+        cap.send(self.in_out, message).await
+      },
+    }
+  }
+
+  /// Persist the UI state.
+  async fn save_ui_state(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    ui_state: &State,
+  ) -> Result<()> {
+    let data = self.data_mut::<TermUiData>(cap);
+    let write_guard = data.ui_state_dir_cap.write().await?;
+    let mut file_cap = write_guard.file_cap(&data.ui_state_file);
+    ui_state
+      .save(&mut file_cap)
+      .await
+      .context("failed to save UI state")
+  }
+
   /// Persist configuration and state.
   async fn save_all(
     &self,
@@ -204,24 +632,56 @@ impl TermUi {
         .await
         .context("failed to save UI configuration")?;
     }
-    {
-      let write_guard = data.ui_state_dir_cap.write().await?;
-      let mut file_cap = write_guard.file_cap(&data.ui_state_file);
-      let () = ui_state
-        .save(&mut file_cap)
-        .await
-        .context("failed to save UI state")?;
-    }
-
+    let () = self.save_ui_state(cap, ui_state).await?;
 
+    let data = self.data_mut::<TermUiData>(cap);
     let () = data
       .task_state
       .save(&mut data.tasks_dir_cap)
       .await
       .context("failed to save task state")?;
+
+    // With everything saved, previously journaled operations are no
+    // longer of interest.
+    let _entries = data.task_state.tasks().take_journal();
+    let () = Self::clear_journal(data).await?;
+
+    // The undo history is saved along with the tasks it belongs to, so
+    // that it is available in the next session.
+    let history = data
+      .task_state
+      .tasks()
+      .history_to_serde()
+      .context("failed to save undo history")?;
+    let write_guard = data.ui_state_dir_cap.write().await?;
+    let mut file_cap = write_guard.file_cap(HISTORY_FILE.as_ref());
+    let () = save_state_to_file::<Json, _>(&mut file_cap, &history)
+      .await
+      .context("failed to save undo history")?;
     Ok(())
   }
 
+  /// Restore the undo history saved in a previous session, provided
+  /// that tasks have not changed since.
+  async fn restore_history(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    let path = data.ui_state_dir_cap.path().join(HISTORY_FILE);
+    let result = match load_state_from_file::<Json, SerHistory>(&path).await {
+      Ok(Some(history)) => data.task_state.tasks().restore_history(history),
+      Ok(None) => Ok(false),
+      Err(err) => Err(err),
+    }
+    .with_context(|| format!("failed to restore undo history from {}", path.display()));
+
+    match result {
+      Ok(_restored) => None,
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{}", err)));
+        cap.send(self.in_out, message).await
+      },
+    }
+  }
+
   /// Save the current configuration and state.
   async fn save_and_report(
     &self,
@@ -263,20 +723,166 @@ impl TermUi {
       views,
       colors: data.colors,
       toggle_tag: data.toggle_tag.clone(),
+      reminder_command: data.reminder_command.clone(),
+      sync: data.sync.clone(),
+      archive_after_days: data.archive_after_days,
+      max_undo_steps: data.max_undo_steps,
     };
     let state = State {
       selected_tasks,
       selected_view,
+      reminders_checked: Some(data.reminders_checked),
     };
 
     (config, state)
   }
 
+  /// Notify the user about reminders that fired since the last check,
+  /// up to and including `to`.
+  async fn check_reminders(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    to: DateTime<Utc>,
+  ) -> Option<Message> {
+    let data = self.data_mut::<TermUiData>(cap);
+    let from = replace(&mut data.reminders_checked, to);
+    let (due, next) = data
+      .task_state
+      .tasks()
+      .iter(|iter| due_reminders(iter, from, to));
+
+    if due.is_empty() {
+      return Some(Message::CheckedReminders(next, false))
+    }
+
+    // Persist the time of the check along with the remaining UI state,
+    // so that the reminders do not fire again in the next session.
+    let (_config, state) = self.collect_config_and_state(cap).await;
+    let result = self.save_ui_state(cap, &state).await;
+
+    let data = self.data::<TermUiData>(cap);
+
+    let text = due
+      .iter()
+      .map(|task| task.summary())
+      .collect::<Vec<_>>()
+      .join(", ");
+    let in_out = if let Some(command) = &data.reminder_command {
+      match notify(command, &text) {
+        Ok(mut child) => {
+          // We don't care about the command's outcome, but we have to
+          // reap the process eventually.
+          let _handle = thread::spawn(move || child.wait());
+          InOut::Reminder(text)
+        },
+        Err(err) => InOut::Error(format!("{}", err)),
+      }
+    } else {
+      InOut::Reminder(text)
+    };
+    let in_out = match result {
+      Ok(()) => in_out,
+      Err(err) => InOut::Error(format!("{}", err)),
+    };
+
+    let updated = cap
+      .send(self.in_out, Message::SetInOut(in_out))
+      .await
+      .map(|m| m.is_updated())
+      .unwrap_or(false);
+    Some(Message::CheckedReminders(next, updated))
+  }
+
   /// Emit an event that will eventually cause the state to be saved.
   async fn save(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    // Before overwriting anything, make sure that no task file got
+    // modified behind our back. If one did, the user gets to decide how
+    // to proceed first.
+    let data = self.data::<TermUiData>(cap);
+    let tasks_dir = data.tasks_dir_cap.path();
+    let result = data
+      .task_state
+      .conflicts(tasks_dir)
+      .await
+      .context("failed to check task files for external modifications");
+    match result {
+      Ok(conflicts) if conflicts.is_empty() => (),
+      Ok(conflicts) => {
+        let data = self.data_mut::<TermUiData>(cap);
+        data.conflicts = conflicts;
+        if self.prev_focused(cap).is_none() {
+          self.make_focused(cap);
+        }
+        return self.ask_conflict_resolution(cap).await
+      },
+      Err(err) => {
+        let message = Message::SetInOut(InOut::Error(format!("{}", err)));
+        return cap.send(self.in_out, message).await
+      },
+    }
+
     let (config, state) = self.collect_config_and_state(cap).await;
     self.save_and_report(cap, &config, &state).await
   }
+
+  /// Ask the user how to resolve the first pending conflict.
+  async fn ask_conflict_resolution(&self, cap: &mut dyn MutCap<Event, Message>) -> Option<Message> {
+    let data = self.data::<TermUiData>(cap);
+    // SANITY: We only ask for a resolution while conflicts are pending.
+    let conflict = data.conflicts.first().unwrap();
+    let text = format!(
+      "{} changed on disk: keep (o)urs, take (t)heirs, or (m)erge?",
+      conflict.file().to_string_lossy()
+    );
+    let message = Message::SetInOut(InOut::Conflict(text));
+    cap.send(self.in_out, message).await
+  }
+
+  /// Handle a key press while conflicts are pending.
+  async fn handle_conflict_key(
+    &self,
+    cap: &mut dyn MutCap<Event, Message>,
+    key: Key,
+  ) -> Option<Message> {
+    let resolution = match key {
+      Key::Char('o') => Resolution::Ours,
+      Key::Char('t') => Resolution::Theirs,
+      Key::Char('m') => Resolution::Merge,
+      Key::Esc => {
+        let data = self.data_mut::<TermUiData>(cap);
+        let () = data.conflicts.clear();
+        let _widget = self.restore_focus(cap);
+        let message = Message::SetInOut(InOut::Clear);
+        return cap.send(self.in_out, message).await.maybe_update(true)
+      },
+      _ => return self.ask_conflict_resolution(cap).await,
+    };
+
+    let data = self.data_mut::<TermUiData>(cap);
+    let conflict = data.conflicts.remove(0);
+    let () = data.task_state.resolve(conflict, resolution);
+
+    if data.conflicts.is_empty() {
+      let _widget = self.restore_focus(cap);
+      // With all conflicts resolved we can finally save. Note that more
+      // conflicts may have emerged in the meantime, in which case we
+      // just ask again.
+      self.save(cap).await.maybe_update(true)
+    } else {
+      self.ask_conflict_resolution(cap).await.maybe_update(true)
+    }
+  }
+}
+
+impl Modal for TermUi {
+  fn prev_focused(&self, cap: &dyn Cap) -> Option<Id> {
+    self.data::<TermUiData>(cap).prev_focused
+  }
+
+  fn set_prev_focused(&self, cap: &mut dyn MutCap<Event, Message>, focused: Option<Id>) {
+    let data = self.data_mut::<TermUiData>(cap);
+    data.prev_focused = focused;
+  }
 }
 
 #[async_trait(?Send)]
@@ -284,6 +890,12 @@ impl Handleable<Event, Message> for TermUi {
   /// Check for new input and react to it.
   async fn handle(&self, cap: &mut dyn MutCap<Event, Message>, event: Event) -> Option<Event> {
     match event {
+      Event::Key(key, _) if !self.data::<TermUiData>(cap).replay.is_empty() => {
+        self.handle_replay_key(cap, key).await.into_event()
+      },
+      Event::Key(key, _) if !self.data::<TermUiData>(cap).conflicts.is_empty() => {
+        self.handle_conflict_key(cap, key).await.into_event()
+      },
       Event::Key(key, _) => match key {
         Key::Char('u') | Key::Char('U') => {
           let data = self.data::<TermUiData>(cap);
@@ -335,6 +947,10 @@ impl Handleable<Event, Message> for TermUi {
           }
         },
         Key::Char('w') => self.save(cap).await.into_event(),
+        Key::Char('S') => self.sync(cap).await.into_event(),
+        Key::Char('P') => self.show_problems(cap).await.into_event(),
+        Key::Char('T') => self.show_trash(cap).await.into_event(),
+        Key::Char('V') => self.show_archive(cap).await.into_event(),
         // All key events not handled at this point will just get
         // swallowed.
         _ => None,
@@ -350,6 +966,18 @@ impl Handleable<Event, Message> for TermUi {
         // We just forward the event to the TabBar.
         cap.send(self.tab_bar, message).await
       },
+      Message::CheckReminders(to) => self.check_reminders(cap, to).await,
+      Message::ReloadTasks => self.reload_tasks(cap).await,
+      Message::RestoreHistory => self.restore_history(cap).await,
+      Message::CheckJournal => self.check_journal(cap).await,
+      Message::RestoreTask(task) => {
+        let data = self.data::<TermUiData>(cap);
+        let task = data.task_state.tasks().add_from_serde(task);
+        cap
+          .send(self.tab_bar, Message::SelectTask(task, false))
+          .await
+          .maybe_update(true)
+      },
       #[cfg(all(test, not(feature = "readline")))]
       Message::GetTasks => {
         let data = self.data::<TermUiData>(cap);
@@ -377,8 +1005,17 @@ impl Handleable<Event, Message> for TermUi {
 mod tests {
   use super::*;
 
+  use std::ops::Deref as _;
   use std::rc::Rc;
 
+  use anyhow::anyhow;
+
+  use chrono::Days;
+  use chrono::Duration;
+  use chrono::Local;
+  use chrono::NaiveDate;
+  use chrono::TimeZone as _;
+
   use gui::Cap;
   use gui::Ui;
 
@@ -387,15 +1024,20 @@ mod tests {
 
   use tokio::test;
 
+  use crate::line::Line;
+  use crate::priority::Priority;
   use crate::ser::state::TaskState as SerTaskState;
   use crate::ser::state::UiConfig as SerUiConfig;
   use crate::ser::state::UiState as SerUiState;
+  use crate::ser::tasks::Alarm as SerAlarm;
   use crate::ser::tasks::Task as SerTask;
   use crate::ser::tasks::Tasks as SerTasks;
   use crate::ser::tasks::TasksMeta as SerTasksMeta;
+  use crate::ser::tasks::Trigger as SerTrigger;
   use crate::ser::view::View as SerView;
   use crate::ser::ToSerde;
   use crate::state::TaskState;
+  use crate::state::Store;
   use crate::test::default_tasks_and_tags;
   use crate::test::make_task_summaries;
   use crate::test::make_tasks;
@@ -421,6 +1063,8 @@ mod tests {
   struct TestUiBuilder {
     ui_config: SerUiConfig,
     task_state: SerTaskState,
+    archived_tasks: Vec<SerTask>,
+    ui_state: SerUiState,
   }
 
   impl TestUiBuilder {
@@ -429,6 +1073,8 @@ mod tests {
       Self {
         ui_config: Default::default(),
         task_state: Default::default(),
+        archived_tasks: Vec::new(),
+        ui_state: Default::default(),
       }
     }
 
@@ -448,6 +1094,8 @@ mod tests {
           tasks_meta: SerTasksMeta::default(),
           tasks: SerTasks::from(tasks.into()),
         },
+        archived_tasks: Vec::new(),
+        ui_state: Default::default(),
       }
     }
 
@@ -458,16 +1106,47 @@ mod tests {
       TestUiBuilder {
         ui_config,
         task_state,
+        archived_tasks: Vec::new(),
+        ui_state: Default::default(),
       }
     }
 
+    /// Place the given tasks in the archive.
+    fn with_archived_tasks(mut self, tasks: Vec<SerTask>) -> TestUiBuilder {
+      self.archived_tasks = tasks;
+      self
+    }
+
+    /// Start out with the given UI state.
+    fn with_ui_state(mut self, ui_state: SerUiState) -> TestUiBuilder {
+      self.ui_state = ui_state;
+      self
+    }
+
     /// Build the actual UI object that we can test with.
     async fn build(self) -> TestUi {
       let tasks_dir = TempDir::new().unwrap();
-      let task_state = TaskState::with_serde(self.task_state).unwrap();
+      let trash_dir = TempDir::new().unwrap();
+      let mut task_state = TaskState::with_serde(self.task_state).unwrap();
       let tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
         .await
         .unwrap();
+      let trash_root_cap = DirCap::for_dir(trash_dir.path().to_path_buf())
+        .await
+        .unwrap();
+      let () = task_state.set_trash(Store::new(trash_root_cap));
+
+      let archive_dir = TempDir::new().unwrap();
+      let mut archive_root_cap = DirCap::for_dir(archive_dir.path().to_path_buf())
+        .await
+        .unwrap();
+      let archived = SerTaskState {
+        tasks_meta: SerTasksMeta::default(),
+        tasks: SerTasks::from(self.archived_tasks),
+      };
+      let mut archived = TaskState::with_serde(archived).unwrap();
+      let () = archived.save(&mut archive_root_cap).await.unwrap();
+      let () = task_state.set_archive(Store::new(archive_root_cap));
 
       // We have to create an additional directory here for the UI
       // configuration, otherwise we may end up placing files in /tmp/
@@ -484,6 +1163,10 @@ mod tests {
       let Config {
         colors,
         toggle_tag,
+        reminder_command,
+        sync,
+        archive_after_days,
+        max_undo_steps,
         views,
       } = ui_config;
 
@@ -493,7 +1176,7 @@ mod tests {
       let ui_state_dir_cap = DirCap::for_dir(ui_state_file_dir).await.unwrap();
       let ui_state_file_name = ui_state_file.path().file_name().unwrap().to_os_string();
       let ui_state_path = (ui_state_dir_cap, ui_state_file_name);
-      let ui_state = State::default();
+      let ui_state = State::with_serde(self.ui_state);
 
       let (ui, _) = Ui::new(
         || {
@@ -504,6 +1187,10 @@ mod tests {
             ui_state_path,
             colors,
             toggle_tag,
+            reminder_command,
+            sync,
+            archive_after_days,
+            max_undo_steps,
           ))
         },
         |id, cap| Box::new(TermUi::new(id, cap, views, ui_state)),
@@ -511,6 +1198,8 @@ mod tests {
 
       TestUi {
         tasks_root: tasks_dir,
+        trash_root: trash_dir,
+        archive_root: archive_dir,
         ui,
         _ui_config_dir: ui_config_dir,
         ui_config_file,
@@ -524,6 +1213,8 @@ mod tests {
   /// `Ui`.
   struct TestUi {
     tasks_root: TempDir,
+    trash_root: TempDir,
+    archive_root: TempDir,
     ui: Ui<Event, Message>,
     _ui_config_dir: TempDir,
     ui_config_file: NamedTempFile,
@@ -589,64 +1280,368 @@ mod tests {
       }
     }
 
-    /// Retrieve the summaries of the current set of tasks from the UI.
-    async fn task_summaries(&mut self) -> Vec<String> {
-      self.tasks().await.iter().map(|x| x.summary()).collect()
-    }
+    /// Retrieve the summaries of the current set of tasks from the UI.
+    async fn task_summaries(&mut self) -> Vec<String> {
+      self.tasks().await.iter().map(|x| x.summary()).collect()
+    }
+
+    /// Load the UI's configuration and state from a file. Note that
+    /// unless both have been saved, the result will probably just be
+    /// default values.
+    async fn load_config_and_state(&self) -> Result<(Config, State)> {
+      let task_state = TaskState::load(self.tasks_root.path()).await?;
+      let ui_config = Config::load(self.ui_config_file.path(), &task_state).await?;
+      let ui_state = State::load(self.ui_state_file.path()).await?;
+      Ok((ui_config, ui_state))
+    }
+  }
+
+  /// Check that we can quit the program as expected.
+  #[test]
+  async fn exit_on_quit() {
+    let events = vec![
+      Event::from('w'),
+      Event::from(CHAR_QUIT),
+      Event::from('a'),
+      Event::from('f'),
+      Event::from('\n'),
+    ];
+
+    let tasks = TestUiBuilder::new()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+
+    assert_eq!(tasks, make_task_summaries(0))
+  }
+
+  /// Check that the program cannot be quit directly when unsaved
+  /// changes (to the configuration?) are present.
+  #[test]
+  async fn dont_exit_on_unsaved_config() {
+    let events = vec![
+      Event::from(CHAR_QUIT),
+      Event::from('a'),
+      Event::from('f'),
+      Event::from('\n'),
+    ];
+
+    let tasks = TestUiBuilder::new()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+
+    assert_eq!(tasks.len(), 1)
+  }
+
+  /// Check that the problems dialog can be opened and closed again and
+  /// that it swallows key presses while open.
+  #[test]
+  async fn open_close_problems() {
+    let events = vec![
+      Event::from('w'),
+      Event::from('P'),
+      Event::from('a'),
+      Event::from(CHAR_QUIT),
+      Event::from('a'),
+      Event::from('f'),
+      Event::from('\n'),
+    ];
+
+    let tasks = TestUiBuilder::new()
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+
+    assert_eq!(tasks, vec!["f"])
+  }
+
+  /// Check that deleted tasks end up in the trash when saving and that
+  /// they can be restored from there.
+  #[test]
+  async fn restore_task_from_trash() {
+    let tasks = make_tasks(3);
+    let id = tasks[1].id.to_string();
+    let events = vec![
+      Event::from('w'),
+      Event::from('j'),
+      Event::from('d'),
+      Event::from('w'),
+    ];
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let summaries = ui.handle(events).await.task_summaries().await;
+    assert_eq!(summaries, vec!["1", "3"]);
+    assert!(!ui.tasks_root.path().join(&id).exists());
+    assert!(ui.trash_root.path().join(&id).exists());
+
+    // Opening the trash twice must not offer the task once it got
+    // restored.
+    let events = vec![
+      Event::from('T'),
+      Event::from('\n'),
+      Event::from('T'),
+      Event::from('\n'),
+      Event::from('w'),
+    ];
+    let tasks = ui.handle(events).await.tasks().await;
+    let summaries = tasks.iter().map(|task| task.summary()).collect::<Vec<_>>();
+    assert_eq!(summaries, vec!["1", "3", "2"]);
+    assert!(ui.tasks_root.path().join(&id).exists());
+    assert!(!ui.trash_root.path().join(&id).exists());
+  }
+
+  /// Check that we can search the archive and restore a task from it.
+  #[test]
+  async fn search_and_restore_archived_task() {
+    let mut tasks = make_tasks(4);
+    let archived = tasks.split_off(1);
+    let id = archived[1].id.to_string();
+    let events = vec![
+      Event::from('V'),
+      Event::from('/'),
+      Event::from('3'),
+      Event::from('\n'),
+      Event::from('\n'),
+      Event::from('w'),
+    ];
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks)
+      .with_archived_tasks(archived)
+      .build()
+      .await;
+    assert!(ui.archive_root.path().join(&id).exists());
+
+    let summaries = ui.handle(events).await.task_summaries().await;
+    assert_eq!(summaries, vec!["1", "3"]);
+    assert!(ui.tasks_root.path().join(&id).exists());
+    assert!(!ui.archive_root.path().join(&id).exists());
+
+    // A search without matches leaves nothing to restore.
+    let events = vec![
+      Event::from('V'),
+      Event::from('/'),
+      Event::from('x'),
+      Event::from('\n'),
+      Event::from('\n'),
+    ];
+    let summaries = ui.handle(events).await.task_summaries().await;
+    assert_eq!(summaries, vec!["1", "3"]);
+  }
+
+  /// Check that we ask the user how to resolve external modifications
+  /// of task files before saving.
+  #[test]
+  async fn resolve_conflict_on_save() {
+    let tasks = make_tasks(2);
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let _ui = ui.handle(vec![Event::from('w')]).await;
+    assert_eq!(ui.in_out().await, InOut::Saved);
+
+    // Modify the first task behind the UI's back.
+    let mut task_state = TaskState::load(ui.tasks_root.path()).await.unwrap();
+    let task = task_state
+      .tasks()
+      .iter(|mut iter| iter.next().unwrap().clone());
+    let mut updated = task.deref().clone();
+    let () = updated.set_summary("theirs".to_string());
+    let () = task_state.tasks().update(task, updated);
+    let mut tasks_root_cap = DirCap::for_dir(ui.tasks_root.path().to_path_buf())
+      .await
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
+
+    // Unrelated keys are swallowed while the question is pending.
+    let _ui = ui.handle(vec![Event::from('w'), Event::from('a')]).await;
+    assert!(matches!(ui.in_out().await, InOut::Conflict(..)));
+
+    let events = vec![
+      Event::from('m'),
+      Event::from('a'),
+      Event::from('f'),
+      Event::from('\n'),
+    ];
+    let _ui = ui.handle(events).await;
+    assert_eq!(ui.task_summaries().await, vec!["theirs", "f", "2"]);
+
+    let task_state = TaskState::load(ui.tasks_root.path()).await.unwrap();
+    let summaries = task_state
+      .tasks()
+      .iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>());
+    assert_eq!(summaries, vec!["theirs", "2"]);
+  }
+
+  /// Check that we journal task operations and offer to replay them
+  /// after the program terminated without saving.
+  #[test]
+  async fn replay_journal() {
+    let tasks = make_tasks(2);
+    let events = vec![
+      Event::from('a'),
+      Event::from('f'),
+      Event::from('\n'),
+      Event::from('j'),
+      Event::from('a'),
+      Event::from('g'),
+      Event::from('\n'),
+    ];
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks.clone()).build().await;
+    let expected = ui.handle(events).await.task_summaries().await;
+    let path = ui.ui_state_file.path().with_file_name(JOURNAL_FILE);
+    let journal = load_journal(&path).await.unwrap();
+    assert_eq!(journal.len(), 2);
+
+    // Start over with the original tasks, as if we had crashed.
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let path = ui.ui_state_file.path().with_file_name(JOURNAL_FILE);
+    let mut dir_cap = DirCap::for_dir(path.parent().unwrap().to_path_buf())
+      .await
+      .unwrap();
+    {
+      let write_guard = dir_cap.write().await.unwrap();
+      let mut file_cap = write_guard.file_cap(JOURNAL_FILE.as_ref());
+      let () = append_journal(&mut file_cap, &journal).await.unwrap();
+    }
+
+    let root = ui.ui.root_id();
+    let _message = ui.ui.send(root, Message::CheckJournal).await;
+    assert!(matches!(ui.in_out().await, InOut::Recovery(..)));
+
+    // Unrelated keys are swallowed while the question is pending.
+    let _ui = ui.handle(vec![Event::from('a')]).await;
+    assert!(matches!(ui.in_out().await, InOut::Recovery(..)));
+
+    let _ui = ui.handle(vec![Event::from('r')]).await;
+    assert_eq!(ui.in_out().await, InOut::Clear);
+    assert_eq!(ui.task_summaries().await, expected);
+
+    // Once saved, the journal is of no interest anymore.
+    let _ui = ui.handle(vec![Event::from('w')]).await;
+    assert_eq!(ui.in_out().await, InOut::Saved);
+    assert!(load_journal(&path).await.unwrap().is_empty());
+  }
+
+  /// Check that the undo history gets saved and restored in the next
+  /// session, provided tasks have not changed.
+  #[test]
+  async fn restore_undo_history() {
+    let tasks = make_tasks(3);
+    let events = vec![Event::from('j'), Event::from('d'), Event::from('w')];
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let _ui = ui.handle(events).await;
+    assert_eq!(ui.in_out().await, InOut::Saved);
+    let history_path = ui.ui_state_file.path().with_file_name(HISTORY_FILE);
+    let history = load_state_from_file::<Json, SerHistory>(&history_path)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(history.undo.len(), 1);
+
+    // Start a new session with the saved tasks.
+    let task_state = TaskState::load(ui.tasks_root.path()).await.unwrap();
+    let builder = TestUiBuilder {
+      ui_config: Default::default(),
+      task_state: task_state.to_serde(),
+      archived_tasks: Vec::new(),
+      ui_state: Default::default(),
+    };
+    let mut ui = builder.build().await;
+    let history_path = ui.ui_state_file.path().with_file_name(HISTORY_FILE);
+    let mut dir_cap = DirCap::for_dir(history_path.parent().unwrap().to_path_buf())
+      .await
+      .unwrap();
+    {
+      let write_guard = dir_cap.write().await.unwrap();
+      let mut file_cap = write_guard.file_cap(HISTORY_FILE.as_ref());
+      let () = save_state_to_file::<Json, _>(&mut file_cap, &history)
+        .await
+        .unwrap();
+    }
+
+    let root = ui.ui.root_id();
+    let _message = ui.ui.send(root, Message::RestoreHistory).await;
+    assert_eq!(ui.task_summaries().await, vec!["1", "3"]);
 
-    /// Load the UI's configuration and state from a file. Note that
-    /// unless both have been saved, the result will probably just be
-    /// default values.
-    async fn load_config_and_state(&self) -> Result<(Config, State)> {
-      let task_state = TaskState::load(self.tasks_root.path()).await?;
-      let ui_config = Config::load(self.ui_config_file.path(), &task_state).await?;
-      let ui_state = State::load(self.ui_state_file.path()).await?;
-      Ok((ui_config, ui_state))
-    }
+    let _ui = ui.handle(vec![Event::from('u')]).await;
+    assert_eq!(ui.task_summaries().await, vec!["1", "2", "3"]);
+    let _ui = ui.handle(vec![Event::from('U')]).await;
+    assert_eq!(ui.task_summaries().await, vec!["1", "3"]);
   }
 
-  /// Check that we can quit the program as expected.
+  /// Check that we report an error when the user asks to synchronize
+  /// tasks without synchronization being configured.
   #[test]
-  async fn exit_on_quit() {
-    let events = vec![
-      Event::from('w'),
-      Event::from(CHAR_QUIT),
-      Event::from('a'),
-      Event::from('f'),
-      Event::from('\n'),
-    ];
+  async fn sync_not_configured() {
+    let tasks = make_tasks(2);
+    let events = vec![Event::from('S')];
 
-    let tasks = TestUiBuilder::new()
+    let state = TestUiBuilder::with_ser_tasks(tasks)
       .build()
       .await
       .handle(events)
       .await
-      .task_summaries()
+      .in_out()
       .await;
 
-    assert_eq!(tasks, make_task_summaries(0))
+    let expected = InOut::Error("task synchronization is not configured".to_string());
+    assert_eq!(state, expected);
   }
 
-  /// Check that the program cannot be quit directly when unsaved
-  /// changes (to the configuration?) are present.
+  /// Check that tasks changed on disk by another program get merged
+  /// into ours, retaining local edits as well as the selection.
   #[test]
-  async fn dont_exit_on_unsaved_config() {
+  async fn reload_changed_tasks() {
+    let tasks = make_tasks(4);
     let events = vec![
-      Event::from(CHAR_QUIT),
-      Event::from('a'),
-      Event::from('f'),
+      Event::from('w'),
+      Event::from('G'),
+      Event::from('e'),
+      Event::from(Key::Backspace),
+      Event::from('x'),
       Event::from('\n'),
+      Event::from('k'),
     ];
-
-    let tasks = TestUiBuilder::new()
-      .build()
-      .await
-      .handle(events)
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let _ui = ui.handle(events).await;
+
+    // Remove the first task and modify the second behind the UI's back.
+    let mut task_state = TaskState::load(ui.tasks_root.path()).await.unwrap();
+    let (first, second) = task_state.tasks().iter(|mut iter| {
+      let first = iter.next().unwrap().clone();
+      let second = iter.next().unwrap().clone();
+      (first, second)
+    });
+    let () = task_state.tasks().remove(first);
+    let mut updated = second.deref().clone();
+    let () = updated.set_summary("two".to_string());
+    let () = task_state.tasks().update(second, updated);
+    let mut tasks_root_cap = DirCap::for_dir(ui.tasks_root.path().to_path_buf())
       .await
-      .task_summaries()
-      .await;
+      .unwrap();
+    let () = task_state.save(&mut tasks_root_cap).await.unwrap();
 
-    assert_eq!(tasks.len(), 1)
+    let root = ui.ui.root_id();
+    let message = ui.ui.send(root, Message::ReloadTasks).await;
+    assert!(matches!(message, Some(Message::Updated)), "{message:?}");
+    assert_eq!(ui.task_summaries().await, vec!["two", "3", "x"]);
+
+    // The third task should still be selected.
+    let _ui = ui.handle(vec![Event::from('d')]).await;
+    assert_eq!(ui.task_summaries().await, vec!["two", "x"]);
+
+    // Reloading again without changes on disk should not do anything.
+    let message = ui.ui.send(root, Message::ReloadTasks).await;
+    assert!(message.is_none(), "{message:?}");
   }
 
   /// Check that the user can force an exit on unsaved changes.
@@ -1352,6 +2347,389 @@ mod tests {
     assert_eq!(tasks, expected);
   }
 
+  #[test]
+  async fn set_and_clear_due_date() {
+    let tasks = make_tasks(2);
+    let mut events = vec![Event::from('j'), Event::from('D')];
+    events.extend("2023-04-01\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].due(), None);
+    assert_eq!(tasks[1].due(), NaiveDate::from_ymd_opt(2023, 4, 1));
+
+    // Deleting the date again should clear it.
+    let mut events = vec![Event::from('D')];
+    events.extend((0..10).map(|_| Event::from(Key::Backspace)));
+    events.push(Event::from('\n'));
+
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[1].due(), None);
+  }
+
+  /// Check that completing a recurring task advances it to its next
+  /// occurrence, in an undoable fashion.
+  #[test]
+  async fn complete_recurring_task() {
+    let mut events = vec![Event::from('R')];
+    events.extend("FREQ=WEEKLY\n".chars().map(Event::from));
+    events.push(Event::from('D'));
+    events.extend("2023-04-01\n".chars().map(Event::from));
+    events.push(Event::from(' '));
+
+    let mut ui = TestUiBuilder::with_default_tasks_and_tags().build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    let complete_tag = tasks[0].templates().instantiate_from_name(COMPLETE_TAG);
+    assert_eq!(tasks[0].recurrence().as_deref(), Some("FREQ=WEEKLY"));
+    assert_eq!(tasks[0].due(), NaiveDate::from_ymd_opt(2023, 4, 8));
+    assert!(!tasks[0].has_tag(&complete_tag));
+    assert_eq!(tasks[0].completed(), None);
+
+    let events = vec![Event::from('u')];
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].due(), NaiveDate::from_ymd_opt(2023, 4, 1));
+    assert!(!tasks[0].has_tag(&complete_tag));
+  }
+
+  /// Check that a recurring task with a rule we do not support is not
+  /// completed, as that would end its series.
+  #[test]
+  async fn complete_unsupported_recurring_task() {
+    let (ui_config, mut task_state) = default_tasks_and_tags();
+    let rule = "FREQ=WEEKLY;BYDAY=MO";
+    task_state.tasks.0[0].recurrence = Some(rule.to_string());
+    let builder = TestUiBuilder {
+      ui_config,
+      task_state,
+      archived_tasks: Vec::new(),
+      ui_state: Default::default(),
+    };
+
+    let mut ui = builder.build().await;
+    let tasks = ui.handle(vec![Event::from(' ')]).await.tasks().await;
+    let complete_tag = tasks[0].templates().instantiate_from_name(COMPLETE_TAG);
+    assert!(!tasks[0].has_tag(&complete_tag));
+    assert_eq!(tasks[0].completed(), None);
+    assert_eq!(tasks[0].recurrence().as_deref(), Some(rule));
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  #[test]
+  async fn set_invalid_recurrence() {
+    let tasks = make_tasks(1);
+    let mut events = vec![Event::from('R')];
+    events.extend("FREQ=SOMETIMES\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].recurrence(), None);
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  /// Check that we can move a task between collections.
+  #[test]
+  async fn move_task_to_collection() {
+    let tasks = make_tasks(2);
+    let mut events = vec![Event::from('c')];
+    events.extend("work\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].collection().as_deref(), Some("work"));
+    assert_eq!(tasks[1].collection(), None);
+
+    // Clearing the input moves the task back into the default
+    // collection.
+    let mut events = vec![Event::from('c')];
+    events.extend(repeat(Event::from(Key::Backspace)).take(4));
+    events.push(Event::from('\n'));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].collection(), None);
+
+    let mut events = vec![Event::from('c')];
+    events.extend("../elsewhere\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].collection(), None);
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  /// Check that we can add and remove dependencies between tasks.
+  #[test]
+  async fn toggle_task_dependency() {
+    let tasks = make_tasks(3);
+    let mut events = vec![Event::from('b')];
+    events.extend("2\n".chars().map(Event::from));
+    events.push(Event::from('b'));
+    events.extend("3\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    let depends_on = tasks[0]
+      .depends_on()
+      .iter()
+      .map(|task| task.summary())
+      .collect::<Vec<_>>();
+    assert_eq!(depends_on, vec!["2", "3"]);
+    assert!(tasks[0].is_blocked());
+
+    // Dependencies may not be circular.
+    let mut events = vec![Event::from('j'), Event::from('b')];
+    events.extend("1\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert!(tasks[1].depends_on().is_empty());
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+
+    let mut events = vec![Event::from('k'), Event::from('b')];
+    events.extend("2\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].depends_on().len(), 1);
+
+    // An empty input cancels.
+    let mut events = vec![Event::from('b')];
+    events.extend("\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert!(tasks[0].is_blocked());
+
+    let mut events = vec![Event::from('b')];
+    events.extend("3\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert!(!tasks[0].is_blocked());
+  }
+
+  /// Check that the task to depend on has to be identified
+  /// unambiguously.
+  #[test]
+  async fn toggle_dependency_on_ambiguous_task() {
+    let mut tasks = make_tasks(4);
+    tasks[1].summary = "foo bar".to_string();
+    tasks[2].summary = "foo baz".to_string();
+    tasks[3].summary = "foo".to_string();
+
+    let mut events = vec![Event::from('b')];
+    events.extend("bar\n".chars().map(Event::from));
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].depends_on().len(), 1);
+
+    // An exact match takes precedence over other tasks containing the
+    // text.
+    let mut events = vec![Event::from('b')];
+    events.extend("foo\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    let depends_on = tasks[0]
+      .depends_on()
+      .iter()
+      .map(|task| task.summary())
+      .collect::<Vec<_>>();
+    assert_eq!(depends_on, vec!["foo bar", "foo"]);
+
+    let mut events = vec![Event::from('b')];
+    events.extend("FOO B\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].depends_on().len(), 2);
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  #[test]
+  async fn toggle_dependency_on_unknown_task() {
+    let tasks = make_tasks(2);
+    let mut events = vec![Event::from('b')];
+    events.extend("foobar\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert!(tasks[0].depends_on().is_empty());
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  /// Check that we can snooze a task using presets as well as custom
+  /// dates.
+  #[test]
+  async fn snooze_task() {
+    let today = Local::now().date_naive();
+    let tasks = make_tasks(3);
+    let mut events = vec![Event::from('s')];
+    events.extend("tomorrow\n".chars().map(Event::from));
+    events.push(Event::from('j'));
+    events.push(Event::from('s'));
+    events.extend("Next Week\n".chars().map(Event::from));
+    events.push(Event::from('j'));
+    events.push(Event::from('s'));
+    events.extend("2023-04-01\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].start(), today.checked_add_days(Days::new(1)));
+    assert_eq!(tasks[1].start(), today.checked_add_days(Days::new(7)));
+    assert_eq!(tasks[2].start(), NaiveDate::from_ymd_opt(2023, 4, 1));
+    assert!(tasks[0].is_snoozed());
+    assert!(!tasks[2].is_snoozed());
+
+    // An empty input wakes up the task again.
+    let mut events = vec![Event::from('g'), Event::from('s')];
+    events.extend(repeat(Event::from(Key::Backspace)).take(10));
+    events.push(Event::from('\n'));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].start(), None);
+  }
+
+  #[test]
+  async fn snooze_task_invalid() {
+    let tasks = make_tasks(1);
+    let mut events = vec![Event::from('s')];
+    events.extend("someday\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].start(), None);
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  /// Check that we can set and clear the reminder of a task.
+  #[test]
+  async fn set_task_reminder() {
+    let tasks = make_tasks(1);
+    let mut events = vec![Event::from('A')];
+    events.extend("2023-04-01 09:30\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    let expected = Local
+      .with_ymd_and_hms(2023, 4, 1, 9, 30, 0)
+      .unwrap()
+      .with_timezone(&Utc);
+    assert_eq!(tasks[0].reminders(), vec![expected]);
+
+    let mut events = vec![Event::from('A')];
+    events.extend(repeat(Event::from(Key::Backspace)).take(16));
+    events.push(Event::from('\n'));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert!(tasks[0].reminders().is_empty());
+
+    let mut events = vec![Event::from('A')];
+    events.extend("9:30\n".chars().map(Event::from));
+    let tasks = ui.handle(events).await.tasks().await;
+    assert!(tasks[0].reminders().is_empty());
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
+  /// Check that firing reminders are reported in the input/output area,
+  /// unless the user is currently entering text.
+  #[test]
+  async fn fire_reminder() {
+    let now = Utc::now();
+    let mut tasks = make_tasks(2);
+    tasks[1].alarms = vec![SerAlarm {
+      trigger: SerTrigger::Absolute(now - Duration::minutes(1)),
+      properties: Vec::new(),
+    }];
+
+    let ui_state = SerUiState {
+      reminders_checked: Some((now - Duration::minutes(5)).timestamp()),
+      ..Default::default()
+    };
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks)
+      .with_ui_state(ui_state)
+      .build()
+      .await;
+    let root = ui.ui.root_id();
+    let message = Message::CheckReminders(now);
+    let resp = ui.ui.send(root, message).await.unwrap();
+    assert!(matches!(resp, Message::CheckedReminders(None, true)));
+    assert_eq!(ui.in_out().await, InOut::Reminder("2".to_string()));
+
+    // The time of the check got persisted, so that the reminder does
+    // not fire again in the next session.
+    let state = State::load(ui.ui_state_file.path()).await.unwrap();
+    assert_eq!(state.reminders_checked.map(|time| time.timestamp()), Some(now.timestamp()));
+
+    // Any key press clears the reminder.
+    let in_out = ui.handle(vec![Event::from('j')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Clear);
+
+    let in_out = ui.handle(vec![Event::from('a')]).await.in_out().await;
+    assert_eq!(in_out, InOut::Input(Line::default()));
+
+    let message = Message::CheckReminders(now);
+    let resp = ui.ui.send(root, message).await.unwrap();
+    assert!(matches!(resp, Message::CheckedReminders(None, false)));
+    assert_eq!(ui.in_out().await, InOut::Input(Line::default()));
+  }
+
+  #[test]
+  async fn raise_and_lower_priority() {
+    let tasks = make_tasks(2);
+    let events = vec![Event::from('j'), Event::from('+'), Event::from('+')];
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].priority(), None);
+    assert_eq!(tasks[1].priority(), Some(Priority::Medium));
+
+    let events = vec![Event::from('-'), Event::from('-'), Event::from('-')];
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[1].priority(), None);
+
+    // Changing the priority should be undoable.
+    let events = vec![Event::from('u')];
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[1].priority(), Some(Priority::Low));
+  }
+
+  #[test]
+  async fn edit_notes() {
+    let tasks = make_tasks(2);
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+
+    let _event = ui.evaluate(Event::from('j')).await;
+    let event = ui.evaluate(Event::from('E')).await;
+    let (id, task) = match event {
+      Some(Event::EditNotes(id, task)) => (id, task),
+      _ => panic!("Unexpected event: {event:?}"),
+    };
+    assert_eq!(task.summary(), "2");
+
+    let notes = Ok("some notes\nspanning lines\n".to_string());
+    let message = Message::EditedNotes(task.clone(), notes);
+    let _message = ui.ui.send(id, message).await;
+    assert_eq!(task.notes().as_deref(), Some("some notes\nspanning lines"));
+
+    // Editing notes should be undoable.
+    let _event = ui.evaluate(Event::from('u')).await;
+    assert_eq!(task.notes(), None);
+  }
+
+  #[test]
+  async fn edit_notes_failure() {
+    let tasks = make_tasks(1);
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+
+    let event = ui.evaluate(Event::from('E')).await;
+    let (id, task) = match event {
+      Some(Event::EditNotes(id, task)) => (id, task),
+      _ => panic!("Unexpected event: {event:?}"),
+    };
+
+    let message = Message::EditedNotes(task.clone(), Err(anyhow!("editor crashed")));
+    let _message = ui.ui.send(id, message).await;
+    assert_eq!(task.notes(), None);
+
+    let expected = InOut::Error("Failed to edit notes: editor crashed".to_string());
+    assert_eq!(ui.in_out().await, expected);
+  }
+
+  #[test]
+  async fn set_invalid_due_date() {
+    let tasks = make_tasks(1);
+    let mut events = vec![Event::from('D')];
+    events.extend("tomorrow\n".chars().map(Event::from));
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    assert_eq!(tasks[0].due(), None);
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+  }
+
   #[test]
   async fn remove_before_multi_byte_characters() {
     let tasks = make_tasks(1);
@@ -1509,6 +2887,93 @@ mod tests {
     assert_eq!(tasks, expected);
   }
 
+  /// Check that moving a task moves its sub-tasks along.
+  #[test]
+  async fn move_task_with_sub_tasks() {
+    let mut tasks = make_tasks(3);
+    tasks[1].parent = Some(tasks[0].id);
+    let events = vec![Event::from('J')];
+
+    let tasks = TestUiBuilder::with_ser_tasks(tasks)
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+
+    let expected = vec!["3", "1", "2"];
+    assert_eq!(tasks, expected);
+  }
+
+  /// Check that removing a task with sub-tasks requires confirmation.
+  #[test]
+  async fn remove_task_with_sub_tasks() {
+    let mut tasks = make_tasks(3);
+    tasks[1].parent = Some(tasks[0].id);
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(vec![Event::from('d')]).await.task_summaries().await;
+    assert_eq!(tasks, vec!["1", "2", "3"]);
+    assert!(matches!(ui.in_out().await, InOut::Error(..)));
+
+    // Any other key press in between cancels the removal.
+    let events = vec![Event::from('j'), Event::from('k'), Event::from('d')];
+    let tasks = ui.handle(events).await.task_summaries().await;
+    assert_eq!(tasks, vec!["1", "2", "3"]);
+
+    let tasks = ui.handle(vec![Event::from('d')]).await.task_summaries().await;
+    assert_eq!(tasks, vec!["3"]);
+  }
+
+  /// Check that we can indent and outdent tasks.
+  #[test]
+  async fn indent_and_outdent_tasks() {
+    let tasks = make_tasks(3);
+    let events = vec![
+      Event::from('j'),
+      Event::from('>'),
+      Event::from('j'),
+      Event::from('>'),
+      Event::from('>'),
+    ];
+
+    let mut ui = TestUiBuilder::with_ser_tasks(tasks).build().await;
+    let tasks = ui.handle(events).await.tasks().await;
+    let depths = tasks.iter().map(|task| task.depth()).collect::<Vec<_>>();
+    assert_eq!(depths, vec![0, 1, 2]);
+
+    let events = vec![Event::from('<')];
+    let tasks = ui.handle(events).await.tasks().await;
+    let depths = tasks.iter().map(|task| task.depth()).collect::<Vec<_>>();
+    assert_eq!(depths, vec![0, 1, 1]);
+    assert!(tasks[2].is_sibling_of(&tasks[1]));
+  }
+
+  /// Check that sub-tasks of a collapsed task can no longer be
+  /// selected.
+  #[test]
+  async fn collapse_sub_tasks() {
+    let mut tasks = make_tasks(3);
+    tasks[1].parent = Some(tasks[0].id);
+    let events = vec![
+      Event::from('z'),
+      Event::from('j'),
+      Event::from('d'),
+    ];
+
+    let tasks = TestUiBuilder::with_ser_tasks(tasks)
+      .build()
+      .await
+      .handle(events)
+      .await
+      .task_summaries()
+      .await;
+
+    let expected = vec!["1", "2"];
+    assert_eq!(tasks, expected);
+  }
+
   #[test]
   async fn transparent_task_removal_down_to_empty_view() {
     let events = vec![
@@ -1685,11 +3150,18 @@ mod tests {
     for c in 0u8..127u8 {
       let c = c as char;
       if c != 'a'
+        && c != 'b'
+        && c != 'c'
+        && c != 'A'
+        && c != 'D'
+        && c != 'R'
+        && c != 's'
         && c != 'e'
         && c != 'n'
         && c != 'N'
         && c != 't'
         && c != 'w'
+        && c != 'S'
         && c != '/'
         && c != '?'
         && c != '*'
@@ -2336,8 +3808,17 @@ mod tests {
         .map_or(false, |x| x.is_updated());
 
       let c = c as char;
-      let expected =
-        c == '/' || c == '?' || c == 'a' || c == 'n' || c == 'N' || c == 'w' || c == CHAR_QUIT;
+      let expected = c == '/'
+        || c == '?'
+        || c == 'a'
+        || c == 'n'
+        || c == 'N'
+        || c == 'w'
+        || c == 'P'
+        || c == 'S'
+        || c == 'T'
+        || c == 'V'
+        || c == CHAR_QUIT;
       assert_eq!(updated, expected, "char: {} ({})", c, c as u8);
     }
   }
@@ -2380,9 +3861,14 @@ mod tests {
       views: vec![SerView {
         name: "all".to_string(),
         lits: vec![],
+        sort: None,
       }],
       colors: Default::default(),
       toggle_tag: None,
+      reminder_command: None,
+      sync: None,
+      archive_after_days: None,
+      max_undo_steps: None,
     };
     assert_eq!(config, expected);
 
@@ -2390,6 +3876,7 @@ mod tests {
     let expected = SerUiState {
       selected_tasks: vec![Some(0)],
       selected_view: Some(0),
+      reminders_checked: state.reminders_checked,
     };
     assert_eq!(state, expected);
   }
@@ -2415,9 +3902,14 @@ mod tests {
       views: vec![SerView {
         name: "all".to_string(),
         lits: vec![],
+        sort: None,
       }],
       colors: Default::default(),
       toggle_tag: None,
+      reminder_command: None,
+      sync: None,
+      archive_after_days: None,
+      max_undo_steps: None,
     };
     assert_eq!(config, expected);
 
@@ -2425,6 +3917,7 @@ mod tests {
     let expected = SerUiState {
       selected_tasks: vec![Some(2)],
       selected_view: Some(0),
+      reminders_checked: state.reminders_checked,
     };
     assert_eq!(state, expected)
   }
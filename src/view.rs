@@ -1,12 +1,22 @@
 // Copyright (C) 2017-2023 Daniel Mueller (deso@posteo.net)
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::vec::IntoIter as VecIntoIter;
 
 use anyhow::anyhow;
 use anyhow::Result;
 
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use crate::priority::Priority;
+use crate::ser::view::Sort as SerSort;
 use crate::ser::view::TagLit as SerTagLit;
+use crate::ser::view::Term as SerTerm;
 use crate::ser::view::View as SerView;
 use crate::ser::ToSerde;
 use crate::tags::Tag;
@@ -16,18 +26,103 @@ use crate::tasks::TaskIter;
 use crate::tasks::Tasks;
 
 
-/// A literal describing whether a tag is negated or not.
+/// A term that a literal may refer to.
+#[derive(Clone, Debug)]
+enum Term {
+  /// The term is matched by tasks having the tag set.
+  Tag(Tag),
+  /// The term is matched by tasks having the given priority.
+  Priority(Priority),
+  /// The term is matched by tasks created within the given number of
+  /// days.
+  CreatedWithin(u32),
+  /// The term is matched by tasks modified within the given number of
+  /// days.
+  ModifiedWithin(u32),
+  /// The term is matched by tasks that are (or are not) blocked on
+  /// another task.
+  Blocked(bool),
+  /// The term is matched by tasks that are (or are not) snoozed, i.e.,
+  /// have a start date in the future.
+  Snoozed(bool),
+  /// The term is matched by tasks belonging to the collection with the
+  /// given name, or to the default one.
+  Collection(Option<String>),
+}
+
+impl Term {
+  /// Create a `Term` from a serializable one.
+  fn with_serde(term: SerTerm, templates: &Rc<Templates>) -> Result<Self> {
+    let term = match term {
+      SerTerm::Tag(tag) => {
+        let tag = templates
+          .instantiate(tag.id)
+          .ok_or_else(|| anyhow!("encountered invalid tag ID {}", tag.id))?;
+        Term::Tag(tag)
+      },
+      SerTerm::Priority { priority } => Term::Priority(Priority::from(priority)),
+      SerTerm::CreatedWithin {
+        created_within_days,
+      } => Term::CreatedWithin(created_within_days),
+      SerTerm::ModifiedWithin {
+        modified_within_days,
+      } => Term::ModifiedWithin(modified_within_days),
+      SerTerm::Blocked { blocked } => Term::Blocked(blocked),
+      SerTerm::Snoozed { snoozed } => Term::Snoozed(snoozed),
+      SerTerm::Collection { collection } => {
+        Term::Collection((!collection.is_empty()).then_some(collection))
+      },
+    };
+    Ok(term)
+  }
+
+  /// Check whether the provided time lies within the given number of
+  /// days from now.
+  fn is_within(time: Option<DateTime<Utc>>, days: u32) -> bool {
+    time
+      .map(|time| Utc::now() - time <= Duration::days(i64::from(days)))
+      .unwrap_or(false)
+  }
+}
+
+impl ToSerde for Term {
+  type Output = SerTerm;
+
+  /// Convert this object into a serializable one.
+  fn to_serde(&self) -> Self::Output {
+    match self {
+      Term::Tag(tag) => SerTerm::Tag(tag.to_serde()),
+      Term::Priority(priority) => SerTerm::Priority {
+        priority: priority.to_serde(),
+      },
+      Term::CreatedWithin(days) => SerTerm::CreatedWithin {
+        created_within_days: *days,
+      },
+      Term::ModifiedWithin(days) => SerTerm::ModifiedWithin {
+        modified_within_days: *days,
+      },
+      Term::Blocked(blocked) => SerTerm::Blocked { blocked: *blocked },
+      Term::Snoozed(snoozed) => SerTerm::Snoozed { snoozed: *snoozed },
+      Term::Collection(collection) => SerTerm::Collection {
+        collection: collection.clone().unwrap_or_default(),
+      },
+    }
+  }
+}
+
+
+/// A literal describing whether a term is negated or not.
 #[derive(Clone, Debug)]
 enum TagLit {
-  Pos(Tag),
-  Neg(Tag),
+  Pos(Term),
+  Neg(Term),
 }
 
 impl TagLit {
-  /// Retrieve the contained `Tag`.
-  fn tag(&self) -> &Tag {
+  /// Retrieve the contained `Term`.
+  fn term(&self) -> &Term {
     match self {
-      TagLit::Pos(tag) | TagLit::Neg(tag) => tag,
+      TagLit::Pos(term) | TagLit::Neg(term) => term,
     }
   }
 
@@ -46,8 +141,56 @@ impl ToSerde for TagLit {
   /// Convert this object into a serializable one.
   fn to_serde(&self) -> Self::Output {
     match self {
-      TagLit::Pos(tag) => SerTagLit::Pos(tag.to_serde()),
-      TagLit::Neg(tag) => SerTagLit::Neg(tag.to_serde()),
+      TagLit::Pos(term) => SerTagLit::Pos(term.to_serde()),
+      TagLit::Neg(term) => SerTagLit::Neg(term.to_serde()),
+    }
+  }
+}
+
+
+/// The criterion by which the tasks of a view are sorted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sort {
+  /// Sort by creation time, most recently created tasks first.
+  Created,
+  /// Sort by modification time, most recently modified tasks first.
+  Modified,
+  /// Sort by priority, highest priority first.
+  Priority,
+}
+
+impl Sort {
+  /// Compare two tasks according to this sort criterion.
+  ///
+  /// Tasks lacking the attribute in question are ordered last.
+  fn compare(&self, task1: &Task, task2: &Task) -> Ordering {
+    match self {
+      Sort::Created => task2.created().cmp(&task1.created()),
+      Sort::Modified => task2.modified().cmp(&task1.modified()),
+      Sort::Priority => task2.priority().cmp(&task1.priority()),
+    }
+  }
+}
+
+impl From<SerSort> for Sort {
+  fn from(other: SerSort) -> Self {
+    match other {
+      SerSort::Created => Sort::Created,
+      SerSort::Modified => Sort::Modified,
+      SerSort::Priority => Sort::Priority,
+    }
+  }
+}
+
+impl ToSerde for Sort {
+  type Output = SerSort;
+
+  /// Convert this object into a serializable one.
+  fn to_serde(&self) -> Self::Output {
+    match self {
+      Sort::Created => SerSort::Created,
+      Sort::Modified => SerSort::Modified,
+      Sort::Priority => SerSort::Priority,
     }
   }
 }
@@ -58,43 +201,115 @@ impl ToSerde for TagLit {
 pub struct Filter<'tasks> {
   iter: TaskIter<'tasks>,
   lits: &'tasks [Vec<TagLit>],
+  /// The matching tasks in sorted order, if sorting was requested.
+  sorted: Option<VecIntoIter<&'tasks Rc<Task>>>,
 }
 
 impl<'tasks> Filter<'tasks> {
   /// Create a new `Filter` wrapping an iterator and filtering using the given set of literals.
   fn new(iter: TaskIter<'tasks>, lits: &'tasks [Vec<TagLit>]) -> Self {
-    Self { iter, lits }
+    Self {
+      iter,
+      lits,
+      sorted: None,
+    }
   }
 
-  /// Check if one of the given tags matches the available ones.
-  fn matches<'tag, I>(lits: &[TagLit], avail_tags: &I) -> bool
+  /// Sort the remaining matching tasks according to the provided
+  /// criterion.
+  ///
+  /// Sorting is stable, meaning that tasks comparing equal stay in
+  /// their original order. Sub-tasks are only sorted among their
+  /// siblings and stay with their parent (or, if it is not matched,
+  /// their closest matched ancestor), so that, for example, sorting by
+  /// priority does not tear a task hierarchy apart.
+  fn sort(mut self, sort: Sort) -> Self {
+    let tasks = self.by_ref().collect::<Vec<_>>();
+    let indices = tasks
+      .iter()
+      .enumerate()
+      .map(|(idx, task)| (Rc::as_ptr(task), idx))
+      .collect::<HashMap<_, _>>();
+
+    let mut roots = Vec::new();
+    let mut children = vec![Vec::new(); tasks.len()];
+    for (idx, task) in tasks.iter().enumerate() {
+      let mut ancestor = task.parent();
+      let parent_idx = loop {
+        match ancestor {
+          Some(task) => match indices.get(&Rc::as_ptr(&task)) {
+            Some(parent_idx) => break Some(*parent_idx),
+            None => ancestor = task.parent(),
+          },
+          None => break None,
+        }
+      };
+
+      match parent_idx {
+        Some(parent_idx) => children[parent_idx].push(idx),
+        None => roots.push(idx),
+      }
+    }
+
+    let compare = |idx1: &usize, idx2: &usize| sort.compare(tasks[*idx1], tasks[*idx2]);
+    let () = roots.sort_by(compare);
+    let () = children.iter_mut().for_each(|children| children.sort_by(compare));
+
+    let mut sorted = Vec::with_capacity(tasks.len());
+    let mut stack = roots.into_iter().rev().collect::<Vec<_>>();
+    while let Some(idx) = stack.pop() {
+      let () = sorted.push(tasks[idx]);
+      let () = stack.extend(children[idx].iter().rev());
+    }
+
+    self.sorted = Some(sorted.into_iter());
+    self
+  }
+
+  /// Check if one of the given literals matches the task with the
+  /// provided tags.
+  fn matches<'tag, I>(lits: &[TagLit], task: &Task, avail_tags: &I) -> bool
   where
     I: Iterator<Item = &'tag Tag> + Clone,
   {
     // Iterate over disjunctions and check if any of them matches.
     for lit in lits {
-      let tag = lit.tag();
+      let exists = match lit.term() {
+        Term::Tag(tag) => avail_tags.clone().any(|x| x == tag),
+        Term::Priority(priority) => task.priority() == Some(*priority),
+        Term::CreatedWithin(days) => Term::is_within(task.created(), *days),
+        Term::ModifiedWithin(days) => Term::is_within(task.modified(), *days),
+        Term::Blocked(blocked) => task.is_blocked() == *blocked,
+        Term::Snoozed(snoozed) => task.is_snoozed() == *snoozed,
+        Term::Collection(collection) => task.collection() == *collection,
+      };
       let must_exist = lit.is_pos();
 
-      if avail_tags.clone().any(|x| x == tag) == must_exist {
+      if exists == must_exist {
         return true
       }
     }
     false
   }
 
-  /// Check if the given `tags` match this view's requirements.
-  fn matched_by<'tag, I>(&self, avail_tags: &I) -> bool
+  /// Check if the given task, having `tags`, matches this view's
+  /// requirements.
+  fn matched_by<'tag, I>(&self, task: &Task, avail_tags: &I) -> bool
   where
     I: Iterator<Item = &'tag Tag> + Clone,
   {
+    // Sub-tasks of collapsed tasks are never shown.
+    if task.is_hidden() {
+      return false
+    }
+
     // Iterate over conjunctions; all of them need to match.
     for req_lits in self.lits {
       // We could create a set for faster inclusion checks instead of
       // passing in an iterator. However, typically tasks only use a
       // small set of tags and so the allocation overhead is assumed to
       // be higher than the iteration cost we incur right now.
-      if !Self::matches(req_lits, avail_tags) {
+      if !Self::matches(req_lits, task, avail_tags) {
         return false
       }
     }
@@ -107,13 +322,17 @@ impl<'tasks> Iterator for Filter<'tasks> {
 
   /// Advance the iterator yielding the next matching task or None.
   fn next(&mut self) -> Option<Self::Item> {
+    if let Some(sorted) = &mut self.sorted {
+      return sorted.next()
+    }
+
     // TODO: Should really be a for loop or even just a .find()
     //       invocation, however, both versions do not compile due to
     //       borrowing/ownership conflicts.
     loop {
       match self.iter.next() {
         Some(task) => {
-          if task.tags(|iter| self.matched_by(&iter)) {
+          if task.tags(|iter| self.matched_by(task, &iter)) {
             return Some(task)
           }
         },
@@ -125,10 +344,14 @@ impl<'tasks> Iterator for Filter<'tasks> {
 
 impl<'tasks> DoubleEndedIterator for Filter<'tasks> {
   fn next_back(&mut self) -> Option<Self::Item> {
+    if let Some(sorted) = &mut self.sorted {
+      return sorted.next_back()
+    }
+
     loop {
       match self.iter.next_back() {
         Some(task) => {
-          if task.tags(|iter| self.matched_by(&iter)) {
+          if task.tags(|iter| self.matched_by(task, &iter)) {
             return Some(task)
           }
         },
@@ -147,6 +370,7 @@ impl<'tasks> DoubleEndedIterator for Filter<'tasks> {
 pub struct ViewBuilder {
   tasks: Rc<Tasks>,
   lits: Vec<Vec<TagLit>>,
+  sort: Option<Sort>,
 }
 
 impl ViewBuilder {
@@ -155,9 +379,17 @@ impl ViewBuilder {
     Self {
       tasks,
       lits: Default::default(),
+      sort: None,
     }
   }
 
+  /// Sort the tasks of the view according to the given criterion.
+  #[cfg(test)]
+  fn sort(mut self, sort: Sort) -> ViewBuilder {
+    self.sort = Some(sort);
+    self
+  }
+
   /// Add a new conjunction containing the given literal to the view.
   #[cfg(test)]
   fn and_lit(mut self, lit: TagLit) -> ViewBuilder {
@@ -181,7 +413,7 @@ impl ViewBuilder {
   /// Is equivalent to tag1 && (tag2 || tag3) && tag4.
   #[cfg(test)]
   pub fn and(self, tag: impl Into<Tag>) -> ViewBuilder {
-    self.and_lit(TagLit::Pos(tag.into()))
+    self.and_lit(TagLit::Pos(Term::Tag(tag.into())))
   }
 
   /// Add a new conjunction containing the given tag in negated form to
@@ -191,7 +423,7 @@ impl ViewBuilder {
   /// associate with one another and with ORed ones.
   #[cfg(test)]
   pub fn and_not(self, tag: impl Into<Tag>) -> ViewBuilder {
-    self.and_lit(TagLit::Neg(tag.into()))
+    self.and_lit(TagLit::Neg(Term::Tag(tag.into())))
   }
 
   /// Add a new literal to the last disjunction.
@@ -214,7 +446,7 @@ impl ViewBuilder {
   /// associate with one another and with ANDed ones.
   #[cfg(test)]
   pub fn or(self, tag: impl Into<Tag>) -> ViewBuilder {
-    self.or_lit(TagLit::Pos(tag.into()))
+    self.or_lit(TagLit::Pos(Term::Tag(tag.into())))
   }
 
   /// Add a new tag in negated form to the last disjunction.
@@ -223,7 +455,7 @@ impl ViewBuilder {
   /// associate with one another and with ANDed ones.
   #[cfg(test)]
   pub fn or_not(self, tag: impl Into<Tag>) -> ViewBuilder {
-    self.or_lit(TagLit::Neg(tag.into()))
+    self.or_lit(TagLit::Neg(Term::Tag(tag.into())))
   }
 
   /// Build the final `View` instance.
@@ -232,6 +464,7 @@ impl ViewBuilder {
       name: name.into(),
       tasks: self.tasks,
       lits: self.lits,
+      sort: self.sort,
     }
   }
 }
@@ -260,6 +493,8 @@ pub struct View {
   /// large AND (all elements in the outer vector) of ORs (all the
   /// elements in the inner vector).
   lits: Vec<Vec<TagLit>>,
+  /// The criterion by which to sort the tasks of the view, if any.
+  sort: Option<Sort>,
 }
 
 impl View {
@@ -269,12 +504,10 @@ impl View {
     for lits in view.lits.into_iter() {
       let mut or_lits = Vec::with_capacity(lits.len());
       for lit in lits.into_iter() {
-        let tag = templates
-          .instantiate(lit.id())
-          .ok_or_else(|| anyhow!("encountered invalid tag ID {}", lit.id()))?;
+        let term = Term::with_serde(lit.term().clone(), templates)?;
         let lit = match lit {
-          SerTagLit::Pos(_) => TagLit::Pos(tag),
-          SerTagLit::Neg(_) => TagLit::Neg(tag),
+          SerTagLit::Pos(_) => TagLit::Pos(term),
+          SerTagLit::Neg(_) => TagLit::Neg(term),
         };
         or_lits.push(lit);
       }
@@ -286,6 +519,7 @@ impl View {
       name: view.name,
       tasks,
       lits: and_lits,
+      sort: view.sort.map(Sort::from),
     })
   }
 
@@ -296,7 +530,13 @@ impl View {
   where
     F: FnMut(Filter<'_>) -> R,
   {
-    self.tasks.iter(|iter| f(Filter::new(iter, &self.lits)))
+    self.tasks.iter(|iter| {
+      let filter = Filter::new(iter, &self.lits);
+      match self.sort {
+        Some(sort) => f(filter.sort(sort)),
+        None => f(filter),
+      }
+    })
   }
 
   /// Retrieve an iterator over all tags of the positive literals in
@@ -304,8 +544,8 @@ impl View {
   pub fn positive_tag_iter(&self) -> impl Iterator<Item = &Tag> {
     self.lits.iter().flat_map(|disjunctions| {
       disjunctions.iter().filter_map(|literal| match literal {
-        TagLit::Pos(tag) => Some(tag),
-        TagLit::Neg(..) => None,
+        TagLit::Pos(Term::Tag(tag)) => Some(tag),
+        TagLit::Pos(..) | TagLit::Neg(..) => None,
       })
     })
   }
@@ -336,6 +576,7 @@ impl ToSerde for View {
     SerView {
       name: self.name.clone(),
       lits,
+      sort: self.sort.as_ref().map(Sort::to_serde),
     }
   }
 }
@@ -345,7 +586,12 @@ impl ToSerde for View {
 mod tests {
   use super::*;
 
+  use chrono::Days;
+  use chrono::Local;
+  use chrono::TimeZone as _;
+
   use crate::ser::tags::Templates as SerTemplates;
+  use crate::ser::tasks::Priority as SerPriority;
   use crate::ser::tasks::Tasks as SerTasks;
   use crate::tags::Templates;
   use crate::test::make_tasks;
@@ -591,4 +837,226 @@ mod tests {
       assert!(iter.next().is_none());
     });
   }
+
+  /// Check that we can filter tasks based on their priority.
+  #[test]
+  fn filter_priority() {
+    let mut tasks = make_tasks(4);
+    tasks[1].priority = Some(SerPriority::High);
+    tasks[2].priority = Some(SerPriority::Low);
+    tasks[3].priority = Some(SerPriority::High);
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Pos(Term::Priority(Priority::High)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2", "4"]);
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Neg(Term::Priority(Priority::High)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["1", "3"]);
+
+    let view = ViewBuilder::new(tasks)
+      .and_lit(TagLit::Pos(Term::Priority(Priority::Low)))
+      .or_lit(TagLit::Pos(Term::Priority(Priority::Medium)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["3"]);
+  }
+
+  /// Check that we can filter tasks based on their creation and
+  /// modification times.
+  #[test]
+  fn filter_created_modified_within() {
+    let now = Utc::now();
+    let mut tasks = make_tasks(3);
+    tasks[0].created = Some(now - Duration::days(10));
+    tasks[0].modified = Some(now - Duration::hours(1));
+    tasks[1].created = Some(now - Duration::days(2));
+    tasks[1].modified = Some(now - Duration::days(2));
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Pos(Term::CreatedWithin(7)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2"]);
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Pos(Term::ModifiedWithin(1)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["1"]);
+
+    let view = ViewBuilder::new(tasks)
+      .and_lit(TagLit::Neg(Term::CreatedWithin(7)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["1", "3"]);
+  }
+
+  /// Check that views sort their tasks as requested.
+  #[test]
+  fn sort_tasks() {
+    let mut tasks = make_tasks(4);
+    tasks[0].priority = Some(SerPriority::Low);
+    tasks[0].created = Some(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap());
+    tasks[1].created = Some(Utc.with_ymd_and_hms(2023, 3, 1, 0, 0, 0).unwrap());
+    tasks[2].priority = Some(SerPriority::High);
+    tasks[2].modified = Some(Utc.with_ymd_and_hms(2023, 2, 1, 0, 0, 0).unwrap());
+    tasks[3].priority = Some(SerPriority::Low);
+    tasks[3].modified = Some(Utc.with_ymd_and_hms(2023, 4, 1, 0, 0, 0).unwrap());
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .sort(Sort::Created)
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2", "1", "3", "4"]);
+
+    let view = ViewBuilder::new(tasks.clone())
+      .sort(Sort::Modified)
+      .build("test");
+    assert_eq!(summaries(&view), vec!["4", "3", "1", "2"]);
+
+    let view = ViewBuilder::new(tasks.clone())
+      .sort(Sort::Priority)
+      .build("test");
+    assert_eq!(summaries(&view), vec!["3", "1", "4", "2"]);
+
+    let reversed = view.iter(|iter| iter.rev().map(|task| task.summary()).collect::<Vec<_>>());
+    assert_eq!(reversed, vec!["2", "4", "1", "3"]);
+  }
+
+  /// Check that sorting keeps sub-tasks with their parents.
+  #[test]
+  fn sort_tasks_with_sub_tasks() {
+    let mut tasks = make_tasks(5);
+    tasks[0].priority = Some(SerPriority::Low);
+    tasks[1].parent = Some(tasks[0].id);
+    tasks[2].parent = Some(tasks[0].id);
+    tasks[2].priority = Some(SerPriority::High);
+    tasks[3].parent = Some(tasks[2].id);
+    tasks[4].priority = Some(SerPriority::Medium);
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .sort(Sort::Priority)
+      .build("test");
+    assert_eq!(summaries(&view), vec!["5", "1", "3", "4", "2"]);
+
+    // Sub-tasks of a task that is not matched stay with the closest
+    // ancestor that is.
+    let view = ViewBuilder::new(tasks)
+      .and_lit(TagLit::Neg(Term::Priority(Priority::High)))
+      .sort(Sort::Priority)
+      .build("test");
+    assert_eq!(summaries(&view), vec!["5", "1", "2", "4"]);
+  }
+
+  /// Check that sub-tasks of collapsed tasks are not matched by views.
+  #[test]
+  fn collapsed_sub_tasks_hidden() {
+    let mut tasks = make_tasks(4);
+    tasks[1].parent = Some(tasks[0].id);
+    tasks[2].parent = Some(tasks[1].id);
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+    let view = ViewBuilder::new(tasks.clone()).build("test");
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+    assert_eq!(summaries(&view), vec!["1", "2", "3", "4"]);
+
+    let task = tasks.iter(|mut iter| iter.nth(1).unwrap().clone());
+    let () = tasks.set_collapsed(&task, true);
+    assert_eq!(summaries(&view), vec!["1", "2", "4"]);
+
+    let task = tasks.iter(|mut iter| iter.next().unwrap().clone());
+    let () = tasks.set_collapsed(&task, true);
+    assert_eq!(summaries(&view), vec!["1", "4"]);
+  }
+
+  /// Check that views can filter on whether tasks are blocked.
+  #[test]
+  fn filter_blocked() {
+    let mut tasks = make_tasks(4);
+    tasks[0].depends_on = vec![tasks[1].id];
+    tasks[2].depends_on = vec![tasks[3].id];
+    tasks[3].completed = Some(Utc::now());
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Pos(Term::Blocked(true)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["1"]);
+
+    let view = ViewBuilder::new(tasks)
+      .and_lit(TagLit::Pos(Term::Blocked(false)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2", "3", "4"]);
+  }
+
+  /// Check that views can filter on whether tasks are snoozed.
+  #[test]
+  fn filter_snoozed() {
+    let today = Local::now().date_naive();
+    let mut tasks = make_tasks(3);
+    tasks[0].start = today.checked_add_days(Days::new(1));
+    tasks[1].start = Some(today);
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Neg(Term::Snoozed(true)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2", "3"]);
+
+    let view = ViewBuilder::new(tasks)
+      .and_lit(TagLit::Pos(Term::Snoozed(true)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["1"]);
+  }
+
+  /// Check that views can filter on the collection tasks belong to.
+  #[test]
+  fn filter_collection() {
+    let mut tasks = make_tasks(3);
+    tasks[1].collection = Some("work".to_string());
+    tasks[2].collection = Some("home".to_string());
+    let tasks = Rc::new(Tasks::with_serde_tasks(tasks).unwrap());
+
+    let summaries = |view: &View| {
+      view.iter(|iter| iter.map(|task| task.summary()).collect::<Vec<_>>())
+    };
+
+    let view = ViewBuilder::new(tasks.clone())
+      .and_lit(TagLit::Pos(Term::Collection(Some("work".to_string()))))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2"]);
+
+    let view = ViewBuilder::new(tasks)
+      .and_lit(TagLit::Neg(Term::Collection(None)))
+      .build("test");
+    assert_eq!(summaries(&view), vec!["2", "3"]);
+  }
 }
@@ -0,0 +1,232 @@
+// Copyright (C) 2023 Daniel Mueller (deso@posteo.net)
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Infrastructure for watching the tasks directory for changes made by
+//! other programs.
+//!
+//! The purpose of this module is to set up an inotify(7) watch for a
+//! directory and the collection directories below it and to send an
+//! event through a supplied `std::sync::mpsc::Sender` whenever a file
+//! in any of them got written, created, moved, or removed. A dedicated
+//! thread blocks on the inotify file descriptor for that purpose.
+//! Collection directories appearing later on are watched as well.
+//!
+//! Hidden files are ignored, as they are not considered part of a vdir.
+//! Among others, that means that the temporary files we (and other
+//! programs) create when writing files atomically do not cause any
+//! events by themselves.
+
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Result;
+use std::mem::size_of;
+use std::os::unix::ffi::OsStrExt as _;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::FromRawFd as _;
+use std::os::unix::io::OwnedFd;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use libc::c_void;
+use libc::inotify_add_watch;
+use libc::inotify_event;
+use libc::inotify_init1;
+use libc::read;
+use libc::IN_CLOEXEC;
+use libc::IN_CLOSE_WRITE;
+use libc::IN_CREATE;
+use libc::IN_DELETE;
+use libc::IN_ISDIR;
+use libc::IN_MOVED_FROM;
+use libc::IN_MOVED_TO;
+
+use crate::Event;
+
+
+/// The size of the buffer we read inotify events into. It is large
+/// enough to hold at least one event with a maximum length file name.
+const BUFFER_SIZE: usize = 4096;
+
+
+/// Check the return value of a system call.
+fn check(result: i32) -> Result<i32> {
+  if result < 0 {
+    Err(Error::last_os_error())
+  } else {
+    Ok(result)
+  }
+}
+
+/// Decode the inotify events contained in a buffer, along with the
+/// names of the files they refer to (which may be empty).
+fn decode_events(buffer: &[u8]) -> Vec<(inotify_event, &[u8])> {
+  let mut events = Vec::new();
+  let mut offset = 0;
+  while offset + size_of::<inotify_event>() <= buffer.len() {
+    // SAFETY: The kernel only ever provides complete events and we
+    //         checked that the buffer is large enough for the fixed
+    //         size part.
+    let event = unsafe {
+      (buffer.as_ptr().add(offset) as *const inotify_event).read_unaligned()
+    };
+    let start = offset + size_of::<inotify_event>();
+    let end = (start + event.len as usize).min(buffer.len());
+    let name = &buffer[start..end];
+    // The name is padded with NUL bytes, if present at all.
+    let name = name.split(|byte| *byte == b'\0').next().unwrap_or_default();
+    let () = events.push((event, name));
+    offset = end;
+  }
+  events
+}
+
+/// Add a watch for changes to files in the directory at `dir`,
+/// returning the watch descriptor.
+fn add_watch(fd: &OwnedFd, dir: &Path) -> Result<i32> {
+  let path = CString::new(dir.as_os_str().as_bytes())
+    .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+  // Directory creation is of interest for recognizing new collections.
+  let mask = IN_CLOSE_WRITE | IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO;
+
+  // SAFETY: `fd` is a valid inotify file descriptor and `path` is a
+  //         NUL terminated string that outlives the call.
+  check(unsafe { inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) })
+}
+
+/// Watch the directory at `dir`, as well as the directories of the
+/// given `collections` below it, for changes to files and send an
+/// `Event::TasksChanged` object upon detection.
+///
+/// Directories created in `dir` later on are considered collections
+/// and watched as well.
+pub fn receive_task_changes<I>(
+  dir: &Path,
+  collections: I,
+  send_event: Sender<Result<Event>>,
+) -> Result<()>
+where
+  I: IntoIterator,
+  I::Item: AsRef<OsStr>,
+{
+  // SAFETY: `inotify_init1` has no preconditions.
+  let fd = check(unsafe { inotify_init1(IN_CLOEXEC) })?;
+  // SAFETY: `fd` is a file descriptor that we just opened and that is
+  //         not owned by anything else.
+  let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+  let root_wd = add_watch(&fd, dir)?;
+  for collection in collections {
+    let _wd = add_watch(&fd, &dir.join(collection.as_ref()))?;
+  }
+
+  let dir = dir.to_path_buf();
+  // The thread takes ownership of the file descriptor, which gets
+  // closed once it exits.
+  thread::spawn(move || loop {
+    let mut buffer = [0u8; BUFFER_SIZE];
+    // SAFETY: `fd` is open for as long as the thread owns it and
+    //         `buffer` is valid for writes of `buffer.len()` bytes.
+    let result = unsafe {
+      read(
+        fd.as_raw_fd(),
+        buffer.as_mut_ptr() as *mut c_void,
+        buffer.len(),
+      )
+    };
+    if result < 0 {
+      let error = Error::last_os_error();
+      if error.kind() == ErrorKind::Interrupted {
+        continue
+      }
+      let _result = send_event.send(Err(error));
+      break
+    }
+
+    let mut relevant = false;
+    for (event, name) in decode_events(&buffer[..result as usize]) {
+      let is_dir = event.mask & IN_ISDIR != 0;
+      // Hidden files are not part of a vdir. Newly created files are
+      // only of interest once they got written.
+      if name.is_empty() || name.starts_with(b".") || (event.mask & IN_CREATE != 0 && !is_dir) {
+        continue
+      }
+      relevant = true;
+
+      let created = event.mask & (IN_CREATE | IN_MOVED_TO) != 0;
+      if event.wd == root_wd && is_dir && created {
+        // Files written to the directory before the watch got added are
+        // covered by the reload that the event triggers.
+        let collection = dir.join(OsStr::from_bytes(name));
+        match add_watch(&fd, &collection) {
+          Ok(_wd) => (),
+          // The directory may have vanished in the meantime.
+          Err(err) if err.kind() == ErrorKind::NotFound => (),
+          Err(err) => {
+            let _result = send_event.send(Err(err));
+            return
+          },
+        }
+      }
+    }
+
+    if relevant && send_event.send(Ok(Event::TasksChanged)).is_err() {
+      break
+    }
+  });
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs::create_dir;
+  use std::fs::write;
+  use std::sync::mpsc::channel;
+  use std::time::Duration;
+
+  use tempfile::TempDir;
+
+
+  /// Check that we get notified about changes to non-hidden files in a
+  /// watched directory.
+  #[test]
+  fn watch_directory() {
+    let dir = TempDir::new().unwrap();
+    let (send, recv) = channel();
+    let () = receive_task_changes(dir.path(), None::<&str>, send).unwrap();
+
+    let () = write(dir.path().join(".hidden"), b"data").unwrap();
+    let result = recv.recv_timeout(Duration::from_millis(100));
+    assert!(result.is_err(), "{result:?}");
+
+    let () = write(dir.path().join("visible"), b"data").unwrap();
+    let event = recv.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert!(matches!(event, Event::TasksChanged), "{event:?}");
+  }
+
+  /// Check that changes in collection directories are reported, including
+  /// in ones created after the watch got set up.
+  #[test]
+  fn watch_collections() {
+    let dir = TempDir::new().unwrap();
+    let () = create_dir(dir.path().join("work")).unwrap();
+    let (send, recv) = channel();
+    let () = receive_task_changes(dir.path(), ["work"], send).unwrap();
+
+    let () = write(dir.path().join("work").join("task"), b"data").unwrap();
+    let event = recv.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert!(matches!(event, Event::TasksChanged), "{event:?}");
+
+    let () = create_dir(dir.path().join("home")).unwrap();
+    let event = recv.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert!(matches!(event, Event::TasksChanged), "{event:?}");
+
+    let () = write(dir.path().join("home").join("task"), b"data").unwrap();
+    let event = recv.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert!(matches!(event, Event::TasksChanged), "{event:?}");
+  }
+}
@@ -3,6 +3,9 @@
 
 use std::ffi::OsString;
 use std::io::sink;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::io::Write as _;
 
 use notnow::run_prog;
 use notnow::test::default_tasks_and_tags;
@@ -10,6 +13,7 @@ use notnow::DirCap;
 use notnow::TaskState;
 use notnow::UiConfig;
 
+use tempfile::tempfile;
 use tempfile::TempDir;
 
 use tokio::test;
@@ -22,7 +26,7 @@ async fn prog_running() {
 
   let (ui_config, task_state) = default_tasks_and_tags();
 
-  let task_state = TaskState::with_serde(task_state).unwrap();
+  let mut task_state = TaskState::with_serde(task_state).unwrap();
   let tasks_dir = TempDir::new().unwrap();
   let mut tasks_root_cap = DirCap::for_dir(tasks_dir.path().to_path_buf())
     .await
@@ -43,16 +47,25 @@ async fn prog_running() {
   let mut ui_config_file_cap = ui_config_dir_write_guard.file_cap(&ui_config_file_name);
   let () = ui_config.save(&mut ui_config_file_cap).await.unwrap();
 
+  let trash_dir = TempDir::new().unwrap();
+  let archive_dir = TempDir::new().unwrap();
   let ui_state_dir = TempDir::new().unwrap();
   let ui_state_file_name = OsString::from("ui-state.json");
   let ui_state_file_path = (ui_state_dir.path().to_path_buf(), ui_state_file_name);
 
+  // Input is required to be backed by a file descriptor.
+  let mut input = tempfile().unwrap();
+  let () = input.write_all(&KEYS).unwrap();
+  let _offset = input.seek(SeekFrom::Start(0)).unwrap();
+
   let mut output = sink();
 
   run_prog(
-    KEYS.as_slice(),
+    input,
     &mut output,
     tasks_dir.path().to_path_buf(),
+    trash_dir.path().to_path_buf(),
+    archive_dir.path().to_path_buf(),
     ui_config_file_path,
     ui_state_file_path,
   )